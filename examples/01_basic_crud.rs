@@ -8,7 +8,7 @@
 use eyre::Result;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use taskstore::{IndexValue, Record, Store, now_ms};
+use taskstore::{IndexValue, Query, Record, Store, now_ms};
 
 /// A simple note record for demonstration
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -29,6 +29,10 @@ impl Record for Note {
         self.updated_at
     }
 
+    fn created_at(&self) -> Option<i64> {
+        Some(self.created_at)
+    }
+
     fn collection_name() -> &'static str {
         "notes"
     }
@@ -99,7 +103,9 @@ fn main() -> Result<()> {
 
     // LIST: Show all notes
     println!("4. LIST - Showing all notes...");
-    let all_notes: Vec<Note> = store.list(&[])?;
+    // created_at() makes creation time sortable without listing it in indexed_fields
+    let by_creation = Query::new().order_by("created_at");
+    let all_notes: Vec<Note> = store.list_with(by_creation.filters(), by_creation.options())?;
     println!("   Total notes: {}", all_notes.len());
     for note in &all_notes {
         println!("   - {} : {}", note.id, note.title);
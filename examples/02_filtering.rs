@@ -8,7 +8,7 @@
 use eyre::Result;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use taskstore::{Filter, FilterOp, IndexValue, Record, Store, now_ms};
+use taskstore::{Filter, FilterOp, IndexValue, Query, Record, Store, now_ms};
 
 /// A task with multiple indexed fields for filtering
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -37,11 +37,16 @@ impl Record for Task {
 
     fn indexed_fields(&self) -> HashMap<String, IndexValue> {
         let mut fields = HashMap::new();
-        fields.insert("status".to_string(), IndexValue::String(self.status.clone()));
-        fields.insert("priority".to_string(), IndexValue::Int(self.priority));
-        fields.insert("assigned".to_string(), IndexValue::Bool(self.assigned));
+        fields.insert("status".into(), self.status.clone().into());
+        fields.insert("priority".into(), self.priority.into());
+        fields.insert("assigned".into(), self.assigned.into());
         fields
     }
+
+    // Speeds up "status = X AND priority > N" queries (query 4 below)
+    fn composite_indexes() -> &'static [&'static [&'static str]] {
+        &[&["status", "priority"]]
+    }
 }
 
 fn main() -> Result<()> {
@@ -179,6 +184,15 @@ fn main() -> Result<()> {
     }
     println!("   Found: {} tasks\n", active.len());
 
+    // Filter 6: Query builder with ordering and a limit
+    println!("6. Top 2 open tasks by priority (Query builder):");
+    let query = Query::new().ne("status", "complete").order_by_desc("priority").limit(2);
+    let top: Vec<Task> = store.list_with(query.filters(), query.options())?;
+    for task in &top {
+        println!("   - {} : {} (priority={})", task.id, task.title, task.priority);
+    }
+    println!();
+
     println!("Example complete!");
     Ok(())
 }
@@ -299,6 +299,17 @@ fn main() -> Result<()> {
     for emp in &engineers {
         println!("   - {} : {}", emp.id, emp.name);
     }
+
+    // Human-entered strings vary in case; the *IgnoreCase operators match them all
+    let engineers: Vec<Employee> = store.list(&[taskstore::Filter {
+        field: "department".to_string(),
+        op: taskstore::FilterOp::EqIgnoreCase,
+        value: IndexValue::String("engineering".to_string()),
+    }])?;
+    println!(
+        "   department = 'engineering' ignoring case: {} employees",
+        engineers.len()
+    );
     println!();
 
     // Query by nested field
@@ -2,7 +2,7 @@
 //!
 //! This example demonstrates how TaskStore handles sync operations:
 //! - Rebuilding SQLite from JSONL files
-//! - Rebuilding indexes after sync
+//! - Automatic reindexing after sync (with `register`)
 //! - Staleness detection
 //!
 //! Run with: cargo run --example 04_sync_and_rebuild
@@ -129,7 +129,7 @@ fn main() -> Result<()> {
         // Give filesystem time to update mtime
         std::thread::sleep(std::time::Duration::from_millis(100));
 
-        let mut store = Store::open(temp_dir.path())?;
+        let store = Store::open(temp_dir.path())?;
 
         // Check if store detected the changes
         let all_docs: Vec<Document> = store.list(&[])?;
@@ -139,15 +139,13 @@ fn main() -> Result<()> {
             println!("   - {} : {} (v{})", doc.id, doc.title, doc.version);
         }
 
-        // Note: After sync, indexes need to be rebuilt!
-        println!("\n   Rebuilding indexes for Document type...");
-        let indexed_count = store.rebuild_indexes::<Document>()?;
-        println!("   Rebuilt indexes for {} documents", indexed_count);
+        // Sync reindexes every collection. Document's indexed fields are plain
+        // top-level fields, so they're restored even before the type is registered.
     }
     println!();
 
-    // Phase 4: Verify filtering works after rebuild
-    println!("Phase 4: Verifying filters work after index rebuild...");
+    // Phase 4: Verify filtering works after sync
+    println!("Phase 4: Verifying filters work after sync...");
     {
         let store = Store::open(temp_dir.path())?;
 
@@ -178,13 +176,17 @@ fn main() -> Result<()> {
     {
         let mut store = Store::open(temp_dir.path())?;
 
+        // Registered types are reindexed through Record::indexed_fields on every sync
+        store.register::<Document>();
+
         println!("   Calling store.sync() explicitly...");
         store.sync()?;
         println!("   Sync complete.");
 
-        println!("   Rebuilding indexes...");
-        let count = store.rebuild_indexes::<Document>()?;
-        println!("   Rebuilt indexes for {} documents", count);
+        println!("   Rebuilding all indexes...");
+        for (collection, count) in store.rebuild_all_indexes()? {
+            println!("   Rebuilt indexes for {} {}", count, collection);
+        }
 
         let all: Vec<Document> = store.list(&[])?;
         println!("   Total documents: {}", all.len());
@@ -194,7 +196,7 @@ fn main() -> Result<()> {
     println!("Example complete!");
     println!("\nKey takeaways:");
     println!("  1. Store::open() auto-detects stale state and syncs");
-    println!("  2. After sync, call rebuild_indexes::<T>() for each type");
+    println!("  2. Sync reindexes every collection; register::<T>() types for exact indexes");
     println!("  3. JSONL is source of truth - external changes are imported");
     println!("  4. Multiple versions of same ID in JSONL: latest wins");
 
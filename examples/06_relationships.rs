@@ -195,11 +195,11 @@ impl Record for Category {
 // ============================================================================
 
 fn get_team_members(store: &Store, team_id: &str) -> Result<Vec<Member>> {
-    store.list(&[Filter {
+    Ok(store.list(&[Filter {
         field: "team_id".to_string(),
         op: FilterOp::Eq,
         value: IndexValue::String(team_id.to_string()),
-    }])
+    }])?)
 }
 
 fn get_article_tags(store: &Store, article_id: &str) -> Result<Vec<String>> {
@@ -213,11 +213,11 @@ fn get_article_tags(store: &Store, article_id: &str) -> Result<Vec<String>> {
 }
 
 fn get_child_categories(store: &Store, parent_id: &str) -> Result<Vec<Category>> {
-    store.list(&[Filter {
+    Ok(store.list(&[Filter {
         field: "parent_id".to_string(),
         op: FilterOp::Eq,
         value: IndexValue::String(parent_id.to_string()),
-    }])
+    }])?)
 }
 
 fn main() -> Result<()> {
@@ -292,6 +292,19 @@ fn main() -> Result<()> {
     }
     println!();
 
+    // Integrity: team_id is a plain string, so nothing stops it dangling
+    store.create(Member {
+        id: "mem-004".to_string(),
+        name: "Dave".to_string(),
+        team_id: "team-disbanded".to_string(),
+        role: "developer".to_string(),
+        created_at: now_ms(),
+        updated_at: now_ms(),
+    })?;
+    let orphans = store.find_orphans("members", "team_id", "teams")?;
+    println!("   Members with a missing team: {:?}", orphans);
+    println!();
+
     // ========================================================================
     // Many-to-Many: Articles and Tags
     // ========================================================================
@@ -381,11 +394,11 @@ fn main() -> Result<()> {
 
     // Query: Get tags for article art-001
     println!("   Tags for 'Getting Started with Rust':");
+    // Two queries in total: the join records, then every tag at once
     let art1_tags = get_article_tags(&store, "art-001")?;
-    for tag_id in &art1_tags {
-        if let Some(tag) = store.get::<Tag>(tag_id)? {
-            println!("   - {}", tag.name);
-        }
+    let tag_ids: Vec<&str> = art1_tags.iter().map(String::as_str).collect();
+    for tag in store.get_many::<Tag>(&tag_ids)?.into_iter().flatten() {
+        println!("   - {}", tag.name);
     }
     println!();
 
@@ -8,7 +8,7 @@
 use eyre::{Result, eyre};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use taskstore::{Filter, FilterOp, IndexValue, Record, Store, now_ms};
+use taskstore::{Filter, FilterOp, IndexValue, Record, Store, StoreError, ValidationError, now_ms};
 
 // ============================================================================
 // Issue Tracker with Status Workflow
@@ -135,6 +135,20 @@ impl Record for Issue {
         }
         fields
     }
+
+    fn validate(&self) -> std::result::Result<(), ValidationError> {
+        if self.title.trim().is_empty() {
+            return Err(ValidationError::new("issue title cannot be empty"));
+        }
+        // Work in progress must have an owner
+        if matches!(self.status, IssueStatus::InProgress | IssueStatus::InReview) && self.assignee.is_none() {
+            return Err(ValidationError::new(format!(
+                "issue in status {:?} must have an assignee",
+                self.status
+            )));
+        }
+        Ok(())
+    }
 }
 
 impl Issue {
@@ -286,8 +300,19 @@ fn main() -> Result<()> {
     }
     println!();
 
+    // Try writing an issue that violates Issue::validate
+    println!("4. Testing record validation...");
+    let mut issue3: Issue = store.get("ISS-003")?.unwrap();
+    issue3.transition(IssueStatus::InProgress)?; // valid transition, but no assignee
+    match store.update(issue3) {
+        Ok(_) => println!("   Update succeeded (unexpected!)"),
+        Err(StoreError::Validation(v)) => println!("   Update rejected (expected): {}", v),
+        Err(e) => return Err(e.into()),
+    }
+    println!();
+
     // Query by status
-    println!("4. Query issues by status...");
+    println!("5. Query issues by status...");
 
     let open_issues: Vec<Issue> = store.list(&[Filter {
         field: "status".to_string(),
@@ -311,7 +336,7 @@ fn main() -> Result<()> {
     println!();
 
     // Query by priority
-    println!("5. Query high-priority issues...");
+    println!("6. Query high-priority issues...");
     let urgent: Vec<Issue> = store.list(&[Filter {
         field: "priority_int".to_string(),
         op: FilterOp::Gte,
@@ -74,6 +74,11 @@ impl Record for Event {
         fields.insert("timestamp".to_string(), IndexValue::Int(self.timestamp));
         fields
     }
+
+    // Backs the per-entity timeline reads below
+    fn composite_indexes() -> &'static [&'static [&'static str]] {
+        &[&["entity_id", "timestamp"]]
+    }
 }
 
 // ============================================================================
@@ -113,19 +118,29 @@ impl EventBuilder {
 // ============================================================================
 
 fn get_events_for_entity(store: &Store, entity_id: &str) -> Result<Vec<Event>> {
-    store.list(&[Filter {
-        field: "entity_id".to_string(),
-        op: FilterOp::Eq,
-        value: IndexValue::String(entity_id.to_string()),
-    }])
+    get_entity_timeline(store, entity_id, i64::MIN, i64::MAX)
+}
+
+/// Events for one entity with `from <= timestamp < to`, oldest first
+fn get_entity_timeline(store: &Store, entity_id: &str, from: i64, to: i64) -> Result<Vec<Event>> {
+    Ok(store.list_timeline(
+        &[Filter {
+            field: "entity_id".to_string(),
+            op: FilterOp::Eq,
+            value: IndexValue::String(entity_id.to_string()),
+        }],
+        "timestamp",
+        from,
+        to,
+    )?)
 }
 
 fn get_events_by_type(store: &Store, event_type: &str) -> Result<Vec<Event>> {
-    store.list(&[Filter {
+    Ok(store.list(&[Filter {
         field: "event_type".to_string(),
         op: FilterOp::Eq,
         value: IndexValue::String(event_type.to_string()),
-    }])
+    }])?)
 }
 
 fn main() -> Result<()> {
@@ -272,8 +287,17 @@ fn main() -> Result<()> {
     }
     println!();
 
+    // Query: A time window of order-001's timeline
+    println!("4. order-001 events in the 2 seconds after it was placed:");
+    let placed_at = order_events[0].timestamp;
+    for event in get_entity_timeline(&store, "order-001", placed_at, placed_at + 2000)? {
+        println!("   {} | {}", event.payload.event_type(), event.timestamp);
+    }
+    println!("   (start inclusive, end exclusive: OrderShipped at +2000 is left out)");
+    println!();
+
     // Query: All login events
-    println!("4. All login events:");
+    println!("5. All login events:");
     let logins = get_events_by_type(&store, "user_logged_in")?;
     for event in &logins {
         if let EventPayload::UserLoggedIn { ip_address } = &event.payload {
@@ -283,7 +307,7 @@ fn main() -> Result<()> {
     println!();
 
     // Query: System alerts
-    println!("5. System alerts:");
+    println!("6. System alerts:");
     let alerts = get_events_by_type(&store, "system_alert")?;
     for event in &alerts {
         if let EventPayload::SystemAlert { level, message } = &event.payload {
@@ -292,25 +316,41 @@ fn main() -> Result<()> {
     }
     println!();
 
-    // Summary
-    println!("6. Event summary:");
-    let all_events: Vec<Event> = store.list(&[])?;
-    let mut type_counts: HashMap<String, usize> = HashMap::new();
-    for event in &all_events {
-        *type_counts.entry(event.payload.event_type().to_string()).or_default() += 1;
+    // Query: Time window, both ends inclusive
+    println!("7. Events in the first three seconds (any entity):");
+    let window_start = builder.base_time + 1000;
+    let window: Vec<Event> = store.list(&[Filter::between("timestamp", window_start, window_start + 2000)])?;
+    for event in window.iter().rev() {
+        println!("   {} | {} | {}", event.id, event.timestamp, event.payload.event_type());
     }
-    println!("   Total events: {}", all_events.len());
+    println!();
+
+    // Summary
+    println!("8. Event summary:");
+    let type_counts = store.count_by::<Event>("event_type")?;
+    println!("   Total events: {}", store.count::<Event>(&[])?);
     for (event_type, count) in &type_counts {
         println!("   - {}: {}", event_type, count);
     }
     println!();
 
+    // Stream every event instead of buffering the whole log in a Vec
+    println!("9. Entities touched (streamed with list_iter):");
+    let mut entities = std::collections::BTreeSet::new();
+    for event in store.list_iter::<Event>(&[])? {
+        entities.insert(event?.entity_id);
+    }
+    println!("   {} entities: {:?}", entities.len(), entities);
+    println!();
     println!("Example complete!");
     println!("\nKey points:");
     println!("  - Events are append-only records");
     println!("  - Each event has entity_id for grouping");
     println!("  - Payload uses serde tagged enum for type safety");
     println!("  - Query by entity_id, event_type, or timestamp range");
+    println!("  - list_timeline reads an entity's events in time order");
+    println!("  - Filter::between selects an inclusive time window");
+    println!("  - list_iter streams a large log page by page");
 
     Ok(())
 }
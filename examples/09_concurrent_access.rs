@@ -10,7 +10,7 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::{Arc, Barrier};
 use std::thread;
-use taskstore::{IndexValue, Record, Store, now_ms};
+use taskstore::{IndexValue, Record, SharedStore, Store, now_ms};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct Counter {
@@ -190,15 +190,123 @@ fn main() -> Result<()> {
         let counter: Counter = store.get("main-counter")?.unwrap();
         println!("   Final counter value: {}", counter.value);
         println!("   (Note: Due to race conditions, may not be exactly 5)");
+        println!("   (Counters should use Store::increment instead, see step 6)");
+    }
+    println!();
+
+    // Share one Store across threads for atomic read-modify-write
+    println!("5. Atomic updates through a SharedStore...");
+    {
+        let shared = SharedStore::open(&base_path)?;
+        let start: Counter = shared.get("main-counter")?.unwrap();
+
+        let handles: Vec<_> = (0..5)
+            .map(|i| {
+                let shared = shared.clone();
+                thread::spawn(move || {
+                    // The closure holds the store lock, so no other thread can
+                    // interleave between the read and the write
+                    shared.with(|store| {
+                        let counter: Counter = store.get("main-counter").unwrap().unwrap();
+                        store
+                            .update(Counter {
+                                value: counter.value + 1,
+                                updated_at: now_ms(),
+                                ..counter
+                            })
+                            .unwrap();
+                    });
+                    i
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            let thread_id = handle.join().unwrap();
+            println!("   Thread {} updated counter", thread_id);
+        }
+
+        let counter: Counter = shared.get("main-counter")?.unwrap();
+        println!("   Counter went from {} to {}", start.value, counter.value);
+        println!("   (Always exactly +5: updates are serialized)");
+    }
+    println!();
+
+    // Store::increment is atomic even across separately opened Stores
+    println!("6. Atomic increments with Store::increment...");
+    {
+        let start: Counter = Store::open(&base_path)?.get("main-counter")?.unwrap();
+
+        let handles: Vec<_> = (0..5)
+            .map(|i| {
+                let path = base_path.clone();
+                thread::spawn(move || {
+                    // Read, add, and write happen under one SQLite write lock
+                    let mut store = Store::open(&path).unwrap();
+                    store.increment::<Counter>("main-counter", "value", 1).unwrap();
+                    i
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            let thread_id = handle.join().unwrap();
+            println!("   Thread {} incremented counter", thread_id);
+        }
+
+        let counter: Counter = Store::open(&base_path)?.get("main-counter")?.unwrap();
+        println!("   Counter went from {} to {}", start.value, counter.value);
+        println!("   (Always exactly +5, with no shared handle needed)");
+    }
+    println!();
+
+    // Store::update_if_unchanged retries a read-modify-write until no one else wrote in between
+    println!("7. Compare-and-swap updates with Store::update_if_unchanged...");
+    {
+        let start: Counter = Store::open(&base_path)?.get("main-counter")?.unwrap();
+
+        let handles: Vec<_> = (0..5)
+            .map(|i| {
+                let path = base_path.clone();
+                thread::spawn(move || {
+                    let mut store = Store::open(&path).unwrap();
+                    let mut attempts = 0;
+                    loop {
+                        attempts += 1;
+                        let counter: Counter = store.get("main-counter").unwrap().unwrap();
+                        let expected = counter.updated_at;
+                        let updated = Counter {
+                            value: counter.value + 1,
+                            updated_at: now_ms(),
+                            ..counter
+                        };
+                        if store.update_if_unchanged(updated, expected).unwrap() {
+                            return (i, attempts);
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            let (thread_id, attempts) = handle.join().unwrap();
+            println!("   Thread {} updated counter after {} attempt(s)", thread_id, attempts);
+        }
+
+        let counter: Counter = Store::open(&base_path)?.get("main-counter")?.unwrap();
+        println!("   Counter went from {} to {}", start.value, counter.value);
+        println!("   (Always exactly +5: stale writes are rejected and retried)");
     }
     println!();
 
     println!("Example complete!");
     println!("\nKey points:");
     println!("  - File locking (fs2) prevents JSONL corruption during concurrent writes");
-    println!("  - Each thread should open its own Store instance");
-    println!("  - Read-modify-write cycles may still have race conditions");
-    println!("  - For atomic increments, use transactions or application-level locking");
+    println!("  - Threads can open their own Store, or share one via SharedStore");
+    println!("  - Read-modify-write cycles across separate Stores may race");
+    println!("  - SharedStore::with makes read-modify-write atomic across threads");
+    println!("  - Store::increment makes counters atomic across Stores and processes");
+    println!("  - Store::update_if_unchanged turns any read-modify-write into a retryable CAS");
 
     Ok(())
 }
@@ -0,0 +1,205 @@
+// Async wrapper over the blocking Store
+
+use crate::error::{StoreError, StoreResult as Result};
+use crate::filter::Filter;
+use crate::record::Record;
+use crate::shared::SharedStore;
+use crate::store::Store;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Async handle to a `Store` for use inside a tokio runtime
+///
+/// Each operation runs the blocking `Store` method on tokio's blocking thread
+/// pool via `spawn_blocking`, so file and SQLite I/O never stall the async
+/// executor. All logic lives in `Store`; this type only moves calls off the
+/// runtime. Clones share one underlying store through a `SharedStore`, so
+/// operations are serialized the same way.
+///
+/// Requires the `async` feature.
+#[derive(Clone)]
+pub struct AsyncStore {
+    inner: SharedStore,
+}
+
+impl AsyncStore {
+    /// Open or create a store at the given path, see `Store::open`
+    pub async fn open<P: Into<PathBuf>>(path: P) -> Result<Self> {
+        let path = path.into();
+        let store = Self::blocking(move || Store::open(path)).await??;
+        Ok(Self::new(store))
+    }
+
+    /// Wrap an already-open store
+    pub fn new(store: Store) -> Self {
+        Self {
+            inner: SharedStore::new(store),
+        }
+    }
+
+    /// Get the `SharedStore` backing this handle, for blocking access
+    pub fn shared(&self) -> &SharedStore {
+        &self.inner
+    }
+
+    /// Run a closure with exclusive access to the store on the blocking pool
+    ///
+    /// Use this for any `Store` method without an async counterpart, or to make
+    /// a read-modify-write sequence atomic.
+    pub async fn with<R, F>(&self, f: F) -> Result<R>
+    where
+        F: FnOnce(&mut Store) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let inner = self.inner.clone();
+        Self::blocking(move || inner.with(f)).await
+    }
+
+    /// Create a new record, see `Store::create`
+    pub async fn create<T: Record>(&self, record: T) -> Result<String> {
+        self.with(move |store| store.create(record)).await?
+    }
+
+    /// Get a record by ID, see `Store::get`
+    pub async fn get<T: Record>(&self, id: &str) -> Result<Option<T>> {
+        let id = id.to_string();
+        self.with(move |store| store.get(&id)).await?
+    }
+
+    /// Update a record, see `Store::update`
+    pub async fn update<T: Record>(&self, record: T) -> Result<()> {
+        self.with(move |store| store.update(record)).await?
+    }
+
+    /// Delete a record, see `Store::delete`
+    pub async fn delete<T: Record>(&self, id: &str) -> Result<()> {
+        let id = id.to_string();
+        self.with(move |store| store.delete::<T>(&id)).await?
+    }
+
+    /// List records with optional filtering, see `Store::list`
+    pub async fn list<T: Record>(&self, filters: &[Filter]) -> Result<Vec<T>> {
+        let filters = filters.to_vec();
+        self.with(move |store| store.list(&filters)).await?
+    }
+
+    /// Sync SQLite from JSONL files, see `Store::sync`
+    pub async fn sync(&self) -> Result<()> {
+        self.with(|store| store.sync()).await?
+    }
+
+    /// Sync one collection from its JSONL file, see `Store::sync_collection`
+    pub async fn sync_collection(&self, collection: &str) -> Result<()> {
+        let collection = collection.to_string();
+        self.with(move |store| store.sync_collection(&collection)).await?
+    }
+
+    /// Rebuild indexes for a record type, see `Store::rebuild_indexes`
+    pub async fn rebuild_indexes<T: Record>(&self) -> Result<usize> {
+        self.with(|store| store.rebuild_indexes::<T>()).await?
+    }
+
+    /// Register a record type for reindexing, see `Store::register`
+    pub async fn register<T: Record>(&self) -> Result<()> {
+        self.with(|store| store.register::<T>()).await
+    }
+
+    /// Rebuild indexes for every collection, see `Store::rebuild_all_indexes`
+    pub async fn rebuild_all_indexes(&self) -> Result<HashMap<String, usize>> {
+        self.with(|store| store.rebuild_all_indexes()).await?
+    }
+
+    async fn blocking<R, F>(f: F) -> Result<R>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        match tokio::task::spawn_blocking(f).await {
+            Ok(value) => Ok(value),
+            // Surface a panic in the store call as if it had happened here
+            Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+            Err(e) => Err(StoreError::Io(std::io::Error::other(format!(
+                "Blocking store task failed: {}",
+                e
+            )))),
+        }
+    }
+}
+
+impl From<Store> for AsyncStore {
+    fn from(store: Store) -> Self {
+        Self::new(store)
+    }
+}
+
+impl From<SharedStore> for AsyncStore {
+    fn from(inner: SharedStore) -> Self {
+        Self { inner }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::FilterOp;
+    use crate::record::IndexValue;
+    use crate::store::now_ms;
+    use serde::{Deserialize, Serialize};
+    use std::collections::HashMap;
+    use tempfile::TempDir;
+
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    struct Task {
+        id: String,
+        status: String,
+        updated_at: i64,
+    }
+
+    impl Record for Task {
+        fn id(&self) -> &str {
+            &self.id
+        }
+
+        fn updated_at(&self) -> i64 {
+            self.updated_at
+        }
+
+        fn collection_name() -> &'static str {
+            "tasks"
+        }
+
+        fn indexed_fields(&self) -> HashMap<String, IndexValue> {
+            let mut fields = HashMap::new();
+            fields.insert("status".to_string(), IndexValue::String(self.status.clone()));
+            fields
+        }
+    }
+
+    #[tokio::test]
+    async fn test_async_store_crud() {
+        let temp = TempDir::new().unwrap();
+        let store = AsyncStore::open(temp.path()).await.unwrap();
+
+        let task = Task {
+            id: "t1".to_string(),
+            status: "pending".to_string(),
+            updated_at: now_ms(),
+        };
+        store.create(task.clone()).await.unwrap();
+
+        let retrieved: Option<Task> = store.get("t1").await.unwrap();
+        assert_eq!(retrieved, Some(task));
+
+        let filters = vec![Filter {
+            field: "status".to_string(),
+            op: FilterOp::Eq,
+            value: IndexValue::String("pending".to_string()),
+        }];
+        let pending: Vec<Task> = store.list(&filters).await.unwrap();
+        assert_eq!(pending.len(), 1);
+
+        store.delete::<Task>("t1").await.unwrap();
+        let retrieved: Option<Task> = store.get("t1").await.unwrap();
+        assert!(retrieved.is_none());
+    }
+}
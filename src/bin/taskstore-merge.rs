@@ -1,7 +1,11 @@
 // Git merge driver for JSONL files
 //
-// Usage: taskstore-merge %O %A %B
-// Where: %O = ancestor file, %A = ours, %B = theirs
+// Usage: taskstore-merge [--union-adds] [--verbose] %O %A %B [%P]
+// Where: %O = ancestor file, %A = ours, %B = theirs, %P = path in the repo (unused)
+//
+// Options:
+//   --union-adds  merge a record added on both sides field by field (see `union_fields`)
+//   --verbose     print per-ID decisions to stderr (also: TASKSTORE_MERGE_LOG=1)
 //
 // Exit codes:
 //   0 = merge successful
@@ -10,12 +14,13 @@
 
 use eyre::{Context, Result};
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::env;
 use std::fs::{self, File};
 use std::io::{BufRead, BufReader};
 use std::path::Path;
 use std::process;
+use taskstore::jsonl::{supersedes, to_canonical_json, updated_at};
 
 fn main() {
     if let Err(e) = run() {
@@ -25,19 +30,50 @@ fn main() {
 }
 
 fn run() -> Result<()> {
-    let args: Vec<String> = env::args().collect();
+    let mut options = MergeOptions {
+        verbose: env::var_os("TASKSTORE_MERGE_LOG").is_some_and(|v| !v.is_empty() && v != "0"),
+        ..Default::default()
+    };
+    let mut args = Vec::new();
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--union-adds" => options.union_adds = true,
+            "--verbose" => options.verbose = true,
+            _ => args.push(arg),
+        }
+    }
 
-    if args.len() != 4 {
-        eprintln!("Usage: taskstore-merge <ancestor> <ours> <theirs>");
+    // git passes %P as well when the driver is configured with it
+    if !(3..=4).contains(&args.len()) {
+        eprintln!("Usage: taskstore-merge [--union-adds] [--verbose] <ancestor> <ours> <theirs> [<path>]");
         eprintln!("Example: taskstore-merge plans.jsonl.base plans.jsonl plans.jsonl.theirs");
-        return Ok(());
+        process::exit(2);
     }
 
-    let ancestor_path = &args[1];
-    let ours_path = &args[2];
-    let theirs_path = &args[3];
+    let ancestor_path = &args[0];
+    let ours_path = &args[1];
+    let theirs_path = &args[2];
 
-    let result = merge_jsonl_files(ancestor_path, ours_path, theirs_path)?;
+    let result = merge_jsonl_files_with(ancestor_path, ours_path, theirs_path, &options)?;
+
+    if options.verbose {
+        eprintln!(
+            "taskstore-merge: ancestor={} ours={} theirs={}",
+            ancestor_path, ours_path, theirs_path
+        );
+        for (id, decision) in &result.decisions {
+            eprintln!("  {}: {}", id, decision.label());
+        }
+        let count = |d: Decision| result.decisions.iter().filter(|(_, decision)| *decision == d).count();
+        eprintln!(
+            "taskstore-merge: {} kept ours, {} kept theirs, {} merged, {} deleted, {} conflicts",
+            count(Decision::Ours),
+            count(Decision::Theirs),
+            count(Decision::Merged),
+            count(Decision::Deleted),
+            count(Decision::Conflict)
+        );
+    }
 
     // Write merged result to ours file (this is what git expects)
     fs::write(ours_path, result.content)?;
@@ -53,10 +89,69 @@ fn run() -> Result<()> {
 struct MergeResult {
     content: String,
     has_conflicts: bool,
+    /// How each ID was resolved, sorted by ID
+    decisions: Vec<(String, Decision)>,
+}
+
+/// How the merge resolved one record ID
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Decision {
+    /// Kept our version (also when both sides agree)
+    Ours,
+    /// Kept their version
+    Theirs,
+    /// Combined fields from both versions
+    Merged,
+    /// Resolved to a tombstone written by a delete
+    Deleted,
+    /// Written as a conflict block
+    Conflict,
+}
+
+impl Decision {
+    /// `Ours` or `Theirs` for whichever side `winner` is, or `Merged` if `record` isn't just the winner
+    fn of(record: &Value, winner: &Value, ours: &Value) -> Self {
+        if record != winner {
+            Decision::Merged
+        } else if std::ptr::eq(winner, ours) {
+            Decision::Ours
+        } else {
+            Decision::Theirs
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Decision::Ours => "kept ours",
+            Decision::Theirs => "kept theirs",
+            Decision::Merged => "merged",
+            Decision::Deleted => "deleted",
+            Decision::Conflict => "conflict",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct MergeOptions {
+    /// Merge a record added on both sides (no ancestor) field by field instead of picking one
+    union_adds: bool,
+    /// Report per-ID decisions and counts on stderr
+    verbose: bool,
 }
 
 /// Merge three JSONL files using three-way merge logic
+#[cfg(test)]
 fn merge_jsonl_files(ancestor_path: &str, ours_path: &str, theirs_path: &str) -> Result<MergeResult> {
+    merge_jsonl_files_with(ancestor_path, ours_path, theirs_path, &MergeOptions::default())
+}
+
+/// Like `merge_jsonl_files`, with options
+fn merge_jsonl_files_with(
+    ancestor_path: &str,
+    ours_path: &str,
+    theirs_path: &str,
+    options: &MergeOptions,
+) -> Result<MergeResult> {
     // Parse all three files
     let ancestor_records = parse_jsonl(ancestor_path)?;
     let ours_records = parse_jsonl(ours_path)?;
@@ -70,6 +165,7 @@ fn merge_jsonl_files(ancestor_path: &str, ours_path: &str, theirs_path: &str) ->
     // Perform three-way merge
     let mut merged = HashMap::new();
     let mut conflicts = Vec::new();
+    let mut decisions = Vec::new();
 
     // Collect all unique IDs
     let mut all_ids: Vec<String> = ours_map
@@ -85,59 +181,73 @@ fn merge_jsonl_files(ancestor_path: &str, ours_path: &str, theirs_path: &str) ->
         let ours = ours_map.get(&id);
         let theirs = theirs_map.get(&id);
 
-        match (ancestor, ours, theirs) {
+        let decision = match (ancestor, ours, theirs) {
             (None, Some(o), None) => {
                 // Added in ours only
-                merged.insert(id, o.clone());
+                merged.insert(id.clone(), o.clone());
+                Decision::Ours
             }
             (None, None, Some(t)) => {
                 // Added in theirs only
-                merged.insert(id, t.clone());
+                merged.insert(id.clone(), t.clone());
+                Decision::Theirs
             }
-            (Some(_), Some(_o), None) => {
-                // Deleted in theirs, keep deletion
-                // (don't add to merged)
+            (Some(_), Some(o), None) => {
+                // Deletes leave a tombstone, so a line missing from theirs was
+                // dropped by a rewrite (e.g. a hand edit), not deleted
+                merged.insert(id.clone(), o.clone());
+                Decision::Ours
             }
-            (Some(_), None, Some(_t)) => {
-                // Deleted in ours, keep deletion
-                // (don't add to merged)
+            (Some(_), None, Some(t)) => {
+                merged.insert(id.clone(), t.clone());
+                Decision::Theirs
             }
             (None, Some(o), Some(t)) => {
-                // Added in both (concurrent add)
+                // Added in both (concurrent add); never a conflict, since there
+                // is no ancestor edit to lose
                 if records_equal(o, t) {
                     merged.insert(id.clone(), o.clone());
+                    Decision::Ours
                 } else {
-                    // Different versions added, use timestamp resolution
-                    let ours_timestamp = get_updated_at(o);
-                    let theirs_timestamp = get_updated_at(t);
-
-                    if ours_timestamp > theirs_timestamp {
-                        merged.insert(id.clone(), o.clone());
-                    } else if theirs_timestamp > ours_timestamp {
-                        merged.insert(id.clone(), t.clone());
+                    let (winner, loser) = newer(o, t);
+                    let record = if options.union_adds && !is_tombstone(winner) && !is_tombstone(loser) {
+                        union_fields(winner, loser)
                     } else {
-                        // Same timestamp, conflict
-                        conflicts.push((id.clone(), o.clone(), t.clone()));
-                    }
+                        winner.clone()
+                    };
+                    let decision = Decision::of(&record, winner, o);
+                    merged.insert(id.clone(), record);
+                    decision
                 }
             }
-            (Some(_), Some(o), Some(t)) => {
+            (Some(a), Some(o), Some(t)) => {
                 // Modified in both (or one), need to merge
                 if records_equal(o, t) {
                     // Both made same change
                     merged.insert(id.clone(), o.clone());
+                    Decision::Ours
+                } else if let Some(record) = merge_fields(a, o, t) {
+                    let (winner, _) = newer(o, t);
+                    let decision = Decision::of(&record, winner, o);
+                    merged.insert(id.clone(), record);
+                    decision
                 } else {
-                    // Different changes, pick based on timestamp
-                    let ours_timestamp = get_updated_at(o);
-                    let theirs_timestamp = get_updated_at(t);
+                    // A tombstone, or the same field changed differently at the
+                    // same timestamp: pick a whole version based on timestamp
+                    let ours_timestamp = updated_at(o);
+                    let theirs_timestamp = updated_at(t);
 
-                    if ours_timestamp > theirs_timestamp {
-                        merged.insert(id.clone(), o.clone());
-                    } else if theirs_timestamp > ours_timestamp {
-                        merged.insert(id.clone(), t.clone());
-                    } else {
+                    if ours_timestamp == theirs_timestamp {
                         // Same timestamp, conflict
                         conflicts.push((id.clone(), o.clone(), t.clone()));
+                        Decision::Conflict
+                    } else if ours_timestamp > theirs_timestamp {
+                        // A newer delete wins outright; a newer edit brings a deleted record back
+                        merged.insert(id.clone(), o.clone());
+                        Decision::Ours
+                    } else {
+                        merged.insert(id.clone(), t.clone());
+                        Decision::Theirs
                     }
                 }
             }
@@ -145,8 +255,14 @@ fn merge_jsonl_files(ancestor_path: &str, ours_path: &str, theirs_path: &str) ->
                 // Other cases: (None, None, None) and (Some(_), None, None)
                 // These shouldn't happen as we're iterating over keys from ours/theirs
                 // but we need to handle them for exhaustiveness
+                continue;
             }
-        }
+        };
+        let decision = match merged.get(&id) {
+            Some(record) if is_tombstone(record) => Decision::Deleted,
+            _ => decision,
+        };
+        decisions.push((id, decision));
     }
 
     // Build output
@@ -159,22 +275,23 @@ fn merge_jsonl_files(ancestor_path: &str, ours_path: &str, theirs_path: &str) ->
 
     for id in ids {
         let record = &merged[id];
-        output.push_str(&serde_json::to_string(record)?);
+        output.push_str(&to_canonical_json(record)?);
         output.push('\n');
     }
 
     // Write conflicts
     for (id, ours, theirs) in conflicts {
         output.push_str(&format!("<<<<<<< OURS ({})\n", id));
-        output.push_str(&serde_json::to_string(&ours)?);
+        output.push_str(&to_canonical_json(&ours)?);
         output.push_str("\n=======\n");
-        output.push_str(&serde_json::to_string(&theirs)?);
+        output.push_str(&to_canonical_json(&theirs)?);
         output.push_str("\n>>>>>>> THEIRS\n");
     }
 
     Ok(MergeResult {
         content: output,
         has_conflicts,
+        decisions,
     })
 }
 
@@ -202,21 +319,14 @@ fn parse_jsonl(path: &str) -> Result<Vec<Value>> {
     Ok(records)
 }
 
-/// Build a map of ID -> latest record (by updated_at)
+/// Build a map of ID -> latest record, picked as the store's reads do (see `jsonl::supersedes`)
 fn build_latest_map(records: Vec<Value>) -> HashMap<String, Value> {
-    let mut map = HashMap::new();
+    let mut map: HashMap<String, Value> = HashMap::new();
 
     for record in records {
         if let Some(id) = record.get("id").and_then(|v| v.as_str()) {
             let id = id.to_string();
-            let timestamp = get_updated_at(&record);
-
-            if let Some(existing) = map.get(&id) {
-                let existing_timestamp = get_updated_at(existing);
-                if timestamp > existing_timestamp {
-                    map.insert(id, record);
-                }
-            } else {
+            if map.get(&id).is_none_or(|existing| supersedes(&record, existing)) {
                 map.insert(id, record);
             }
         }
@@ -225,13 +335,74 @@ fn build_latest_map(records: Vec<Value>) -> HashMap<String, Value> {
     map
 }
 
-/// Get updated_at timestamp from a record (or created_at as fallback)
-fn get_updated_at(record: &Value) -> i64 {
-    record
-        .get("updated_at")
-        .and_then(|v| v.as_i64())
-        .or_else(|| record.get("created_at").and_then(|v| v.as_i64()))
-        .unwrap_or(0)
+/// Order two versions of a record as `(winner, loser)`
+///
+/// The newer `updated_at` wins. On equal timestamps the version with the
+/// lexicographically larger JSON wins (see `jsonl::supersedes`), so both sides
+/// of a merge resolve the same way regardless of which one is "ours".
+fn newer<'a>(a: &'a Value, b: &'a Value) -> (&'a Value, &'a Value) {
+    if supersedes(b, a) { (b, a) } else { (a, b) }
+}
+
+/// Union of two versions of a record added on both sides
+///
+/// Fields only one side has are kept. Where both sides set a field to
+/// different values, the winner's value (see `newer`) takes precedence,
+/// including for `updated_at`.
+fn union_fields(winner: &Value, loser: &Value) -> Value {
+    let mut merged = winner.clone();
+    if let (Some(fields), Some(other)) = (merged.as_object_mut(), loser.as_object()) {
+        for (key, value) in other {
+            fields.entry(key.clone()).or_insert_with(|| value.clone());
+        }
+    }
+    merged
+}
+
+/// Merge two edits of a record field by field against their common ancestor
+///
+/// A field changed on one side only takes that side's value, so edits to
+/// different fields both survive; that includes fields one side added or
+/// removed. A field changed differently on both sides takes the newer
+/// version's value (see `newer`), which also makes `updated_at` the newer
+/// one. Field values are compared whole; nested objects aren't merged.
+///
+/// Returns `None` if a field changed differently at equal `updated_at` (a
+/// genuine conflict), if either side is a tombstone, or if any version isn't
+/// an object.
+fn merge_fields(ancestor: &Value, ours: &Value, theirs: &Value) -> Option<Value> {
+    if is_tombstone(ours) || is_tombstone(theirs) {
+        return None;
+    }
+    let (a, o, t) = (ancestor.as_object()?, ours.as_object()?, theirs.as_object()?);
+    let ours_newer = std::ptr::eq(newer(ours, theirs).0, ours);
+    let tied = updated_at(ours) == updated_at(theirs);
+
+    let keys: BTreeSet<&String> = o.keys().chain(t.keys()).collect();
+    let mut merged = serde_json::Map::new();
+    for key in keys {
+        let (av, ov, tv) = (a.get(key), o.get(key), t.get(key));
+        let value = if ov == tv || tv == av {
+            ov
+        } else if ov == av {
+            tv
+        } else if tied {
+            return None;
+        } else if ours_newer {
+            ov
+        } else {
+            tv
+        };
+        if let Some(value) = value {
+            merged.insert(key.clone(), value.clone());
+        }
+    }
+    Some(Value::Object(merged))
+}
+
+/// True for a tombstone line written by a delete
+fn is_tombstone(record: &Value) -> bool {
+    record.get("deleted").and_then(|v| v.as_bool()).unwrap_or(false)
 }
 
 /// Check if two records are semantically equal (ignoring formatting)
@@ -285,6 +456,22 @@ mod tests {
 
         assert!(!result.has_conflicts);
         assert!(result.content.contains("Updated by us"));
+        assert_eq!(result.decisions, vec![("1".to_string(), Decision::Ours)]);
+    }
+
+    #[test]
+    fn test_build_latest_map_picks_like_reads() {
+        let a = serde_json::json!({"id": "1", "title": "A", "updated_at": 1000});
+        let b = serde_json::json!({"id": "1", "title": "B", "updated_at": 1000});
+        // Equal timestamps go to the larger JSON whichever line comes first
+        for records in [vec![a.clone(), b.clone()], vec![b.clone(), a.clone()]] {
+            assert_eq!(build_latest_map(records)["1"], b);
+        }
+
+        // Only `updated_at` counts, as in `jsonl::read_jsonl_latest`
+        let created = serde_json::json!({"id": "2", "title": "Created", "created_at": 5000});
+        let updated = serde_json::json!({"id": "2", "title": "Updated", "updated_at": 1000});
+        assert_eq!(build_latest_map(vec![updated.clone(), created])["2"], updated);
     }
 
     #[test]
@@ -326,6 +513,111 @@ mod tests {
         assert!(result.content.contains("Updated by them")); // Theirs wins (newer)
     }
 
+    #[test]
+    fn test_merge_keeps_fields_added_by_older_side() {
+        let temp = TempDir::new().unwrap();
+
+        let ancestor = temp.path().join("ancestor.jsonl");
+        fs::write(
+            &ancestor,
+            r#"{"id":"1","title":"Original","updated_at":1000}
+"#,
+        )
+        .unwrap();
+
+        // Ours runs a newer client that writes `labels`; theirs writes `priority`
+        let ours = temp.path().join("ours.jsonl");
+        fs::write(
+            &ours,
+            r#"{"id":"1","title":"Updated by us","labels":["x"],"updated_at":2000}
+"#,
+        )
+        .unwrap();
+
+        let theirs = temp.path().join("theirs.jsonl");
+        fs::write(
+            &theirs,
+            r#"{"id":"1","title":"Updated by them","priority":3,"updated_at":3000}
+"#,
+        )
+        .unwrap();
+
+        let result = merge_jsonl_files(
+            ancestor.to_str().unwrap(),
+            ours.to_str().unwrap(),
+            theirs.to_str().unwrap(),
+        )
+        .unwrap();
+
+        assert!(!result.has_conflicts);
+        let merged: Value = serde_json::from_str(result.content.trim()).unwrap();
+        assert_eq!(
+            merged,
+            serde_json::json!({"id":"1","title":"Updated by them","priority":3,"labels":["x"],"updated_at":3000})
+        );
+        assert_eq!(result.decisions, vec![("1".to_string(), Decision::Merged)]);
+    }
+
+    #[test]
+    fn test_merge_edits_to_different_fields() {
+        let temp = TempDir::new().unwrap();
+
+        let ancestor = temp.path().join("ancestor.jsonl");
+        fs::write(
+            &ancestor,
+            r#"{"id":"1","title":"Original","status":"open","owner":"amy","updated_at":1000}
+{"id":"2","title":"Original","status":"open","updated_at":1000}
+"#,
+        )
+        .unwrap();
+
+        // Record 1: ours retitles, theirs closes, both reassign
+        // Record 2: different fields at the same timestamp still merge
+        let ours = temp.path().join("ours.jsonl");
+        fs::write(
+            &ours,
+            r#"{"id":"1","title":"Retitled","status":"open","owner":"bob","updated_at":2000}
+{"id":"2","title":"Retitled","status":"open","updated_at":2000}
+"#,
+        )
+        .unwrap();
+
+        let theirs = temp.path().join("theirs.jsonl");
+        fs::write(
+            &theirs,
+            r#"{"id":"1","title":"Original","status":"done","owner":"cat","updated_at":3000}
+{"id":"2","title":"Original","status":"done","updated_at":2000}
+"#,
+        )
+        .unwrap();
+
+        let result = merge_jsonl_files(
+            ancestor.to_str().unwrap(),
+            ours.to_str().unwrap(),
+            theirs.to_str().unwrap(),
+        )
+        .unwrap();
+
+        assert!(!result.has_conflicts);
+        let merged: Vec<Value> = result
+            .content
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        // `owner` changed on both sides: the newer version (theirs) wins it
+        assert_eq!(
+            merged,
+            vec![
+                serde_json::json!({"id":"1","title":"Retitled","status":"done","owner":"cat","updated_at":3000}),
+                serde_json::json!({"id":"2","title":"Retitled","status":"done","updated_at":2000}),
+            ]
+        );
+        assert_eq!(
+            result.decisions,
+            vec![("1".to_string(), Decision::Merged), ("2".to_string(), Decision::Merged)]
+        );
+    }
+
     #[test]
     fn test_merge_same_timestamp_conflict() {
         let temp = TempDir::new().unwrap();
@@ -364,6 +656,7 @@ mod tests {
         assert!(result.has_conflicts);
         assert!(result.content.contains("<<<<<<< OURS"));
         assert!(result.content.contains(">>>>>>> THEIRS"));
+        assert_eq!(result.decisions, vec![("1".to_string(), Decision::Conflict)]);
     }
 
     #[test]
@@ -398,5 +691,143 @@ mod tests {
 
         assert!(!result.has_conflicts);
         assert!(result.content.contains("Added by them")); // Newer wins
+
+        // Same timestamp: a deterministic pick, the same whichever side is ours
+        fs::write(
+            &theirs,
+            r#"{"id":"1","title":"Added by them","updated_at":1000}
+"#,
+        )
+        .unwrap();
+        let forward = merge_jsonl_files(
+            ancestor.to_str().unwrap(),
+            ours.to_str().unwrap(),
+            theirs.to_str().unwrap(),
+        )
+        .unwrap();
+        let backward = merge_jsonl_files(
+            ancestor.to_str().unwrap(),
+            theirs.to_str().unwrap(),
+            ours.to_str().unwrap(),
+        )
+        .unwrap();
+        assert!(!forward.has_conflicts);
+        assert!(!forward.content.contains("<<<<<<<"));
+        assert_eq!(forward.content, backward.content);
+    }
+
+    #[test]
+    fn test_merge_added_in_both_union() {
+        let temp = TempDir::new().unwrap();
+
+        let ancestor = temp.path().join("ancestor.jsonl");
+        fs::write(&ancestor, "").unwrap();
+
+        let ours = temp.path().join("ours.jsonl");
+        fs::write(
+            &ours,
+            r#"{"id":"1","title":"Ours","owner":"alice","updated_at":1000}
+"#,
+        )
+        .unwrap();
+
+        let theirs = temp.path().join("theirs.jsonl");
+        fs::write(
+            &theirs,
+            r#"{"id":"1","title":"Theirs","status":"open","updated_at":2000}
+"#,
+        )
+        .unwrap();
+
+        let result = merge_jsonl_files_with(
+            ancestor.to_str().unwrap(),
+            ours.to_str().unwrap(),
+            theirs.to_str().unwrap(),
+            &MergeOptions {
+                union_adds: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert!(!result.has_conflicts);
+        let merged: Value = serde_json::from_str(result.content.trim()).unwrap();
+        assert_eq!(
+            merged,
+            serde_json::json!({"id":"1","title":"Theirs","owner":"alice","status":"open","updated_at":2000})
+        );
+    }
+
+    #[test]
+    fn test_merge_tombstones() {
+        let temp = TempDir::new().unwrap();
+
+        let ancestor = temp.path().join("ancestor.jsonl");
+        fs::write(
+            &ancestor,
+            concat!(
+                r#"{"id":"1","title":"Deleted by us","updated_at":1000}"#,
+                "\n",
+                r#"{"id":"2","title":"Edited after delete","updated_at":1000}"#,
+                "\n",
+                r#"{"id":"3","title":"Dropped line","updated_at":1000}"#,
+                "\n",
+            ),
+        )
+        .unwrap();
+
+        let ours = temp.path().join("ours.jsonl");
+        fs::write(
+            &ours,
+            concat!(
+                r#"{"deleted":true,"deleted_at":2000,"id":"1","title":"Deleted by us","updated_at":2000}"#,
+                "\n",
+                r#"{"deleted":true,"deleted_at":2000,"id":"2","title":"Edited after delete","updated_at":2000}"#,
+                "\n",
+            ),
+        )
+        .unwrap();
+
+        let theirs = temp.path().join("theirs.jsonl");
+        fs::write(
+            &theirs,
+            concat!(
+                r#"{"id":"1","title":"Edited before delete","note":"new","updated_at":1500}"#,
+                "\n",
+                r#"{"id":"2","title":"Restored","updated_at":3000}"#,
+                "\n",
+                r#"{"id":"3","title":"Dropped line","updated_at":1000}"#,
+                "\n",
+            ),
+        )
+        .unwrap();
+
+        let result = merge_jsonl_files(
+            ancestor.to_str().unwrap(),
+            ours.to_str().unwrap(),
+            theirs.to_str().unwrap(),
+        )
+        .unwrap();
+
+        assert!(!result.has_conflicts);
+        let lines: Vec<Value> = result
+            .content
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        // The newer tombstone wins without picking up the other side's fields
+        assert!(is_tombstone(&lines[0]));
+        assert!(lines[0].get("note").is_none());
+        assert_eq!(lines[1]["title"], "Restored");
+        // A missing line is not a delete
+        assert_eq!(lines[2]["title"], "Dropped line");
+        assert_eq!(
+            result.decisions,
+            vec![
+                ("1".to_string(), Decision::Deleted),
+                ("2".to_string(), Decision::Theirs),
+                ("3".to_string(), Decision::Theirs),
+            ]
+        );
     }
 }
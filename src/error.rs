@@ -0,0 +1,169 @@
+// Error types returned by the store
+
+use crate::record::ValidationError;
+use rusqlite::ErrorCode;
+
+/// Result type returned by `Store` and friends
+pub type StoreResult<T> = std::result::Result<T, StoreError>;
+
+/// Everything that can go wrong in a store operation
+///
+/// Library callers can match on the variant; applications using `eyre` or
+/// `anyhow` can keep using `?`, since this implements `std::error::Error`.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum StoreError {
+    /// No record with this ID exists in the collection
+    NotFound { collection: String, id: String },
+    /// A write collided with existing data, see `DuplicateKeyError`
+    Conflict(DuplicateKeyError),
+    /// JSONL or database content that can't be interpreted
+    Corrupt(String),
+    /// SQLite stayed busy or locked past the busy timeout and retries
+    Locked(rusqlite::Error),
+    /// Filesystem error
+    Io(std::io::Error),
+    /// A record couldn't be serialized or deserialized
+    Serde(serde_json::Error),
+    /// A name, field, filter, or option the store can't use (e.g. an unindexed field)
+    Schema(String),
+    /// A record failed `Record::validate`, or its ID is malformed
+    Validation(ValidationError),
+    /// Any other SQLite error
+    Sqlite(rusqlite::Error),
+    /// A write was attempted on a store that can't accept it
+    ReadOnly(String),
+    /// Git hooks or merge driver setup failed
+    Git(String),
+}
+
+impl StoreError {
+    /// Wrap an I/O error, prefixing its message with what was being attempted
+    pub(crate) fn io(context: &str, err: std::io::Error) -> Self {
+        StoreError::Io(std::io::Error::new(err.kind(), format!("{}: {}", context, err)))
+    }
+
+    /// True for `NotFound`
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, StoreError::NotFound { .. })
+    }
+}
+
+impl std::fmt::Display for StoreError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StoreError::NotFound { collection, id } => write!(f, "Record not found in {}: {}", collection, id),
+            StoreError::Conflict(e) => write!(f, "{}", e),
+            StoreError::Corrupt(message) => write!(f, "Corrupt store data: {}", message),
+            StoreError::Locked(e) => write!(f, "Database is locked: {}", e),
+            StoreError::Io(e) => write!(f, "{}", e),
+            StoreError::Serde(e) => write!(f, "Failed to (de)serialize record: {}", e),
+            StoreError::Schema(message) => write!(f, "{}", message),
+            StoreError::Validation(e) => write!(f, "{}", e),
+            StoreError::Sqlite(e) => write!(f, "SQLite error: {}", e),
+            StoreError::ReadOnly(message) => write!(f, "{}", message),
+            StoreError::Git(message) => write!(f, "Git integration failed: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        // Variants that display their inner error verbatim forward to its source,
+        // so error reports don't print the same message twice
+        match self {
+            StoreError::Conflict(e) => e.source(),
+            StoreError::Locked(e) | StoreError::Sqlite(e) => Some(e),
+            StoreError::Io(e) => e.source(),
+            StoreError::Serde(e) => Some(e),
+            StoreError::Validation(e) => e.source(),
+            _ => None,
+        }
+    }
+}
+
+impl From<rusqlite::Error> for StoreError {
+    fn from(err: rusqlite::Error) -> Self {
+        match err.sqlite_error_code() {
+            Some(ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked) => StoreError::Locked(err),
+            _ => StoreError::Sqlite(err),
+        }
+    }
+}
+
+impl From<std::io::Error> for StoreError {
+    fn from(err: std::io::Error) -> Self {
+        StoreError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for StoreError {
+    fn from(err: serde_json::Error) -> Self {
+        StoreError::Serde(err)
+    }
+}
+
+impl From<ValidationError> for StoreError {
+    fn from(err: ValidationError) -> Self {
+        StoreError::Validation(err)
+    }
+}
+
+impl From<DuplicateKeyError> for StoreError {
+    fn from(err: DuplicateKeyError) -> Self {
+        StoreError::Conflict(err)
+    }
+}
+
+/// A write would give a record the same value for a unique field as an existing record
+///
+/// See `Record::unique_fields`. Returned as `StoreError::Conflict`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateKeyError {
+    pub collection: String,
+    pub field: String,
+    pub value: String,
+    /// ID of the record that already holds the value
+    pub existing_id: String,
+}
+
+impl std::fmt::Display for DuplicateKeyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Duplicate key in {}: {} = {} already used by {}",
+            self.collection, self.field, self.value, self.existing_id
+        )
+    }
+}
+
+impl std::error::Error for DuplicateKeyError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_busy_sqlite_error_is_locked() {
+        let busy = rusqlite::Error::SqliteFailure(rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_BUSY), None);
+        assert!(matches!(StoreError::from(busy), StoreError::Locked(_)));
+
+        let other = rusqlite::Error::QueryReturnedNoRows;
+        assert!(matches!(StoreError::from(other), StoreError::Sqlite(_)));
+    }
+
+    #[test]
+    fn test_converts_into_eyre() {
+        fn fails() -> eyre::Result<()> {
+            Err(StoreError::NotFound {
+                collection: "plans".to_string(),
+                id: "p1".to_string(),
+            })?;
+            Ok(())
+        }
+
+        let err = fails().unwrap_err();
+        assert_eq!(err.to_string(), "Record not found in plans: p1");
+        assert!(err.downcast_ref::<StoreError>().unwrap().is_not_found());
+    }
+}
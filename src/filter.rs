@@ -1,5 +1,6 @@
 // Query filtering for generic records
 
+use crate::error::StoreError;
 use crate::record::IndexValue;
 
 /// Filter for querying records
@@ -13,6 +14,203 @@ pub struct Filter {
     pub value: IndexValue,
 }
 
+impl Filter {
+    /// Match values from `low` to `high`, both inclusive
+    ///
+    /// Both bounds must be the same kind (`Int`, `Float`, or `String`). If
+    /// `low > high` nothing matches.
+    pub fn between(field: impl Into<String>, low: impl Into<IndexValue>, high: impl Into<IndexValue>) -> Self {
+        Filter {
+            field: field.into(),
+            op: FilterOp::Between,
+            value: IndexValue::Range(Box::new(low.into()), Box::new(high.into())),
+        }
+    }
+
+    /// Match timestamps strictly before `at_ms` (milliseconds since the epoch)
+    ///
+    /// The value is an `IndexValue::DateTime`, so it displays as a date but
+    /// compares with fields indexed as millisecond `Int`s.
+    pub fn before(field: impl Into<String>, at_ms: i64) -> Self {
+        Filter {
+            field: field.into(),
+            op: FilterOp::Lt,
+            value: IndexValue::DateTime(at_ms),
+        }
+    }
+
+    /// Match timestamps strictly after `at_ms`, see `before`
+    pub fn after(field: impl Into<String>, at_ms: i64) -> Self {
+        Filter {
+            field: field.into(),
+            op: FilterOp::Gt,
+            value: IndexValue::DateTime(at_ms),
+        }
+    }
+
+    /// Evaluate the filter against a record's JSON, without SQLite
+    ///
+    /// Follows the SQL a `Store` runs: a missing or null field, or one of a
+    /// different kind than the value, matches nothing, and `Contains` and
+    /// `StartsWith` ignore ASCII case like SQLite's `LIKE`. Any top-level field
+    /// can be tested, indexed or not. A `DateTime` value is compared with a
+    /// string field as a string, as `Store::list_json` does.
+    pub fn matches(&self, record: &serde_json::Value) -> bool {
+        let Some(json) = record.get(&self.field) else {
+            return false;
+        };
+        if json.is_string()
+            && let Some(value) = self.value.datetime_as_string()
+        {
+            let filter = Filter {
+                field: self.field.clone(),
+                op: self.op,
+                value,
+            };
+            return filter.matches(record);
+        }
+        let Some(actual) = json_as(json, &self.value) else {
+            return false;
+        };
+
+        match (self.op, &self.value, &actual) {
+            (FilterOp::Between, IndexValue::Range(low, high), _) => {
+                compare(&actual, low).is_some_and(|o| o.is_ge()) && compare(&actual, high).is_some_and(|o| o.is_le())
+            }
+            (FilterOp::Contains, IndexValue::String(pattern), IndexValue::String(s)) => {
+                like(&pattern.chars().collect::<Vec<_>>(), &s.chars().collect::<Vec<_>>())
+            }
+            (FilterOp::ContainsIgnoreCase, IndexValue::String(needle), IndexValue::String(s)) => {
+                s.to_ascii_lowercase().contains(&needle.to_ascii_lowercase())
+            }
+            (FilterOp::StartsWith, IndexValue::String(prefix), IndexValue::String(s)) => {
+                s.to_ascii_lowercase().starts_with(&prefix.to_ascii_lowercase())
+            }
+            (FilterOp::EqIgnoreCase, IndexValue::String(value), IndexValue::String(s)) => s.eq_ignore_ascii_case(value),
+            (FilterOp::NeIgnoreCase, IndexValue::String(value), IndexValue::String(s)) => {
+                !s.eq_ignore_ascii_case(value)
+            }
+            (op, value, _) => compare(&actual, value).is_some_and(|o| match op {
+                FilterOp::Eq => o.is_eq(),
+                FilterOp::Ne => o.is_ne(),
+                FilterOp::Gt => o.is_gt(),
+                FilterOp::Lt => o.is_lt(),
+                FilterOp::Gte => o.is_ge(),
+                FilterOp::Lte => o.is_le(),
+                _ => false,
+            }),
+        }
+    }
+}
+
+/// A JSON field as an `IndexValue` of the same kind as `like`, if it is one
+fn json_as(json: &serde_json::Value, like: &IndexValue) -> Option<IndexValue> {
+    match like {
+        IndexValue::String(_) => json.as_str().map(|s| IndexValue::String(s.to_string())),
+        IndexValue::Int(_) | IndexValue::DateTime(_) => json.as_i64().map(IndexValue::Int),
+        IndexValue::Float(_) => json.as_f64().map(IndexValue::Float),
+        IndexValue::Bool(_) => json.as_bool().map(IndexValue::Bool),
+        IndexValue::Range(low, _) => json_as(json, low),
+    }
+}
+
+/// Order two values of the same kind; `None` for different kinds or NaN
+fn compare(a: &IndexValue, b: &IndexValue) -> Option<std::cmp::Ordering> {
+    match (a, b) {
+        (IndexValue::String(a), IndexValue::String(b)) => Some(a.cmp(b)),
+        (IndexValue::Int(a) | IndexValue::DateTime(a), IndexValue::Int(b) | IndexValue::DateTime(b)) => Some(a.cmp(b)),
+        (IndexValue::Float(a), IndexValue::Float(b)) => a.partial_cmp(b),
+        (IndexValue::Bool(a), IndexValue::Bool(b)) => Some(a.cmp(b)),
+        _ => None,
+    }
+}
+
+/// SQLite's `LIKE` without `ESCAPE`: `%` matches any run of characters, `_` any one, ignoring ASCII case
+fn like(pattern: &[char], text: &[char]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some(('%', rest)) => (0..=text.len()).any(|skip| like(rest, &text[skip..])),
+        Some(('_', rest)) => !text.is_empty() && like(rest, &text[1..]),
+        Some((c, rest)) => text.first().is_some_and(|t| t.eq_ignore_ascii_case(c)) && like(rest, &text[1..]),
+    }
+}
+
+/// Filters combined with AND and OR, for `Store::list_where`
+///
+/// ```
+/// use taskstore::{FilterExpr, Query};
+///
+/// // status == "pending" OR status == "running"
+/// let expr = FilterExpr::any(Query::new().eq("status", "pending").eq("status", "running").filters());
+/// ```
+#[derive(Debug, Clone)]
+pub enum FilterExpr {
+    /// A single filter
+    Cond(Filter),
+    /// Every term must match; an empty `And` matches everything
+    And(Vec<FilterExpr>),
+    /// At least one term must match; an empty `Or` matches nothing
+    Or(Vec<FilterExpr>),
+}
+
+impl FilterExpr {
+    /// All of `filters` must match, like `Store::list`
+    pub fn all(filters: &[Filter]) -> Self {
+        FilterExpr::And(filters.iter().cloned().map(FilterExpr::Cond).collect())
+    }
+
+    /// At least one of `filters` must match
+    pub fn any(filters: &[Filter]) -> Self {
+        FilterExpr::Or(filters.iter().cloned().map(FilterExpr::Cond).collect())
+    }
+
+    /// Merge groups nested in a group of the same kind and unwrap single-term groups
+    pub(crate) fn flattened(self) -> Self {
+        match self {
+            FilterExpr::Cond(_) => self,
+            FilterExpr::And(terms) => Self::flatten_group(terms, true),
+            FilterExpr::Or(terms) => Self::flatten_group(terms, false),
+        }
+    }
+
+    fn flatten_group(terms: Vec<FilterExpr>, and: bool) -> Self {
+        let mut flat = Vec::with_capacity(terms.len());
+        for term in terms.into_iter().map(FilterExpr::flattened) {
+            match term {
+                FilterExpr::And(inner) if and => flat.extend(inner),
+                FilterExpr::Or(inner) if !and => flat.extend(inner),
+                term => flat.push(term),
+            }
+        }
+        match (flat.len(), and) {
+            (1, _) => flat.pop().expect("one term"),
+            (_, true) => FilterExpr::And(flat),
+            (_, false) => FilterExpr::Or(flat),
+        }
+    }
+
+    /// The filters of a plain AND (or a single filter), if that's all this is
+    pub(crate) fn as_conjunction(&self) -> Option<Vec<Filter>> {
+        match self {
+            FilterExpr::Cond(filter) => Some(vec![filter.clone()]),
+            FilterExpr::And(terms) => terms
+                .iter()
+                .map(|term| match term {
+                    FilterExpr::Cond(filter) => Some(filter.clone()),
+                    _ => None,
+                })
+                .collect(),
+            FilterExpr::Or(_) => None,
+        }
+    }
+}
+
+impl From<Filter> for FilterExpr {
+    fn from(filter: Filter) -> Self {
+        FilterExpr::Cond(filter)
+    }
+}
+
 /// Comparison operators for filtering
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FilterOp {
@@ -23,6 +221,17 @@ pub enum FilterOp {
     Gte,      // >=
     Lte,      // <=
     Contains, // LIKE %value%
+    /// `Eq` ignoring ASCII case; `String` values only
+    EqIgnoreCase,
+    /// `Ne` ignoring ASCII case; `String` values only
+    NeIgnoreCase,
+    /// Substring match ignoring ASCII case; `String` values only
+    ContainsIgnoreCase,
+    /// Prefix match ignoring ASCII case, like SQLite's `LIKE`; `String` values
+    /// only, `%` and `_` in the value match literally
+    StartsWith,
+    /// `low <= field <= high`, with an `IndexValue::Range` value (see `Filter::between`)
+    Between,
 }
 
 impl FilterOp {
@@ -36,10 +245,300 @@ impl FilterOp {
             FilterOp::Gte => ">=",
             FilterOp::Lte => "<=",
             FilterOp::Contains => "LIKE",
+            FilterOp::EqIgnoreCase => "=",
+            FilterOp::NeIgnoreCase => "!=",
+            FilterOp::ContainsIgnoreCase => "LIKE",
+            FilterOp::StartsWith => "LIKE",
+            FilterOp::Between => "BETWEEN",
+        }
+    }
+
+    /// True for the `*IgnoreCase` operators
+    pub fn is_case_insensitive(self) -> bool {
+        matches!(
+            self,
+            FilterOp::EqIgnoreCase | FilterOp::NeIgnoreCase | FilterOp::ContainsIgnoreCase
+        )
+    }
+
+    /// True for operators that only accept `IndexValue::String` values
+    pub fn requires_string(self) -> bool {
+        self.is_case_insensitive() || self == FilterOp::StartsWith
+    }
+}
+
+/// Aggregate functions for `Store::aggregate`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Agg {
+    Sum,
+    Avg,
+    Min,
+    Max,
+    Count,
+}
+
+impl Agg {
+    pub(crate) fn to_sql(self) -> &'static str {
+        match self {
+            Agg::Sum => "SUM",
+            Agg::Avg => "AVG",
+            Agg::Min => "MIN",
+            Agg::Max => "MAX",
+            Agg::Count => "COUNT",
+        }
+    }
+}
+
+/// Sort direction for `ListOptions::order_by`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortDir {
+    Asc,
+    Desc,
+}
+
+impl SortDir {
+    pub(crate) fn to_sql(self) -> &'static str {
+        match self {
+            SortDir::Asc => "ASC",
+            SortDir::Desc => "DESC",
+        }
+    }
+}
+
+/// Ordering and pagination for `Store::list_with`
+///
+/// `order_by` names an indexed field, or `updated_at` / `id` to sort on the
+/// record columns (`id` sorts by `Record::id_index_value` when the collection
+/// indexes one, e.g. numerically for integer keys). Without it records come most recently updated first, as
+/// with `Store::list`. Ties are broken by ID so pages are stable.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ListOptions {
+    pub order_by: Option<(String, SortDir)>,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+/// Fluent builder for filters and list options
+///
+/// ```
+/// use taskstore::Query;
+///
+/// let query = Query::new().eq("status", "pending").gte("priority", 7).order_by("priority").limit(20);
+/// assert_eq!(query.filters().len(), 2);
+/// ```
+///
+/// Pass `query.filters()` anywhere a `&[Filter]` is taken, or both halves to
+/// `Store::list_with(query.filters(), query.options())`.
+#[derive(Debug, Clone, Default)]
+pub struct Query {
+    filters: Vec<Filter>,
+    options: ListOptions,
+}
+
+impl Query {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a filter with an explicit operator
+    pub fn filter(mut self, field: impl Into<String>, op: FilterOp, value: impl Into<IndexValue>) -> Self {
+        self.filters.push(Filter {
+            field: field.into(),
+            op,
+            value: value.into(),
+        });
+        self
+    }
+
+    pub fn eq(self, field: impl Into<String>, value: impl Into<IndexValue>) -> Self {
+        self.filter(field, FilterOp::Eq, value)
+    }
+
+    pub fn ne(self, field: impl Into<String>, value: impl Into<IndexValue>) -> Self {
+        self.filter(field, FilterOp::Ne, value)
+    }
+
+    pub fn gt(self, field: impl Into<String>, value: impl Into<IndexValue>) -> Self {
+        self.filter(field, FilterOp::Gt, value)
+    }
+
+    pub fn gte(self, field: impl Into<String>, value: impl Into<IndexValue>) -> Self {
+        self.filter(field, FilterOp::Gte, value)
+    }
+
+    pub fn lt(self, field: impl Into<String>, value: impl Into<IndexValue>) -> Self {
+        self.filter(field, FilterOp::Lt, value)
+    }
+
+    pub fn lte(self, field: impl Into<String>, value: impl Into<IndexValue>) -> Self {
+        self.filter(field, FilterOp::Lte, value)
+    }
+
+    /// Add a `Filter::between`
+    pub fn between(
+        mut self,
+        field: impl Into<String>,
+        low: impl Into<IndexValue>,
+        high: impl Into<IndexValue>,
+    ) -> Self {
+        self.filters.push(Filter::between(field, low, high));
+        self
+    }
+
+    /// Add a `Filter::before`
+    pub fn before(mut self, field: impl Into<String>, at_ms: i64) -> Self {
+        self.filters.push(Filter::before(field, at_ms));
+        self
+    }
+
+    /// Add a `Filter::after`
+    pub fn after(mut self, field: impl Into<String>, at_ms: i64) -> Self {
+        self.filters.push(Filter::after(field, at_ms));
+        self
+    }
+
+    pub fn contains(self, field: impl Into<String>, value: impl Into<IndexValue>) -> Self {
+        self.filter(field, FilterOp::Contains, value)
+    }
+
+    /// Match string values of `field` that start with `prefix`, ignoring ASCII case
+    pub fn starts_with(self, field: impl Into<String>, prefix: impl Into<String>) -> Self {
+        self.filter(field, FilterOp::StartsWith, prefix.into())
+    }
+
+    pub fn eq_ignore_case(self, field: impl Into<String>, value: impl Into<String>) -> Self {
+        self.filter(field, FilterOp::EqIgnoreCase, value.into())
+    }
+
+    pub fn ne_ignore_case(self, field: impl Into<String>, value: impl Into<String>) -> Self {
+        self.filter(field, FilterOp::NeIgnoreCase, value.into())
+    }
+
+    pub fn contains_ignore_case(self, field: impl Into<String>, value: impl Into<String>) -> Self {
+        self.filter(field, FilterOp::ContainsIgnoreCase, value.into())
+    }
+
+    /// Sort ascending by `field`
+    pub fn order_by(mut self, field: impl Into<String>) -> Self {
+        self.options.order_by = Some((field.into(), SortDir::Asc));
+        self
+    }
+
+    /// Sort descending by `field`
+    pub fn order_by_desc(mut self, field: impl Into<String>) -> Self {
+        self.options.order_by = Some((field.into(), SortDir::Desc));
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.options.limit = Some(limit);
+        self
+    }
+
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.options.offset = Some(offset);
+        self
+    }
+
+    pub fn filters(&self) -> &[Filter] {
+        &self.filters
+    }
+
+    pub fn options(&self) -> &ListOptions {
+        &self.options
+    }
+
+    pub fn into_parts(self) -> (Vec<Filter>, ListOptions) {
+        (self.filters, self.options)
+    }
+}
+
+impl From<Query> for Vec<Filter> {
+    fn from(query: Query) -> Self {
+        query.filters
+    }
+}
+
+impl std::str::FromStr for Filter {
+    type Err = StoreError;
+
+    /// Parse `field<op>value`, e.g. `status=pending` or `priority>=5`
+    ///
+    /// Operators are `=`, `!=`, `>`, `<`, `>=`, `<=`, `~` (a substring match:
+    /// `Contains` with `%value%`) and `^=` (a prefix match: `StartsWith`; both
+    /// ignore ASCII case and always take the value as a string). Otherwise the
+    /// value's kind is inferred: `true`/`false` are bools, then integers, then
+    /// finite floats, then values starting like `YYYY-MM-DD`, which must parse
+    /// as a date or RFC 3339 timestamp and become a `DateTime`; anything else,
+    /// or a value in double quotes, is a string.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            StoreError::Schema(format!(
+                "Invalid filter: {} (expected field<op>value with op one of = != > < >= <= ~ ^=)",
+                s
+            ))
+        };
+        let start = s.find(['=', '!', '<', '>', '~', '^']).ok_or_else(invalid)?;
+        let (field, rest) = s.split_at(start);
+        let field = field.trim();
+        if field.is_empty() {
+            return Err(invalid());
         }
+        let (op, literal) = [
+            ("^=", FilterOp::StartsWith),
+            (">=", FilterOp::Gte),
+            ("<=", FilterOp::Lte),
+            ("!=", FilterOp::Ne),
+            ("=", FilterOp::Eq),
+            (">", FilterOp::Gt),
+            ("<", FilterOp::Lt),
+            ("~", FilterOp::Contains),
+        ]
+        .into_iter()
+        .find_map(|(token, op)| rest.strip_prefix(token).map(|literal| (op, literal.trim())))
+        .ok_or_else(invalid)?;
+
+        let quoted = literal.len() >= 2 && literal.starts_with('"') && literal.ends_with('"');
+        let value = if quoted {
+            IndexValue::String(literal[1..literal.len() - 1].to_string())
+        } else if matches!(op, FilterOp::Contains | FilterOp::StartsWith) {
+            IndexValue::String(literal.to_string())
+        } else if let Ok(b) = literal.parse::<bool>() {
+            IndexValue::Bool(b)
+        } else if let Ok(i) = literal.parse::<i64>() {
+            IndexValue::Int(i)
+        } else if let Some(x) = literal.parse::<f64>().ok().filter(|x| x.is_finite()) {
+            IndexValue::Float(x)
+        } else if is_date_like(literal) {
+            IndexValue::parse_datetime(literal)
+                .map_err(|e| StoreError::Schema(format!("Invalid filter: {}: {}", s, e)))?
+        } else {
+            IndexValue::String(literal.to_string())
+        };
+        let value = match value {
+            IndexValue::String(s) if op == FilterOp::Contains => IndexValue::String(format!("%{}%", s)),
+            value => value,
+        };
+
+        Ok(Filter {
+            field: field.to_string(),
+            op,
+            value,
+        })
     }
 }
 
+/// True for literals starting like a `YYYY-MM-DD` date, which `Filter::from_str` parses as a `DateTime`
+fn is_date_like(literal: &str) -> bool {
+    let bytes = literal.as_bytes();
+    bytes.len() >= 10
+        && bytes[..4].iter().all(u8::is_ascii_digit)
+        && bytes[4] == b'-'
+        && bytes[5..7].iter().all(u8::is_ascii_digit)
+        && bytes[7] == b'-'
+        && bytes[8..10].iter().all(u8::is_ascii_digit)
+}
+
 impl std::fmt::Display for FilterOp {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -50,6 +549,11 @@ impl std::fmt::Display for FilterOp {
             FilterOp::Gte => write!(f, ">="),
             FilterOp::Lte => write!(f, "<="),
             FilterOp::Contains => write!(f, "LIKE"),
+            FilterOp::EqIgnoreCase => write!(f, "= NOCASE"),
+            FilterOp::NeIgnoreCase => write!(f, "!= NOCASE"),
+            FilterOp::ContainsIgnoreCase => write!(f, "LIKE NOCASE"),
+            FilterOp::StartsWith => write!(f, "STARTS WITH"),
+            FilterOp::Between => write!(f, "BETWEEN"),
         }
     }
 }
@@ -79,6 +583,152 @@ mod tests {
         assert_eq!(FilterOp::Gte.to_sql(), ">=");
         assert_eq!(FilterOp::Lte.to_sql(), "<=");
         assert_eq!(FilterOp::Contains.to_sql(), "LIKE");
+        assert_eq!(FilterOp::EqIgnoreCase.to_sql(), "=");
+        assert!(FilterOp::EqIgnoreCase.is_case_insensitive());
+        assert!(!FilterOp::Eq.is_case_insensitive());
+        assert_eq!(FilterOp::StartsWith.to_sql(), "LIKE");
+        assert!(FilterOp::StartsWith.requires_string());
+        assert!(!FilterOp::Contains.requires_string());
+    }
+
+    #[test]
+    fn test_agg_to_sql() {
+        assert_eq!(Agg::Sum.to_sql(), "SUM");
+        assert_eq!(Agg::Avg.to_sql(), "AVG");
+        assert_eq!(Agg::Count.to_sql(), "COUNT");
+    }
+
+    #[test]
+    fn test_query_builder() {
+        let query = Query::new()
+            .eq("status", "pending")
+            .gte("priority", 7)
+            .ne("archived", true)
+            .order_by_desc("priority")
+            .limit(20)
+            .offset(40);
+
+        let filters = query.filters();
+        assert_eq!(filters.len(), 3);
+        assert_eq!(filters[0].field, "status");
+        assert_eq!(filters[0].value, IndexValue::String("pending".to_string()));
+        assert_eq!(filters[1].op, FilterOp::Gte);
+        assert_eq!(filters[1].value, IndexValue::Int(7));
+        assert_eq!(filters[2].value, IndexValue::Bool(true));
+
+        let (_, options) = query.into_parts();
+        assert_eq!(
+            options,
+            ListOptions {
+                order_by: Some(("priority".to_string(), SortDir::Desc)),
+                limit: Some(20),
+                offset: Some(40),
+            }
+        );
+    }
+
+    #[test]
+    fn test_filter_from_str() {
+        let parse = |s: &str| {
+            let filter: Filter = s.parse().unwrap();
+            (filter.field, filter.op, filter.value)
+        };
+        assert_eq!(
+            parse("status=pending"),
+            (
+                "status".to_string(),
+                FilterOp::Eq,
+                IndexValue::String("pending".to_string())
+            )
+        );
+        assert_eq!(
+            parse("priority>=5"),
+            ("priority".to_string(), FilterOp::Gte, IndexValue::Int(5))
+        );
+        assert_eq!(
+            parse("score < 0.5"),
+            ("score".to_string(), FilterOp::Lt, IndexValue::Float(0.5))
+        );
+        assert_eq!(
+            parse("active!=false"),
+            ("active".to_string(), FilterOp::Ne, IndexValue::Bool(false))
+        );
+        assert_eq!(
+            parse("code=\"42\""),
+            ("code".to_string(), FilterOp::Eq, IndexValue::String("42".to_string()))
+        );
+        assert_eq!(
+            parse("title~auth"),
+            (
+                "title".to_string(),
+                FilterOp::Contains,
+                IndexValue::String("%auth%".to_string())
+            )
+        );
+        assert_eq!(
+            parse("branch^=42"),
+            (
+                "branch".to_string(),
+                FilterOp::StartsWith,
+                IndexValue::String("42".to_string())
+            )
+        );
+        assert_eq!(parse("name=nan").2, IndexValue::String("nan".to_string()));
+
+        for invalid in ["status", "=pending", "status!pending", "status^pending"] {
+            assert!(
+                matches!(invalid.parse::<Filter>(), Err(StoreError::Schema(_))),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_filter_from_str_parses_dates() {
+        let parse = |s: &str| {
+            let filter: Filter = s.parse().unwrap();
+            (filter.field, filter.op, filter.value)
+        };
+        assert_eq!(
+            parse("created_at>2024-01-01"),
+            (
+                "created_at".to_string(),
+                FilterOp::Gt,
+                IndexValue::DateTime(1_704_067_200_000)
+            )
+        );
+        assert_eq!(
+            parse("created_at<=2024-01-01T00:00:01+01:00").2,
+            IndexValue::DateTime(1_704_063_601_000)
+        );
+        // Quoted, a date stays a string
+        assert_eq!(
+            parse("day=\"2024-01-01\"").2,
+            IndexValue::String("2024-01-01".to_string())
+        );
+        let err = "created_at>2024-13-01".parse::<Filter>().unwrap_err().to_string();
+        assert!(err.contains("Invalid date '2024-13-01'"), "{}", err);
+    }
+
+    #[test]
+    fn test_matches_compares_dates_with_string_fields_as_strings() {
+        let record = serde_json::json!({
+            "due": "2024-01-15",
+            "seen_at": "2024-01-15T12:30:00Z",
+            "created_at": 1_705_276_800_000i64,
+        });
+        let matches = |filter: &str| filter.parse::<Filter>().unwrap().matches(&record);
+
+        assert!(matches("due=2024-01-15") && !matches("due=2024-01-16"));
+        assert!(matches("due>=2024-01-01") && !matches("due<2024-01-15"));
+        assert!(matches("seen_at=2024-01-15T12:30:00Z"));
+        assert!(matches("seen_at>2024-01-15") && matches("seen_at<2024-01-16"));
+        assert!(
+            Filter::between("due", IndexValue::DateTime(0), IndexValue::DateTime(1_705_276_800_000)).matches(&record)
+        );
+        // Millisecond ints still compare as timestamps
+        assert!(matches("created_at=2024-01-15"));
     }
 
     #[test]
@@ -86,4 +736,33 @@ mod tests {
         assert_eq!(FilterOp::Eq.to_string(), "=");
         assert_eq!(FilterOp::Ne.to_string(), "!=");
     }
+
+    #[test]
+    fn test_matches_evaluates_like_sql() {
+        let record = serde_json::json!({
+            "status": "Active",
+            "priority": 5,
+            "score": 0.5,
+            "done": false,
+            "created_at": 1_704_067_200_000i64,
+            "note": null,
+        });
+        let matches = |filter: &str| filter.parse::<Filter>().unwrap().matches(&record);
+
+        assert!(matches("status=Active"));
+        assert!(!matches("status=active"));
+        assert!(matches("status~tiv"));
+        assert!(matches("status^=act"));
+        assert!(matches("priority>=5") && !matches("priority>5"));
+        assert!(matches("score<1.5"));
+        assert!(matches("done=false"));
+        assert!(matches("created_at>2023-12-31") && !matches("created_at>2024-01-01"));
+        assert!(Filter::between("priority", 1, 5).matches(&record));
+        assert!(Filter::after("created_at", 0).matches(&record));
+
+        // Missing, null, or mismatched fields match nothing, even with !=
+        assert!(!matches("missing!=1"));
+        assert!(!matches("note!=x"));
+        assert!(!matches("priority=\"5\""));
+    }
 }
@@ -0,0 +1,170 @@
+// Callbacks around every record mutation
+
+use crate::error::StoreResult as Result;
+use serde_json::Value;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Kind of mutation passed to `StoreHooks` callbacks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteOp {
+    /// No record with the ID existed when the write started
+    Create,
+    /// An existing record is being replaced
+    Update,
+    /// A record is being deleted; the JSON is its tombstone
+    Delete,
+}
+
+/// Runs before a record is written: `(collection, op, record_json)`
+///
+/// May modify the JSON (e.g. stamp an audit field) or veto the write by
+/// returning an error. Changes to a created or updated record must round-trip
+/// through the record type; fields it doesn't have are dropped.
+pub type BeforeWriteHook = Box<dyn Fn(&str, WriteOp, &mut Value) -> Result<()> + Send + Sync>;
+
+/// Runs after a record is written to JSONL and SQLite, before the write commits
+///
+/// Returning an error rolls the write back.
+pub type AfterWriteHook = Box<dyn Fn(&str, WriteOp, &Value) -> Result<()> + Send + Sync>;
+
+/// What a committed write did to a record, see `ChangeEvent`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Created,
+    Updated,
+    Deleted,
+}
+
+impl From<WriteOp> for ChangeKind {
+    fn from(op: WriteOp) -> Self {
+        match op {
+            WriteOp::Create => ChangeKind::Created,
+            WriteOp::Update => ChangeKind::Updated,
+            WriteOp::Delete => ChangeKind::Deleted,
+        }
+    }
+}
+
+/// A record changed by a committed write, passed to `Store::on_change` callbacks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangeEvent {
+    pub collection: String,
+    pub id: String,
+    pub kind: ChangeKind,
+}
+
+/// Runs after a write commits, once per changed record; an error is only logged
+pub type ChangeCallback = Box<dyn Fn(&ChangeEvent) -> Result<()> + Send + Sync>;
+
+/// Callbacks fired around every create, update, and delete, see `Store::set_hooks`
+///
+/// Deletes fire once per record, including records removed by a cascade.
+#[derive(Default)]
+pub struct StoreHooks {
+    pub before_write: Option<BeforeWriteHook>,
+    pub after_write: Option<AfterWriteHook>,
+}
+
+impl StoreHooks {
+    /// True if no callback is set
+    pub fn is_empty(&self) -> bool {
+        self.before_write.is_none() && self.after_write.is_none()
+    }
+
+    pub(crate) fn before(&self, collection: &str, op: WriteOp, json: &mut Value) -> Result<()> {
+        match &self.before_write {
+            Some(hook) => hook(collection, op, json),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn after(&self, collection: &str, op: WriteOp, json: &Value) -> Result<()> {
+        match &self.after_write {
+            Some(hook) => hook(collection, op, json),
+            None => Ok(()),
+        }
+    }
+}
+
+impl std::fmt::Debug for StoreHooks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StoreHooks")
+            .field("before_write", &self.before_write.is_some())
+            .field("after_write", &self.after_write.is_some())
+            .finish()
+    }
+}
+
+/// The user's `StoreHooks` plus the changes of the write in progress
+///
+/// Shared with write closures like `StoreHooks` was; every `after` call also
+/// records the change, which `Store` hands to its `on_change` callbacks once
+/// the write commits.
+#[derive(Default)]
+pub(crate) struct Hooks {
+    user: StoreHooks,
+    /// Set once a change callback is registered; until then nothing is recorded
+    recording: AtomicBool,
+    changes: Mutex<Vec<ChangeEvent>>,
+}
+
+impl Hooks {
+    pub(crate) fn new(user: StoreHooks, recording: bool) -> Self {
+        Self {
+            user,
+            recording: AtomicBool::new(recording),
+            changes: Mutex::default(),
+        }
+    }
+
+    pub(crate) fn start_recording(&self) {
+        self.recording.store(true, Ordering::Relaxed);
+    }
+
+    pub(crate) fn is_recording(&self) -> bool {
+        self.recording.load(Ordering::Relaxed)
+    }
+
+    /// Note that the write in progress changed a record
+    pub(crate) fn record(&self, collection: &str, id: &str, op: WriteOp) {
+        if self.is_recording() {
+            self.changes
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push(ChangeEvent {
+                    collection: collection.to_string(),
+                    id: id.to_string(),
+                    kind: op.into(),
+                });
+        }
+    }
+
+    /// Take the changes recorded so far
+    pub(crate) fn take_changes(&self) -> Vec<ChangeEvent> {
+        std::mem::take(&mut *self.changes.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// Record the change, then run the `after_write` hook
+    pub(crate) fn after(&self, collection: &str, op: WriteOp, json: &Value) -> Result<()> {
+        self.record(collection, json["id"].as_str().unwrap_or_default(), op);
+        self.user.after(collection, op, json)
+    }
+
+    /// Like `after`, parsing the written line only if an `after_write` hook needs it
+    pub(crate) fn after_line(&self, collection: &str, id: &str, op: WriteOp, line: &str) -> Result<()> {
+        self.record(collection, id, op);
+        match &self.user.after_write {
+            Some(hook) => hook(collection, op, &serde_json::from_str(line)?),
+            None => Ok(()),
+        }
+    }
+}
+
+impl std::ops::Deref for Hooks {
+    type Target = StoreHooks;
+
+    fn deref(&self) -> &StoreHooks {
+        &self.user
+    }
+}
@@ -1,52 +1,321 @@
 // JSONL file operations
 
-use eyre::{Context, Result};
+use crate::error::{StoreError, StoreResult as Result};
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
 use fs2::FileExt;
 use serde::Serialize;
 use serde_json::Value;
-use std::collections::HashMap;
-use std::fs::{File, OpenOptions};
-use std::io::{BufRead, BufReader, Write};
-use std::path::Path;
-use tracing::{info, warn};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::{debug, info, warn};
 
-/// Append a record to a JSONL file
+/// Append a record to a JSONL file, fsyncing it before returning
 pub fn append_jsonl<T: Serialize>(path: &Path, record: &T) -> Result<()> {
+    append_line(path, &to_canonical_json(record)?, &Fsync::default())?;
+    Ok(())
+}
+
+/// When appended JSONL lines are flushed to disk
+///
+/// Until a line is fsynced, a crash or power loss can lose it (a process
+/// exiting normally can't; the OS still writes it out). The SQLite cache may
+/// then hold a record JSONL lacks, and the next sync drops it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FsyncPolicy {
+    /// fsync every append before the write returns; nothing acknowledged is lost
+    #[default]
+    EveryWrite,
+    /// fsync after every `n` appends and on `Store::flush`; up to `n - 1` lines at risk
+    Batched(usize),
+    /// fsync only on `Store::flush` (and when the store is dropped); fastest
+    OnFlush,
+}
+
+/// Applies an `FsyncPolicy`, tracking files with appends not yet fsynced
+#[derive(Debug, Default)]
+pub(crate) struct Fsync {
+    policy: FsyncPolicy,
+    pending: Mutex<PendingFsync>,
+    /// Size past which an appended file is due for `rotate_jsonl`, if rotation is on
+    rotate_bytes: Option<u64>,
+    oversized: Mutex<HashSet<PathBuf>>,
+}
+
+#[derive(Debug, Default)]
+struct PendingFsync {
+    appends: usize,
+    paths: HashSet<PathBuf>,
+}
+
+impl Fsync {
+    pub(crate) fn new(policy: FsyncPolicy) -> Self {
+        Self {
+            policy,
+            ..Default::default()
+        }
+    }
+
+    /// Track files that grow past `bytes`, see `take_oversized`
+    pub(crate) fn rotate_above(mut self, bytes: Option<u64>) -> Self {
+        self.rotate_bytes = bytes;
+        self
+    }
+
+    /// Files appended past the rotation threshold since the last call
+    pub(crate) fn take_oversized(&self) -> Vec<PathBuf> {
+        let mut oversized = self.oversized.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut paths: Vec<PathBuf> = oversized.drain().collect();
+        paths.sort();
+        paths
+    }
+
+    /// Called after a line was written to `file`, the open handle of `path`
+    fn after_append(&self, file: &File, path: &Path) -> Result<()> {
+        if let Some(limit) = self.rotate_bytes
+            && file.metadata()?.len() > limit
+        {
+            self.oversized
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .insert(path.to_path_buf());
+        }
+        match self.policy {
+            FsyncPolicy::EveryWrite => file.sync_all()?,
+            FsyncPolicy::Batched(n) => {
+                let due = {
+                    let mut pending = self.lock();
+                    pending.appends += 1;
+                    pending.paths.insert(path.to_path_buf());
+                    pending.appends >= n
+                };
+                if due {
+                    self.flush()?;
+                }
+            }
+            FsyncPolicy::OnFlush => {
+                self.lock().paths.insert(path.to_path_buf());
+            }
+        }
+        Ok(())
+    }
+
+    /// fsync every file appended to since the last flush
+    pub(crate) fn flush(&self) -> Result<()> {
+        let paths = {
+            let mut pending = self.lock();
+            pending.appends = 0;
+            std::mem::take(&mut pending.paths)
+        };
+        for path in paths {
+            match File::open(&path) {
+                Ok(file) => file.sync_all()?,
+                // Removed since (e.g. an import rolled back), nothing to flush
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(StoreError::io("Failed to open JSONL file for fsync", e)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Number of files with appends not yet fsynced
+    #[cfg(test)]
+    pub(crate) fn pending_files(&self) -> usize {
+        self.lock().paths.len()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, PendingFsync> {
+        self.pending.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Serialize a record as a JSONL line, with object keys sorted at every level
+///
+/// Every line written to JSONL goes through this, so the bytes of a record
+/// depend only on its contents, not on struct field order or `HashMap`
+/// iteration order. Git diffs then show only real changes.
+pub fn to_canonical_json<T: Serialize + ?Sized>(record: &T) -> Result<String> {
+    let mut value = serde_json::to_value(record)?;
+    value.sort_all_objects();
+    Ok(serde_json::to_string(&value)?)
+}
+
+/// Where a line was appended to a JSONL file, so a failed write can take it back
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct JsonlAppend {
+    pub(crate) path: PathBuf,
+    pub(crate) start: u64,
+    pub(crate) end: u64,
+}
+
+impl JsonlAppend {
+    /// Truncate the file back to before the append
+    ///
+    /// Only done if nothing was appended after it; otherwise the line is left in
+    /// place (the next sync picks it up) and a warning is logged.
+    pub(crate) fn undo(&self) -> Result<()> {
+        let file = OpenOptions::new()
+            .write(true)
+            .open(&self.path)
+            .map_err(|e| StoreError::io("Failed to open JSONL file for truncation", e))?;
+        file.lock_exclusive()
+            .map_err(|e| StoreError::io("Failed to acquire file lock", e))?;
+
+        if file.metadata()?.len() != self.end {
+            warn!(file = ?self.path, "JSONL file grew since a failed write, leaving its line in place");
+            return Ok(());
+        }
+        file.set_len(self.start)?;
+        file.sync_all()?;
+        Ok(())
+    }
+}
+
+/// Append one serialized record as a line, under an exclusive file lock
+pub(crate) fn append_line(path: &Path, json: &str, fsync: &Fsync) -> Result<JsonlAppend> {
     let mut file = OpenOptions::new()
         .create(true)
         .append(true)
         .open(path)
-        .context("Failed to open JSONL file for appending")?;
+        .map_err(|e| StoreError::io("Failed to open JSONL file for appending", e))?;
 
     // Acquire exclusive lock before writing
-    file.lock_exclusive().context("Failed to acquire file lock")?;
+    file.lock_exclusive()
+        .map_err(|e| StoreError::io("Failed to acquire file lock", e))?;
+
+    // A compaction may have renamed a new file over the path while we waited
+    // for the lock; appending to the old inode would lose the line
+    if !is_current_file(&file, path)? {
+        drop(file);
+        return append_line(path, json, fsync);
+    }
 
-    let json = serde_json::to_string(record)?;
-    writeln!(file, "{}", json)?;
-    file.sync_all()?; // Ensure data is flushed to disk
+    // One write of the whole line on an O_APPEND file: `writeln!` would write
+    // the JSON and the newline separately, and a crash between them leaves a
+    // line with no end that the next append runs into
+    let mut line = String::with_capacity(json.len() + 1);
+    line.push_str(json);
+    line.push('\n');
+    let start = file.metadata()?.len();
+    file.write_all(line.as_bytes())?;
+    fsync.after_append(&file, path)?;
 
     // Lock is automatically released when file is dropped
-    Ok(())
+    Ok(JsonlAppend {
+        path: path.to_path_buf(),
+        start,
+        end: start + json.len() as u64 + 1,
+    })
+}
+
+/// A record's `updated_at`, or 0 if it has none
+pub fn updated_at(record: &Value) -> i64 {
+    record.get("updated_at").and_then(|v| v.as_i64()).unwrap_or(0)
+}
+
+/// True if `candidate` replaces `current` as the latest version of a record
+///
+/// The later `updated_at` wins. Equal timestamps (a collision, e.g. from two
+/// clones) go to the larger canonical JSON (serde_json sorts object keys), as
+/// in the merge driver, so the winner doesn't depend on line order.
+pub fn supersedes(candidate: &Value, current: &Value) -> bool {
+    match updated_at(candidate).cmp(&updated_at(current)) {
+        std::cmp::Ordering::Greater => true,
+        std::cmp::Ordering::Less => false,
+        std::cmp::Ordering::Equal => {
+            let (candidate, current) = (candidate.to_string(), current.to_string());
+            candidate > current
+        }
+    }
 }
 
 /// Read all records from a JSONL file, returning latest version per ID
 ///
 /// This assumes records have an "id" field and "updated_at" field.
-/// For records with duplicate IDs, the one with the highest updated_at wins.
+/// For records with duplicate IDs, the one with the highest updated_at wins,
+/// see `supersedes`; equal-timestamp versions that differ are logged.
+/// Gzipped archives left by `rotate_jsonl` are read too, before the file itself.
 pub fn read_jsonl_latest(path: &Path) -> Result<HashMap<String, Value>> {
-    if !path.exists() {
-        // File doesn't exist yet, return empty map
-        return Ok(HashMap::new());
+    let mut records: HashMap<String, Value> = HashMap::new();
+    let mut ambiguous: HashSet<String> = HashSet::new();
+    for (source, reader) in history_readers(path)? {
+        read_latest_lines(reader, &source, &mut records, &mut ambiguous);
     }
 
-    let file = File::open(path).context("Failed to open JSONL file")?;
+    if !ambiguous.is_empty() {
+        let mut ids: Vec<String> = ambiguous.into_iter().collect();
+        ids.sort();
+        warn!(
+            file = ?path,
+            ids = ?ids,
+            "Versions with equal updated_at and different content, kept the larger canonical JSON"
+        );
+    }
 
-    // Acquire shared lock to allow concurrent reads while blocking writes
-    file.lock_shared().context("Failed to acquire shared file lock")?;
+    info!(
+        file = ?path,
+        count = records.len(),
+        "Loaded latest records from JSONL"
+    );
 
-    let reader = BufReader::new(file);
-    let mut records: HashMap<String, Value> = HashMap::new();
+    Ok(records)
+}
+
+/// Readers over a JSONL file's gzipped archives, oldest first, then the file itself
+///
+/// The file stays under a shared lock until its reader is dropped, and the
+/// archives are listed under that lock, so a concurrent `rotate_jsonl` can't
+/// move lines between them. Archives are decompressed up front. Empty if
+/// neither the file nor any archive exists.
+fn history_readers(path: &Path) -> Result<Vec<(PathBuf, Box<dyn BufRead>)>> {
+    let live = match File::open(path) {
+        Ok(file) => Some(file),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(StoreError::io("Failed to open JSONL file", e)),
+    };
+
+    if let Some(file) = &live {
+        // Acquire shared lock to allow concurrent reads while blocking writes
+        file.lock_shared()
+            .map_err(|e| StoreError::io("Failed to acquire shared file lock", e))?;
+        // Rotated while waiting for the lock: its lines are in a new archive now
+        if !is_current_file(file, path)? {
+            return history_readers(path);
+        }
+    }
+
+    let mut readers: Vec<(PathBuf, Box<dyn BufRead>)> = Vec::new();
+    for archive in archive_paths(path)? {
+        let lines = read_archive(&archive)?;
+        readers.push((archive, Box::new(std::io::Cursor::new(lines))));
+    }
+    if let Some(file) = live {
+        readers.push((path.to_path_buf(), Box::new(BufReader::new(file))));
+    }
+    Ok(readers)
+}
 
+/// Decompressed contents of a JSONL archive
+fn read_archive(archive: &Path) -> Result<Vec<u8>> {
+    let mut lines = Vec::new();
+    GzDecoder::new(File::open(archive).map_err(|e| StoreError::io("Failed to open JSONL archive", e))?)
+        .read_to_end(&mut lines)
+        .map_err(|e| StoreError::io("Failed to decompress JSONL archive", e))?;
+    Ok(lines)
+}
+
+/// Fold the lines of `reader` (read from `path`) into the latest version per ID
+fn read_latest_lines(
+    reader: impl BufRead,
+    path: &Path,
+    records: &mut HashMap<String, Value>,
+    ambiguous: &mut HashSet<String>,
+) {
     for (line_num, line) in reader.lines().enumerate() {
         let line = match line {
             Ok(l) => l,
@@ -91,26 +360,726 @@ pub fn read_jsonl_latest(path: &Path) -> Result<HashMap<String, Value>> {
             }
         };
 
-        let updated_at = record.get("updated_at").and_then(|v| v.as_i64()).unwrap_or(0);
-
-        // Keep the record with the latest updated_at
-        if let Some(existing) = records.get(&id) {
-            let existing_updated_at = existing.get("updated_at").and_then(|v| v.as_i64()).unwrap_or(0);
-            if updated_at > existing_updated_at {
+        // Keep the latest version, independent of line order
+        match records.get(&id) {
+            Some(existing) if !supersedes(&record, existing) => {
+                if updated_at(&record) == updated_at(existing) && record != *existing {
+                    ambiguous.insert(id);
+                }
+            }
+            Some(existing) => {
+                if updated_at(&record) == updated_at(existing) {
+                    ambiguous.insert(id.clone());
+                }
+                records.insert(id, record);
+            }
+            None => {
                 records.insert(id, record);
             }
-        } else {
-            records.insert(id, record);
         }
     }
+}
+
+/// Latest version of one record with `updated_at <= at_ms`, tombstones included
+///
+/// Resolves like `read_jsonl_latest` restricted to versions at or before
+/// `at_ms`. Reads the whole file and its archives; unparseable lines are skipped.
+pub fn read_jsonl_at(path: &Path, id: &str, at_ms: i64) -> Result<Option<Value>> {
+    let mut latest: Option<Value> = None;
+    for (_, reader) in history_readers(path)? {
+        for line in reader.lines() {
+            let Ok(line) = line else {
+                continue;
+            };
+            let Ok(record) = serde_json::from_str::<Value>(&line) else {
+                continue;
+            };
+            if record.get("id").and_then(|v| v.as_str()) != Some(id) || updated_at(&record) > at_ms {
+                continue;
+            }
+            if latest.as_ref().is_none_or(|existing| supersedes(&record, existing)) {
+                latest = Some(record);
+            }
+        }
+    }
+    Ok(latest)
+}
+
+/// Problems found in a JSONL file by `scan_jsonl`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct JsonlScan {
+    /// Latest versions in the file and its archives that aren't tombstones
+    pub live_records: usize,
+    /// Non-blank lines in the file and its archives, including superseded versions and tombstones
+    pub total_lines: usize,
+    /// 1-based numbers of lines in the file that aren't a JSON object with a string `id`
+    pub unparseable_lines: Vec<usize>,
+    /// How many lines in the archives aren't a JSON object with a string `id`
+    pub archived_unparseable_lines: usize,
+    /// IDs (sorted) whose latest `updated_at` is shared by versions with different content
+    pub ambiguous_ids: Vec<String>,
+}
+
+impl JsonlScan {
+    pub fn is_clean(&self) -> bool {
+        self.unparseable_lines.is_empty() && self.archived_unparseable_lines == 0 && self.ambiguous_ids.is_empty()
+    }
+}
+
+/// Read a JSONL file and report lines `read_jsonl_latest` would skip or has to tie-break
+///
+/// Gzipped archives left by `rotate_jsonl` are read too, before the file
+/// itself. Never modifies anything. A missing file scans clean.
+pub fn scan_jsonl(path: &Path) -> Result<JsonlScan> {
+    let mut scan = JsonlScan::default();
+
+    // Per ID: the latest version seen and whether another version ties with it
+    let mut latest: HashMap<String, (i64, Value, bool)> = HashMap::new();
+
+    for (source, reader) in history_readers(path)? {
+        let archived = source != path;
+        for (line_num, line) in reader.lines().enumerate() {
+            let record = match line {
+                Ok(line) if line.trim().is_empty() => continue,
+                Ok(line) => serde_json::from_str::<Value>(&line).ok(),
+                Err(_) => None,
+            };
+            scan.total_lines += 1;
+
+            let Some((id, record)) = record.and_then(|r| Some((r.get("id").and_then(|v| v.as_str())?.to_string(), r)))
+            else {
+                if archived {
+                    scan.archived_unparseable_lines += 1;
+                } else {
+                    scan.unparseable_lines.push(line_num + 1);
+                }
+                continue;
+            };
+            let updated_at = record.get("updated_at").and_then(|v| v.as_i64()).unwrap_or(0);
+
+            match latest.get_mut(&id) {
+                Some((ts, existing, tied)) if updated_at == *ts => {
+                    *tied |= *existing != record;
+                    if supersedes(&record, existing) {
+                        *existing = record;
+                    }
+                }
+                Some((ts, _, _)) if updated_at < *ts => {}
+                _ => {
+                    latest.insert(id, (updated_at, record, false));
+                }
+            }
+        }
+    }
+
+    for (id, (_, record, tied)) in latest {
+        if tied {
+            scan.ambiguous_ids.push(id);
+        }
+        if !record.get("deleted").and_then(|v| v.as_bool()).unwrap_or(false) {
+            scan.live_records += 1;
+        }
+    }
+    scan.ambiguous_ids.sort();
+
+    Ok(scan)
+}
+
+/// Line and byte counts of a JSONL file before and after `compact_jsonl`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct CompactStats {
+    pub lines_before: usize,
+    pub lines_after: usize,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+}
+
+impl CompactStats {
+    pub fn bytes_saved(&self) -> u64 {
+        self.bytes_before.saturating_sub(self.bytes_after)
+    }
+}
+
+/// Rewrite a JSONL file keeping only the latest line per ID
+///
+/// The winner for each ID is the line `read_jsonl_latest` would pick, so
+/// tombstones are kept. Lines that can't be parsed are kept verbatim for
+/// `scan_jsonl` to report; blank lines are dropped. Surviving lines stay in
+/// their original order.
+///
+/// Runs under the file's exclusive lock and writes a temp file that is renamed
+/// over the original, so an interrupted compaction leaves the original intact.
+/// A missing file, or one with nothing to drop, is left untouched.
+pub fn compact_jsonl(path: &Path) -> Result<CompactStats> {
+    rewrite_latest(path, None, false)
+}
+
+/// Compact a JSONL file like `compact_jsonl`, folding its archives in first
+///
+/// Versions superseded in the archives are dropped with the rest, and the
+/// archives left by `rotate_jsonl` are removed, so older lines of a record
+/// survive nowhere once its latest version is written. Unparseable lines are
+/// kept, as by `compact_jsonl`.
+pub fn compact_jsonl_with_archives(path: &Path) -> Result<CompactStats> {
+    rewrite_latest(path, None, true)
+}
+
+/// Rewrite a JSONL file so `scan_jsonl` has nothing to report
+///
+/// Compacts like `compact_jsonl`, which settles ambiguous IDs on the version
+/// reads already pick, and moves unparseable lines to `<file>.rejected`
+/// instead of keeping them. Archives left by `rotate_jsonl` are folded back
+/// into the file and removed, since their problems can't be fixed in place.
+pub fn repair_jsonl(path: &Path) -> Result<CompactStats> {
+    let mut rejected_name = path.file_name().unwrap_or_default().to_os_string();
+    rejected_name.push(".rejected");
+    rewrite_latest(path, Some(&path.with_file_name(rejected_name)), true)
+}
+
+/// What `recover_partial_line` did to a JSONL file's last line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TailRecovery {
+    /// The file ends with a newline (or is empty or missing); nothing changed
+    Clean,
+    /// The last line was a complete record missing its newline, which was added
+    Terminated,
+    /// An incomplete last line of this many bytes was truncated
+    Truncated(u64),
+}
+
+/// Clean up a last line left unfinished by an interrupted append
+///
+/// Appends write a whole line under the file's exclusive lock, so bytes after
+/// the last newline can only come from a writer that crashed mid-line (or a
+/// hand edit). Left in place, the next append would be glued onto them and
+/// become unreadable too. If the bytes parse as JSON, the newline is added;
+/// otherwise they are truncated. Newline-terminated lines are never touched.
+pub fn recover_partial_line(path: &Path) -> Result<TailRecovery> {
+    let mut file = match OpenOptions::new().read(true).write(true).open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(TailRecovery::Clean),
+        Err(e) => return Err(StoreError::io("Failed to open JSONL file", e)),
+    };
+    file.lock_exclusive()
+        .map_err(|e| StoreError::io("Failed to acquire file lock", e))?;
+
+    // Scan back from the end for the last newline
+    let len = file.metadata()?.len();
+    let mut tail_start = len;
+    let mut chunk = vec![0u8; 8192];
+    'scan: while tail_start > 0 {
+        let read_len = chunk.len().min(tail_start as usize);
+        file.seek(SeekFrom::Start(tail_start - read_len as u64))?;
+        file.read_exact(&mut chunk[..read_len])?;
+        for (i, byte) in chunk[..read_len].iter().enumerate().rev() {
+            if *byte == b'\n' {
+                tail_start = tail_start - read_len as u64 + i as u64 + 1;
+                break 'scan;
+            }
+        }
+        tail_start -= read_len as u64;
+    }
+    if tail_start == len {
+        return Ok(TailRecovery::Clean);
+    }
+
+    let mut tail = Vec::with_capacity((len - tail_start) as usize);
+    file.seek(SeekFrom::Start(tail_start))?;
+    file.read_to_end(&mut tail)?;
+    let recovery = if serde_json::from_slice::<Value>(&tail).is_ok() {
+        file.seek(SeekFrom::End(0))?;
+        file.write_all(b"\n")?;
+        TailRecovery::Terminated
+    } else {
+        file.set_len(tail_start)?;
+        TailRecovery::Truncated(len - tail_start)
+    };
+    file.sync_all()?;
+    Ok(recovery)
+}
+
+/// Keep the latest line per ID; unparseable lines are kept, or appended to `rejected_path`
+///
+/// With `fold_archives`, the archives' lines are read first and the archives
+/// removed once the rewritten file is in place.
+fn rewrite_latest(path: &Path, rejected_path: Option<&Path>, fold_archives: bool) -> Result<CompactStats> {
+    if !path.exists() {
+        return Ok(CompactStats::default());
+    }
+
+    let file = File::open(path).map_err(|e| StoreError::io("Failed to open JSONL file", e))?;
+    file.lock_exclusive()
+        .map_err(|e| StoreError::io("Failed to acquire file lock", e))?;
+    // Rotated or compacted while waiting for the lock; rewriting the old inode
+    // over the new file would drop lines appended since and revive archived ones
+    if !is_current_file(&file, path)? {
+        drop(file);
+        return rewrite_latest(path, rejected_path, fold_archives);
+    }
+
+    let mut stats = CompactStats {
+        bytes_before: file.metadata()?.len(),
+        ..Default::default()
+    };
+
+    // Per ID: index into `lines` of the latest version and the version itself
+    let archives = if fold_archives {
+        archive_paths(path)?
+    } else {
+        Vec::new()
+    };
+    let mut archived = Vec::new();
+    for archive in &archives {
+        archived.extend(read_archive(archive)?);
+        if archived.last().is_some_and(|b| *b != b'\n') {
+            archived.push(b'\n');
+        }
+    }
+
+    let mut lines = Vec::new();
+    let mut latest: HashMap<String, (usize, Value)> = HashMap::new();
+    for line in archived.as_slice().chain(BufReader::new(&file)).lines() {
+        let line = line?;
+        stats.lines_before += 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let parsed = serde_json::from_str::<Value>(&line).ok();
+        let id = parsed
+            .as_ref()
+            .and_then(|r| r.get("id"))
+            .and_then(|v| v.as_str())
+            .map(str::to_string);
+        match (id, parsed) {
+            (Some(id), Some(record)) => {
+                // Keep the version reads pick, see `supersedes`
+                if latest
+                    .get(&id)
+                    .is_none_or(|(_, existing)| supersedes(&record, existing))
+                {
+                    latest.insert(id.clone(), (lines.len(), record));
+                }
+                lines.push((Some(id), line));
+            }
+            _ => lines.push((None, line)),
+        }
+    }
+
+    let mut kept = Vec::new();
+    let mut rejected = Vec::new();
+    for (i, (id, line)) in lines.iter().enumerate() {
+        match id {
+            Some(id) if latest[id].0 != i => {}
+            None if rejected_path.is_some() => rejected.push(line.as_str()),
+            _ => kept.push(line.as_str()),
+        }
+    }
+    stats.lines_after = kept.len();
+
+    if stats.lines_after == stats.lines_before && archives.is_empty() {
+        stats.bytes_after = stats.bytes_before;
+        return Ok(stats);
+    }
+
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".compact.tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+    {
+        let mut tmp =
+            File::create(&tmp_path).map_err(|e| StoreError::io("Failed to create compaction temp file", e))?;
+        for line in &kept {
+            writeln!(tmp, "{}", line)?;
+        }
+        tmp.sync_all()?;
+        stats.bytes_after = tmp.metadata()?.len();
+    }
+
+    // Set rejected lines aside before they disappear from the original
+    if let Some(rejected_path) = rejected_path.filter(|_| !rejected.is_empty()) {
+        let mut out = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(rejected_path)
+            .map_err(|e| StoreError::io("Failed to open rejected lines file", e))?;
+        for line in &rejected {
+            writeln!(out, "{}", line)?;
+        }
+        out.sync_all()?;
+        warn!(file = ?rejected_path, count = rejected.len(), "Moved unparseable JSONL lines aside");
+    }
+
+    fs::rename(&tmp_path, path).map_err(|e| StoreError::io("Failed to replace JSONL file with compacted copy", e))?;
+    #[cfg(unix)]
+    if let Some(dir) = path.parent() {
+        // Make the rename itself durable
+        File::open(dir)?.sync_all()?;
+    }
+    // Only now that their lines are in the file
+    for archive in &archives {
+        fs::remove_file(archive).map_err(|e| StoreError::io("Failed to remove folded JSONL archive", e))?;
+    }
 
     info!(
         file = ?path,
-        count = records.len(),
-        "Loaded latest records from JSONL"
+        lines_before = stats.lines_before,
+        lines_after = stats.lines_after,
+        "Compacted JSONL file"
     );
+    Ok(stats)
+}
 
-    Ok(records)
+/// Move a JSONL file's lines into a gzipped archive and start the file over empty
+///
+/// The archive is `<file>.<n>.gz`, numbered after the existing ones, see
+/// `archive_paths`. Runs under the file's exclusive lock. The archive is on
+/// disk before an empty file is renamed over the original, so an interrupted
+/// rotation loses nothing; at worst lines are in both, which reads resolve.
+/// Returns the archive's path, or `None` if the file is missing or empty.
+pub fn rotate_jsonl(path: &Path) -> Result<Option<PathBuf>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(StoreError::io("Failed to open JSONL file", e)),
+    };
+    file.lock_exclusive()
+        .map_err(|e| StoreError::io("Failed to acquire file lock", e))?;
+    if !is_current_file(&file, path)? {
+        // Rotated or compacted while waiting for the lock
+        return rotate_jsonl(path);
+    }
+    if file.metadata()?.len() == 0 {
+        return Ok(None);
+    }
+
+    let next = archive_paths(path)?
+        .last()
+        .and_then(|p| archive_number(path, p))
+        .unwrap_or(0)
+        + 1;
+    let archive = archive_path(path, next);
+    let mut tmp_name = archive.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = archive.with_file_name(tmp_name);
+    {
+        let out = File::create(&tmp_path).map_err(|e| StoreError::io("Failed to create JSONL archive", e))?;
+        let mut encoder = GzEncoder::new(out, Compression::default());
+        std::io::copy(&mut &file, &mut encoder)?;
+        encoder.finish()?.sync_all()?;
+    }
+    fs::rename(&tmp_path, &archive).map_err(|e| StoreError::io("Failed to move JSONL archive into place", e))?;
+
+    // Replaced rather than truncated, so appenders holding the old file notice
+    let mut empty_name = path.file_name().unwrap_or_default().to_os_string();
+    empty_name.push(".rotate.tmp");
+    let empty_path = path.with_file_name(empty_name);
+    File::create(&empty_path)
+        .map_err(|e| StoreError::io("Failed to create empty JSONL file", e))?
+        .sync_all()?;
+    fs::rename(&empty_path, path).map_err(|e| StoreError::io("Failed to replace rotated JSONL file", e))?;
+    #[cfg(unix)]
+    if let Some(dir) = path.parent() {
+        // Make both renames durable
+        File::open(dir)?.sync_all()?;
+    }
+
+    info!(file = ?path, archive = ?archive, "Rotated JSONL file into gzip archive");
+    Ok(Some(archive))
+}
+
+/// Gzipped archives of a JSONL file written by `rotate_jsonl`, oldest first
+pub fn archive_paths(path: &Path) -> Result<Vec<PathBuf>> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(StoreError::io("Failed to list JSONL archives", e)),
+    };
+    let mut archives = Vec::new();
+    for entry in entries {
+        let archive = dir.join(entry?.file_name());
+        if let Some(n) = archive_number(path, &archive) {
+            archives.push((n, archive));
+        }
+    }
+    archives.sort();
+    Ok(archives.into_iter().map(|(_, archive)| archive).collect())
+}
+
+/// Path of archive `n` of a JSONL file
+fn archive_path(path: &Path, n: u64) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}.gz", n));
+    path.with_file_name(name)
+}
+
+/// `n` if `archive` is archive `n` of the JSONL file at `path`
+fn archive_number(path: &Path, archive: &Path) -> Option<u64> {
+    let name = path.file_name()?.to_str()?;
+    archive
+        .file_name()?
+        .to_str()?
+        .strip_prefix(name)?
+        .strip_prefix('.')?
+        .strip_suffix(".gz")?
+        .parse()
+        .ok()
+}
+
+/// True if `file` is still the file at `path` (not replaced by a rename)
+fn is_current_file(file: &File, path: &Path) -> Result<bool> {
+    let open = file_identity(&file.metadata()?);
+    Ok(match fs::metadata(path) {
+        Ok(current) => file_identity(&current) == open,
+        Err(_) => false,
+    })
+}
+
+/// Device and inode of a file, to tell a replaced file from the original; `None` off Unix
+#[cfg(unix)]
+fn file_identity(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_identity(_metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// Follows a JSONL file like `tail -f`, returning records appended since the last poll
+///
+/// The file being read stays open between polls. If it is replaced (e.g. by
+/// `rotate_jsonl` or `compact_jsonl`), lines appended to the old file before
+/// the swap are read from the open handle first, then the new file from its
+/// start; likewise a truncated file is read again from its start. Versions
+/// already returned (same ID, no newer `updated_at`) are skipped in that
+/// reread, so a compacted file isn't replayed. That takes remembering the
+/// latest `updated_at` returned per ID.
+#[derive(Debug)]
+pub struct JsonlTail {
+    path: PathBuf,
+    offset: u64,
+    identity: Option<(u64, u64)>,
+    /// The file being followed, kept open to finish it after it's replaced
+    file: Option<File>,
+    /// Bytes of a line whose newline hasn't been written yet
+    partial: Vec<u8>,
+    /// Latest `updated_at` returned per ID
+    seen: HashMap<String, i64>,
+    /// Reading a replaced or truncated file from its start, see `seen`
+    rewound: bool,
+}
+
+impl JsonlTail {
+    /// Start following at the file's current end (or its start, if it doesn't exist yet)
+    pub fn from_end(path: &Path) -> Result<Self> {
+        let mut tail = Self::from_start(path);
+        match File::open(path) {
+            Ok(file) => {
+                let metadata = file.metadata()?;
+                tail.offset = metadata.len();
+                tail.identity = file_identity(&metadata);
+                tail.file = Some(file);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(StoreError::io("Failed to open JSONL file", e)),
+        }
+        Ok(tail)
+    }
+
+    /// Start following at the file's start, so existing lines are returned by the first poll
+    pub fn from_start(path: &Path) -> Self {
+        Self::from_offset(path, 0)
+    }
+
+    /// Start following at a byte offset, which should be the start of a line
+    pub fn from_offset(path: &Path, offset: u64) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            offset,
+            identity: None,
+            file: None,
+            partial: Vec::new(),
+            seen: HashMap::new(),
+            rewound: false,
+        }
+    }
+
+    /// Offset just past the last complete line returned so far
+    pub fn position(&self) -> u64 {
+        self.offset - self.partial.len() as u64
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Records on complete lines appended since the last poll, in file order
+    ///
+    /// Lines that aren't valid JSON are logged and skipped.
+    pub fn poll(&mut self) -> Result<Vec<Value>> {
+        let current = match fs::metadata(&self.path) {
+            Ok(metadata) => Some(metadata),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(StoreError::io("Failed to stat JSONL file", e)),
+        };
+        let identity = current.as_ref().and_then(file_identity);
+
+        let mut records = Vec::new();
+        if self.identity.is_some() && identity != self.identity {
+            debug!(file = ?self.path, "JSONL file was replaced, finishing the old one");
+            if let Some(mut file) = self.file.take() {
+                records = self.read_from(&mut file)?;
+            }
+            self.rewind();
+        }
+        let Some(metadata) = current else {
+            // Removed; a new file will be read from its start
+            self.rewind();
+            return Ok(records);
+        };
+        if metadata.len() < self.offset {
+            debug!(file = ?self.path, "JSONL file was truncated, reading it from its start");
+            self.rewind();
+        }
+
+        if self.file.is_none() {
+            let file = File::open(&self.path).map_err(|e| StoreError::io("Failed to open JSONL file", e))?;
+            // The open file's identity, in case it was replaced again since the stat
+            self.identity = file_identity(&file.metadata()?);
+            self.file = Some(file);
+        }
+        if let Some(mut file) = self.file.take() {
+            let read = self.read_from(&mut file);
+            self.file = Some(file);
+            records.extend(read?);
+            self.rewound = false;
+        }
+        Ok(records)
+    }
+
+    /// Start over at the beginning of whatever file is at the path next
+    fn rewind(&mut self) {
+        self.offset = 0;
+        self.identity = None;
+        self.file = None;
+        self.partial.clear();
+        self.rewound = true;
+    }
+
+    /// Records on the complete lines of `file` past the offset
+    fn read_from(&mut self, file: &mut File) -> Result<Vec<Value>> {
+        if file.metadata()?.len() <= self.offset {
+            return Ok(Vec::new());
+        }
+        file.lock_shared()
+            .map_err(|e| StoreError::io("Failed to acquire shared file lock", e))?;
+        file.seek(SeekFrom::Start(self.offset))?;
+        let read = file.read_to_end(&mut self.partial);
+        file.unlock()
+            .map_err(|e| StoreError::io("Failed to release shared file lock", e))?;
+        self.offset += read? as u64;
+
+        let complete = match self.partial.iter().rposition(|b| *b == b'\n') {
+            Some(end) => self.partial.drain(..=end).collect::<Vec<_>>(),
+            None => return Ok(Vec::new()),
+        };
+
+        let mut records = Vec::new();
+        for line in complete.split(|b| *b == b'\n') {
+            if line.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+            let record: Value = match serde_json::from_slice(line) {
+                Ok(record) => record,
+                Err(e) => {
+                    warn!(file = ?self.path, error = ?e, "Failed to parse appended JSON, skipping");
+                    continue;
+                }
+            };
+            if let Some(id) = record.get("id").and_then(|v| v.as_str()) {
+                let ts = updated_at(&record);
+                match self.seen.get_mut(id) {
+                    Some(seen) if self.rewound && ts <= *seen => continue,
+                    Some(seen) => *seen = (*seen).max(ts),
+                    None => {
+                        self.seen.insert(id.to_string(), ts);
+                    }
+                }
+            }
+            records.push(record);
+        }
+        Ok(records)
+    }
+}
+
+/// Compute a hex-encoded SHA-256 hash of a JSONL file's contents
+///
+/// Used for staleness detection, where mtimes alone can't be trusted
+/// (git checkouts, copies, coarse filesystem timestamps).
+pub fn hash_file(path: &Path) -> Result<String> {
+    let file = File::open(path).map_err(|e| StoreError::io("Failed to open JSONL file for hashing", e))?;
+
+    // Acquire shared lock so we don't hash a half-written append
+    file.lock_shared()
+        .map_err(|e| StoreError::io("Failed to acquire shared file lock", e))?;
+
+    let mut hasher = Sha256::new();
+    hash_into(&mut hasher, BufReader::new(file))?;
+    Ok(hex(hasher))
+}
+
+/// Hashes (as `hash_file` computes them) of a file's first `len` bytes, for each ascending `len`
+///
+/// Reads the file once. Used by incremental sync to check that the bytes it
+/// already synced are unchanged while hashing up to the new offset.
+pub fn hash_prefixes(path: &Path, lens: &[u64]) -> Result<Vec<String>> {
+    let file = File::open(path).map_err(|e| StoreError::io("Failed to open JSONL file for hashing", e))?;
+    file.lock_shared()
+        .map_err(|e| StoreError::io("Failed to acquire shared file lock", e))?;
+
+    let mut reader = BufReader::new(file);
+    let mut hasher = Sha256::new();
+    let mut hashed = 0;
+    let mut hashes = Vec::with_capacity(lens.len());
+    for &len in lens {
+        hashed += hash_into(&mut hasher, (&mut reader).take(len.saturating_sub(hashed)))?;
+        hashes.push(hex(hasher.clone()));
+    }
+    Ok(hashes)
+}
+
+/// Feed everything `reader` yields into `hasher`, returning the byte count
+fn hash_into(hasher: &mut Sha256, mut reader: impl Read) -> Result<u64> {
+    let mut buf = [0u8; 8192];
+    let mut total = 0;
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            return Ok(total);
+        }
+        hasher.update(&buf[..n]);
+        total += n as u64;
+    }
+}
+
+fn hex(hasher: Sha256) -> String {
+    hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// True if `offset` is 0 or the byte before it is a newline, i.e. a line starts there
+pub(crate) fn is_line_start(path: &Path, offset: u64) -> Result<bool> {
+    if offset == 0 {
+        return Ok(true);
+    }
+    let mut file = File::open(path).map_err(|e| StoreError::io("Failed to open JSONL file", e))?;
+    file.seek(SeekFrom::Start(offset - 1))?;
+    let mut byte = [0u8];
+    Ok(file.read(&mut byte)? == 1 && byte[0] == b'\n')
 }
 
 #[cfg(test)]
@@ -138,6 +1107,346 @@ mod tests {
         assert!(content.contains("\"name\":\"Test\""));
     }
 
+    #[test]
+    fn test_concurrent_appends_write_whole_lines() {
+        let temp = TempDir::new().unwrap();
+        let jsonl_path = temp.path().join("test.jsonl");
+
+        // Lines larger than a pipe buffer, so a split write would show
+        let payload = "x".repeat(64 * 1024);
+        std::thread::scope(|scope| {
+            for thread in 0..4 {
+                let (jsonl_path, payload) = (&jsonl_path, &payload);
+                scope.spawn(move || {
+                    for i in 0..25 {
+                        let record = serde_json::json!({
+                            "id": format!("t{}-{}", thread, i),
+                            "payload": payload,
+                            "updated_at": 1000,
+                        });
+                        append_jsonl(jsonl_path, &record).unwrap();
+                    }
+                });
+            }
+        });
+
+        let content = fs::read_to_string(&jsonl_path).unwrap();
+        assert!(content.ends_with('\n'));
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 100);
+        for line in lines {
+            let record: Value = serde_json::from_str(line).unwrap();
+            assert_eq!(record["payload"].as_str().unwrap().len(), payload.len());
+        }
+        assert_eq!(read_jsonl_latest(&jsonl_path).unwrap().len(), 100);
+    }
+
+    #[test]
+    fn test_to_canonical_json_sorts_keys() {
+        #[derive(Serialize)]
+        struct Task {
+            updated_at: i64,
+            id: String,
+            labels: HashMap<String, i64>,
+        }
+
+        let labels: HashMap<String, i64> = (0..20).map(|i| (format!("k{:02}", 19 - i), i)).collect();
+        let line = to_canonical_json(&Task {
+            updated_at: 1000,
+            id: "a".to_string(),
+            labels: labels.clone(),
+        })
+        .unwrap();
+        let expected_labels: Vec<String> = (0..20).map(|i| format!("\"k{:02}\":{}", i, 19 - i)).collect();
+        assert_eq!(
+            line,
+            format!(
+                r#"{{"id":"a","labels":{{{}}},"updated_at":1000}}"#,
+                expected_labels.join(",")
+            )
+        );
+    }
+
+    #[test]
+    fn test_fsync_policies_track_pending_appends() {
+        let temp = TempDir::new().unwrap();
+        let a = temp.path().join("a.jsonl");
+        let b = temp.path().join("b.jsonl");
+
+        let every = Fsync::new(FsyncPolicy::EveryWrite);
+        append_line(&a, "{}", &every).unwrap();
+        assert_eq!(every.pending_files(), 0);
+
+        // Flushed on the third append, across files
+        let batched = Fsync::new(FsyncPolicy::Batched(3));
+        append_line(&a, "{}", &batched).unwrap();
+        append_line(&b, "{}", &batched).unwrap();
+        assert_eq!(batched.pending_files(), 2);
+        append_line(&a, "{}", &batched).unwrap();
+        assert_eq!(batched.pending_files(), 0);
+
+        let on_flush = Fsync::new(FsyncPolicy::OnFlush);
+        for _ in 0..10 {
+            append_line(&a, "{}", &on_flush).unwrap();
+        }
+        assert_eq!(on_flush.pending_files(), 1);
+        fs::remove_file(&a).unwrap();
+        on_flush.flush().unwrap();
+        assert_eq!(on_flush.pending_files(), 0);
+    }
+
+    #[test]
+    fn test_recover_partial_line() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("test.jsonl");
+        let full = "{\"id\":\"a\",\"updated_at\":1}\n";
+
+        assert_eq!(recover_partial_line(&path).unwrap(), TailRecovery::Clean);
+        fs::write(&path, full).unwrap();
+        assert_eq!(recover_partial_line(&path).unwrap(), TailRecovery::Clean);
+
+        // A complete record missing its newline only gets the newline
+        fs::write(&path, format!("{}{{\"id\":\"b\"}}", full)).unwrap();
+        assert_eq!(recover_partial_line(&path).unwrap(), TailRecovery::Terminated);
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            format!("{}{{\"id\":\"b\"}}\n", full)
+        );
+
+        // A partial record is cut off, including one longer than a read chunk
+        let partial = format!("{{\"id\":\"c\",\"body\":\"{}", "x".repeat(20_000));
+        fs::write(&path, format!("{}{}", full, partial)).unwrap();
+        assert_eq!(
+            recover_partial_line(&path).unwrap(),
+            TailRecovery::Truncated(partial.len() as u64)
+        );
+        assert_eq!(fs::read_to_string(&path).unwrap(), full);
+
+        // Even when it is the only line
+        fs::write(&path, "{\"id\":").unwrap();
+        assert_eq!(recover_partial_line(&path).unwrap(), TailRecovery::Truncated(6));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "");
+    }
+
+    #[test]
+    fn test_scan_jsonl_reports_problems() {
+        let temp = TempDir::new().unwrap();
+        let jsonl_path = temp.path().join("test.jsonl");
+
+        let content = [
+            r#"{"id":"a","name":"A","updated_at":1000}"#,
+            r#"{"id":"a","name":"A2","updated_at":2000}"#,
+            r#"{"id":"b","name":"B1","updated_at":1000}"#,
+            r#"{"id":"b","name":"B2","updated_at":1000}"#,
+            "not json",
+            r#"{"name":"missing id"}"#,
+            r#"{"id":"c","name":"C","updated_at":1000}"#,
+            r#"{"id":"c","name":"C","updated_at":1000}"#,
+            r#"{"id":"d","deleted":true,"updated_at":1000}"#,
+            r#"{"id":"e","name":"E","updat"#,
+        ]
+        .join("\n");
+        fs::write(&jsonl_path, content).unwrap();
+
+        let scan = scan_jsonl(&jsonl_path).unwrap();
+        assert_eq!(scan.live_records, 3);
+        assert_eq!(scan.unparseable_lines, vec![5, 6, 10]);
+        // Identical duplicate lines ("c") are not ambiguous
+        assert_eq!(scan.ambiguous_ids, vec!["b"]);
+        assert!(!scan.is_clean());
+
+        assert!(scan_jsonl(&temp.path().join("missing.jsonl")).unwrap().is_clean());
+    }
+
+    #[test]
+    fn test_compact_jsonl_keeps_latest_lines() {
+        let temp = TempDir::new().unwrap();
+        let jsonl_path = temp.path().join("test.jsonl");
+
+        let content = [
+            r#"{"id":"a","name":"A1","updated_at":1000}"#,
+            r#"{"id":"b","name":"B","updated_at":1000}"#,
+            "",
+            r#"{"id":"a","name":"A2","updated_at":2000}"#,
+            "not json",
+            r#"{"id":"b","deleted":true,"updated_at":3000}"#,
+            r#"{"id":"a","name":"A0","updated_at":500}"#,
+        ]
+        .join("\n");
+        fs::write(&jsonl_path, &content).unwrap();
+        let before = read_jsonl_latest(&jsonl_path).unwrap();
+
+        let stats = compact_jsonl(&jsonl_path).unwrap();
+        assert_eq!((stats.lines_before, stats.lines_after), (7, 3));
+        assert!(stats.bytes_saved() > 0);
+        assert_eq!(
+            fs::read_to_string(&jsonl_path).unwrap(),
+            [
+                r#"{"id":"a","name":"A2","updated_at":2000}"#,
+                "not json",
+                r#"{"id":"b","deleted":true,"updated_at":3000}"#,
+                "",
+            ]
+            .join("\n")
+        );
+        assert_eq!(read_jsonl_latest(&jsonl_path).unwrap(), before);
+        assert!(!temp.path().join("test.jsonl.compact.tmp").exists());
+
+        // Already compact: nothing to do, and appends still land in the live file
+        let again = compact_jsonl(&jsonl_path).unwrap();
+        assert_eq!((again.lines_before, again.lines_after), (3, 3));
+        append_jsonl(&jsonl_path, &json!({"id": "c", "updated_at": 1})).unwrap();
+        assert!(read_jsonl_latest(&jsonl_path).unwrap().contains_key("c"));
+    }
+
+    #[test]
+    fn test_rotate_jsonl_archives_are_read() {
+        let temp = TempDir::new().unwrap();
+        let jsonl_path = temp.path().join("test.jsonl");
+        assert_eq!(rotate_jsonl(&jsonl_path).unwrap(), None);
+
+        fs::write(
+            &jsonl_path,
+            concat!(
+                r#"{"id":"a","name":"A1","updated_at":1000}"#,
+                "\n",
+                r#"{"id":"b","name":"B","updated_at":1000}"#,
+                "\n",
+            ),
+        )
+        .unwrap();
+        let archive = rotate_jsonl(&jsonl_path).unwrap().unwrap();
+        assert_eq!(archive, temp.path().join("test.jsonl.1.gz"));
+        assert_eq!(fs::metadata(&jsonl_path).unwrap().len(), 0);
+        // Nothing to rotate in an empty file
+        assert_eq!(rotate_jsonl(&jsonl_path).unwrap(), None);
+
+        fs::write(
+            &jsonl_path,
+            concat!(r#"{"id":"b","deleted":true,"updated_at":2000}"#, "\n"),
+        )
+        .unwrap();
+        assert_eq!(
+            rotate_jsonl(&jsonl_path).unwrap().unwrap(),
+            temp.path().join("test.jsonl.2.gz")
+        );
+        // An older version in the live file loses to the archived one
+        fs::write(
+            &jsonl_path,
+            concat!(
+                r#"{"id":"a","name":"A0","updated_at":500}"#,
+                "\n",
+                r#"{"id":"c","name":"C","updated_at":1000}"#,
+                "\n",
+            ),
+        )
+        .unwrap();
+
+        assert_eq!(archive_paths(&jsonl_path).unwrap().len(), 2);
+        let records = read_jsonl_latest(&jsonl_path).unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records["a"]["name"], "A1");
+        assert_eq!(records["b"]["deleted"], true);
+        assert_eq!(records["c"]["name"], "C");
+
+        // Archives alone are still read
+        fs::remove_file(&jsonl_path).unwrap();
+        assert_eq!(read_jsonl_latest(&jsonl_path).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_repair_jsonl_moves_bad_lines_aside() {
+        let temp = TempDir::new().unwrap();
+        let jsonl_path = temp.path().join("test.jsonl");
+
+        let content = [
+            r#"{"id":"a","name":"A1","updated_at":1000}"#,
+            r#"{"id":"a","name":"A2","updated_at":1000}"#,
+            "not json",
+            r#"{"id":"b","name":"B","updated_at":1000}"#,
+        ]
+        .join("\n");
+        fs::write(&jsonl_path, &content).unwrap();
+        let before = read_jsonl_latest(&jsonl_path).unwrap();
+
+        let stats = repair_jsonl(&jsonl_path).unwrap();
+        assert_eq!((stats.lines_before, stats.lines_after), (4, 2));
+        assert!(scan_jsonl(&jsonl_path).unwrap().is_clean());
+        assert_eq!(read_jsonl_latest(&jsonl_path).unwrap(), before);
+        assert_eq!(
+            fs::read_to_string(temp.path().join("test.jsonl.rejected")).unwrap(),
+            "not json\n"
+        );
+    }
+
+    #[test]
+    fn test_jsonl_tail_follows_appends_and_compaction() {
+        let temp = TempDir::new().unwrap();
+        let jsonl_path = temp.path().join("test.jsonl");
+        let mut tail = JsonlTail::from_end(&jsonl_path).unwrap();
+        assert!(tail.poll().unwrap().is_empty());
+
+        append_jsonl(&jsonl_path, &json!({"id": "a", "updated_at": 1})).unwrap();
+        append_jsonl(&jsonl_path, &json!({"id": "a", "updated_at": 2})).unwrap();
+        let ids: Vec<_> = tail.poll().unwrap().iter().map(|r| r["updated_at"].clone()).collect();
+        assert_eq!(ids, vec![json!(1), json!(2)]);
+
+        // A line without its newline yet is held back until it's complete
+        let mut file = OpenOptions::new().append(true).open(&jsonl_path).unwrap();
+        write!(file, r#"{{"id":"b","#).unwrap();
+        assert!(tail.poll().unwrap().is_empty());
+        writeln!(file, r#""updated_at":1}}"#).unwrap();
+        assert_eq!(tail.poll().unwrap()[0]["id"], "b");
+
+        // Compaction replaces the file; nothing is replayed, later appends still arrive
+        compact_jsonl(&jsonl_path).unwrap();
+        assert!(tail.poll().unwrap().is_empty());
+        append_jsonl(&jsonl_path, &json!({"id": "c", "updated_at": 1})).unwrap();
+        assert_eq!(tail.poll().unwrap()[0]["id"], "c");
+    }
+
+    #[test]
+    fn test_jsonl_tail_reads_lines_around_rotation_and_compaction() {
+        let temp = TempDir::new().unwrap();
+        let jsonl_path = temp.path().join("test.jsonl");
+        append_jsonl(&jsonl_path, &json!({"id": "a", "updated_at": 1})).unwrap();
+        let mut tail = JsonlTail::from_end(&jsonl_path).unwrap();
+        let ids = |records: Vec<Value>| -> Vec<Value> { records.iter().map(|r| r["id"].clone()).collect() };
+
+        // b went into the archive, c into the new file
+        append_jsonl(&jsonl_path, &json!({"id": "b", "updated_at": 1})).unwrap();
+        rotate_jsonl(&jsonl_path).unwrap().unwrap();
+        append_jsonl(&jsonl_path, &json!({"id": "c", "updated_at": 1})).unwrap();
+        assert_eq!(ids(tail.poll().unwrap()), vec![json!("b"), json!("c")]);
+
+        // Lines appended just before a compaction arrive, ones already seen don't
+        append_jsonl(&jsonl_path, &json!({"id": "c", "updated_at": 2})).unwrap();
+        append_jsonl(&jsonl_path, &json!({"id": "d", "updated_at": 1})).unwrap();
+        compact_jsonl(&jsonl_path).unwrap();
+        let records = tail.poll().unwrap();
+        assert_eq!(ids(records.clone()), vec![json!("c"), json!("d")]);
+        assert_eq!(records[0]["updated_at"], 2);
+        assert!(tail.poll().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_hash_prefixes_match_hash_file() {
+        let temp = TempDir::new().unwrap();
+        let jsonl_path = temp.path().join("test.jsonl");
+
+        let first_line = "{\"id\":\"a\"}\n";
+        fs::write(&jsonl_path, first_line).unwrap();
+        let first = hash_file(&jsonl_path).unwrap();
+        append_jsonl(&jsonl_path, &json!({"id": "b"})).unwrap();
+        let len = fs::metadata(&jsonl_path).unwrap().len();
+
+        let first_len = first_line.len() as u64;
+        let hashes = hash_prefixes(&jsonl_path, &[first_len, len]).unwrap();
+        assert_eq!(hashes, vec![first, hash_file(&jsonl_path).unwrap()]);
+        assert!(is_line_start(&jsonl_path, first_len).unwrap());
+        assert!(!is_line_start(&jsonl_path, 5).unwrap());
+    }
+
     #[test]
     fn test_read_jsonl_latest() {
         let temp = TempDir::new().unwrap();
@@ -168,6 +1477,26 @@ mod tests {
         assert_eq!(latest.get("updated_at").and_then(|v| v.as_i64()), Some(2000));
     }
 
+    #[test]
+    fn test_read_jsonl_latest_breaks_timestamp_ties_by_content() {
+        let temp = TempDir::new().unwrap();
+        let alpha = r#"{"id":"test-1","name":"Alpha","updated_at":1000}"#;
+        let beta = r#"{"updated_at":1000,"name":"Beta","id":"test-1"}"#;
+
+        // Same winner whichever line comes first, for reads and compaction alike
+        for (i, lines) in [[alpha, beta], [beta, alpha]].iter().enumerate() {
+            let jsonl_path = temp.path().join(format!("tie{}.jsonl", i));
+            fs::write(&jsonl_path, format!("{}\n{}\n", lines[0], lines[1])).unwrap();
+
+            let records = read_jsonl_latest(&jsonl_path).unwrap();
+            assert_eq!(records["test-1"]["name"], "Beta");
+            assert_eq!(scan_jsonl(&jsonl_path).unwrap().ambiguous_ids, vec!["test-1"]);
+
+            compact_jsonl(&jsonl_path).unwrap();
+            assert_eq!(fs::read_to_string(&jsonl_path).unwrap(), format!("{}\n", beta));
+        }
+    }
+
     #[test]
     fn test_read_jsonl_nonexistent_file() {
         let temp = TempDir::new().unwrap();
@@ -198,4 +1527,18 @@ mod tests {
         assert!(records.contains_key("test-1"));
         assert!(records.contains_key("test-2"));
     }
+
+    #[test]
+    fn test_hash_file() {
+        let temp = TempDir::new().unwrap();
+        let jsonl_path = temp.path().join("test.jsonl");
+
+        fs::write(&jsonl_path, "{\"id\":\"test-1\",\"updated_at\":1000}\n").unwrap();
+        let hash1 = hash_file(&jsonl_path).unwrap();
+        assert_eq!(hash1.len(), 64);
+        assert_eq!(hash1, hash_file(&jsonl_path).unwrap());
+
+        fs::write(&jsonl_path, "{\"id\":\"test-1\",\"updated_at\":2000}\n").unwrap();
+        assert_ne!(hash1, hash_file(&jsonl_path).unwrap());
+    }
 }
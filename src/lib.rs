@@ -1,14 +1,32 @@
 // TaskStore - Generic persistent state management with SQLite+JSONL+Git
 
+#[cfg(feature = "async")]
+pub mod async_store;
+pub mod error;
 pub mod filter;
+pub mod hooks;
 pub mod jsonl;
+pub mod metrics;
 pub mod record;
+pub mod shared;
 pub mod store;
 
 // Re-export main types for convenience
-pub use filter::{Filter, FilterOp};
-pub use record::{IndexValue, Record};
-pub use store::{Store, now_ms};
+#[cfg(feature = "async")]
+pub use async_store::AsyncStore;
+pub use error::{DuplicateKeyError, StoreError, StoreResult};
+pub use filter::{Agg, Filter, FilterExpr, FilterOp, ListOptions, Query, SortDir};
+pub use hooks::{AfterWriteHook, BeforeWriteHook, ChangeCallback, ChangeEvent, ChangeKind, StoreHooks, WriteOp};
+pub use jsonl::{CompactStats, FsyncPolicy, TailRecovery};
+#[cfg(feature = "metrics")]
+pub use metrics::{CollectionMetrics, Histogram, Metrics};
+pub use record::{ChildRelation, IndexKind, IndexSpec, IndexValue, Record, Reference, ValidationError};
+pub use shared::SharedStore;
+pub use store::{
+    Batch, BundleReport, ClearConfirm, CollectionDiff, CollectionJsonlHealth, CollectionSizes, CollectionSyncPlan,
+    DanglingReference, HealthReport, ImportReport, ImportStrategy, ListIter, MaybeDeleted, OrphanedRecords, RowIter,
+    Store, StoreDiff, StoreOptions, SyncPlan, Synchronous, Txn, now_ms,
+};
 
 // Re-export rusqlite for CLI use
 pub use rusqlite;
@@ -1,8 +1,17 @@
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use eyre::Result;
+use notify::{RecursiveMode, Watcher};
 use rusqlite::params;
+use std::collections::{BTreeMap, HashSet};
 use std::path::PathBuf;
-use taskstore::{Store, rusqlite};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, mpsc};
+use std::time::Duration;
+use taskstore::jsonl::{self, JsonlTail};
+use taskstore::{Filter, HealthReport, ImportStrategy, IndexSpec, Store, StoreOptions, rusqlite};
+
+/// Exit status when the requested record doesn't exist
+const EXIT_NOT_FOUND: i32 = 2;
 
 #[derive(Parser)]
 #[command(name = "taskstore")]
@@ -13,6 +22,10 @@ struct Cli {
     #[arg(short, long, default_value = ".")]
     store_path: PathBuf,
 
+    /// Open the store read-only (fails if it needs a sync instead of syncing)
+    #[arg(long, global = true)]
+    read_only: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -20,7 +33,66 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     /// Sync SQLite database from JSONL files
-    Sync,
+    Sync {
+        /// Print what would change without modifying the database
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Reclaim unused space in the SQLite database (needs free disk space ~= db size)
+    Vacuum,
+
+    /// Delete and rebuild the SQLite database from JSONL, reporting JSONL problems found along the way
+    Repair {
+        /// Also fix those problems: move unparseable lines to <collection>.jsonl.rejected and keep
+        /// only the version reads already pick for duplicate IDs with equal timestamps
+        #[arg(long)]
+        fix_jsonl: bool,
+    },
+
+    /// Drop superseded lines from JSONL files, keeping the latest version of each record
+    Compact {
+        /// Collection to compact (default: every collection with a JSONL file)
+        collection: Option<String>,
+    },
+
+    /// Show per-collection JSONL size, line and live-record counts, and SQLite index rows
+    Sizes {
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Pretty)]
+        format: OutputFormat,
+    },
+
+    /// Check referential integrity between collections
+    Validate {
+        /// Reference to check as child.field=parent, e.g. members.team_id=teams (can be repeated)
+        #[arg(short, long = "ref", value_name = "CHILD.FIELD=PARENT", required = true)]
+        references: Vec<String>,
+    },
+
+    /// Report store health (staleness, index drift, bad JSONL lines, orphans) without changing anything
+    Check {
+        /// Also check a reference as child.field=parent (can be repeated)
+        #[arg(short, long = "ref", value_name = "CHILD.FIELD=PARENT")]
+        references: Vec<String>,
+
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Compare this store's records with another store's, read from JSONL without syncing either
+    Diff {
+        /// Path to the other store (the directory containing its .taskstore)
+        other: PathBuf,
+
+        /// Only compare this collection
+        collection: Option<String>,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Pretty)]
+        format: OutputFormat,
+    },
 
     /// Install git hooks for automatic syncing
     InstallHooks,
@@ -40,9 +112,32 @@ enum Commands {
         /// Limit number of results
         #[arg(short, long)]
         limit: Option<usize>,
+
+        /// Print only the IDs of matching records
+        #[arg(long)]
+        ids_only: bool,
+    },
+
+    /// List records matching indexed-field conditions, newest first
+    Query {
+        /// Collection name
+        collection: String,
+
+        /// Condition as field<op>value with op one of = != > < >= <= ~ ^= (can be repeated);
+        /// dates like 2024-01-01 compare with millisecond timestamps
+        #[arg(short = 'w', long = "where", value_name = "CONDITION")]
+        conditions: Vec<String>,
+
+        /// Limit number of results
+        #[arg(short, long)]
+        limit: Option<usize>,
+
+        /// Output format (pretty prints a table, json prints JSONL)
+        #[arg(long, value_enum, default_value_t = OutputFormat::Pretty)]
+        format: OutputFormat,
     },
 
-    /// Get a specific record by ID
+    /// Get a specific record by ID (exits with status 2 if it doesn't exist)
     Get {
         /// Collection name
         collection: String,
@@ -51,12 +146,82 @@ enum Commands {
         id: String,
     },
 
+    /// Show the latest version of a record straight from its JSONL file (exits with status 2 if absent)
+    ShowRecord {
+        /// Collection name
+        collection: String,
+
+        /// Record ID
+        id: String,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Pretty)]
+        format: OutputFormat,
+    },
+
+    /// Create one record from a JSON file (or stdin with `--file -`) and print its ID
+    Create {
+        /// Collection name
+        collection: String,
+
+        /// JSON file holding one record object, or - for stdin
+        #[arg(long)]
+        file: PathBuf,
+
+        /// Require FIELD to hold the ID of a record in collection PARENT (repeatable)
+        #[arg(long = "ref", value_name = "FIELD=PARENT")]
+        references: Vec<String>,
+    },
+
+    /// Append records from a JSONL file (or stdin with `--file -`) and reindex the collection
+    Import {
+        /// Collection name
+        collection: String,
+
+        /// JSONL file to read, or - for stdin
+        #[arg(long)]
+        file: PathBuf,
+
+        /// What to do with records whose ID already exists
+        #[arg(long, value_enum, default_value_t = Strategy::KeepNewer)]
+        strategy: Strategy,
+
+        /// Exit successfully even if some lines were rejected
+        #[arg(long)]
+        ignore_errors: bool,
+    },
+
+    /// Print records as they are appended to JSONL files, until Ctrl-C
+    Watch {
+        /// Only watch this collection (default: all)
+        collection: Option<String>,
+
+        /// Only print records matching field<op>value, as in `query --where` (can be repeated)
+        #[arg(long = "filter", value_name = "CONDITION")]
+        filters: Vec<String>,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Pretty)]
+        format: OutputFormat,
+    },
+
     /// Show indexes for a collection
     Indexes {
         /// Collection name
         collection: String,
     },
 
+    /// Rebuild a collection's indexes from its raw JSON, indexing the given fields
+    Reindex {
+        /// Collection name
+        collection: String,
+
+        /// Field to index and its type (string, int, float, or bool), e.g. status:string (can be repeated;
+        /// default: the collection's fields in .taskstore/schema.json)
+        #[arg(short, long = "field", value_name = "FIELD:KIND")]
+        fields: Vec<IndexSpec>,
+    },
+
     /// Run raw SQL query (read-only)
     Sql {
         /// SQL query to execute
@@ -64,27 +229,275 @@ enum Commands {
     },
 }
 
+#[derive(Clone, Copy, ValueEnum)]
+enum OutputFormat {
+    /// Human-readable (records as indented JSON)
+    Pretty,
+    /// JSON on a single line for scripts (records as stored)
+    Json,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Strategy {
+    /// Import only records newer than the existing version
+    KeepNewer,
+    /// Always import, replacing the existing version
+    Overwrite,
+    /// Leave existing records alone
+    SkipExisting,
+}
+
+impl From<Strategy> for ImportStrategy {
+    fn from(strategy: Strategy) -> Self {
+        match strategy {
+            Strategy::KeepNewer => ImportStrategy::KeepNewer,
+            Strategy::Overwrite => ImportStrategy::Overwrite,
+            Strategy::SkipExisting => ImportStrategy::SkipExisting,
+        }
+    }
+}
+
 fn main() -> Result<()> {
     // Setup tracing
     tracing_subscriber::fmt::init();
 
     let cli = Cli::parse();
 
+    // A dry run must see the index as it is, so skip the automatic sync on open
+    if let Commands::Sync { dry_run: true } = cli.command {
+        let store = Store::open_without_sync(&cli.store_path)?;
+        let plan = store.sync_dry_run()?;
+        if plan.is_empty() {
+            println!("Database is up to date, sync would make no changes");
+        } else {
+            println!("Sync would make the following changes:");
+            for c in &plan.collections {
+                println!(
+                    "  {}: {} added, {} updated, {} removed",
+                    c.collection, c.added, c.updated, c.removed
+                );
+            }
+        }
+        return Ok(());
+    }
+
+    // A health check reports staleness and drift, so it must not sync (or write) on open
+    if let Commands::Check { references, json } = &cli.command {
+        let store = Store::open_with(
+            &cli.store_path,
+            StoreOptions {
+                read_only: true,
+                auto_sync: false,
+                ..Default::default()
+            },
+        )?;
+        let refs = references
+            .iter()
+            .map(|r| parse_reference(r))
+            .collect::<Result<Vec<_>>>()?;
+        let report = store.check_with_refs(&refs)?;
+
+        if *json {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        } else {
+            print_health_report(&report);
+        }
+        if !report.is_healthy() {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    // Diff reads both stores' JSONL and must not write to either
+    if let Commands::Diff {
+        other,
+        collection,
+        format,
+    } = &cli.command
+    {
+        let read_only = || StoreOptions {
+            read_only: true,
+            auto_sync: false,
+            ..Default::default()
+        };
+        let store = Store::open_with(&cli.store_path, read_only())?;
+        let other_store = Store::open_with(other, read_only())?;
+        let diff = store.diff(&other_store, collection.as_deref())?;
+
+        match format {
+            OutputFormat::Json => println!("{}", serde_json::to_string(&diff)?),
+            OutputFormat::Pretty if diff.is_empty() => println!("No differences"),
+            OutputFormat::Pretty => {
+                println!("< only here, > only in {}, ~ differs", other.display());
+                for c in &diff.collections {
+                    println!("{}:", c.collection);
+                    for (marker, ids) in [("<", &c.only_in_self), (">", &c.only_in_other), ("~", &c.changed)] {
+                        for id in ids {
+                            println!("  {} {}", marker, id);
+                        }
+                    }
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    // Repair replaces the database, so it must not be opened (and synced) first
+    if let Commands::Repair { fix_jsonl } = cli.command {
+        let mut store = Store::rebuild_with(&cli.store_path, StoreOptions::default())?;
+        println!("Rebuilt database from JSONL");
+
+        let mut unfixed = false;
+        for collection in store.jsonl_collections()? {
+            let path = store.jsonl_dir().join(format!("{}.jsonl", collection));
+            let scan = jsonl::scan_jsonl(&path)?;
+            if !scan.unparseable_lines.is_empty() {
+                println!(
+                    "  Unparseable lines in {}.jsonl: {:?}",
+                    collection, scan.unparseable_lines
+                );
+            }
+            if scan.archived_unparseable_lines > 0 {
+                println!(
+                    "  Unparseable lines in {}.jsonl archives: {}",
+                    collection, scan.archived_unparseable_lines
+                );
+            }
+            if !scan.ambiguous_ids.is_empty() {
+                println!(
+                    "  Duplicate IDs with equal timestamps in {}.jsonl: {}",
+                    collection,
+                    scan.ambiguous_ids.join(", ")
+                );
+            }
+            if scan.is_clean() {
+                continue;
+            }
+
+            if fix_jsonl {
+                jsonl::repair_jsonl(&path)?;
+                store.sync_collection(&collection)?;
+                println!("  Fixed {}.jsonl", collection);
+            } else {
+                unfixed = true;
+            }
+        }
+        if unfixed {
+            println!("Run `taskstore repair --fix-jsonl` to fix the JSONL problems above");
+        }
+
+        let mut stmt = store
+            .db()
+            .prepare("SELECT collection, COUNT(*) FROM records GROUP BY collection ORDER BY collection")?;
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)))?;
+        for row in rows {
+            let (collection, count) = row?;
+            println!("  {} ({} records)", collection, count);
+        }
+        return Ok(());
+    }
+
     // Open store
-    let store = Store::open(&cli.store_path)?;
+    let store = if cli.read_only {
+        Store::open_read_only(&cli.store_path)?
+    } else {
+        Store::open(&cli.store_path)?
+    };
 
     match cli.command {
-        Commands::Sync => {
+        Commands::Sync { .. } => {
             let mut store = store;
             println!("Syncing database from JSONL files...");
             store.sync()?;
             println!("Sync complete");
         }
+        Commands::Vacuum => {
+            let mut store = store;
+            println!("Vacuuming database...");
+            store.vacuum()?;
+            println!("Vacuum complete");
+        }
+        Commands::Compact { collection } => {
+            let mut store = store;
+            let collections = match collection {
+                Some(collection) => vec![collection],
+                None => store.jsonl_collections()?,
+            };
+
+            // Keep going past failures so one bad file doesn't block the rest
+            let mut failed = Vec::new();
+            for collection in &collections {
+                match store.compact_collection(collection) {
+                    Ok(stats) => println!(
+                        "  {}: {} -> {} lines, {} bytes saved",
+                        collection,
+                        stats.lines_before,
+                        stats.lines_after,
+                        stats.bytes_saved()
+                    ),
+                    Err(e) => {
+                        eprintln!("  {}: compaction failed: {}", collection, e);
+                        failed.push(collection.as_str());
+                    }
+                }
+            }
+            if !failed.is_empty() {
+                eyre::bail!("Compaction failed for: {}", failed.join(", "));
+            }
+        }
+        Commands::Validate { references } => {
+            let mut total_orphans = 0;
+            for reference in &references {
+                let (child, field, parent) = parse_reference(reference)?;
+
+                let orphans = store.find_orphans(child, field, parent)?;
+                if orphans.is_empty() {
+                    println!("{}.{} -> {}: ok", child, field, parent);
+                } else {
+                    println!(
+                        "{}.{} -> {}: {} orphaned record(s)",
+                        child,
+                        field,
+                        parent,
+                        orphans.len()
+                    );
+                    for id in &orphans {
+                        println!("  {}", id);
+                    }
+                }
+                total_orphans += orphans.len();
+            }
+
+            if total_orphans > 0 {
+                std::process::exit(1);
+            }
+        }
+        Commands::Check { .. } | Commands::Diff { .. } | Commands::Repair { .. } => {
+            unreachable!("handled before the store is opened")
+        }
         Commands::InstallHooks => {
             println!("Installing git hooks...");
             store.install_git_hooks()?;
             println!("Git hooks installed successfully");
         }
+        Commands::Sizes { format } => {
+            let sizes = store.sizes()?;
+            match format {
+                OutputFormat::Json => println!("{}", serde_json::to_string(&sizes)?),
+                OutputFormat::Pretty => {
+                    println!(
+                        "{:<24} {:>12} {:>10} {:>10} {:>10} {:>7}",
+                        "COLLECTION", "JSONL BYTES", "LINES", "LIVE", "INDEX ROWS", "STALE"
+                    );
+                    for s in &sizes {
+                        println!(
+                            "{:<24} {:>12} {:>10} {:>10} {:>10} {:>7.2}",
+                            s.collection, s.jsonl_bytes, s.total_lines, s.live_records, s.index_rows, s.stale_ratio
+                        );
+                    }
+                }
+            }
+        }
         Commands::Collections => {
             println!("Collections in store:");
             let db = store.db();
@@ -101,6 +514,7 @@ fn main() -> Result<()> {
             collection,
             filter,
             limit,
+            ids_only,
         } => {
             let db = store.db();
             let limit_clause = limit.map(|l| format!(" LIMIT {}", l)).unwrap_or_default();
@@ -117,6 +531,10 @@ fn main() -> Result<()> {
 
                 for row in rows {
                     let (id, json) = row?;
+                    if ids_only {
+                        println!("{}", id);
+                        continue;
+                    }
                     // Pretty print JSON
                     let value: serde_json::Value = serde_json::from_str(&json)?;
                     println!("--- {} ---", id);
@@ -161,6 +579,10 @@ fn main() -> Result<()> {
 
                 for row in rows {
                     let (id, json) = row?;
+                    if ids_only {
+                        println!("{}", id);
+                        continue;
+                    }
                     let value: serde_json::Value = serde_json::from_str(&json)?;
                     println!("--- {} ---", id);
                     println!("{}", serde_json::to_string_pretty(&value)?);
@@ -168,6 +590,30 @@ fn main() -> Result<()> {
                 }
             }
         }
+        Commands::Query {
+            collection,
+            conditions,
+            limit,
+            format,
+        } => {
+            let filters = conditions
+                .iter()
+                .map(|c| c.parse::<Filter>())
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            let mut records = store.list_json(&collection, &filters)?;
+            if let Some(limit) = limit {
+                records.truncate(limit);
+            }
+
+            match format {
+                OutputFormat::Json => {
+                    for record in &records {
+                        println!("{}", serde_json::to_string(record)?);
+                    }
+                }
+                OutputFormat::Pretty => print_table(&records),
+            }
+        }
         Commands::Get { collection, id } => {
             let db = store.db();
             let mut stmt = db.prepare("SELECT data_json FROM records WHERE collection = ?1 AND id = ?2")?;
@@ -180,14 +626,110 @@ fn main() -> Result<()> {
                 }
                 None => {
                     eprintln!("Record not found: {}:{}", collection, id);
-                    std::process::exit(1);
+                    std::process::exit(EXIT_NOT_FOUND);
+                }
+            }
+        }
+        Commands::ShowRecord { collection, id, format } => {
+            Store::validate_collection_name(&collection)?;
+            let path = store.jsonl_dir().join(format!("{}.jsonl", collection));
+
+            // Tombstones win over older versions like they do in sync, and mean "not found"
+            let record = jsonl::read_jsonl_latest(&path)?
+                .remove(&id)
+                .filter(|value| !is_tombstone(value));
+            match record {
+                Some(value) => match format {
+                    OutputFormat::Pretty => println!("{}", serde_json::to_string_pretty(&value)?),
+                    OutputFormat::Json => println!("{}", serde_json::to_string(&value)?),
+                },
+                None => {
+                    eprintln!("Record not found in {}.jsonl: {}", collection, id);
+                    std::process::exit(EXIT_NOT_FOUND);
+                }
+            }
+        }
+        Commands::Create {
+            collection,
+            file,
+            references,
+        } => {
+            let input = if file.as_os_str() == "-" {
+                std::io::read_to_string(std::io::stdin())?
+            } else {
+                std::fs::read_to_string(&file).map_err(|e| eyre::eyre!("{}: {}", file.display(), e))?
+            };
+            let record: serde_json::Value =
+                serde_json::from_str(&input).map_err(|e| eyre::eyre!("{}: invalid JSON: {}", file.display(), e))?;
+
+            let mut refs = Vec::new();
+            for reference in &references {
+                let (field, parent) = reference
+                    .split_once('=')
+                    .ok_or_else(|| eyre::eyre!("Invalid reference: {} (expected field=parent)", reference))?;
+                if record.get(field).is_none_or(|v| v.is_null()) {
+                    eyre::bail!("Record is missing required reference field '{}'", field);
                 }
+                refs.push((field, parent));
             }
+
+            let mut store = store;
+            let id = store.create_json_with_refs(&collection, record, &refs)?;
+            println!("{}", id);
+        }
+        Commands::Import {
+            collection,
+            file,
+            strategy,
+            ignore_errors,
+        } => {
+            let mut store = store;
+            let report = if file.as_os_str() == "-" {
+                store.import_jsonl(&collection, std::io::stdin().lock(), strategy.into())?
+            } else {
+                let input = std::fs::File::open(&file).map_err(|e| eyre::eyre!("{}: {}", file.display(), e))?;
+                store.import_jsonl(&collection, std::io::BufReader::new(input), strategy.into())?
+            };
+
+            for (line, reason) in &report.rejected {
+                eprintln!("  line {}: {}", line, reason);
+            }
+            println!(
+                "Imported {} new, {} updated, {} skipped, {} rejected",
+                report.imported,
+                report.updated,
+                report.skipped,
+                report.rejected.len()
+            );
+            if !report.rejected.is_empty() && !ignore_errors {
+                std::process::exit(1);
+            }
+        }
+        Commands::Watch {
+            collection,
+            filters,
+            format,
+        } => {
+            let filters = filters
+                .iter()
+                .map(|c| c.parse::<Filter>())
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            watch(&store, collection.as_deref(), &filters, format)?;
+        }
+        Commands::Reindex { collection, fields } => {
+            let mut store = store;
+            let fields = match (fields.is_empty(), store.index_specs(&collection)) {
+                (false, _) => fields,
+                (true, Some(specs)) => specs.to_vec(),
+                (true, None) => eyre::bail!("No --field given and no schema.json entry for {}", collection),
+            };
+            let count = store.rebuild_indexes_raw(&collection, &fields)?;
+            println!("Reindexed {} records in {}", count, collection);
         }
         Commands::Indexes { collection } => {
             let db = store.db();
             let mut stmt = db.prepare(
-                "SELECT id, field_name, field_value_str, field_value_int, field_value_bool
+                "SELECT id, field_name, field_value_str, field_value_int, field_value_bool, field_value_real
                  FROM record_indexes WHERE collection = ?1 ORDER BY id, field_name",
             )?;
             let rows = stmt.query_map(params![&collection], |row| {
@@ -197,13 +739,14 @@ fn main() -> Result<()> {
                     row.get::<_, Option<String>>(2)?,
                     row.get::<_, Option<i64>>(3)?,
                     row.get::<_, Option<i64>>(4)?,
+                    row.get::<_, Option<f64>>(5)?,
                 ))
             })?;
 
             println!("Indexes for collection '{}':", collection);
             let mut current_id = String::new();
             for row in rows {
-                let (id, field, str_val, int_val, bool_val) = row?;
+                let (id, field, str_val, int_val, bool_val, real_val) = row?;
                 if id != current_id {
                     println!("\n  {}:", id);
                     current_id = id;
@@ -211,6 +754,7 @@ fn main() -> Result<()> {
                 let value = str_val
                     .map(|s| format!("\"{}\"", s))
                     .or(int_val.map(|i| i.to_string()))
+                    .or(real_val.map(|x| x.to_string()))
                     .or(bool_val.map(|b| (b != 0).to_string()))
                     .unwrap_or_else(|| "null".to_string());
                 println!("    {} = {}", field, value);
@@ -252,3 +796,222 @@ fn main() -> Result<()> {
 
     Ok(())
 }
+
+/// Widest a table cell gets before it's cut off
+const MAX_CELL_WIDTH: usize = 40;
+
+/// Print records as a table with a column per top-level field, in first-seen order
+fn print_table(records: &[serde_json::Value]) {
+    let mut columns: Vec<&str> = Vec::new();
+    for record in records {
+        for key in record.as_object().into_iter().flat_map(|fields| fields.keys()) {
+            if !columns.contains(&key.as_str()) {
+                columns.push(key);
+            }
+        }
+    }
+
+    let rows: Vec<Vec<String>> = records
+        .iter()
+        .map(|record| {
+            columns
+                .iter()
+                .map(|column| {
+                    let cell = match record.get(column) {
+                        None => String::new(),
+                        Some(serde_json::Value::String(s)) => s.clone(),
+                        Some(value) => value.to_string(),
+                    };
+                    match cell.char_indices().nth(MAX_CELL_WIDTH - 1) {
+                        Some((end, _)) => format!("{}…", &cell[..end]),
+                        None => cell,
+                    }
+                })
+                .collect()
+        })
+        .collect();
+    let widths: Vec<usize> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, column)| {
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .chain([column.len()])
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let print_row = |cells: Vec<String>| {
+        let line: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        println!("{}", line.join("  ").trim_end());
+    };
+    print_row(columns.iter().map(|c| c.to_uppercase()).collect());
+    for row in rows {
+        print_row(row);
+    }
+}
+
+/// Parse a `child.field=parent` reference argument
+fn parse_reference(reference: &str) -> Result<(&str, &str, &str)> {
+    reference
+        .split_once('=')
+        .and_then(|(child, parent)| child.split_once('.').map(|(c, f)| (c, f, parent)))
+        .ok_or_else(|| eyre::eyre!("Invalid reference: {} (expected child.field=parent)", reference))
+}
+
+/// Quiet period after a file event before reading, so a burst of writes is read in one pass
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Tail the store's JSONL files and print each appended record matching `filters` until Ctrl-C
+fn watch(store: &Store, collection: Option<&str>, filters: &[Filter], format: OutputFormat) -> Result<()> {
+    let stop = Arc::new(AtomicBool::new(false));
+    {
+        let stop = stop.clone();
+        ctrlc::set_handler(move || stop.store(true, Ordering::SeqCst))?;
+    }
+
+    // Watch the directory, not the files: compaction replaces a file by renaming over it
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    watcher.watch(store.jsonl_dir(), RecursiveMode::NonRecursive)?;
+
+    // Per collection: its tail and the IDs of its live records, to tell new records from updates
+    let mut tails: BTreeMap<String, (JsonlTail, HashSet<String>)> = BTreeMap::new();
+    let watched = match collection {
+        Some(collection) => vec![collection.to_string()],
+        None => store.jsonl_collections()?,
+    };
+    for collection in watched {
+        let path = store.jsonl_dir().join(format!("{}.jsonl", collection));
+        let live = jsonl::read_jsonl_latest(&path)?
+            .into_iter()
+            .filter(|(_, record)| !is_tombstone(record))
+            .map(|(id, _)| id)
+            .collect();
+        tails.insert(collection, (JsonlTail::from_end(&path)?, live));
+    }
+    eprintln!("Watching {} (Ctrl-C to stop)", store.jsonl_dir().display());
+
+    while !stop.load(Ordering::SeqCst) {
+        let mut paths = match rx.recv_timeout(Duration::from_millis(200)) {
+            Ok(event) => event_paths(event),
+            Err(mpsc::RecvTimeoutError::Timeout) => continue,
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        };
+        while let Ok(event) = rx.recv_timeout(WATCH_DEBOUNCE) {
+            paths.extend(event_paths(event));
+        }
+
+        for path in paths {
+            if path.extension().and_then(|s| s.to_str()) != Some("jsonl") {
+                continue;
+            }
+            let Some(name) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            if collection.is_some_and(|c| c != name) {
+                continue;
+            }
+            // A collection created while watching is read from its first line
+            let (tail, live) = tails
+                .entry(name.to_string())
+                .or_insert_with(|| (JsonlTail::from_start(&path), HashSet::new()));
+
+            for record in tail.poll()? {
+                let Some(id) = record.get("id").and_then(|v| v.as_str()) else {
+                    continue;
+                };
+                let change = if is_tombstone(&record) {
+                    live.remove(id);
+                    "deleted"
+                } else if live.insert(id.to_string()) {
+                    "created"
+                } else {
+                    "updated"
+                };
+                // Tombstones keep the record's last fields, so deletes are filtered too
+                if !filters.iter().all(|filter| filter.matches(&record)) {
+                    continue;
+                }
+
+                match format {
+                    OutputFormat::Json => println!(
+                        "{}",
+                        serde_json::json!({"collection": name, "change": change, "id": id, "record": record})
+                    ),
+                    OutputFormat::Pretty if change == "deleted" => println!("{} {} {}", name, change, id),
+                    OutputFormat::Pretty => {
+                        println!("{} {} {}", name, change, id);
+                        println!("{}", serde_json::to_string_pretty(&record)?);
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Paths touched by a file event; watcher errors are logged and ignored
+fn event_paths(event: notify::Result<notify::Event>) -> Vec<PathBuf> {
+    match event {
+        Ok(event) => event.paths,
+        Err(e) => {
+            tracing::warn!(error = %e, "File watcher error");
+            Vec::new()
+        }
+    }
+}
+
+fn is_tombstone(record: &serde_json::Value) -> bool {
+    record.get("deleted").and_then(|v| v.as_bool()).unwrap_or(false)
+}
+
+fn print_health_report(report: &HealthReport) {
+    if report.is_healthy() {
+        println!("Store is healthy");
+        return;
+    }
+
+    if !report.drift.is_empty() {
+        println!("SQLite is behind JSONL (run `taskstore sync`)");
+    }
+    for c in &report.drift {
+        println!(
+            "Drift in {}: {} added, {} updated, {} removed",
+            c.collection, c.added, c.updated, c.removed
+        );
+    }
+    for c in &report.jsonl {
+        if !c.unparseable_lines.is_empty() {
+            println!("Unparseable lines in {}.jsonl: {:?}", c.collection, c.unparseable_lines);
+        }
+        if c.archived_unparseable_lines > 0 {
+            println!(
+                "Unparseable lines in {}.jsonl archives: {}",
+                c.collection, c.archived_unparseable_lines
+            );
+        }
+        if !c.ambiguous_ids.is_empty() {
+            println!(
+                "Duplicate IDs with equal timestamps in {}.jsonl: {}",
+                c.collection,
+                c.ambiguous_ids.join(", ")
+            );
+        }
+    }
+    for o in &report.orphans {
+        println!(
+            "{}.{} -> {}: {} orphaned record(s): {}",
+            o.child_collection,
+            o.fk_field,
+            o.parent_collection,
+            o.ids.len(),
+            o.ids.join(", ")
+        );
+    }
+}
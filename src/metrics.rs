@@ -0,0 +1,269 @@
+// Operation counters and latency histograms (`metrics` feature)
+//
+// Without the feature, `Recorder` and `Timer` are zero-sized no-ops so the
+// store can call them unconditionally.
+
+use crate::hooks::WriteOp;
+#[cfg(feature = "metrics")]
+use serde::Serialize;
+#[cfg(feature = "metrics")]
+use std::collections::BTreeMap;
+#[cfg(feature = "metrics")]
+use std::sync::Mutex;
+#[cfg(feature = "metrics")]
+use std::time::{Duration, Instant};
+
+/// Store operation being measured
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Op {
+    Create,
+    Update,
+    Delete,
+    Read,
+    Sync,
+}
+
+impl From<WriteOp> for Op {
+    fn from(op: WriteOp) -> Self {
+        match op {
+            WriteOp::Create => Op::Create,
+            WriteOp::Update => Op::Update,
+            WriteOp::Delete => Op::Delete,
+        }
+    }
+}
+
+#[cfg_attr(not(feature = "metrics"), allow(dead_code))]
+impl Op {
+    fn name(self) -> &'static str {
+        match self {
+            Op::Create => "create",
+            Op::Update => "update",
+            Op::Delete => "delete",
+            Op::Read => "read",
+            Op::Sync => "sync",
+        }
+    }
+}
+
+/// Upper bounds of the latency histogram buckets, in microseconds
+#[cfg(feature = "metrics")]
+pub const LATENCY_BUCKETS_US: [u64; 10] = [100, 250, 500, 1_000, 2_500, 5_000, 10_000, 50_000, 250_000, 1_000_000];
+
+/// Per-collection operation counts
+#[cfg(feature = "metrics")]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CollectionMetrics {
+    pub creates: u64,
+    pub updates: u64,
+    pub deletes: u64,
+    pub reads: u64,
+}
+
+/// Cumulative latency histogram, Prometheus style
+#[cfg(feature = "metrics")]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Histogram {
+    /// Number of observations at or below each bound in `LATENCY_BUCKETS_US`
+    pub buckets: Vec<u64>,
+    pub count: u64,
+    pub sum_us: u64,
+}
+
+#[cfg(feature = "metrics")]
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            buckets: vec![0; LATENCY_BUCKETS_US.len()],
+            count: 0,
+            sum_us: 0,
+        }
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl Histogram {
+    fn observe(&mut self, elapsed: Duration) {
+        let us = elapsed.as_micros() as u64;
+        for (bound, bucket) in LATENCY_BUCKETS_US.iter().zip(&mut self.buckets) {
+            if us <= *bound {
+                *bucket += 1;
+            }
+        }
+        self.count += 1;
+        self.sum_us += us;
+    }
+}
+
+/// Point-in-time copy of a store's metrics, see `Store::metrics_snapshot`
+///
+/// Counts start at zero when the store is opened.
+#[cfg(feature = "metrics")]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Metrics {
+    /// Operation counts by collection
+    pub collections: BTreeMap<String, CollectionMetrics>,
+    /// Latency by operation (`create`, `update`, `delete`, `read`, `sync`)
+    pub latency: BTreeMap<String, Histogram>,
+    /// Current JSONL file size in bytes by collection
+    pub jsonl_bytes: BTreeMap<String, u64>,
+}
+
+#[cfg(feature = "metrics")]
+impl Metrics {
+    /// Render in the Prometheus text exposition format
+    pub fn to_prometheus(&self) -> String {
+        use std::fmt::Write;
+
+        let mut out = String::new();
+        out.push_str("# TYPE taskstore_operations_total counter\n");
+        for (collection, m) in &self.collections {
+            for (op, value) in [
+                ("create", m.creates),
+                ("update", m.updates),
+                ("delete", m.deletes),
+                ("read", m.reads),
+            ] {
+                let _ = writeln!(
+                    out,
+                    "taskstore_operations_total{{collection=\"{}\",op=\"{}\"}} {}",
+                    collection, op, value
+                );
+            }
+        }
+
+        out.push_str("# TYPE taskstore_operation_duration_seconds histogram\n");
+        for (op, h) in &self.latency {
+            for (bound, count) in LATENCY_BUCKETS_US.iter().zip(&h.buckets) {
+                let _ = writeln!(
+                    out,
+                    "taskstore_operation_duration_seconds_bucket{{op=\"{}\",le=\"{}\"}} {}",
+                    op,
+                    *bound as f64 / 1e6,
+                    count
+                );
+            }
+            let _ = writeln!(
+                out,
+                "taskstore_operation_duration_seconds_bucket{{op=\"{}\",le=\"+Inf\"}} {}",
+                op, h.count
+            );
+            let _ = writeln!(
+                out,
+                "taskstore_operation_duration_seconds_sum{{op=\"{}\"}} {}",
+                op,
+                h.sum_us as f64 / 1e6
+            );
+            let _ = writeln!(
+                out,
+                "taskstore_operation_duration_seconds_count{{op=\"{}\"}} {}",
+                op, h.count
+            );
+        }
+
+        out.push_str("# TYPE taskstore_jsonl_bytes gauge\n");
+        for (collection, bytes) in &self.jsonl_bytes {
+            let _ = writeln!(out, "taskstore_jsonl_bytes{{collection=\"{}\"}} {}", collection, bytes);
+        }
+        out
+    }
+}
+
+/// Start time of a measured operation; zero-sized without the `metrics` feature
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Timer {
+    #[cfg(feature = "metrics")]
+    started: Instant,
+}
+
+/// Per-store metrics state; a no-op without the `metrics` feature
+#[derive(Debug, Default)]
+pub(crate) struct Recorder {
+    #[cfg(feature = "metrics")]
+    state: Mutex<Metrics>,
+}
+
+impl Recorder {
+    pub(crate) fn start(&self) -> Timer {
+        Timer {
+            #[cfg(feature = "metrics")]
+            started: Instant::now(),
+        }
+    }
+
+    /// Count one operation on `collection` (if any) and record its latency
+    #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+    pub(crate) fn record(&self, op: Op, collection: Option<&str>, timer: Timer) {
+        if let Some(collection) = collection {
+            self.count(op, collection);
+        }
+
+        #[cfg(feature = "metrics")]
+        {
+            let elapsed = timer.started.elapsed();
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            state.latency.entry(op.name().to_string()).or_default().observe(elapsed);
+
+            #[cfg(feature = "metrics-rs")]
+            ::metrics::histogram!("taskstore_operation_duration_seconds", "op" => op.name())
+                .record(elapsed.as_secs_f64());
+        }
+    }
+
+    /// Count one operation on `collection` without timing it (e.g. a cascaded delete)
+    #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+    pub(crate) fn count(&self, op: Op, collection: &str) {
+        #[cfg(feature = "metrics")]
+        {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            let counts = state.collections.entry(collection.to_string()).or_default();
+            match op {
+                Op::Create => counts.creates += 1,
+                Op::Update => counts.updates += 1,
+                Op::Delete => counts.deletes += 1,
+                Op::Read => counts.reads += 1,
+                Op::Sync => {}
+            }
+
+            #[cfg(feature = "metrics-rs")]
+            ::metrics::counter!(
+                "taskstore_operations_total",
+                "collection" => collection.to_string(),
+                "op" => op.name()
+            )
+            .increment(1);
+        }
+    }
+
+    /// Copy of the counters and histograms; `jsonl_bytes` is left for the caller to fill
+    #[cfg(feature = "metrics")]
+    pub(crate) fn snapshot(&self) -> Metrics {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram_is_cumulative() {
+        let mut h = Histogram::default();
+        h.observe(Duration::from_micros(50));
+        h.observe(Duration::from_micros(700));
+        h.observe(Duration::from_secs(5));
+
+        assert_eq!(h.count, 3);
+        assert_eq!(h.buckets[0], 1); // <= 100us
+        assert_eq!(h.buckets[3], 2); // <= 1ms
+        assert_eq!(*h.buckets.last().unwrap(), 2); // the 5s call is only in +Inf
+
+        let metrics = Metrics {
+            latency: BTreeMap::from([("read".to_string(), h)]),
+            ..Default::default()
+        };
+        let text = metrics.to_prometheus();
+        assert!(text.contains("taskstore_operation_duration_seconds_bucket{op=\"read\",le=\"+Inf\"} 3"));
+        assert!(text.contains("taskstore_operation_duration_seconds_count{op=\"read\"} 3"));
+    }
+}
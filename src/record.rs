@@ -8,9 +8,28 @@ pub trait Record: Serialize + for<'de> Deserialize<'de> + Clone + Send + Sync +
     /// Unique identifier for this record
     fn id(&self) -> &str;
 
+    /// Value the ID is indexed by, e.g. `IndexValue::Int` for integer-keyed records
+    ///
+    /// `id()` and JSONL keep the canonical string form. Returning anything but the
+    /// default `IndexValue::String` also indexes the ID under the field name `id`,
+    /// so filters on `id` and ordering by `id` compare it by this value (numerically
+    /// for `Int`) instead of as text.
+    fn id_index_value(&self) -> IndexValue {
+        IndexValue::String(self.id().to_string())
+    }
+
     /// Timestamp when this record was last updated (milliseconds since epoch)
     fn updated_at(&self) -> i64;
 
+    /// Timestamp when this record was created (milliseconds since epoch), if it has one
+    ///
+    /// A record returning `Some` is also indexed under `created_at` (unless
+    /// `indexed_fields` already has that key), so it can be filtered and ordered
+    /// by creation time. The default returns `None`.
+    fn created_at(&self) -> Option<i64> {
+        None
+    }
+
     /// Collection name for this record type (e.g., "plans", "specs")
     /// Determines the JSONL filename: {collection}.jsonl
     fn collection_name() -> &'static str
@@ -22,14 +41,174 @@ pub trait Record: Serialize + for<'de> Deserialize<'de> + Clone + Send + Sync +
     fn indexed_fields(&self) -> HashMap<String, IndexValue> {
         HashMap::new()
     }
+
+    /// Indexed fields whose values must be unique within the collection
+    ///
+    /// Each name must also be returned by `indexed_fields()`; records that omit
+    /// the field (e.g. an unset optional value) are not checked.
+    fn unique_fields() -> &'static [&'static str]
+    where
+        Self: Sized,
+    {
+        &[]
+    }
+
+    /// Large fields `Store::list_lite` leaves out, e.g. `&["content"]`
+    ///
+    /// They must deserialize as optional (e.g. `#[serde(default)]`).
+    fn heavy_fields() -> &'static [&'static str]
+    where
+        Self: Sized,
+    {
+        &[]
+    }
+
+    /// Compound indexes over several indexed fields, e.g. `&[&["status", "priority"]]`
+    ///
+    /// Each entry lists 2 to 4 names returned by `indexed_fields()`. `Store::list`
+    /// uses a composite when the filters constrain a prefix of its fields (equality
+    /// on all but the last, which may also be a range), letting SQLite answer the
+    /// whole condition from one index. This only affects speed, never results.
+    fn composite_indexes() -> &'static [&'static [&'static str]]
+    where
+        Self: Sized,
+    {
+        &[]
+    }
+
+    /// Child collections whose records are deleted along with this one
+    ///
+    /// See `Store::delete`. The default declares no children.
+    fn cascade_deletes() -> &'static [ChildRelation]
+    where
+        Self: Sized,
+    {
+        &[]
+    }
+
+    /// Fields that refer to records in other collections, checked on every write
+    ///
+    /// See `Reference` and `Store::resolve`. The default declares none.
+    fn references() -> &'static [Reference]
+    where
+        Self: Sized,
+    {
+        &[]
+    }
+
+    /// Set the record's ID
+    ///
+    /// Override to let `Store::create_with_generated_id` assign an ID.
+    /// The default does nothing.
+    fn set_id(&mut self, _id: String) {}
+
+    /// Set the creation timestamp (milliseconds since epoch)
+    ///
+    /// Override if the record has a `created_at` field. The default does nothing.
+    fn set_created_at(&mut self, _created_at: i64) {}
+
+    /// Set the last-updated timestamp (milliseconds since epoch)
+    ///
+    /// Override to let the store stamp `updated_at`. The default does nothing.
+    fn set_updated_at(&mut self, _updated_at: i64) {}
+
+    /// Check the record's invariants before it is written
+    ///
+    /// Called by `Store::create` and `Store::update` before anything reaches JSONL
+    /// or SQLite. The default accepts every record.
+    fn validate(&self) -> Result<(), ValidationError> {
+        Ok(())
+    }
+
+    /// Stamp both `created_at` and `updated_at` with the current time
+    ///
+    /// Both fields get the same value, so `updated_at` is never older than `created_at`.
+    fn set_timestamps(&mut self) {
+        let now = crate::store::now_ms();
+        self.set_created_at(now);
+        self.set_updated_at(now);
+    }
+}
+
+/// A parent-child relationship followed by cascading deletes
+///
+/// Records in `collection` whose `fk_field` holds the parent's ID are children.
+/// `children` lists the child type's own relationships, so a cascade can span
+/// several levels (e.g. plan -> specs -> executions).
+#[derive(Debug, Clone, Copy)]
+pub struct ChildRelation {
+    pub collection: &'static str,
+    pub fk_field: &'static str,
+    pub children: &'static [ChildRelation],
+}
+
+/// A field holding the key of a record in another collection
+///
+/// `field` is a top-level field of the referring record; its value must match
+/// `key` of some record in `collection`, where `key` is `"id"` or a field the
+/// target type indexes (typically a unique one, e.g. a workflow's `name`).
+/// Creates and updates fail with `StoreError::Validation` if no record
+/// matches, unless `allow_dangling` is set. A null or missing field is never
+/// checked. Only writes are checked: deleting the target later leaves the
+/// reference dangling (see `Record::cascade_deletes`).
+#[derive(Debug, Clone, Copy)]
+pub struct Reference {
+    pub field: &'static str,
+    pub collection: &'static str,
+    pub key: &'static str,
+    pub allow_dangling: bool,
 }
 
+/// A record failed `Record::validate`
+///
+/// Returned by `Store` write methods as `StoreError::Validation`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    message: String,
+}
+
+impl ValidationError {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+        }
+    }
+
+    /// Human-readable description of the violated invariant
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Validation failed: {}", self.message)
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
 /// Value types that can be indexed for filtering
+///
+/// Filters only match values of the same variant: an `Int` filter doesn't
+/// match a `Float` index value. A NaN `Float` is never indexed, and a NaN
+/// filter value matches nothing.
 #[derive(Debug, Clone, PartialEq)]
 pub enum IndexValue {
     String(String),
     Int(i64),
+    Float(f64),
     Bool(bool),
+    /// Milliseconds since the Unix epoch, displayed as RFC 3339 in UTC
+    ///
+    /// Indexed and compared as an `Int`, so it matches and sorts with
+    /// timestamps indexed as plain millisecond integers, e.g. `updated_at`.
+    DateTime(i64),
+    /// Inclusive `(low, high)` bounds of a `FilterOp::Between` filter
+    ///
+    /// Only meaningful as a filter value: writing a record that indexes a
+    /// field as a range fails with `StoreError::Schema`.
+    Range(Box<IndexValue>, Box<IndexValue>),
 }
 
 impl std::fmt::Display for IndexValue {
@@ -37,11 +216,180 @@ impl std::fmt::Display for IndexValue {
         match self {
             IndexValue::String(s) => write!(f, "{}", s),
             IndexValue::Int(i) => write!(f, "{}", i),
+            IndexValue::Float(x) => write!(f, "{}", x),
             IndexValue::Bool(b) => write!(f, "{}", b),
+            IndexValue::DateTime(ms) => match chrono::DateTime::from_timestamp_millis(*ms) {
+                Some(at) => write!(f, "{}", at.to_rfc3339_opts(chrono::SecondsFormat::Millis, true)),
+                None => write!(f, "{}", ms),
+            },
+            IndexValue::Range(low, high) => write!(f, "{}..={}", low, high),
         }
     }
 }
 
+impl IndexValue {
+    /// Parse an RFC 3339 timestamp or a `YYYY-MM-DD` date (midnight UTC) as a `DateTime`
+    pub fn parse_datetime(s: &str) -> Result<Self, String> {
+        if let Ok(at) = chrono::DateTime::parse_from_rfc3339(s) {
+            return Ok(IndexValue::DateTime(at.timestamp_millis()));
+        }
+        match chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+            Ok(date) => Ok(IndexValue::DateTime(
+                date.and_time(chrono::NaiveTime::MIN).and_utc().timestamp_millis(),
+            )),
+            Err(e) => Err(format!(
+                "Invalid date '{}': {} (expected e.g. 2024-01-01 or 2024-01-01T12:00:00Z)",
+                s, e
+            )),
+        }
+    }
+
+    /// The value with each `DateTime` written as a string, or `None` if it has none
+    ///
+    /// Midnight UTC is written as `YYYY-MM-DD` and anything else as RFC 3339,
+    /// the forms `parse_datetime` reads, for comparing with dates kept as strings.
+    pub(crate) fn datetime_as_string(&self) -> Option<IndexValue> {
+        match self {
+            IndexValue::DateTime(ms) => {
+                let at = chrono::DateTime::from_timestamp_millis(*ms)?;
+                Some(IndexValue::String(if at.time() == chrono::NaiveTime::MIN {
+                    at.format("%Y-%m-%d").to_string()
+                } else {
+                    at.to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true)
+                }))
+            }
+            IndexValue::Range(low, high) => match (low.datetime_as_string(), high.datetime_as_string()) {
+                (None, None) => None,
+                (new_low, new_high) => Some(IndexValue::Range(
+                    Box::new(new_low.unwrap_or_else(|| (**low).clone())),
+                    Box::new(new_high.unwrap_or_else(|| (**high).clone())),
+                )),
+            },
+            _ => None,
+        }
+    }
+}
+
+impl From<&str> for IndexValue {
+    fn from(value: &str) -> Self {
+        IndexValue::String(value.to_string())
+    }
+}
+
+impl From<String> for IndexValue {
+    fn from(value: String) -> Self {
+        IndexValue::String(value)
+    }
+}
+
+impl From<i64> for IndexValue {
+    fn from(value: i64) -> Self {
+        IndexValue::Int(value)
+    }
+}
+
+/// Lets integer literals convert without a suffix (they default to `i32`)
+impl From<i32> for IndexValue {
+    fn from(value: i32) -> Self {
+        IndexValue::Int(value.into())
+    }
+}
+
+impl From<f64> for IndexValue {
+    fn from(value: f64) -> Self {
+        IndexValue::Float(value)
+    }
+}
+
+impl From<f32> for IndexValue {
+    fn from(value: f32) -> Self {
+        IndexValue::Float(value.into())
+    }
+}
+
+impl From<bool> for IndexValue {
+    fn from(value: bool) -> Self {
+        IndexValue::Bool(value)
+    }
+}
+
+/// The `IndexValue` variant a raw JSON field is indexed as, see `IndexSpec`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IndexKind {
+    String,
+    Int,
+    Float,
+    Bool,
+}
+
+/// A JSON key to index without the record's Rust type, see `Store::rebuild_indexes_raw`
+///
+/// Parses from `field:kind`, e.g. `status:string` or `priority:int`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexSpec {
+    pub field: String,
+    pub kind: IndexKind,
+}
+
+impl IndexSpec {
+    pub fn new(field: impl Into<String>, kind: IndexKind) -> Self {
+        Self {
+            field: field.into(),
+            kind,
+        }
+    }
+
+    /// This field's value in `record`, or `None` if it's missing or not of this kind
+    pub fn value_from(&self, record: &serde_json::Value) -> Option<IndexValue> {
+        let value = record.get(&self.field)?;
+        match self.kind {
+            IndexKind::String => value.as_str().map(|s| IndexValue::String(s.to_string())),
+            // JSON keeps integer IDs in their string form, see `Record::id_index_value`
+            IndexKind::Int if self.field == "id" => value.as_str()?.parse().ok().map(IndexValue::Int),
+            IndexKind::Int => value.as_i64().map(IndexValue::Int),
+            IndexKind::Float => value.as_f64().map(IndexValue::Float),
+            IndexKind::Bool => value.as_bool().map(IndexValue::Bool),
+        }
+    }
+}
+
+impl IndexKind {
+    /// The kind of an index value; a `Range` has the kind its bounds are compared as
+    pub fn of(value: &IndexValue) -> Self {
+        match value {
+            IndexValue::String(_) => IndexKind::String,
+            IndexValue::Int(_) | IndexValue::DateTime(_) => IndexKind::Int,
+            IndexValue::Float(_) => IndexKind::Float,
+            IndexValue::Bool(_) => IndexKind::Bool,
+            IndexValue::Range(low, _) => IndexKind::of(low),
+        }
+    }
+}
+
+impl std::str::FromStr for IndexSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (field, kind) = s
+            .split_once(':')
+            .ok_or_else(|| format!("Invalid index spec: {} (expected field:kind)", s))?;
+        let kind = match kind {
+            "string" => IndexKind::String,
+            "int" => IndexKind::Int,
+            "float" => IndexKind::Float,
+            "bool" => IndexKind::Bool,
+            _ => {
+                return Err(format!(
+                    "Unknown index kind: {} (expected string, int, float, or bool)",
+                    kind
+                ));
+            }
+        };
+        Ok(Self::new(field, kind))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -79,6 +427,52 @@ mod tests {
         assert_eq!(record.updated_at(), 1000);
         assert_eq!(TestRecord::collection_name(), "test");
         assert!(record.indexed_fields().is_empty());
+        assert!(record.validate().is_ok());
+        assert!(TestRecord::unique_fields().is_empty());
+        assert!(TestRecord::cascade_deletes().is_empty());
+        assert!(TestRecord::composite_indexes().is_empty());
+    }
+
+    #[test]
+    fn test_setters_default_to_noop() {
+        let mut record = TestRecord {
+            id: "test-1".to_string(),
+            name: "Test".to_string(),
+            updated_at: 1000,
+        };
+
+        record.set_id("other".to_string());
+        record.set_timestamps();
+
+        assert_eq!(record.id(), "test-1");
+        assert_eq!(record.updated_at(), 1000);
+    }
+
+    #[test]
+    fn test_index_value_from() {
+        fn convert(value: impl Into<IndexValue>) -> IndexValue {
+            value.into()
+        }
+
+        assert_eq!(convert("open"), IndexValue::String("open".to_string()));
+        assert_eq!(convert("open".to_string()), IndexValue::String("open".to_string()));
+        assert_eq!(convert(7), IndexValue::Int(7));
+        assert_eq!(convert(7i64), IndexValue::Int(7));
+        assert_eq!(convert(i64::MAX), IndexValue::Int(i64::MAX));
+        assert_eq!(convert(false), IndexValue::Bool(false));
+        assert_eq!(convert(2.5), IndexValue::Float(2.5));
+        assert_eq!(convert(0.5f32), IndexValue::Float(0.5));
+
+        let mut fields: HashMap<String, IndexValue> = HashMap::new();
+        fields.insert("priority".into(), 3.into());
+        assert_eq!(fields["priority"], IndexValue::Int(3));
+    }
+
+    #[test]
+    fn test_validation_error_display() {
+        let err = ValidationError::new("title cannot be empty");
+        assert_eq!(err.message(), "title cannot be empty");
+        assert_eq!(err.to_string(), "Validation failed: title cannot be empty");
     }
 
     #[test]
@@ -86,5 +480,31 @@ mod tests {
         assert_eq!(IndexValue::String("test".to_string()).to_string(), "test");
         assert_eq!(IndexValue::Int(42).to_string(), "42");
         assert_eq!(IndexValue::Bool(true).to_string(), "true");
+        assert_eq!(IndexValue::Float(1.25).to_string(), "1.25");
+        assert_eq!(
+            IndexValue::Range(Box::new(1.into()), Box::new(5.into())).to_string(),
+            "1..=5"
+        );
+    }
+
+    #[test]
+    fn test_datetime_index_value() {
+        let day = IndexValue::DateTime(1_704_067_200_000);
+        assert_eq!(day.to_string(), "2024-01-01T00:00:00.000Z");
+        assert_eq!(IndexKind::of(&day), IndexKind::Int);
+        assert_eq!(IndexValue::parse_datetime("2024-01-01"), Ok(day.clone()));
+        assert_eq!(IndexValue::parse_datetime("2024-01-01T01:00:00+01:00"), Ok(day.clone()));
+        assert!(IndexValue::parse_datetime("January 1st").is_err());
+
+        assert_eq!(day.datetime_as_string(), Some("2024-01-01".into()));
+        assert_eq!(
+            IndexValue::DateTime(1_704_067_200_500).datetime_as_string(),
+            Some("2024-01-01T00:00:00.500Z".into())
+        );
+        assert_eq!(
+            IndexValue::Range(Box::new(day.clone()), Box::new(2.into())).datetime_as_string(),
+            Some(IndexValue::Range(Box::new("2024-01-01".into()), Box::new(2.into())))
+        );
+        assert_eq!(IndexValue::Int(1_704_067_200_000).datetime_as_string(), None);
     }
 }
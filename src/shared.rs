@@ -0,0 +1,264 @@
+// Thread-safe handle to a single Store
+
+use crate::error::StoreResult as Result;
+use crate::filter::Filter;
+use crate::record::Record;
+use crate::store::Store;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// A `Store` that can be cloned and shared across threads
+///
+/// `rusqlite::Connection` is `Send` but not `Sync`, so a plain `Store` can't be
+/// shared. `SharedStore` wraps one `Store` in an `Arc<Mutex<_>>`: every clone
+/// refers to the same connection and JSONL directory, so JSONL appends and
+/// SQLite writes are serialized and the index can't drift between handles.
+///
+/// The tradeoff is that reads are serialized too; a `list` blocks a concurrent
+/// `get`. Operations are short (a single SQLite query or a JSONL append plus a
+/// transaction), so this is fine for typical web-server workloads. Callers that
+/// need truly parallel reads can open additional `Store`s for read-heavy work.
+#[derive(Clone)]
+pub struct SharedStore {
+    inner: Arc<Mutex<Store>>,
+}
+
+impl SharedStore {
+    /// Wrap an already-open store
+    pub fn new(store: Store) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(store)),
+        }
+    }
+
+    /// Open or create a store at the given path, see `Store::open`
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Self::new(Store::open(path)?))
+    }
+
+    /// Lock the underlying store for exclusive access
+    ///
+    /// A panic while holding the lock doesn't leave the store unusable: JSONL
+    /// is the source of truth and every SQLite write is transactional, so a
+    /// poisoned lock is recovered rather than propagated.
+    pub fn lock(&self) -> MutexGuard<'_, Store> {
+        self.inner.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Run a closure with exclusive access to the store
+    ///
+    /// Use this to make read-modify-write sequences atomic with respect to
+    /// other users of the same `SharedStore`.
+    pub fn with<R>(&self, f: impl FnOnce(&mut Store) -> R) -> R {
+        f(&mut self.lock())
+    }
+
+    /// Create a new record, see `Store::create`
+    pub fn create<T: Record>(&self, record: T) -> Result<String> {
+        self.lock().create(record)
+    }
+
+    /// Create many records at once, see `Store::create_many`
+    pub fn create_many<T: Record>(&self, records: Vec<T>) -> Result<Vec<String>> {
+        self.lock().create_many(records)
+    }
+
+    /// Create or update many records at once, see `Store::upsert_many`
+    pub fn upsert_many<T: Record>(&self, records: Vec<T>) -> Result<Vec<String>> {
+        self.lock().upsert_many(records)
+    }
+
+    /// Get a record by ID, see `Store::get`
+    pub fn get<T: Record>(&self, id: &str) -> Result<Option<T>> {
+        self.lock().get(id)
+    }
+
+    /// Get several records by ID, see `Store::get_many`
+    pub fn get_many<T: Record>(&self, ids: &[&str]) -> Result<Vec<Option<T>>> {
+        self.lock().get_many(ids)
+    }
+
+    /// Check whether a record exists, see `Store::exists`
+    pub fn exists<T: Record>(&self, id: &str) -> Result<bool> {
+        self.lock().exists::<T>(id)
+    }
+
+    /// Update a record, see `Store::update`
+    pub fn update<T: Record>(&self, record: T) -> Result<()> {
+        self.lock().update(record)
+    }
+
+    /// Update a record that must already exist, see `Store::update_existing`
+    pub fn update_existing<T: Record>(&self, record: T) -> Result<()> {
+        self.lock().update_existing(record)
+    }
+
+    /// Update a record if it hasn't changed since it was read, see `Store::update_if_unchanged`
+    pub fn update_if_unchanged<T: Record>(&self, record: T, expected_updated_at: i64) -> Result<bool> {
+        self.lock().update_if_unchanged(record, expected_updated_at)
+    }
+
+    /// Atomically add to an integer field, see `Store::increment`
+    pub fn increment<T: Record>(&self, id: &str, field: &str, delta: i64) -> Result<i64> {
+        self.lock().increment::<T>(id, field, delta)
+    }
+
+    /// Bump a record's `updated_at` without changing it, see `Store::touch`
+    pub fn touch<T: Record>(&self, id: &str) -> Result<()> {
+        self.lock().touch::<T>(id)
+    }
+
+    /// Overwrite some of a record's fields, see `Store::update_partial`
+    pub fn update_partial<T: Record>(&self, id: &str, patch: serde_json::Value) -> Result<T> {
+        self.lock().update_partial(id, patch)
+    }
+
+    /// Delete a record, see `Store::delete`
+    pub fn delete<T: Record>(&self, id: &str) -> Result<()> {
+        self.lock().delete::<T>(id)
+    }
+
+    /// Delete every record matching `filters`, see `Store::delete_where`
+    pub fn delete_where<T: Record>(&self, filters: &[Filter]) -> Result<u64> {
+        self.lock().delete_where::<T>(filters)
+    }
+
+    /// Expire `T`'s records after `max_age_ms`, see `Store::set_retention`
+    pub fn set_retention<T: Record>(&self, max_age_ms: i64) -> Result<()> {
+        self.lock().set_retention::<T>(max_age_ms)
+    }
+
+    /// Delete records older than their collection's retention, see `Store::prune_expired`
+    pub fn prune_expired(&self) -> Result<u64> {
+        self.lock().prune_expired()
+    }
+
+    /// List records with optional filtering, see `Store::list`
+    pub fn list<T: Record>(&self, filters: &[Filter]) -> Result<Vec<T>> {
+        self.lock().list(filters)
+    }
+
+    /// The matching record with the smallest `order_by` value, see `Store::first`
+    pub fn first<T: Record>(&self, filters: &[Filter], order_by: &str) -> Result<Option<T>> {
+        self.lock().first(filters, order_by)
+    }
+
+    /// The matching record with the largest `order_by` value, see `Store::last`
+    pub fn last<T: Record>(&self, filters: &[Filter], order_by: &str) -> Result<Option<T>> {
+        self.lock().last(filters, order_by)
+    }
+
+    /// Count records matching `filters`, see `Store::count`
+    pub fn count<T: Record>(&self, filters: &[Filter]) -> Result<u64> {
+        self.lock().count::<T>(filters)
+    }
+
+    /// Sync SQLite from JSONL files, see `Store::sync`
+    pub fn sync(&self) -> Result<()> {
+        self.lock().sync()
+    }
+
+    /// Sync one collection from its JSONL file, see `Store::sync_collection`
+    pub fn sync_collection(&self, collection: &str) -> Result<()> {
+        self.lock().sync_collection(collection)
+    }
+
+    /// fsync pending JSONL appends, see `Store::flush`
+    pub fn flush(&self) -> Result<()> {
+        self.lock().flush()
+    }
+
+    /// Rebuild indexes for a record type, see `Store::rebuild_indexes`
+    pub fn rebuild_indexes<T: Record>(&self) -> Result<usize> {
+        self.lock().rebuild_indexes::<T>()
+    }
+
+    /// Register a record type for reindexing, see `Store::register`
+    pub fn register<T: Record>(&self) {
+        self.lock().register::<T>()
+    }
+
+    /// Rebuild indexes for every collection, see `Store::rebuild_all_indexes`
+    pub fn rebuild_all_indexes(&self) -> Result<HashMap<String, usize>> {
+        self.lock().rebuild_all_indexes()
+    }
+}
+
+impl From<Store> for SharedStore {
+    fn from(store: Store) -> Self {
+        Self::new(store)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::now_ms;
+    use serde::{Deserialize, Serialize};
+    use tempfile::TempDir;
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    struct Counter {
+        id: String,
+        value: i64,
+        updated_at: i64,
+    }
+
+    impl Record for Counter {
+        fn id(&self) -> &str {
+            &self.id
+        }
+
+        fn updated_at(&self) -> i64 {
+            self.updated_at
+        }
+
+        fn collection_name() -> &'static str {
+            "counters"
+        }
+    }
+
+    fn assert_send_sync<T: Clone + Send + Sync>() {}
+
+    #[test]
+    fn test_shared_store_is_send_sync() {
+        assert_send_sync::<SharedStore>();
+    }
+
+    #[test]
+    fn test_shared_store_atomic_increment() {
+        let temp = TempDir::new().unwrap();
+        let store = SharedStore::open(temp.path()).unwrap();
+        store
+            .create(Counter {
+                id: "c1".to_string(),
+                value: 0,
+                updated_at: now_ms(),
+            })
+            .unwrap();
+
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let store = store.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..10 {
+                        store.with(|s| {
+                            let mut counter: Counter = s.get("c1").unwrap().unwrap();
+                            counter.value += 1;
+                            counter.updated_at = now_ms();
+                            s.update(counter).unwrap();
+                        });
+                    }
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let counter: Counter = store.get("c1").unwrap().unwrap();
+        assert_eq!(counter.value, 80);
+    }
+}
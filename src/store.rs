@@ -1,22 +1,390 @@
 // Generic store implementation using JSONL + SQLite
 
-use crate::filter::{Filter, FilterOp};
-use crate::jsonl;
-use crate::record::{IndexValue, Record};
-use eyre::{Context, Result, eyre};
-use fs2::FileExt;
-use rusqlite::Connection;
+use crate::error::{DuplicateKeyError, StoreError, StoreResult as Result};
+use crate::filter::{Agg, Filter, FilterExpr, FilterOp, ListOptions, Query, SortDir};
+use crate::hooks::{ChangeCallback, Hooks, StoreHooks, WriteOp};
+use crate::jsonl::{self, CompactStats, Fsync, FsyncPolicy, JsonlAppend, JsonlTail, TailRecovery};
+use crate::metrics::{Op, Recorder};
+use crate::record::{ChildRelation, IndexKind, IndexSpec, IndexValue, Record, ValidationError};
 use rusqlite::OptionalExtension;
+use rusqlite::{Connection, OpenFlags, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::fs;
+use std::io::{BufRead, Read, Write};
+use std::marker::PhantomData;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicI64, Ordering};
 use tracing::{debug, info, warn};
 
 const CURRENT_VERSION: u32 = 1;
 
+/// One step of `MIGRATIONS`, upgrading a store from version `from` to `to`
+#[derive(Clone, Copy)]
+pub(crate) struct Migration {
+    pub from: u32,
+    pub to: u32,
+    /// Runs inside a transaction; must be idempotent (see `Store::migrate_schema`)
+    pub up: fn(&Connection) -> Result<()>,
+}
+
+/// Steps from each older version up to `CURRENT_VERSION`, applied in order
+const MIGRATIONS: &[Migration] = &[];
+
+/// Number of records `ListIter` fetches from SQLite at a time
+const LIST_ITER_PAGE_SIZE: usize = 500;
+
+/// Most IDs `get_many` binds in one query; old SQLite builds allow 999 parameters
+const GET_MANY_CHUNK_SIZE: usize = 500;
+
+/// Number of times an index write is retried after a transient SQLITE_BUSY/SQLITE_LOCKED
+const BUSY_RETRIES: u32 = 5;
+
+/// Most fields a `Record::composite_indexes` entry may combine
+const MAX_COMPOSITE_FIELDS: usize = 4;
+
+/// Deepest nesting of `FilterExpr` groups, after flattening, that `list_where` accepts
+const MAX_FILTER_DEPTH: usize = 32;
+
+/// File in the store directory mapping collection names to their index specs
+pub const SCHEMA_FILENAME: &str = "schema.json";
+
+/// SQLite filename used when `StoreOptions` doesn't name one
+pub const DEFAULT_DB_FILENAME: &str = "taskstore.db";
+
+/// SQLite `synchronous` setting, trading durability of the database for write speed
+///
+/// JSONL is the source of truth (fsynced on every append by default), so a looser
+/// setting only risks the SQLite cache after a crash or power loss, which a
+/// sync (or `taskstore repair`) rebuilds from JSONL.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Synchronous {
+    /// Never wait for the disk; fastest, the database may need a rebuild after power loss
+    Off,
+    /// Wait at the most critical moments
+    #[default]
+    Normal,
+    /// Wait for the disk on every commit
+    Full,
+}
+
+impl Synchronous {
+    fn pragma_value(self) -> &'static str {
+        match self {
+            Synchronous::Off => "OFF",
+            Synchronous::Normal => "NORMAL",
+            Synchronous::Full => "FULL",
+        }
+    }
+}
+
+/// Options controlling how a `Store` is opened
+#[derive(Debug, Clone)]
+pub struct StoreOptions {
+    /// How long SQLite waits on a locked database before returning SQLITE_BUSY
+    pub busy_timeout_ms: u64,
+    /// Sync the database from JSONL on open if it is stale
+    ///
+    /// With `read_only`, a stale store can't be synced and opening fails
+    /// instead; set this to `false` to open it stale anyway (e.g. to inspect it).
+    pub auto_sync: bool,
+    /// Open the database read-only, see `Store::open_read_only`
+    pub read_only: bool,
+    /// Full path of the SQLite database; overrides `db_filename`
+    pub db_path: Option<PathBuf>,
+    /// Directory holding the JSONL files (default: the `.taskstore` directory)
+    pub jsonl_dir: Option<PathBuf>,
+    /// SQLite filename inside the `.taskstore` directory (default: `taskstore.db`)
+    pub db_filename: Option<String>,
+    /// SQLite `synchronous` pragma for a writable store (default: `Normal`)
+    pub synchronous: Synchronous,
+    /// When JSONL appends are fsynced (default: `EveryWrite`), see `FsyncPolicy`
+    pub fsync_policy: FsyncPolicy,
+    /// Rotate JSONL files past `rotate_bytes` into gzipped archives, see `jsonl::rotate_jsonl`
+    ///
+    /// Reads and sync include the archives, latest version wins across them.
+    /// Archives aren't merged by the git merge driver, so clones that rotate
+    /// independently end up with conflicting archive files.
+    pub compress_rotated: bool,
+    /// Size in bytes past which a JSONL file is rotated (default: 64 MiB)
+    pub rotate_bytes: u64,
+}
+
+impl Default for StoreOptions {
+    fn default() -> Self {
+        Self {
+            busy_timeout_ms: 5000,
+            auto_sync: true,
+            read_only: false,
+            db_path: None,
+            jsonl_dir: None,
+            db_filename: None,
+            synchronous: Synchronous::default(),
+            fsync_policy: FsyncPolicy::default(),
+            compress_rotated: false,
+            rotate_bytes: 64 * 1024 * 1024,
+        }
+    }
+}
+
+impl StoreOptions {
+    /// Resolve the JSONL directory and SQLite path for a store rooted at `base_path`
+    fn resolve_paths(&self, base_path: &Path) -> (PathBuf, PathBuf) {
+        let jsonl_dir = self.jsonl_dir.clone().unwrap_or_else(|| base_path.to_path_buf());
+        let db_path = self
+            .db_path
+            .clone()
+            .unwrap_or_else(|| base_path.join(self.db_filename.as_deref().unwrap_or(DEFAULT_DB_FILENAME)));
+        (jsonl_dir, db_path)
+    }
+}
+
+/// Preview of what `sync()` would change in the SQLite index
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SyncPlan {
+    /// Per-collection changes, sorted by collection name
+    pub collections: Vec<CollectionSyncPlan>,
+}
+
+impl SyncPlan {
+    /// True if sync would not change any records
+    pub fn is_empty(&self) -> bool {
+        self.collections
+            .iter()
+            .all(|c| c.added == 0 && c.updated == 0 && c.removed == 0)
+    }
+}
+
+/// Record-level changes `sync()` would make for a single collection
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CollectionSyncPlan {
+    pub collection: String,
+    /// Records in JSONL that are missing from SQLite
+    pub added: usize,
+    /// Records whose SQLite copy differs from the latest JSONL version
+    pub updated: usize,
+    /// Records in SQLite that are absent (or tombstoned) in JSONL
+    pub removed: usize,
+}
+
+/// Result of `Store::check`: everything that looks wrong with a store
+///
+/// Serializes to JSON for scripts; empty sections mean no problems were found.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct HealthReport {
+    /// JSONL has changed since the last sync (see `Store::is_stale`)
+    ///
+    /// Informational only: the store's own writes leave it stale until the
+    /// next sync, so `drift` is what says whether SQLite is actually behind.
+    pub stale: bool,
+    /// Collections where a sync would change SQLite, sorted by name
+    pub drift: Vec<CollectionSyncPlan>,
+    /// Collections whose JSONL has unparseable lines or ambiguous duplicates, sorted by name
+    pub jsonl: Vec<CollectionJsonlHealth>,
+    /// Checked references that have orphaned children
+    pub orphans: Vec<OrphanedRecords>,
+}
+
+impl HealthReport {
+    /// True if no problems were found
+    pub fn is_healthy(&self) -> bool {
+        self.drift.is_empty() && self.jsonl.is_empty() && self.orphans.is_empty()
+    }
+}
+
+/// JSONL problems in one collection, see `jsonl::scan_jsonl`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CollectionJsonlHealth {
+    pub collection: String,
+    pub unparseable_lines: Vec<usize>,
+    pub archived_unparseable_lines: usize,
+    pub ambiguous_ids: Vec<String>,
+}
+
+/// Children whose foreign key points at a missing parent, see `Store::find_orphans`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct OrphanedRecords {
+    pub child_collection: String,
+    pub fk_field: String,
+    pub parent_collection: String,
+    pub ids: Vec<String>,
+}
+
+/// Result of `Store::diff`: records that differ between two stores' JSONL files
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct StoreDiff {
+    /// Collections with at least one difference, sorted by name
+    pub collections: Vec<CollectionDiff>,
+}
+
+impl StoreDiff {
+    /// True if both stores hold the same live records
+    pub fn is_empty(&self) -> bool {
+        self.collections.is_empty()
+    }
+}
+
+/// Record IDs (sorted) that differ in one collection, see `StoreDiff`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CollectionDiff {
+    pub collection: String,
+    /// Live only in this store (absent or tombstoned in the other)
+    pub only_in_self: Vec<String>,
+    /// Live only in the other store
+    pub only_in_other: Vec<String>,
+    /// Live in both, but the latest versions differ
+    pub changed: Vec<String>,
+}
+
+/// On-disk footprint of one collection, see `Store::sizes`
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CollectionSizes {
+    pub collection: String,
+    /// The JSONL file plus its gzipped archives
+    pub jsonl_bytes: u64,
+    /// Non-blank JSONL lines, including superseded versions, tombstones and archived lines
+    pub total_lines: usize,
+    /// Latest versions in the JSONL file and its archives that aren't tombstones
+    pub live_records: usize,
+    /// Rows in `record_indexes` and `record_composite_indexes`
+    pub index_rows: usize,
+    /// `total_lines / live_records`; high values mean compaction would pay off
+    pub stale_ratio: f64,
+}
+
+/// Confirmation argument for `Store::clear_collection`
+///
+/// Has no default, so wiping a collection always reads as intended at the call site.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClearConfirm {
+    /// Delete every record in the collection
+    DeleteAllRecords,
+}
+
+/// How `Store::import_jsonl` treats an incoming record whose ID already exists
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ImportStrategy {
+    /// Import only if its `updated_at` is newer than the existing version's
+    #[default]
+    KeepNewer,
+    /// Always import; `updated_at` is bumped to now if needed so the imported version wins
+    Overwrite,
+    /// Leave existing records alone
+    SkipExisting,
+}
+
+/// Outcome of `Store::import_jsonl`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ImportReport {
+    /// New records
+    pub imported: usize,
+    /// Existing records replaced
+    pub updated: usize,
+    /// Lines left out by the import strategy
+    pub skipped: usize,
+    /// 1-based line numbers and reasons for lines that aren't a valid record
+    pub rejected: Vec<(usize, String)>,
+}
+
+/// Outcome of `Store::export_bundle`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct BundleReport {
+    /// Records written per collection
+    pub records: BTreeMap<String, usize>,
+    /// References from bundled records to records the bundle doesn't contain
+    pub dangling: Vec<DanglingReference>,
+}
+
+/// Document written by `Store::export_snapshot`
+#[derive(Debug, Serialize, Deserialize)]
+struct Snapshot {
+    /// Store version the snapshot was taken at
+    version: u32,
+    /// Index specs from `schema.json`
+    #[serde(default)]
+    schema: BTreeMap<String, Vec<IndexSpec>>,
+    /// Live records per collection, sorted by ID
+    collections: BTreeMap<String, Vec<serde_json::Value>>,
+}
+
+/// A record returned by `Store::list_including_deleted`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MaybeDeleted<T> {
+    /// The live record, or the last version of a deleted one
+    pub record: T,
+    /// When the record was deleted (milliseconds since epoch), `None` if it's live
+    pub deleted_at: Option<i64>,
+}
+
+impl<T> MaybeDeleted<T> {
+    pub fn is_deleted(&self) -> bool {
+        self.deleted_at.is_some()
+    }
+}
+
+/// A bundled record whose foreign key points outside the bundle, see `BundleReport`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DanglingReference {
+    pub collection: String,
+    pub id: String,
+    pub fk_field: String,
+    pub parent_collection: String,
+    pub parent_id: String,
+}
+
+/// How to index one registered collection without knowing its type, see `Store::register`
+#[derive(Clone, Copy)]
+struct Registration {
+    indexed_fields: fn(&str) -> serde_json::Result<HashMap<String, IndexValue>>,
+    unique_fields: &'static [&'static str],
+    composite_indexes: &'static [&'static [&'static str]],
+}
+
+impl Registration {
+    fn of<T: Record>() -> Self {
+        Self {
+            indexed_fields: |data_json| serde_json::from_str::<T>(data_json).map(|record| index_fields(&record)),
+            unique_fields: T::unique_fields(),
+            composite_indexes: T::composite_indexes(),
+        }
+    }
+}
+
+/// A record's `indexed_fields`, plus its ID under `id` if `id_index_value` isn't a string
+/// and its `created_at` if it has one
+fn index_fields<T: Record>(record: &T) -> HashMap<String, IndexValue> {
+    let mut fields = record.indexed_fields();
+    match record.id_index_value() {
+        IndexValue::String(_) => {}
+        value => {
+            fields.insert("id".to_string(), value);
+        }
+    }
+    if let Some(created_at) = record.created_at() {
+        fields
+            .entry("created_at".to_string())
+            .or_insert(IndexValue::Int(created_at));
+    }
+    fields
+}
+
 /// Generic persistent store with SQLite cache and JSONL source of truth
 pub struct Store {
     base_path: PathBuf,
+    jsonl_dir: PathBuf,
     db: Connection,
+    read_only: bool,
+    in_memory: bool,
+    registry: HashMap<&'static str, Registration>,
+    /// Index specs per collection from `schema.json`, see `register_collection`
+    schema: BTreeMap<String, Vec<IndexSpec>>,
+    hooks: Arc<Hooks>,
+    change_callbacks: Vec<ChangeCallback>,
+    /// Maximum record age in milliseconds per collection, see `set_retention`
+    retention: BTreeMap<&'static str, i64>,
+    fsync: Arc<Fsync>,
+    metrics: Recorder,
 }
 
 impl Store {
@@ -24,52 +392,312 @@ impl Store {
     ///
     /// The store will be created in a `.taskstore` subdirectory of the given path.
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::open_with(path, StoreOptions::default())
+    }
+
+    /// Open or create a store without syncing a stale database
+    ///
+    /// Useful for inspecting the current index before a sync, e.g. with `sync_dry_run()`.
+    pub fn open_without_sync<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::open_with(
+            path,
+            StoreOptions {
+                auto_sync: false,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Open an existing store without ever writing to it
+    ///
+    /// The SQLite database is opened with the read-only flag and nothing is
+    /// created on disk, so any number of readers can coexist without write
+    /// contention. Fails if the store doesn't exist, or if it is stale (a
+    /// read-only store can't sync; run `taskstore sync` first). Write methods
+    /// on the returned store fail immediately.
+    pub fn open_read_only<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::open_with(
+            path,
+            StoreOptions {
+                read_only: true,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Delete a store's SQLite database and recreate it from the JSONL files
+    ///
+    /// The recovery path for a corrupt or badly drifted index: JSONL is the
+    /// source of truth, so nothing is lost. No other process should have the
+    /// database open while this runs. `options.read_only` and `auto_sync` are
+    /// ignored; the new store is always writable and fully synced.
+    pub fn rebuild_with<P: AsRef<Path>>(path: P, options: StoreOptions) -> Result<Self> {
+        let (_, db_path) = options.resolve_paths(&path.as_ref().join(".taskstore"));
+        for suffix in ["", "-wal", "-shm", "-journal"] {
+            let mut file = db_path.clone().into_os_string();
+            file.push(suffix);
+            match fs::remove_file(&file) {
+                Ok(()) => debug!(file = ?file, "Removed database file"),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(StoreError::io("Failed to remove database file", e)),
+            }
+        }
+
+        info!(db = ?db_path, "Rebuilding database from JSONL");
+        Self::open_with(
+            path,
+            StoreOptions {
+                read_only: false,
+                auto_sync: true,
+                ..options
+            },
+        )
+    }
+
+    /// Open or create a store with explicit options
+    ///
+    /// The `.taskstore` directory (holding `.version` and `.gitignore`) is always
+    /// created under `path`; `options` can relocate the JSONL files and the
+    /// SQLite database away from it.
+    pub fn open_with<P: AsRef<Path>>(path: P, options: StoreOptions) -> Result<Self> {
+        if options.read_only {
+            return Self::open_read_only_with(path.as_ref(), &options);
+        }
+
         let base_path = path.as_ref().join(".taskstore");
+        let (jsonl_dir, db_path) = options.resolve_paths(&base_path);
 
-        // Create directory if it doesn't exist
-        fs::create_dir_all(&base_path).context("Failed to create store directory")?;
+        // Create directories if they don't exist
+        fs::create_dir_all(&base_path).map_err(|e| StoreError::io("Failed to create store directory", e))?;
+        fs::create_dir_all(&jsonl_dir).map_err(|e| StoreError::io("Failed to create JSONL directory", e))?;
+        if let Some(parent) = db_path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent).map_err(|e| StoreError::io("Failed to create database directory", e))?;
+        }
 
         // Open SQLite database
-        let db_path = base_path.join("taskstore.db");
-        let db = Connection::open(&db_path).context("Failed to open SQLite database")?;
+        let db = Connection::open(&db_path)?;
+
+        // Wait on locks held by other connections instead of failing immediately
+        db.busy_timeout(std::time::Duration::from_millis(options.busy_timeout_ms))?;
+        db.pragma_update(None, "synchronous", options.synchronous.pragma_value())?;
 
         let mut store = Self {
             base_path: base_path.clone(),
+            jsonl_dir,
             db,
+            read_only: false,
+            in_memory: false,
+            registry: HashMap::new(),
+            schema: BTreeMap::new(),
+            hooks: Arc::default(),
+            change_callbacks: Vec::new(),
+            retention: BTreeMap::new(),
+            fsync: Arc::new(
+                Fsync::new(options.fsync_policy).rotate_above(options.compress_rotated.then_some(options.rotate_bytes)),
+            ),
+            metrics: Recorder::default(),
         };
 
         // Initialize schema
         store.create_schema()?;
 
         // Write .gitignore
-        store.create_gitignore()?;
+        let db_filename = db_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| DEFAULT_DB_FILENAME.to_string());
+        store.create_gitignore(&db_filename)?;
 
         // Write/check version
-        store.write_version()?;
+        store.migrate_schema(MIGRATIONS, CURRENT_VERSION)?;
+
+        // Index specs for collections without a registered type
+        store.schema = store.load_schema()?;
+
+        // Before anything appends after a line a crash left unfinished
+        store.recover_partial_lines()?;
+
+        // Sync only the collections whose JSONL changed
+        if options.auto_sync {
+            let stale = store.stale_collections()?;
+            if !stale.is_empty() {
+                info!(collections = ?stale, "Database is stale, syncing changed collections");
+                for collection in &stale {
+                    store.sync_collection(collection)?;
+                }
+            }
+        }
+
+        Ok(store)
+    }
+
+    /// Terminate or truncate the unfinished last line of every JSONL file, see `jsonl::recover_partial_line`
+    fn recover_partial_lines(&self) -> Result<()> {
+        for collection in self.jsonl_collections()? {
+            let path = self.jsonl_dir.join(format!("{}.jsonl", collection));
+            match jsonl::recover_partial_line(&path)? {
+                TailRecovery::Clean => {}
+                TailRecovery::Terminated => {
+                    info!(collection, "Added the missing newline after the last JSONL line");
+                }
+                TailRecovery::Truncated(bytes) => {
+                    warn!(
+                        collection,
+                        bytes, "Truncated an incomplete JSONL line left by an interrupted append"
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Open an ephemeral store backed by an in-memory SQLite database
+    ///
+    /// No JSONL files are written and nothing touches disk: SQLite is the only
+    /// copy of the data, and it is lost when the store is dropped. The generic
+    /// `Record` API (create/get/list/filter/delete/...) behaves as usual, which
+    /// makes this handy for unit tests. `sync` and `sync_dry_run` are no-ops
+    /// (there is no JSONL to rebuild from) and git integration is unavailable.
+    pub fn open_in_memory() -> Result<Self> {
+        let db = Connection::open_in_memory()?;
+
+        let store = Self {
+            base_path: PathBuf::new(),
+            jsonl_dir: PathBuf::new(),
+            db,
+            read_only: false,
+            in_memory: true,
+            registry: HashMap::new(),
+            schema: BTreeMap::new(),
+            hooks: Arc::default(),
+            change_callbacks: Vec::new(),
+            retention: BTreeMap::new(),
+            fsync: Arc::default(),
+            metrics: Recorder::default(),
+        };
+        store.create_schema()?;
+
+        Ok(store)
+    }
+
+    /// True if this store was opened with `open_in_memory`
+    pub fn is_in_memory(&self) -> bool {
+        self.in_memory
+    }
 
-        // Sync if stale
-        if store.is_stale()? {
-            info!("Database is stale, syncing from JSONL files");
-            store.sync()?;
+    fn open_read_only_with(path: &Path, options: &StoreOptions) -> Result<Self> {
+        let base_path = path.join(".taskstore");
+        let (jsonl_dir, db_path) = options.resolve_paths(&base_path);
+        if !db_path.exists() {
+            return Err(StoreError::Io(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("No store database at {}", db_path.display()),
+            )));
+        }
+
+        let db = Connection::open_with_flags(
+            &db_path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX | OpenFlags::SQLITE_OPEN_URI,
+        )?;
+        db.busy_timeout(std::time::Duration::from_millis(options.busy_timeout_ms))?;
+
+        let store = Self {
+            base_path,
+            jsonl_dir,
+            db,
+            read_only: true,
+            in_memory: false,
+            registry: HashMap::new(),
+            schema: BTreeMap::new(),
+            hooks: Arc::default(),
+            change_callbacks: Vec::new(),
+            retention: BTreeMap::new(),
+            fsync: Arc::default(),
+            metrics: Recorder::default(),
+        };
+
+        if options.auto_sync && store.is_stale()? {
+            return Err(StoreError::ReadOnly(format!(
+                "Store at {} is stale and cannot be synced read-only; run `taskstore sync`",
+                store.base_path.display()
+            )));
         }
 
         Ok(store)
     }
 
+    /// True if this store was opened with `open_read_only`
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Fail fast if this store can't be written to
+    fn ensure_writable(&self) -> Result<()> {
+        if self.read_only {
+            return Err(StoreError::ReadOnly(format!(
+                "Store is read-only: {}",
+                self.base_path.display()
+            )));
+        }
+        Ok(())
+    }
+
     /// Get the base path of this store
+    ///
+    /// Empty for an in-memory store.
     pub fn base_path(&self) -> &Path {
         &self.base_path
     }
 
+    /// Get the directory holding this store's JSONL files
+    ///
+    /// Same as `base_path()` unless `StoreOptions::jsonl_dir` was set.
+    pub fn jsonl_dir(&self) -> &Path {
+        &self.jsonl_dir
+    }
+
     /// Get a reference to the SQLite database connection
     pub fn db(&self) -> &Connection {
         &self.db
     }
 
+    /// Run custom SQL against the index, e.g. a window function or recursive CTE
+    ///
+    /// The tables are `records` (`collection`, `id`, `data_json`, `updated_at`)
+    /// and `record_indexes` (one row per indexed field, with the value in
+    /// `field_value_str`, `field_value_int`, `field_value_real`, or
+    /// `field_value_bool`). Meant for
+    /// reads; see `with_connection_mut` before writing.
+    pub fn with_connection<R>(&self, f: impl FnOnce(&Connection) -> rusqlite::Result<R>) -> Result<R> {
+        Ok(f(&self.db)?)
+    }
+
+    /// Run custom SQL in a write transaction, committed if `f` succeeds
+    ///
+    /// The transaction holds SQLite's write lock, which every store writer
+    /// takes before appending to JSONL, so no store write interleaves with it.
+    /// Changes made this way exist only in the SQLite cache: they never reach
+    /// JSONL, other clones never see them, and the next full sync of the
+    /// collection discards them. Use it for derived data or one-off repairs of
+    /// the index, not to change records.
+    pub fn with_connection_mut<R>(
+        &mut self,
+        f: impl FnOnce(&rusqlite::Transaction) -> rusqlite::Result<R>,
+    ) -> Result<R> {
+        self.ensure_writable()?;
+        let tx = self.db.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let result = f(&tx)?;
+        tx.commit()?;
+        Ok(result)
+    }
+
     /// Create database schema
     fn create_schema(&self) -> Result<()> {
         debug!("Creating database schema");
 
+        // Off by default and per connection; makes the `ON DELETE CASCADE` below real
+        self.db.pragma_update(None, "foreign_keys", true)?;
+
         self.db.execute_batch(
             r#"
             -- Generic records table
@@ -92,6 +720,7 @@ impl Store {
                 field_value_str TEXT,
                 field_value_int INTEGER,
                 field_value_bool INTEGER,
+                field_value_real REAL,
                 PRIMARY KEY (collection, id, field_name),
                 FOREIGN KEY (collection, id) REFERENCES records(collection, id) ON DELETE CASCADE
             );
@@ -100,46 +729,214 @@ impl Store {
             CREATE INDEX IF NOT EXISTS idx_record_indexes_field_int ON record_indexes(collection, field_name, field_value_int);
             CREATE INDEX IF NOT EXISTS idx_record_indexes_field_bool ON record_indexes(collection, field_name, field_value_bool);
 
+            -- Compound indexes declared by Record::composite_indexes. Value columns
+            -- have no type affinity so each keeps the storage class it was written with.
+            CREATE TABLE IF NOT EXISTS record_composite_indexes (
+                collection TEXT NOT NULL,
+                index_name TEXT NOT NULL,
+                id TEXT NOT NULL,
+                v0, v1, v2, v3,
+                PRIMARY KEY (collection, index_name, id)
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_record_composite_indexes ON record_composite_indexes(collection, index_name, v0, v1, v2, v3);
+            CREATE INDEX IF NOT EXISTS idx_record_composite_indexes_id ON record_composite_indexes(collection, id);
+
+            -- Composite indexes whose rows cover every record in the collection
+            CREATE TABLE IF NOT EXISTS composite_index_meta (
+                collection TEXT NOT NULL,
+                index_name TEXT NOT NULL,
+                PRIMARY KEY (collection, index_name)
+            );
+
+            -- Field names each collection has been indexed by, so collections with no
+            -- registered type can still be reindexed after a sync (see rebuild_all_indexes)
+            CREATE TABLE IF NOT EXISTS indexed_field_names (
+                collection TEXT NOT NULL,
+                field_name TEXT NOT NULL,
+                PRIMARY KEY (collection, field_name)
+            );
+
             -- Sync metadata for staleness detection
             CREATE TABLE IF NOT EXISTS sync_metadata (
                 collection TEXT PRIMARY KEY,
                 last_sync_time INTEGER NOT NULL,
-                file_mtime INTEGER NOT NULL
+                file_mtime INTEGER NOT NULL,
+                file_size INTEGER NOT NULL DEFAULT 0,
+                file_hash TEXT NOT NULL DEFAULT ''
             );
             "#,
         )?;
 
+        self.migrate_sync_metadata()?;
+        self.migrate_record_indexes()?;
+
+        Ok(())
+    }
+
+    /// Add the `Float` value column to a `record_indexes` table created by an older version
+    fn migrate_record_indexes(&self) -> Result<()> {
+        let has_real: bool = self.db.query_row(
+            "SELECT EXISTS (SELECT 1 FROM pragma_table_info('record_indexes') WHERE name = 'field_value_real')",
+            [],
+            |row| row.get(0),
+        )?;
+        if !has_real {
+            debug!("Adding field_value_real column to record_indexes");
+            self.db
+                .execute("ALTER TABLE record_indexes ADD COLUMN field_value_real REAL", [])?;
+        }
+        self.db.execute(
+            "CREATE INDEX IF NOT EXISTS idx_record_indexes_field_real ON record_indexes(collection, field_name, field_value_real)",
+            [],
+        )?;
+
         Ok(())
     }
 
-    /// Create .gitignore file
-    fn create_gitignore(&self) -> Result<()> {
+    /// Add content-hash columns to a `sync_metadata` table created by an older version
+    fn migrate_sync_metadata(&self) -> Result<()> {
+        let columns: Vec<String> = {
+            let mut stmt = self.db.prepare("PRAGMA table_info(sync_metadata)")?;
+            let rows = stmt.query_map([], |row| row.get::<_, String>(1))?;
+            rows.collect::<rusqlite::Result<_>>()?
+        };
+
+        if !columns.iter().any(|c| c == "file_size") {
+            debug!("Adding file_size column to sync_metadata");
+            self.db.execute(
+                "ALTER TABLE sync_metadata ADD COLUMN file_size INTEGER NOT NULL DEFAULT 0",
+                [],
+            )?;
+        }
+        if !columns.iter().any(|c| c == "file_hash") {
+            debug!("Adding file_hash column to sync_metadata");
+            self.db.execute(
+                "ALTER TABLE sync_metadata ADD COLUMN file_hash TEXT NOT NULL DEFAULT ''",
+                [],
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Create .gitignore file, ignoring the SQLite database and its WAL files
+    ///
+    /// If the file already exists but doesn't mention `db_filename` (the store
+    /// was reopened with a different filename), the missing entries are appended.
+    fn create_gitignore(&self, db_filename: &str) -> Result<()> {
         let gitignore_path = self.base_path.join(".gitignore");
+        let entries = format!("{0}\n{0}-shm\n{0}-wal\n", db_filename);
         if !gitignore_path.exists() {
-            fs::write(
-                gitignore_path,
-                "taskstore.db\ntaskstore.db-shm\ntaskstore.db-wal\ntaskstore.log\n",
-            )?;
+            fs::write(gitignore_path, format!("{}taskstore.log\n", entries))?;
+        } else {
+            let existing = fs::read_to_string(&gitignore_path)?;
+            if !existing.lines().any(|line| line.trim() == db_filename) {
+                let mut file = fs::OpenOptions::new().append(true).open(&gitignore_path)?;
+                use std::io::Write;
+                if !existing.is_empty() && !existing.ends_with('\n') {
+                    writeln!(file)?;
+                }
+                write!(file, "{}", entries)?;
+            }
         }
         Ok(())
     }
 
-    /// Write version file
-    fn write_version(&self) -> Result<()> {
+    /// Bring the store from the version in `.version` up to `target`
+    ///
+    /// A new store just records `target`. Otherwise the migration starting at
+    /// the recorded version runs in an Immediate transaction, and `.version`
+    /// is rewritten after it commits, until `target` is reached. An interrupted
+    /// run resumes from the last recorded version; a crash between a commit and
+    /// the `.version` write reruns that step, so steps must be idempotent (check
+    /// before altering, like `migrate_record_indexes`). Fails with `Schema` if
+    /// the store is newer than `target` or no migration continues the chain.
+    fn migrate_schema(&mut self, migrations: &[Migration], target: u32) -> Result<()> {
         let version_path = self.base_path.join(".version");
         if !version_path.exists() {
-            fs::write(version_path, CURRENT_VERSION.to_string())?;
+            return Self::write_version(&version_path, target);
+        }
+
+        let recorded = fs::read_to_string(&version_path)?;
+        let mut version: u32 = recorded
+            .trim()
+            .parse()
+            .map_err(|_| StoreError::Schema(format!("Invalid store version: {:?}", recorded.trim())))?;
+        if version > target {
+            return Err(StoreError::Schema(format!(
+                "Store version {} is newer than this taskstore supports ({})",
+                version, target
+            )));
+        }
+
+        while version < target {
+            let Some(migration) = migrations
+                .iter()
+                .find(|m| m.from == version && m.to > version && m.to <= target)
+            else {
+                return Err(StoreError::Schema(format!(
+                    "No migration from store version {} towards {}",
+                    version, target
+                )));
+            };
+            info!(from = migration.from, to = migration.to, "Migrating store");
+            self.with_busy_retry(|db| {
+                let tx = db.transaction_with_behavior(TransactionBehavior::Immediate)?;
+                (migration.up)(&tx)?;
+                tx.commit()?;
+                Ok(())
+            })?;
+            Self::write_version(&version_path, migration.to)?;
+            version = migration.to;
         }
         Ok(())
     }
 
+    /// Replace the `.version` file, so a crash leaves the old or the new version
+    fn write_version(path: &Path, version: u32) -> Result<()> {
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, version.to_string()).map_err(|e| StoreError::io("Failed to write version file", e))?;
+        fs::rename(&tmp, path).map_err(|e| StoreError::io("Failed to replace version file", e))?;
+        Ok(())
+    }
+
     /// Check if database needs syncing from JSONL
     ///
-    /// Returns true if any JSONL file has been modified since the last sync,
-    /// or if there are JSONL files that have never been synced.
+    /// Returns true if any collection is stale, see `stale_collections`.
     pub fn is_stale(&self) -> Result<bool> {
+        Ok(!self.stale_collections()?.is_empty())
+    }
+
+    /// Collections whose JSONL file differs from what was last synced, sorted by name
+    ///
+    /// A collection is stale if its JSONL file has never been synced, if its
+    /// content changed since the last sync, or if it was synced but its file has
+    /// since been removed. `sync_collection` brings one back up to date.
+    ///
+    /// The file's mtime and size are used as a fast path: if both match the values
+    /// recorded at the last sync, the file is assumed unchanged. Otherwise the file
+    /// is hashed and compared against the stored content hash, so a checkout that
+    /// moves the mtime backwards (or a filesystem with coarse timestamps) can't
+    /// hide a content change. When the hash still matches, the new mtime and size are
+    /// recorded (unless the store is read-only), so the file isn't hashed again.
+    pub fn stale_collections(&self) -> Result<Vec<String>> {
+        if self.in_memory {
+            return Ok(Vec::new());
+        }
+
+        let mut synced: HashMap<String, (i64, i64, String)> = {
+            let mut stmt = self
+                .db
+                .prepare("SELECT collection, file_mtime, file_size, file_hash FROM sync_metadata")?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, (row.get(1)?, row.get(2)?, row.get(3)?))))?;
+            rows.collect::<rusqlite::Result<_>>()?
+        };
+
+        let mut stale = Vec::new();
+
         // Check each JSONL file
-        for entry in fs::read_dir(&self.base_path)? {
+        for entry in fs::read_dir(&self.jsonl_dir)? {
             let entry = entry?;
             let path = entry.path();
 
@@ -152,32 +949,52 @@ impl Store {
                 None => continue,
             };
 
-            // Get file modification time
-            let metadata = fs::metadata(&path)?;
-            let file_mtime = metadata
-                .modified()?
-                .duration_since(std::time::UNIX_EPOCH)
-                .map(|d| d.as_secs() as i64)
-                .unwrap_or(0);
+            let (file_mtime, file_size) = Self::file_fingerprint(&path)?;
 
-            // Check if we have sync metadata for this collection
-            let stored_mtime: Option<i64> = self
-                .db
-                .query_row(
-                    "SELECT file_mtime FROM sync_metadata WHERE collection = ?1",
-                    [collection],
-                    |row| row.get(0),
-                )
-                .optional()?;
+            let (stored_mtime, stored_size, stored_hash) = match synced.remove(collection) {
+                None => {
+                    // Never synced
+                    stale.push(collection.to_string());
+                    continue;
+                }
+                Some(s) => s,
+            };
+
+            // Fast path: unchanged mtime and size, skip hashing
+            if file_mtime == stored_mtime && file_size == stored_size {
+                continue;
+            }
 
-            match stored_mtime {
-                None => return Ok(true),                              // Never synced
-                Some(mtime) if file_mtime > mtime => return Ok(true), // File modified
-                _ => continue,
+            if jsonl::hash_file(&path)? != stored_hash {
+                debug!(collection, "JSONL content hash changed since last sync");
+                stale.push(collection.to_string());
+            } else if !self.read_only {
+                // Same content under a new mtime: remember it, so later checks take the fast path
+                if let Err(e) = self.db.execute(
+                    "UPDATE sync_metadata SET file_mtime = ?2, file_size = ?3 WHERE collection = ?1",
+                    rusqlite::params![collection, file_mtime, file_size],
+                ) {
+                    debug!(collection, error = %e, "Failed to record new JSONL mtime");
+                }
             }
         }
 
-        Ok(false)
+        // Synced collections whose JSONL file is gone
+        stale.extend(synced.into_keys());
+
+        stale.sort();
+        Ok(stale)
+    }
+
+    /// Get a JSONL file's modification time (ms since epoch) and size in bytes
+    fn file_fingerprint(path: &Path) -> Result<(i64, i64)> {
+        let metadata = fs::metadata(path)?;
+        let file_mtime = metadata
+            .modified()?
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or(0);
+        Ok((file_mtime, metadata.len() as i64))
     }
 
     // ========================================================================
@@ -185,887 +1002,9344 @@ impl Store {
     // ========================================================================
 
     /// Create a new record
+    ///
+    /// Fails with a `ValidationError` (inside the returned report) if `Record::validate` rejects it,
+    /// or a `DuplicateKeyError` if another record already has the same value in one of
+    /// `Record::unique_fields`.
     pub fn create<T: Record>(&mut self, record: T) -> Result<String> {
+        self.ensure_writable()?;
+        let timer = self.metrics.start();
+
         let collection = T::collection_name();
         Self::validate_collection_name(collection)?;
 
-        let id = record.id().to_string();
-        Self::validate_id(&id)?;
+        Self::validate_id(record.id())?;
 
-        // 1. Append to JSONL
-        self.append_jsonl_generic(collection, &record)?;
+        // Reject invalid records before anything is written
+        record.validate()?;
 
-        // 2. Insert into SQLite with transaction
-        let tx = self.db.transaction()?;
+        let op = if self.record_exists(collection, record.id())? {
+            WriteOp::Update
+        } else {
+            WriteOp::Create
+        };
+        let record = self.before_write(record, op)?;
+        let id = record.id().to_string();
 
-        let data_json = serde_json::to_string(&record).context("Failed to serialize record")?;
+        let jsonl_path = self.jsonl_path(collection);
+        let hooks = Arc::clone(&self.hooks);
+        let fsync = Arc::clone(&self.fsync);
+        let mut appended = None;
+
+        // The write transaction is opened first so the unique check, the JSONL
+        // append, and the index insert happen under one SQLite write lock
+        let result = self.with_busy_retry(|db| {
+            let tx = db.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            Self::write_record_tx(&tx, jsonl_path.as_deref(), &fsync, &record, &hooks, op, &mut appended)?;
+            tx.commit()?;
+            Ok(())
+        });
+        Self::undo_appends_on_error(result, appended)?;
+        self.metrics.record(op.into(), Some(collection), timer);
 
-        tx.execute(
-            "INSERT OR REPLACE INTO records (collection, id, data_json, updated_at)
-             VALUES (?1, ?2, ?3, ?4)",
-            rusqlite::params![collection, &id, data_json, record.updated_at()],
-        )?;
+        Ok(id)
+    }
 
-        // 3. Update indexes
-        Self::update_indexes_tx(&tx, collection, &id, &record.indexed_fields())?;
+    /// Replace the hooks fired around every write, see `StoreHooks`
+    ///
+    /// `before_write` runs before anything is written and can veto the write.
+    /// `after_write` runs inside the write transaction, after both JSONL and
+    /// SQLite are updated; if it fails, SQLite is rolled back and the appended
+    /// JSONL line is truncated away. Pass `StoreHooks::default()` to remove them.
+    pub fn set_hooks(&mut self, hooks: StoreHooks) {
+        self.hooks = Arc::new(Hooks::new(hooks, self.hooks.is_recording()));
+    }
 
-        tx.commit()?;
+    /// Register a callback to run after every committed create, update, and delete
+    ///
+    /// Callbacks run in registration order, once per changed record (including
+    /// records removed by a cascade), synchronously on the writing thread once
+    /// the write has committed. They must not call back into this `Store`. An
+    /// error from a callback is logged and doesn't affect the write or the other
+    /// callbacks. Writes that skip the write hooks (imports, syncs, compaction)
+    /// aren't reported.
+    pub fn on_change(&mut self, callback: ChangeCallback) {
+        self.hooks.start_recording();
+        self.change_callbacks.push(callback);
+    }
 
-        Ok(id)
+    /// True if a record with this ID is stored in the collection
+    fn record_exists(&self, collection: &str, id: &str) -> Result<bool> {
+        Ok(self.db.query_row(
+            "SELECT EXISTS (SELECT 1 FROM records WHERE collection = ?1 AND id = ?2)",
+            rusqlite::params![collection, id],
+            |row| row.get(0),
+        )?)
     }
 
-    /// Get a record by ID
-    pub fn get<T: Record>(&self, id: &str) -> Result<Option<T>> {
-        let collection = T::collection_name();
+    /// Run the `before_write` hook, returning the record to write in place of `record`
+    ///
+    /// A record the hook changed is validated again.
+    fn before_write<T: Record>(&self, record: T, op: WriteOp) -> Result<T> {
+        Self::before_write_with(&self.hooks, record, op)
+    }
 
-        let mut stmt = self
-            .db
-            .prepare("SELECT data_json FROM records WHERE collection = ?1 AND id = ?2")?;
+    fn before_write_with<T: Record>(hooks: &Hooks, record: T, op: WriteOp) -> Result<T> {
+        if hooks.before_write.is_none() {
+            return Ok(record);
+        }
 
-        let result = stmt
-            .query_row(rusqlite::params![collection, id], |row| {
-                let json: String = row.get(0)?;
-                Ok(json)
-            })
-            .optional()?;
+        let mut json = serde_json::to_value(&record)?;
+        hooks.before(T::collection_name(), op, &mut json)?;
+        let record: T = serde_json::from_value(json)?;
+        Self::validate_id(record.id())?;
+        record.validate()?;
+        Ok(record)
+    }
 
-        match result {
-            Some(json) => {
-                let record: T = serde_json::from_str(&json).context("Failed to deserialize record from database")?;
-                Ok(Some(record))
+    /// Pass `result` through, first taking back the JSONL lines of a write that failed
+    ///
+    /// SQLite rolls itself back when the transaction is dropped; the lines appended
+    /// under it would otherwise resurrect the write at the next sync.
+    fn undo_appends_on_error<R>(result: Result<R>, appends: impl IntoIterator<Item = JsonlAppend>) -> Result<R> {
+        if result.is_err() {
+            let appends: Vec<JsonlAppend> = appends.into_iter().collect();
+            for append in appends.iter().rev() {
+                if let Err(e) = append.undo() {
+                    warn!(file = ?append.path, error = %e, "Failed to take back JSONL line of a failed write");
+                }
             }
-            None => Ok(None),
         }
+        result
     }
 
-    /// Update a record (same as create for now)
-    pub fn update<T: Record>(&mut self, record: T) -> Result<()> {
-        self.create(record)?;
+    /// Write a validated record to JSONL and SQLite inside an open write transaction
+    ///
+    /// `jsonl_path` is `None` for an in-memory store. `appended` records where the
+    /// JSONL line was written, so a retried transaction doesn't append it twice
+    /// and a failed one can take it back. Fires the `after_write` hook last.
+    fn write_record_tx<T: Record>(
+        tx: &rusqlite::Transaction,
+        jsonl_path: Option<&Path>,
+        fsync: &Fsync,
+        record: &T,
+        hooks: &Hooks,
+        op: WriteOp,
+        appended: &mut Option<JsonlAppend>,
+    ) -> Result<()> {
+        // 1. Check constraints and write the row and its indexes to SQLite
+        let data_json = Self::insert_record_tx(tx, record)?;
+
+        // 2. Append to JSONL (skipped for an in-memory store)
+        if appended.is_none()
+            && let Some(jsonl_path) = jsonl_path
+        {
+            *appended = Some(jsonl::append_line(jsonl_path, &data_json, fsync)?);
+        }
+
+        // 3. Let the after_write hook veto the write before it commits
+        hooks.after_line(T::collection_name(), record.id(), op, &data_json)?;
+
         Ok(())
     }
 
-    /// Delete a record
-    pub fn delete<T: Record>(&mut self, id: &str) -> Result<()> {
+    /// Check a record's unique fields and references, then insert it and its indexes
+    ///
+    /// The SQLite half of `write_record_tx`. Returns the canonical JSON line
+    /// for the caller to append.
+    fn insert_record_tx<T: Record>(tx: &rusqlite::Transaction, record: &T) -> Result<String> {
         let collection = T::collection_name();
+        let id = record.id();
+        let data_json = jsonl::to_canonical_json(record)?;
+        let fields = index_fields(record);
+
+        // Enforce unique fields and references before anything is written
+        Self::check_unique_tx(tx, collection, id, &fields, T::unique_fields())?;
+        if !T::references().is_empty() {
+            let references = T::references()
+                .iter()
+                .filter(|r| !r.allow_dangling)
+                .map(|r| (r.field, r.collection, r.key));
+            Self::check_references(tx, collection, id, &serde_json::from_str(&data_json)?, references)?;
+        }
 
-        // 1. Append tombstone to JSONL
-        let tombstone = serde_json::json!({
-            "id": id,
-            "deleted": true,
-            "updated_at": crate::now_ms(),
-        });
-        self.append_jsonl_raw(collection, &tombstone)?;
-
-        // 2. Delete from SQLite
-        self.db.execute(
-            "DELETE FROM records WHERE collection = ?1 AND id = ?2",
-            rusqlite::params![collection, id],
+        tx.execute(
+            "INSERT OR REPLACE INTO records (collection, id, data_json, updated_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            rusqlite::params![collection, id, data_json, record.updated_at()],
         )?;
 
-        Ok(())
-    }
+        Self::backfill_composites_tx::<T>(tx)?;
+        Self::update_indexes_tx(tx, collection, id, &fields, T::composite_indexes())?;
 
-    /// Delete all records matching an indexed field value.
-    /// Returns the number of records deleted.
-    pub fn delete_by_index<T: Record>(&mut self, field: &str, value: IndexValue) -> Result<usize> {
-        // First list the matching records
-        let filters = vec![Filter {
-            field: field.to_string(),
-            op: FilterOp::Eq,
-            value,
-        }];
-        let records: Vec<T> = self.list(&filters)?;
+        Ok(data_json)
+    }
 
-        // Delete each one
-        let count = records.len();
-        for record in records {
-            self.delete::<T>(record.id())?;
-        }
+    /// Create many new records with one JSONL append and one SQLite transaction
+    ///
+    /// The lines are appended in the order given, with a single fsync. Fails
+    /// with `StoreError::Conflict` if an ID is already stored or repeats within
+    /// `records`; if any record is rejected, nothing is written. Returns the IDs
+    /// in order.
+    pub fn create_many<T: Record>(&mut self, records: Vec<T>) -> Result<Vec<String>> {
+        self.write_many(records, true)
+    }
 
-        Ok(count)
+    /// Create or update many records at once, see `create_many`
+    ///
+    /// Each record replaces any stored record with its ID, like `update`. If
+    /// an ID repeats, the later record wins.
+    pub fn upsert_many<T: Record>(&mut self, records: Vec<T>) -> Result<Vec<String>> {
+        self.write_many(records, false)
     }
 
-    /// List records with optional filtering
-    pub fn list<T: Record>(&self, filters: &[Filter]) -> Result<Vec<T>> {
+    fn write_many<T: Record>(&mut self, records: Vec<T>, create_only: bool) -> Result<Vec<String>> {
+        self.ensure_writable()?;
         let collection = T::collection_name();
+        Self::validate_collection_name(collection)?;
+        if records.is_empty() {
+            return Ok(Vec::new());
+        }
+        let timer = self.metrics.start();
 
-        // If no filters, return all records
-        if filters.is_empty() {
-            let mut stmt = self
-                .db
-                .prepare("SELECT data_json FROM records WHERE collection = ?1 ORDER BY updated_at DESC")?;
+        let mut staged = Vec::with_capacity(records.len());
+        for record in records {
+            Self::validate_id(record.id())?;
+            record.validate()?;
+            let op = if !create_only && self.record_exists(collection, record.id())? {
+                WriteOp::Update
+            } else {
+                WriteOp::Create
+            };
+            staged.push((self.before_write(record, op)?, op));
+        }
 
-            let rows = stmt.query_map([collection], |row| row.get::<_, String>(0))?;
+        let jsonl_path = self.jsonl_path(collection);
+        let hooks = Arc::clone(&self.hooks);
+        let fsync = Arc::clone(&self.fsync);
+        let mut appended = None;
+
+        let result = self.with_busy_retry(|db| {
+            let tx = db.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            let mut lines = Vec::with_capacity(staged.len());
+            for (record, _) in &staged {
+                if create_only {
+                    let existing: bool = tx.query_row(
+                        "SELECT EXISTS (SELECT 1 FROM records WHERE collection = ?1 AND id = ?2)",
+                        rusqlite::params![collection, record.id()],
+                        |row| row.get(0),
+                    )?;
+                    if existing {
+                        return Err(StoreError::Conflict(DuplicateKeyError {
+                            collection: collection.to_string(),
+                            field: "id".to_string(),
+                            value: record.id().to_string(),
+                            existing_id: record.id().to_string(),
+                        }));
+                    }
+                }
+                lines.push(Self::insert_record_tx(&tx, record)?);
+            }
 
-            let mut results = Vec::new();
-            for row_result in rows {
-                let data_json = row_result?;
-                let record: T = serde_json::from_str(&data_json).context("Failed to deserialize record")?;
-                results.push(record);
+            if appended.is_none()
+                && let Some(jsonl_path) = &jsonl_path
+            {
+                appended = Some(jsonl::append_line(jsonl_path, &lines.join("\n"), &fsync)?);
             }
-            return Ok(results);
+
+            for ((record, op), line) in staged.iter().zip(&lines) {
+                hooks.after_line(collection, record.id(), *op, line)?;
+            }
+            tx.commit()?;
+            Ok(())
+        });
+        Self::undo_appends_on_error(result, appended)?;
+        let op = if create_only { Op::Create } else { Op::Update };
+        self.metrics.record(op, Some(collection), timer);
+
+        Ok(staged.into_iter().map(|(record, _)| record.id().to_string()).collect())
+    }
+
+    /// Get a record by ID, creating it from `default` if it doesn't exist
+    ///
+    /// The existence check and the insert run in one SQLite write transaction, so
+    /// two callers racing on the same ID can't both create it: the loser gets the
+    /// winner's record back, and nothing is appended to JSONL on its behalf.
+    /// `default` runs only when the record is absent, and must return a record
+    /// with the requested ID.
+    pub fn get_or_create<T: Record>(&mut self, id: &str, default: impl FnOnce() -> T) -> Result<T> {
+        // Fast path without taking the write lock
+        if let Some(existing) = self.get::<T>(id)? {
+            return Ok(existing);
         }
+        self.ensure_writable()?;
 
-        // With filters: query the record_indexes table
-        let mut query = String::from(
-            "SELECT DISTINCT r.data_json
-             FROM records r
-             WHERE r.collection = ?1",
-        );
+        let collection = T::collection_name();
+        Self::validate_collection_name(collection)?;
+        Self::validate_id(id)?;
 
-        for (i, filter) in filters.iter().enumerate() {
-            Self::validate_field_name(&filter.field)?;
+        let timer = self.metrics.start();
+        let jsonl_path = self.jsonl_path(collection);
+        let hooks = Arc::clone(&self.hooks);
+        let fsync = Arc::clone(&self.fsync);
+        let mut default = Some(default);
+        let mut created: Option<T> = None;
+        let mut appended = None;
 
-            let join_alias = format!("idx{}", i);
-            query.push_str(&format!(
-                " AND EXISTS (
-                    SELECT 1 FROM record_indexes {}
-                    WHERE {}.collection = r.collection
-                      AND {}.id = r.id
-                      AND {}.field_name = ?{}",
-                join_alias,
-                join_alias,
-                join_alias,
-                join_alias,
-                i + 2
-            ));
+        let result = self.with_busy_retry(|db| {
+            let tx = db.transaction_with_behavior(TransactionBehavior::Immediate)?;
 
-            // Add value comparison based on type
-            match &filter.value {
-                IndexValue::String(_) => {
-                    query.push_str(&format!(
-                        " AND {}.field_value_str {} ?{}",
-                        join_alias,
-                        filter.op.to_sql(),
-                        i + 2 + filters.len()
-                    ));
-                }
-                IndexValue::Int(_) => {
-                    query.push_str(&format!(
-                        " AND {}.field_value_int {} ?{}",
-                        join_alias,
-                        filter.op.to_sql(),
-                        i + 2 + filters.len()
-                    ));
-                }
-                IndexValue::Bool(_) => {
-                    query.push_str(&format!(
-                        " AND {}.field_value_bool {} ?{}",
-                        join_alias,
-                        filter.op.to_sql(),
-                        i + 2 + filters.len()
-                    ));
+            let existing: Option<String> = tx
+                .query_row(
+                    "SELECT data_json FROM records WHERE collection = ?1 AND id = ?2",
+                    rusqlite::params![collection, id],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            if let Some(json) = existing {
+                let record: T = serde_json::from_str(&json)?;
+                return Ok((record, Op::Read));
+            }
+
+            if created.is_none()
+                && let Some(default) = default.take()
+            {
+                let record = default();
+                if record.id() != id {
+                    return Err(StoreError::Validation(ValidationError::new(format!(
+                        "get_or_create default returned ID '{}', expected '{}'",
+                        record.id(),
+                        id
+                    ))));
                 }
+                record.validate()?;
+                created = Some(Self::before_write_with(&hooks, record, WriteOp::Create)?);
             }
+            let record = created
+                .clone()
+                .ok_or_else(|| StoreError::Schema("get_or_create default already consumed".to_string()))?;
+
+            Self::write_record_tx(
+                &tx,
+                jsonl_path.as_deref(),
+                &fsync,
+                &record,
+                &hooks,
+                WriteOp::Create,
+                &mut appended,
+            )?;
+            tx.commit()?;
+            Ok((record, Op::Create))
+        });
+        let (record, op) = Self::undo_appends_on_error(result, appended)?;
+        self.metrics.record(op, Some(collection), timer);
+        Ok(record)
+    }
 
-            query.push(')');
+    /// Create a new record, assigning an ID and timestamps
+    ///
+    /// If the record's ID is empty, a UUIDv7 is assigned via `Record::set_id`.
+    /// `created_at` and `updated_at` are stamped with the current time via
+    /// `Record::set_timestamps`. Records must override those setters for this
+    /// to have any effect. Returns the record's (possibly generated) ID.
+    pub fn create_with_generated_id<T: Record>(&mut self, mut record: T) -> Result<String> {
+        if record.id().is_empty() {
+            record.set_id(uuid::Uuid::now_v7().to_string());
         }
+        record.set_timestamps();
+        self.create(record)
+    }
 
-        query.push_str(" ORDER BY r.updated_at DESC");
+    /// Get a record by ID
+    pub fn get<T: Record>(&self, id: &str) -> Result<Option<T>> {
+        let timer = self.metrics.start();
+        let collection = T::collection_name();
 
-        let mut stmt = self.db.prepare(&query)?;
+        let mut stmt = self
+            .db
+            .prepare("SELECT data_json FROM records WHERE collection = ?1 AND id = ?2")?;
 
-        // Bind parameters: collection, then field names, then values
-        let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
-        params.push(Box::new(collection.to_string()));
+        let result = stmt
+            .query_row(rusqlite::params![collection, id], |row| {
+                let json: String = row.get(0)?;
+                Ok(json)
+            })
+            .optional()?;
 
-        // Field names
-        for filter in filters {
-            params.push(Box::new(filter.field.clone()));
-        }
+        let record = match result {
+            Some(json) => Some(serde_json::from_str(&json)?),
+            None => None,
+        };
+        self.metrics.record(Op::Read, Some(collection), timer);
+        Ok(record)
+    }
 
-        // Values
-        for filter in filters {
-            match &filter.value {
-                IndexValue::String(s) => params.push(Box::new(s.clone())),
-                IndexValue::Int(i) => params.push(Box::new(*i)),
-                IndexValue::Bool(b) => params.push(Box::new(*b as i64)),
+    /// Get several records by ID, one `Option` per ID in the order given
+    ///
+    /// Reads them with `WHERE id IN (...)` queries of up to
+    /// `GET_MANY_CHUNK_SIZE` IDs rather than one query per ID. A missing ID
+    /// gives `None` at its position; a repeated ID gives the record each time.
+    pub fn get_many<T: Record>(&self, ids: &[&str]) -> Result<Vec<Option<T>>> {
+        let timer = self.metrics.start();
+        let collection = T::collection_name();
+
+        let mut unique = ids.to_vec();
+        unique.sort_unstable();
+        unique.dedup();
+        let mut found: HashMap<String, String> = HashMap::new();
+        for chunk in unique.chunks(GET_MANY_CHUNK_SIZE) {
+            let placeholders: Vec<String> = (0..chunk.len()).map(|i| format!("?{}", i + 2)).collect();
+            let mut stmt = self.db.prepare(&format!(
+                "SELECT id, data_json FROM records WHERE collection = ?1 AND id IN ({})",
+                placeholders.join(", ")
+            ))?;
+            let mut params: Vec<&dyn rusqlite::ToSql> = vec![&collection];
+            params.extend(chunk.iter().map(|id| id as &dyn rusqlite::ToSql));
+            let rows = stmt.query_map(params.as_slice(), |row| Ok((row.get(0)?, row.get(1)?)))?;
+            for row in rows {
+                let (id, json) = row?;
+                found.insert(id, json);
             }
         }
 
-        let params_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+        let records = ids
+            .iter()
+            .map(|id| found.get(*id).map(|json| serde_json::from_str(json)).transpose())
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        self.metrics.record(Op::Read, Some(collection), timer);
+        Ok(records)
+    }
 
-        let rows = stmt.query_map(params_refs.as_slice(), |row| row.get::<_, String>(0))?;
+    /// Check whether a record with this ID exists, without reading its JSON
+    pub fn exists<T: Record>(&self, id: &str) -> Result<bool> {
+        let timer = self.metrics.start();
+        let collection = T::collection_name();
+        let exists = self.record_exists(collection, id)?;
+        self.metrics.record(Op::Read, Some(collection), timer);
+        Ok(exists)
+    }
 
-        let mut results = Vec::new();
-        for row_result in rows {
-            let data_json = row_result?;
-            let record: T = serde_json::from_str(&data_json).context("Failed to deserialize record")?;
-            results.push(record);
+    /// Get the record that `record`'s reference `field` points at
+    ///
+    /// `field` must be declared in `T::references()` with `P`'s collection.
+    /// Returns `None` if the field is null or missing, or the reference is
+    /// dangling (possible with `allow_dangling`, or after the target was deleted).
+    pub fn resolve<T: Record, P: Record>(&self, record: &T, field: &str) -> Result<Option<P>> {
+        let reference = T::references()
+            .iter()
+            .find(|r| r.field == field && r.collection == P::collection_name())
+            .ok_or_else(|| {
+                StoreError::Schema(format!(
+                    "{} declares no reference '{}' to {}",
+                    T::collection_name(),
+                    field,
+                    P::collection_name()
+                ))
+            })?;
+        let value = serde_json::to_value(record)?[field].take();
+        if value.is_null() {
+            return Ok(None);
+        }
+        match Self::reference_target(&self.db, reference.collection, reference.key, &value)? {
+            Some(id) => self.get(&id),
+            None => Ok(None),
         }
-
-        Ok(results)
     }
 
-    // ========================================================================
-    // Helper methods
-    // ========================================================================
-
-    fn append_jsonl_generic<T: Record>(&self, collection: &str, record: &T) -> Result<()> {
-        let jsonl_path = self.base_path.join(format!("{}.jsonl", collection));
-
-        let mut file = std::fs::OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&jsonl_path)
-            .context("Failed to open JSONL file for appending")?;
-
-        // Acquire exclusive lock before writing
-        file.lock_exclusive().context("Failed to acquire file lock")?;
-
-        let json = serde_json::to_string(record)?;
+    /// Get a record as it was at `at_ms`, from the history kept in JSONL
+    ///
+    /// Returns the latest version with `updated_at <= at_ms`, or `None` if the
+    /// record didn't exist yet or was deleted at that time. SQLite only holds
+    /// the latest versions, so every call scans the collection's whole JSONL
+    /// file. History only reaches back to the last `compact_collection`, which
+    /// drops superseded versions. Fails for an in-memory store, which keeps no
+    /// history.
+    pub fn get_at_time<T: Record>(&self, id: &str, at_ms: i64) -> Result<Option<T>> {
+        let timer = self.metrics.start();
+        let collection = T::collection_name();
+        Self::validate_collection_name(collection)?;
+        let Some(path) = self.jsonl_path(collection) else {
+            return Err(StoreError::Io(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "An in-memory store keeps no history",
+            )));
+        };
 
-        use std::io::Write;
-        writeln!(file, "{}", json)?;
-        file.sync_all()?;
+        let record = match jsonl::read_jsonl_at(&path, id, at_ms)? {
+            Some(record) if !record.get("deleted").and_then(|v| v.as_bool()).unwrap_or(false) => {
+                Some(serde_json::from_value(record)?)
+            }
+            _ => None,
+        };
+        self.metrics.record(Op::Read, Some(collection), timer);
+        Ok(record)
+    }
 
-        // Lock is automatically released when file is dropped
+    /// Update a record (same as create for now)
+    ///
+    /// The caller must bump `updated_at` (e.g. via `Record::set_updated_at(now_ms())`):
+    /// sync and the merge driver keep the version with the latest `updated_at`, so an
+    /// update that doesn't advance it can be lost.
+    pub fn update<T: Record>(&mut self, record: T) -> Result<()> {
+        self.create(record)?;
         Ok(())
     }
 
-    fn append_jsonl_raw(&self, collection: &str, value: &serde_json::Value) -> Result<()> {
-        let jsonl_path = self.base_path.join(format!("{}.jsonl", collection));
-
-        let mut file = std::fs::OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&jsonl_path)
-            .context("Failed to open JSONL file for appending")?;
-
-        // Acquire exclusive lock before writing
-        file.lock_exclusive().context("Failed to acquire file lock")?;
-
-        let json = serde_json::to_string(value)?;
-
-        use std::io::Write;
-        writeln!(file, "{}", json)?;
-        file.sync_all()?;
+    /// Update a record that must already exist
+    ///
+    /// Like `update`, but fails with `StoreError::NotFound` instead of inserting
+    /// when no record with this ID is stored. The existence check and the write
+    /// happen in one SQLite write transaction, so a concurrent delete can't slip
+    /// in between.
+    pub fn update_existing<T: Record>(&mut self, record: T) -> Result<()> {
+        self.ensure_writable()?;
 
-        // Lock is automatically released when file is dropped
+        let collection = T::collection_name();
+        Self::validate_collection_name(collection)?;
+        Self::validate_id(record.id())?;
+        record.validate()?;
+
+        let timer = self.metrics.start();
+        let record = self.before_write(record, WriteOp::Update)?;
+
+        let jsonl_path = self.jsonl_path(collection);
+        let hooks = Arc::clone(&self.hooks);
+        let fsync = Arc::clone(&self.fsync);
+        let mut appended = None;
+
+        let result = self.with_busy_retry(|db| {
+            let tx = db.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            let exists: bool = tx.query_row(
+                "SELECT EXISTS (SELECT 1 FROM records WHERE collection = ?1 AND id = ?2)",
+                rusqlite::params![collection, record.id()],
+                |row| row.get(0),
+            )?;
+            if !exists {
+                return Err(StoreError::NotFound {
+                    collection: collection.to_string(),
+                    id: record.id().to_string(),
+                });
+            }
+            Self::write_record_tx(
+                &tx,
+                jsonl_path.as_deref(),
+                &fsync,
+                &record,
+                &hooks,
+                WriteOp::Update,
+                &mut appended,
+            )?;
+            tx.commit()?;
+            Ok(())
+        });
+        Self::undo_appends_on_error(result, appended)?;
+        self.metrics.record(Op::Update, Some(collection), timer);
         Ok(())
     }
 
-    fn update_indexes_tx(
-        tx: &rusqlite::Transaction,
-        collection: &str,
-        id: &str,
-        fields: &std::collections::HashMap<String, IndexValue>,
-    ) -> Result<()> {
-        debug!(collection, id, field_count = fields.len(), "update_indexes_tx: called");
+    /// Update a record only if it hasn't changed since it was read
+    ///
+    /// Writes `record` if the stored version's `updated_at` equals
+    /// `expected_updated_at` and returns `true`; returns `false`, writing
+    /// nothing, if another write got there first. Fails with
+    /// `StoreError::NotFound` if the record doesn't exist. The comparison, the
+    /// JSONL append, and the SQLite update happen under one SQLite write lock,
+    /// so the compare-and-swap is atomic across stores and processes. On
+    /// `false`, re-read the record and retry.
+    pub fn update_if_unchanged<T: Record>(&mut self, record: T, expected_updated_at: i64) -> Result<bool> {
+        self.ensure_writable()?;
 
-        // Delete old indexes
-        tx.execute(
-            "DELETE FROM record_indexes WHERE collection = ?1 AND id = ?2",
-            rusqlite::params![collection, id],
-        )?;
+        let collection = T::collection_name();
+        Self::validate_collection_name(collection)?;
+        Self::validate_id(record.id())?;
+        record.validate()?;
 
-        // Insert new indexes
-        for (field_name, value) in fields {
-            debug!(collection, id, field_name, ?value, "update_indexes_tx: inserting index");
-            Self::validate_field_name(field_name)?;
+        let timer = self.metrics.start();
+        let record = self.before_write(record, WriteOp::Update)?;
 
-            match value {
-                IndexValue::String(s) => {
-                    tx.execute(
-                        "INSERT INTO record_indexes (collection, id, field_name, field_value_str, field_value_int, field_value_bool)
-                         VALUES (?1, ?2, ?3, ?4, NULL, NULL)",
-                        rusqlite::params![collection, id, field_name, s],
-                    )?;
-                }
-                IndexValue::Int(i) => {
-                    tx.execute(
-                        "INSERT INTO record_indexes (collection, id, field_name, field_value_str, field_value_int, field_value_bool)
-                         VALUES (?1, ?2, ?3, NULL, ?4, NULL)",
-                        rusqlite::params![collection, id, field_name, i],
-                    )?;
-                }
-                IndexValue::Bool(b) => {
-                    tx.execute(
-                        "INSERT INTO record_indexes (collection, id, field_name, field_value_str, field_value_int, field_value_bool)
-                         VALUES (?1, ?2, ?3, NULL, NULL, ?4)",
-                        rusqlite::params![collection, id, field_name, *b as i64],
-                    )?;
+        let jsonl_path = self.jsonl_path(collection);
+        let hooks = Arc::clone(&self.hooks);
+        let fsync = Arc::clone(&self.fsync);
+        let mut appended = None;
+
+        let result = self.with_busy_retry(|db| {
+            let tx = db.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            let current: Option<i64> = tx
+                .query_row(
+                    "SELECT updated_at FROM records WHERE collection = ?1 AND id = ?2",
+                    rusqlite::params![collection, record.id()],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            match current {
+                None => {
+                    return Err(StoreError::NotFound {
+                        collection: collection.to_string(),
+                        id: record.id().to_string(),
+                    });
                 }
+                Some(current) if current != expected_updated_at => return Ok(false),
+                Some(_) => {}
             }
+            Self::write_record_tx(
+                &tx,
+                jsonl_path.as_deref(),
+                &fsync,
+                &record,
+                &hooks,
+                WriteOp::Update,
+                &mut appended,
+            )?;
+            tx.commit()?;
+            Ok(true)
+        });
+        let written = Self::undo_appends_on_error(result, appended)?;
+        if written {
+            self.metrics.record(Op::Update, Some(collection), timer);
+        } else {
+            debug!(
+                collection,
+                id = record.id(),
+                "Record changed since it was read, not updating"
+            );
         }
-
-        Ok(())
+        Ok(written)
     }
 
-    fn validate_collection_name(name: &str) -> Result<()> {
-        if name.is_empty() {
-            return Err(eyre!("Collection name cannot be empty"));
-        }
-        if name.len() > 64 {
-            return Err(eyre!("Collection name too long: {} (max 64 chars)", name));
-        }
-        if !name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-') {
-            return Err(eyre!(
-                "Invalid collection name: {} (must be alphanumeric with _/-)",
-                name
+    /// Apply `f` to every record matching `filters` and write them back in one transaction
+    ///
+    /// Returns the number of records updated. Like `update`, `f` should bump
+    /// `updated_at`. Empty filters are rejected so a missing filter can't
+    /// rewrite the whole collection; use `update_all` for that. If any record
+    /// fails validation or a hook, nothing is written.
+    pub fn update_where<T: Record>(&mut self, filters: &[Filter], f: impl Fn(&mut T)) -> Result<usize> {
+        if filters.is_empty() {
+            return Err(StoreError::Schema(
+                "update_where needs at least one filter; use update_all to update every record".to_string(),
             ));
         }
-        Ok(())
+        self.update_matching(filters, f)
     }
 
-    fn validate_field_name(name: &str) -> Result<()> {
-        if name.is_empty() {
-            return Err(eyre!("Field name cannot be empty"));
-        }
-        if name.len() > 64 {
-            return Err(eyre!("Field name too long: {} (max 64 chars)", name));
-        }
-        if !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
-            return Err(eyre!("Invalid field name: {} (must be alphanumeric with _)", name));
-        }
-        Ok(())
+    /// Apply `f` to every record in the collection, see `update_where`
+    pub fn update_all<T: Record>(&mut self, f: impl Fn(&mut T)) -> Result<usize> {
+        self.update_matching(&[], f)
     }
 
-    /// Validate record ID
-    fn validate_id(id: &str) -> Result<()> {
-        // Check not empty or whitespace-only
-        if id.trim().is_empty() {
-            return Err(eyre!("Record ID cannot be empty or whitespace-only"));
-        }
+    fn update_matching<T: Record>(&mut self, filters: &[Filter], f: impl Fn(&mut T)) -> Result<usize> {
+        self.ensure_writable()?;
+        let timer = self.metrics.start();
 
-        // Check reasonable length (prevent DoS via huge IDs)
-        if id.len() > 256 {
-            return Err(eyre!("Record ID too long: {} chars (max 256)", id.len()));
-        }
+        let collection = T::collection_name();
+        Self::validate_collection_name(collection)?;
+        let clause = Self::filter_clause(filters, &self.ready_composites::<T>(filters)?)?;
+        let (query, params) = self.list_query::<T>(clause, &ListOptions::default(), false)?;
+
+        let jsonl_path = self.jsonl_path(collection);
+        let hooks = Arc::clone(&self.hooks);
+        let fsync = Arc::clone(&self.fsync);
+        let mut appended: Vec<Option<JsonlAppend>> = Vec::new();
+
+        let result = self.with_busy_retry(|db| {
+            // A retry re-reads the records, so it writes its own lines
+            for previous in appended.drain(..).rev().flatten() {
+                previous.undo()?;
+            }
+            let tx = db.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+            let mut records: Vec<T> = Vec::new();
+            {
+                let mut stmt = tx.prepare(&query)?;
+                let params_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+                let rows = stmt.query_map(params_refs.as_slice(), |row| row.get::<_, String>(0))?;
+                for row in rows {
+                    let mut record: T = serde_json::from_str(&row?)?;
+                    let id = record.id().to_string();
+                    f(&mut record);
+                    if record.id() != id {
+                        return Err(StoreError::Schema(format!(
+                            "update_where can't change a record's ID ({} -> {}); use rename_id",
+                            id,
+                            record.id()
+                        )));
+                    }
+                    record.validate()?;
+                    records.push(Self::before_write_with(&hooks, record, WriteOp::Update)?);
+                }
+            }
 
-        Ok(())
+            for record in &records {
+                let mut slot = None;
+                let written = Self::write_record_tx(
+                    &tx,
+                    jsonl_path.as_deref(),
+                    &fsync,
+                    record,
+                    &hooks,
+                    WriteOp::Update,
+                    &mut slot,
+                );
+                appended.push(slot);
+                written?;
+            }
+            tx.commit()?;
+            Ok(records.len())
+        });
+        let count = Self::undo_appends_on_error(result, appended.into_iter().flatten())?;
+        self.metrics.record(Op::Update, Some(collection), timer);
+        debug!(collection, count, "Updated matching records");
+        Ok(count)
     }
 
-    // ========================================================================
-    // Sync operations
-    // ========================================================================
-
-    /// Sync SQLite database from JSONL files
+    /// Add `delta` to an integer field of a record and return the new value
     ///
-    /// After sync, call `rebuild_indexes::<T>()` for each record type to restore indexes.
-    pub fn sync(&mut self) -> Result<()> {
-        info!("Syncing database from JSONL files");
+    /// The read, the new JSONL line, and the index update all happen under one
+    /// SQLite write transaction, so concurrent increments serialize instead of
+    /// losing updates. That holds for every `Store` on the same database, in
+    /// this process or another; writers that bypass the store (e.g. editing
+    /// JSONL by hand) aren't coordinated. A missing or null field counts as 0.
+    /// `updated_at` is bumped so the new version wins at the next sync.
+    pub fn increment<T: Record>(&mut self, id: &str, field: &str, delta: i64) -> Result<i64> {
+        Self::validate_field_name(field)?;
+        if field == "id" || field == "updated_at" {
+            return Err(StoreError::Schema(format!("Can't increment '{}'", field)));
+        }
 
-        // Clear all tables
-        self.db.execute("DELETE FROM record_indexes", [])?;
-        self.db.execute("DELETE FROM records", [])?;
+        let collection = T::collection_name();
+        self.modify_json::<T, _>(id, |json| {
+            let current = match &json[field] {
+                serde_json::Value::Null => 0,
+                value => value.as_i64().ok_or_else(|| {
+                    StoreError::Schema(format!(
+                        "Field '{}' of {}/{} isn't an integer: {}",
+                        field, collection, id, value
+                    ))
+                })?,
+            };
+            let value = current.checked_add(delta).ok_or_else(|| {
+                StoreError::Schema(format!("Incrementing '{}' of {}/{} overflows", field, collection, id))
+            })?;
+            json[field] = serde_json::Value::from(value);
+            Ok(value)
+        })
+        .map(|(value, _)| value)
+    }
 
-        // Read all JSONL files
-        for entry in fs::read_dir(&self.base_path)? {
-            let entry = entry?;
-            let path = entry.path();
+    /// Write a record again unchanged, except for a new `updated_at`
+    ///
+    /// Re-asserts the record as the current version, e.g. so it wins a
+    /// latest-wins merge or restarts a TTL. Like `increment`, the read and the
+    /// write happen under one SQLite write transaction. Fails with `NotFound`
+    /// if the record doesn't exist.
+    pub fn touch<T: Record>(&mut self, id: &str) -> Result<()> {
+        self.modify_json::<T, _>(id, |_| Ok(()))?;
+        Ok(())
+    }
 
-            if path.extension().and_then(|s| s.to_str()) != Some("jsonl") {
-                continue;
-            }
+    /// Overwrite some of a record's fields and return the updated record
+    ///
+    /// Each key of the `patch` object replaces that top-level field of the
+    /// stored JSON; other fields keep their stored values, so concurrent edits
+    /// to different fields don't overwrite each other. Like `increment`, the
+    /// read and the write happen under one SQLite write transaction, and
+    /// `updated_at` is bumped (an `updated_at` in the patch is ignored). Fails
+    /// with `NotFound` if the record doesn't exist, and with
+    /// `StoreError::Schema` if `patch` isn't an object or changes `id`.
+    pub fn update_partial<T: Record>(&mut self, id: &str, patch: serde_json::Value) -> Result<T> {
+        let serde_json::Value::Object(patch) = patch else {
+            return Err(StoreError::Schema(format!("Patch for {} must be a JSON object", id)));
+        };
+        if let Some(new_id) = patch.get("id")
+            && new_id.as_str() != Some(id)
+        {
+            return Err(StoreError::Schema(format!(
+                "update_partial can't change a record's ID ({} -> {}); use rename_id",
+                id, new_id
+            )));
+        }
 
-            let collection = path
-                .file_stem()
-                .and_then(|s| s.to_str())
-                .ok_or_else(|| eyre!("Invalid JSONL filename: {:?}", path))?;
+        let (_, record) = self.modify_json::<T, _>(id, |json| {
+            for (field, value) in &patch {
+                json[field] = value.clone();
+            }
+            Ok(())
+        })?;
+        Ok(record)
+    }
 
-            debug!("Syncing collection: {}", collection);
+    /// Read a record's JSON, let `f` change it, and write it back with a newer `updated_at`
+    ///
+    /// The new `updated_at` is `now_ms()`, or one past the old value if that is
+    /// later. All of it runs in one immediate transaction; `f` runs again if the
+    /// transaction is retried. Returns `f`'s output and the written record.
+    fn modify_json<T: Record, R>(
+        &mut self,
+        id: &str,
+        mut f: impl FnMut(&mut serde_json::Value) -> Result<R>,
+    ) -> Result<(R, T)> {
+        self.ensure_writable()?;
+        let timer = self.metrics.start();
 
-            // Get file modification time for staleness tracking
-            let file_mtime = fs::metadata(&path)?
-                .modified()?
-                .duration_since(std::time::UNIX_EPOCH)
-                .map(|d| d.as_secs() as i64)
-                .unwrap_or(0);
+        let collection = T::collection_name();
+        Self::validate_collection_name(collection)?;
 
-            // Read records from JSONL
-            let records = jsonl::read_jsonl_latest(&path)?;
+        let jsonl_path = self.jsonl_path(collection);
+        let hooks = Arc::clone(&self.hooks);
+        let fsync = Arc::clone(&self.fsync);
+        let mut appended: Option<JsonlAppend> = None;
 
-            // Insert into SQLite
-            for (id, record) in records {
-                // Skip tombstones
-                if record.get("deleted").and_then(|v| v.as_bool()).unwrap_or(false) {
-                    continue;
-                }
+        let result = self.with_busy_retry(|db| {
+            // A retry may read a different value, so it writes its own line
+            if let Some(previous) = appended.take() {
+                previous.undo()?;
+            }
+            let tx = db.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            let data_json: String = tx
+                .query_row(
+                    "SELECT data_json FROM records WHERE collection = ?1 AND id = ?2",
+                    rusqlite::params![collection, id],
+                    |row| row.get(0),
+                )
+                .optional()?
+                .ok_or_else(|| StoreError::NotFound {
+                    collection: collection.to_string(),
+                    id: id.to_string(),
+                })?;
+
+            let mut json: serde_json::Value = serde_json::from_str(&data_json)?;
+            let updated_at = json["updated_at"].as_i64().unwrap_or(0);
+            let output = f(&mut json)?;
+            json["updated_at"] = serde_json::Value::from(crate::now_ms().max(updated_at + 1));
+
+            let record: T = serde_json::from_value(json)?;
+            record.validate()?;
+            let record = Self::before_write_with(&hooks, record, WriteOp::Update)?;
+            Self::write_record_tx(
+                &tx,
+                jsonl_path.as_deref(),
+                &fsync,
+                &record,
+                &hooks,
+                WriteOp::Update,
+                &mut appended,
+            )?;
+            tx.commit()?;
+            Ok((output, record))
+        });
+        let output = Self::undo_appends_on_error(result, appended)?;
+        self.metrics.record(Op::Update, Some(collection), timer);
+        Ok(output)
+    }
 
-                let data_json = serde_json::to_string(&record)?;
-                let updated_at = record.get("updated_at").and_then(|v| v.as_i64()).unwrap_or(0);
+    /// Delete a record
+    ///
+    /// Records declared in `Record::cascade_deletes` that reference this record
+    /// (and, recursively, their own declared children) are deleted too. The
+    /// records are found, given tombstones in their JSONL files and removed
+    /// from SQLite in a single write transaction, so a concurrent write can't
+    /// change them in between. This is the only cascade that reaches JSONL;
+    /// nothing relies on SQL-level `ON DELETE CASCADE`.
+    pub fn delete<T: Record>(&mut self, id: &str) -> Result<()> {
+        self.ensure_writable()?;
+        let timer = self.metrics.start();
 
-                self.db.execute(
-                    "INSERT OR REPLACE INTO records (collection, id, data_json, updated_at)
-                     VALUES (?1, ?2, ?3, ?4)",
-                    rusqlite::params![collection, &id, data_json, updated_at],
-                )?;
+        let collection = T::collection_name();
+        let hooks = Arc::clone(&self.hooks);
+        let fsync = Arc::clone(&self.fsync);
+        // Paths are built from this, as the write holds `self`, see `jsonl_path`
+        let jsonl_dir = (!self.in_memory).then(|| self.jsonl_dir.clone());
+        let mut appended: Vec<JsonlAppend> = Vec::new();
+
+        let result = self.with_busy_retry(|db| {
+            // A retry may find other children, so it writes its own tombstones
+            for previous in appended.drain(..).rev() {
+                previous.undo()?;
+            }
+            let tx = db.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            let mut targets = vec![(collection.to_string(), id.to_string())];
+            Self::collect_cascade_targets(&tx, id, T::cascade_deletes(), &mut targets)?;
+
+            // 1. Append a tombstone per record, letting the before_write hook veto the delete
+            let mut tombstones = Vec::with_capacity(targets.len());
+            for (collection, id) in &targets {
+                let stored: Option<String> = tx
+                    .query_row(
+                        "SELECT data_json FROM records WHERE collection = ?1 AND id = ?2",
+                        rusqlite::params![collection, id],
+                        |row| row.get(0),
+                    )
+                    .optional()?;
+                let mut tombstone = Self::tombstone(id, stored.as_deref())?;
+                hooks.before(collection, WriteOp::Delete, &mut tombstone)?;
+                if let Some(jsonl_dir) = &jsonl_dir {
+                    let line = jsonl::to_canonical_json(&tombstone)?;
+                    appended.push(jsonl::append_line(
+                        &Self::jsonl_file(jsonl_dir, collection),
+                        &line,
+                        &fsync,
+                    )?);
+                }
+                tombstones.push(tombstone);
+            }
 
-                // Note: We don't restore indexes during sync since we don't know
-                // which fields were indexed. Call rebuild_indexes<T>() after sync.
+            // 2. Delete the records and their indexes from SQLite
+            for (collection, id) in &targets {
+                Self::delete_record_tx(&tx, collection, id)?;
             }
+            for ((collection, _), tombstone) in targets.iter().zip(&tombstones) {
+                hooks.after(collection, WriteOp::Delete, tombstone)?;
+            }
+            tx.commit()?;
+            Ok(targets)
+        });
+        let targets = Self::undo_appends_on_error(result, appended)?;
 
-            // Record sync metadata for this collection
-            self.db.execute(
-                "INSERT OR REPLACE INTO sync_metadata (collection, last_sync_time, file_mtime)
-                 VALUES (?1, ?2, ?3)",
-                rusqlite::params![collection, now_ms(), file_mtime],
-            )?;
+        self.metrics.record(Op::Delete, Some(collection), timer);
+        for (collection, _) in &targets[1..] {
+            self.metrics.count(Op::Delete, collection);
+        }
+        if targets.len() > 1 {
+            debug!(
+                collection,
+                id,
+                cascaded = targets.len() - 1,
+                "Cascade deleted child records"
+            );
         }
 
-        // Clean up orphaned sync metadata (for deleted JSONL files)
-        self.db.execute(
-            "DELETE FROM sync_metadata WHERE collection NOT IN (SELECT DISTINCT collection FROM records)",
-            [],
-        )?;
+        Ok(())
+    }
 
-        info!("Sync complete");
+    /// Remove a record and its indexes from SQLite
+    fn delete_record_tx(conn: &Connection, collection: &str, id: &str) -> Result<()> {
+        for sql in [
+            "DELETE FROM record_indexes WHERE collection = ?1 AND id = ?2",
+            "DELETE FROM record_composite_indexes WHERE collection = ?1 AND id = ?2",
+            "DELETE FROM records WHERE collection = ?1 AND id = ?2",
+        ] {
+            conn.execute(sql, rusqlite::params![collection, id])?;
+        }
         Ok(())
     }
 
-    /// Rebuild indexes for a specific record type after sync
+    /// The tombstone line that deletes a record, given its stored JSON if it has any
     ///
-    /// Call this for each record type after `sync()` completes. The method:
-    /// - Reads all records from SQLite for the collection
-    /// - Deserializes each to type T to extract `indexed_fields()`
-    /// - Rebuilds the `record_indexes` table entries
-    ///
-    /// Returns the number of records successfully indexed.
-    ///
-    /// # Edge case handling
-    /// If records in the collection don't deserialize to type T (e.g., wrong type
-    /// passed), those records are skipped with a warning log. This prevents crashes
-    /// while alerting to potential misconfiguration.
-    pub fn rebuild_indexes<T: Record>(&mut self) -> Result<usize> {
-        let collection = T::collection_name();
-
-        // Get raw JSON from SQLite (bypass list<T> to handle deserialization errors)
-        // Use a block to ensure stmt is dropped before we start a transaction
-        let records_data: Vec<(String, String)> = {
-            let mut stmt = self
-                .db
-                .prepare("SELECT id, data_json FROM records WHERE collection = ?1")?;
-
-            let rows = stmt.query_map([collection], |row| {
-                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
-            })?;
-
-            rows.filter_map(|r| r.ok()).collect()
+    /// The tombstone keeps the record's last fields, for
+    /// `list_including_deleted`, and adds `"deleted": true` and `deleted_at`.
+    /// Its `updated_at` is bumped past the record's so it wins as the latest
+    /// version in `read_jsonl_latest` and the merge driver.
+    fn tombstone(id: &str, stored: Option<&str>) -> Result<serde_json::Value> {
+        let mut tombstone = match stored {
+            Some(json) => serde_json::from_str(json)?,
+            None => serde_json::json!({ "id": id }),
+        };
+        let now = crate::now_ms();
+        let updated_at = now.max(jsonl::updated_at(&tombstone) + 1);
+        let Some(fields) = tombstone.as_object_mut() else {
+            return Err(StoreError::Corrupt(format!(
+                "Stored record {} is not a JSON object",
+                id
+            )));
         };
+        fields.insert("deleted".to_string(), true.into());
+        fields.insert("deleted_at".to_string(), now.into());
+        fields.insert("updated_at".to_string(), updated_at.into());
+        Ok(tombstone)
+    }
 
-        let tx = self.db.transaction()?;
-        let mut count = 0;
+    /// A tombstone like `tombstone`'s without the record's fields
+    ///
+    /// Only `id`, `deleted`, `deleted_at` and `updated_at` are kept, for
+    /// deletes like `prune_expired`'s whose point is to drop the data.
+    fn minimal_tombstone(id: &str, stored: Option<&str>) -> Result<serde_json::Value> {
+        let mut tombstone = Self::tombstone(id, stored)?;
+        if let Some(fields) = tombstone.as_object_mut() {
+            fields.retain(|field, _| matches!(field.as_str(), "id" | "deleted" | "deleted_at" | "updated_at"));
+        }
+        Ok(tombstone)
+    }
 
-        for (id, data_json) in records_data {
-            // Attempt deserialization - skip records that don't match type T
-            let record: T = match serde_json::from_str(&data_json) {
-                Ok(r) => r,
-                Err(e) => {
-                    warn!(
-                        collection = collection,
-                        id = &id,
-                        error = ?e,
-                        "Skipping record that doesn't match type"
-                    );
-                    continue;
-                }
+    /// Delete every record in a collection and return how many there were
+    ///
+    /// Each record gets a tombstone, so the deletes survive a git merge the way
+    /// `delete`'s do. The tombstones are appended in one go under the JSONL
+    /// file lock, and the SQLite rows removed, inside one write transaction, so
+    /// a concurrent writer's record is either cleared or written after the
+    /// clear. Write hooks fire for each record. Children declared in
+    /// `cascade_deletes` are not touched; clear their collections too.
+    pub fn clear_collection<T: Record>(&mut self, confirm: ClearConfirm) -> Result<usize> {
+        let ClearConfirm::DeleteAllRecords = confirm;
+        self.ensure_writable()?;
+        let timer = self.metrics.start();
+
+        let collection = T::collection_name();
+        Self::validate_collection_name(collection)?;
+        let jsonl_path = self.jsonl_path(collection);
+        let hooks = Arc::clone(&self.hooks);
+        let fsync = Arc::clone(&self.fsync);
+        let mut appended: Option<JsonlAppend> = None;
+
+        let result = self.with_busy_retry(|db| {
+            // A retry may see other records, so it writes its own tombstones
+            if let Some(previous) = appended.take() {
+                previous.undo()?;
+            }
+            let tx = db.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            let stored: Vec<(String, String)> = {
+                let mut stmt = tx.prepare("SELECT id, data_json FROM records WHERE collection = ?1 ORDER BY id")?;
+                stmt.query_map([collection], |row| Ok((row.get(0)?, row.get(1)?)))?
+                    .collect::<rusqlite::Result<_>>()?
             };
 
-            Self::update_indexes_tx(&tx, collection, &id, &record.indexed_fields())?;
-            count += 1;
-        }
+            let mut tombstones = Vec::with_capacity(stored.len());
+            for (id, data_json) in &stored {
+                let mut tombstone = Self::tombstone(id, Some(data_json))?;
+                hooks.before(collection, WriteOp::Delete, &mut tombstone)?;
+                tombstones.push(tombstone);
+            }
+            if let Some(jsonl_path) = &jsonl_path
+                && !tombstones.is_empty()
+            {
+                let lines = tombstones
+                    .iter()
+                    .map(jsonl::to_canonical_json)
+                    .collect::<Result<Vec<_>>>()?;
+                appended = Some(jsonl::append_line(jsonl_path, &lines.join("\n"), &fsync)?);
+            }
 
-        tx.commit()?;
-        debug!(collection = collection, count = count, "Rebuilt indexes for collection");
+            for table in ["record_indexes", "record_composite_indexes", "records"] {
+                tx.execute(&format!("DELETE FROM {} WHERE collection = ?1", table), [collection])?;
+            }
+            for tombstone in &tombstones {
+                hooks.after(collection, WriteOp::Delete, tombstone)?;
+            }
+            tx.commit()?;
+            Ok(stored.len())
+        });
+        let count = Self::undo_appends_on_error(result, appended)?;
+
+        self.metrics.record(Op::Delete, Some(collection), timer);
+        info!(collection, count, "Cleared collection");
         Ok(count)
     }
 
-    // ========================================================================
-    // Git Integration
-    // ========================================================================
+    /// Delete every record matching `filters` and return how many there were
+    ///
+    /// Matches exactly what `list` and `count` would for the same filters. Like
+    /// `clear_collection`, the tombstones go to JSONL in one append and the rows
+    /// leave SQLite in one write transaction, and write hooks fire for each
+    /// record. Children declared in `cascade_deletes` are not touched; use
+    /// `delete` for a cascade. Empty filters are rejected; use
+    /// `clear_collection` to delete every record.
+    pub fn delete_where<T: Record>(&mut self, filters: &[Filter]) -> Result<u64> {
+        if filters.is_empty() {
+            return Err(StoreError::Schema(
+                "delete_where needs at least one filter; use clear_collection to delete every record".to_string(),
+            ));
+        }
+        let composites = self.ready_composites::<T>(filters)?;
+        self.delete_matching(T::collection_name(), filters, &composites, false)
+    }
 
-    /// Install git hooks for automatic sync
-    pub fn install_git_hooks(&self) -> Result<()> {
-        info!("Installing git hooks");
+    /// Delete the records of a collection matching `filters`, see `delete_where`
+    ///
+    /// With `minimal`, the tombstones drop the records' fields, see `minimal_tombstone`.
+    fn delete_matching(
+        &mut self,
+        collection: &'static str,
+        filters: &[Filter],
+        composites: &[&'static [&'static str]],
+        minimal: bool,
+    ) -> Result<u64> {
+        self.ensure_writable()?;
+        let timer = self.metrics.start();
 
-        // Find git directory
-        let git_dir = self.find_git_dir()?;
-        let hooks_dir = git_dir.join("hooks");
+        Self::validate_collection_name(collection)?;
+        let (filter_sql, filter_params) = Self::filter_clause(filters, composites)?;
+        let query = format!(
+            "SELECT r.id, r.data_json FROM records r WHERE r.collection = ?1{} ORDER BY r.id",
+            filter_sql
+        );
+        let mut params: Vec<Box<dyn rusqlite::ToSql>> = vec![Box::new(collection.to_string())];
+        params.extend(filter_params);
+
+        let jsonl_path = self.jsonl_path(collection);
+        let hooks = Arc::clone(&self.hooks);
+        let fsync = Arc::clone(&self.fsync);
+        let mut appended: Option<JsonlAppend> = None;
+
+        let result = self.with_busy_retry(|db| {
+            // A retry may match other records, so it writes its own tombstones
+            if let Some(previous) = appended.take() {
+                previous.undo()?;
+            }
+            let tx = db.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            let stored: Vec<(String, String)> = {
+                let mut stmt = tx.prepare(&query)?;
+                let params_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+                stmt.query_map(params_refs.as_slice(), |row| Ok((row.get(0)?, row.get(1)?)))?
+                    .collect::<rusqlite::Result<_>>()?
+            };
 
-        // Create hooks directory if it doesn't exist
-        fs::create_dir_all(&hooks_dir).context("Failed to create hooks directory")?;
+            let mut tombstones = Vec::with_capacity(stored.len());
+            for (id, data_json) in &stored {
+                let mut tombstone = if minimal {
+                    Self::minimal_tombstone(id, Some(data_json))?
+                } else {
+                    Self::tombstone(id, Some(data_json))?
+                };
+                hooks.before(collection, WriteOp::Delete, &mut tombstone)?;
+                tombstones.push(tombstone);
+            }
+            if let Some(jsonl_path) = &jsonl_path
+                && !tombstones.is_empty()
+            {
+                let lines = tombstones
+                    .iter()
+                    .map(jsonl::to_canonical_json)
+                    .collect::<Result<Vec<_>>>()?;
+                appended = Some(jsonl::append_line(jsonl_path, &lines.join("\n"), &fsync)?);
+            }
 
-        // Install all hooks
-        self.install_hook(&hooks_dir, "pre-commit", "taskstore sync")?;
-        self.install_hook(&hooks_dir, "post-merge", "taskstore sync")?;
-        self.install_hook(&hooks_dir, "post-rebase", "taskstore sync")?;
-        self.install_hook(&hooks_dir, "pre-push", "taskstore sync")?;
-        self.install_hook(&hooks_dir, "post-checkout", "taskstore sync")?;
+            for (id, _) in &stored {
+                for table in ["record_indexes", "record_composite_indexes", "records"] {
+                    tx.prepare_cached(&format!("DELETE FROM {} WHERE collection = ?1 AND id = ?2", table))?
+                        .execute([collection, id])?;
+                }
+            }
+            for tombstone in &tombstones {
+                hooks.after(collection, WriteOp::Delete, tombstone)?;
+            }
+            tx.commit()?;
+            Ok(stored.len() as u64)
+        });
+        let count = Self::undo_appends_on_error(result, appended)?;
 
-        // Install .gitattributes for merge driver
-        self.install_gitattributes()?;
+        self.metrics.record(Op::Delete, Some(collection), timer);
+        debug!(collection, count, "Deleted matching records");
+        Ok(count)
+    }
 
-        info!("Git hooks installed successfully");
+    /// Expire `T`'s records once their `updated_at` is more than `max_age_ms` in the past
+    ///
+    /// Only recorded in this `Store`; nothing is deleted until `prune_expired`
+    /// runs. Setting it again replaces the previous age.
+    pub fn set_retention<T: Record>(&mut self, max_age_ms: i64) -> Result<()> {
+        if max_age_ms <= 0 {
+            return Err(StoreError::Schema(format!(
+                "Retention for {} must be positive, got {} ms",
+                T::collection_name(),
+                max_age_ms
+            )));
+        }
+        self.retention.insert(T::collection_name(), max_age_ms);
         Ok(())
     }
 
-    fn find_git_dir(&self) -> Result<PathBuf> {
-        let mut current = self.base_path.clone();
-
-        // Walk up to find .git
-        loop {
-            let git_path = current.join(".git");
-            if git_path.exists() {
-                if git_path.is_dir() {
-                    return Ok(git_path);
-                } else {
-                    // Worktree - read .git file
-                    let content = fs::read_to_string(&git_path)?;
-                    let gitdir = content
-                        .strip_prefix("gitdir: ")
-                        .ok_or_else(|| eyre!("Invalid .git file format"))?
-                        .trim();
-                    return Ok(PathBuf::from(gitdir));
+    /// Delete the records older than their collection's retention, see `set_retention`
+    ///
+    /// Age is measured from `updated_at`, so `touch` keeps a record alive. Each
+    /// collection's expired records go in one write transaction, as with
+    /// `delete_where` (tombstones, write hooks, no cascades), except that the
+    /// tombstones keep none of the records' fields. The JSONL file and its
+    /// archives are then compacted into one file, see
+    /// `jsonl::compact_jsonl_with_archives`, so only those bare tombstones are
+    /// left of the expired records. Returns the number of records deleted.
+    pub fn prune_expired(&mut self) -> Result<u64> {
+        self.ensure_writable()?;
+        let now = now_ms();
+        let mut total = 0;
+        for (collection, max_age_ms) in self.retention.clone() {
+            let expired = [Filter {
+                field: "updated_at".to_string(),
+                op: FilterOp::Lt,
+                value: IndexValue::Int(now.saturating_sub(max_age_ms)),
+            }];
+            let count = self.delete_matching(collection, &expired, &[], true)?;
+            if count > 0 {
+                if let Some(path) = self.jsonl_path(collection) {
+                    jsonl::compact_jsonl_with_archives(&path)?;
                 }
+                info!(collection, count, "Pruned expired records");
             }
-
-            if !current.pop() {
-                break;
-            }
+            total += count;
         }
-
-        Err(eyre!("Not in a git repository"))
+        Ok(total)
     }
 
-    fn install_hook(&self, hooks_dir: &Path, hook_name: &str, command: &str) -> Result<()> {
-        let hook_path = hooks_dir.join(hook_name);
-        let hook_content = format!("#!/bin/sh\n# Auto-generated by taskstore\n{}\n", command);
+    /// Recursively collect the (collection, id) of every child record that references `parent_id`
+    fn collect_cascade_targets(
+        conn: &Connection,
+        parent_id: &str,
+        relations: &[ChildRelation],
+        targets: &mut Vec<(String, String)>,
+    ) -> Result<()> {
+        for relation in relations {
+            Self::validate_field_name(relation.fk_field)?;
 
-        if hook_path.exists() {
-            let existing = fs::read_to_string(&hook_path)?;
-            if existing.contains(command) {
-                debug!("Hook {} already contains command", hook_name);
-                return Ok(());
-            }
-            // Append to existing hook
-            fs::write(&hook_path, format!("{}\n{}", existing, command))?;
-        } else {
-            fs::write(&hook_path, hook_content)?;
-        }
+            let child_ids: Vec<String> = {
+                let mut stmt = conn.prepare(
+                    "SELECT id FROM records
+                     WHERE collection = ?1 AND CAST(json_extract(data_json, '$.' || ?2) AS TEXT) = ?3",
+                )?;
+                let rows = stmt.query_map(
+                    rusqlite::params![relation.collection, relation.fk_field, parent_id],
+                    |row| row.get::<_, String>(0),
+                )?;
+                rows.collect::<rusqlite::Result<_>>()?
+            };
 
-        // Make executable (Unix only)
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-            let mut perms = fs::metadata(&hook_path)?.permissions();
-            perms.set_mode(0o755);
-            fs::set_permissions(&hook_path, perms)?;
+            for child_id in child_ids {
+                // Guard against cycles in self-referential relations
+                if targets.iter().any(|(c, i)| c == relation.collection && *i == child_id) {
+                    continue;
+                }
+                targets.push((relation.collection.to_string(), child_id.clone()));
+                Self::collect_cascade_targets(conn, &child_id, relation.children, targets)?;
+            }
         }
 
         Ok(())
     }
 
-    fn install_gitattributes(&self) -> Result<()> {
-        // Find repo root
-        let mut repo_root = self.base_path.clone();
-        while !repo_root.join(".git").exists() && repo_root.pop() {}
+    /// Change a record's ID
+    ///
+    /// Writes a tombstone for `old_id` and a copy of the record under `new_id`
+    /// (with `updated_at` bumped) in one transaction. Fails with `NotFound` if
+    /// `old_id` doesn't exist, or a `Conflict` if `new_id` already does.
+    ///
+    /// Afterwards, direct children declared in `Record::cascade_deletes` that
+    /// reference `old_id` are repointed to `new_id`. This is best-effort: it runs
+    /// after the rename has committed, so a failure leaves the rename in place
+    /// and only some children repointed. Each repointed collection is logged.
+    pub fn rename_id<T: Record>(&mut self, old_id: &str, new_id: &str) -> Result<()> {
+        self.ensure_writable()?;
+        let timer = self.metrics.start();
 
-        let gitattributes_path = repo_root.join(".gitattributes");
-        let merge_rule = ".taskstore/*.jsonl merge=taskstore-merge";
+        let collection = T::collection_name();
+        Self::validate_collection_name(collection)?;
+        Self::validate_id(new_id)?;
 
-        if gitattributes_path.exists() {
-            let existing = fs::read_to_string(&gitattributes_path)?;
-            if existing.contains(merge_rule) {
-                info!(".gitattributes already configured");
-                return Ok(());
-            }
+        let Some(record) = self.get::<T>(old_id)? else {
+            return Err(StoreError::NotFound {
+                collection: collection.to_string(),
+                id: old_id.to_string(),
+            });
+        };
 
-            // Append rule
-            let mut file = fs::OpenOptions::new().append(true).open(&gitattributes_path)?;
-            use std::io::Write;
-            writeln!(file, "\n{}", merge_rule)?;
-        } else {
-            // Create new
-            fs::write(&gitattributes_path, format!("{}\n", merge_rule))?;
+        // 1. Build the renamed copy and the tombstone, letting the hooks veto them
+        let mut json = serde_json::to_value(&record)?;
+        json["id"] = serde_json::Value::String(new_id.to_string());
+        json["updated_at"] = serde_json::Value::from(crate::now_ms());
+        let renamed: T = serde_json::from_value(json)?;
+        if renamed.id() != new_id {
+            return Err(StoreError::Schema(format!(
+                "Can't rename {} records: the ID isn't stored in the `id` field",
+                collection
+            )));
         }
+        renamed.validate()?;
+        let renamed = self.before_write(renamed, WriteOp::Create)?;
+
+        let mut tombstone = Self::tombstone(old_id, Some(&jsonl::to_canonical_json(&record)?))?;
+        self.hooks.before(collection, WriteOp::Delete, &mut tombstone)?;
+
+        // 2. Swap them in one transaction; the old record goes first so its
+        //    unique field values don't collide with the copy
+        let jsonl_path = self.jsonl_path(collection);
+        let hooks = Arc::clone(&self.hooks);
+        let fsync = Arc::clone(&self.fsync);
+        let mut tombstone_appended = None;
+        let mut appended = None;
+        let result = self.with_busy_retry(|db| {
+            let tx = db.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            let taken: bool = tx.query_row(
+                "SELECT EXISTS (SELECT 1 FROM records WHERE collection = ?1 AND id = ?2)",
+                rusqlite::params![collection, new_id],
+                |row| row.get(0),
+            )?;
+            if taken {
+                return Err(StoreError::Conflict(DuplicateKeyError {
+                    collection: collection.to_string(),
+                    field: "id".to_string(),
+                    value: new_id.to_string(),
+                    existing_id: new_id.to_string(),
+                }));
+            }
 
-        // Configure git merge driver
-        self.configure_merge_driver()?;
+            if tombstone_appended.is_none()
+                && let Some(jsonl_path) = &jsonl_path
+            {
+                tombstone_appended = Some(jsonl::append_line(
+                    jsonl_path,
+                    &jsonl::to_canonical_json(&tombstone)?,
+                    &fsync,
+                )?);
+            }
+            for table in ["record_indexes", "record_composite_indexes", "records"] {
+                tx.execute(
+                    &format!("DELETE FROM {} WHERE collection = ?1 AND id = ?2", table),
+                    rusqlite::params![collection, old_id],
+                )?;
+            }
+            hooks.after(collection, WriteOp::Delete, &tombstone)?;
+
+            Self::write_record_tx(
+                &tx,
+                jsonl_path.as_deref(),
+                &fsync,
+                &renamed,
+                &hooks,
+                WriteOp::Create,
+                &mut appended,
+            )?;
+            tx.commit()?;
+            Ok(())
+        });
+        Self::undo_appends_on_error(result, tombstone_appended.into_iter().chain(appended))?;
+        self.metrics.record(Op::Update, Some(collection), timer);
+        debug!(collection, old_id, new_id, "Renamed record");
+
+        // 3. Repoint children that referenced the old ID
+        for relation in T::cascade_deletes() {
+            let repointed = self.repoint_children(relation, old_id, new_id)?;
+            if repointed > 0 {
+                info!(
+                    collection = relation.collection,
+                    field = relation.fk_field,
+                    old_id,
+                    new_id,
+                    repointed,
+                    "Repointed references to renamed record"
+                );
+            }
+        }
 
-        info!(".gitattributes configured");
         Ok(())
     }
 
-    fn configure_merge_driver(&self) -> Result<()> {
-        use std::process::Command;
+    /// Set `relation.fk_field` to `new_id` on every record in `relation.collection` that holds `old_id`
+    ///
+    /// Returns the number of records updated.
+    fn repoint_children(&mut self, relation: &ChildRelation, old_id: &str, new_id: &str) -> Result<usize> {
+        Self::validate_field_name(relation.fk_field)?;
+
+        let children: Vec<(String, String)> = {
+            let mut stmt = self.db.prepare(
+                "SELECT id, data_json FROM records
+                 WHERE collection = ?1 AND CAST(json_extract(data_json, '$.' || ?2) AS TEXT) = ?3",
+            )?;
+            let rows = stmt.query_map(
+                rusqlite::params![relation.collection, relation.fk_field, old_id],
+                |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+            )?;
+            rows.collect::<rusqlite::Result<_>>()?
+        };
+        if children.is_empty() {
+            return Ok(0);
+        }
 
-        let output = Command::new("git")
-            .args([
-                "config",
-                "--local",
-                "merge.taskstore-merge.name",
-                "TaskStore JSONL merge driver",
-            ])
-            .output()?;
+        let mut updates = Vec::with_capacity(children.len());
+        for (id, data_json) in &children {
+            let mut child: serde_json::Value = serde_json::from_str(data_json)?;
+            child[relation.fk_field] = serde_json::Value::String(new_id.to_string());
+            child["updated_at"] = serde_json::Value::from(crate::now_ms());
+            self.hooks.before(relation.collection, WriteOp::Update, &mut child)?;
+            updates.push((id.clone(), child));
+        }
 
-        if !output.status.success() {
-            return Err(eyre!("Failed to configure merge driver name"));
+        let mut appended = Vec::new();
+        for (_, child) in &updates {
+            let result = self.append_jsonl_raw(relation.collection, child);
+            appended.extend(Self::undo_appends_on_error(result, appended.clone())?);
         }
 
-        let output = Command::new("git")
+        let hooks = Arc::clone(&self.hooks);
+        let result = self.with_busy_retry(|db| {
+            let tx = db.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            for (id, child) in &updates {
+                tx.execute(
+                    "UPDATE records SET data_json = ?3, updated_at = ?4 WHERE collection = ?1 AND id = ?2",
+                    rusqlite::params![
+                        relation.collection,
+                        id,
+                        serde_json::to_string(child)?,
+                        child["updated_at"].as_i64()
+                    ],
+                )?;
+                hooks.after(relation.collection, WriteOp::Update, child)?;
+            }
+            tx.commit()?;
+            Ok(())
+        });
+        Self::undo_appends_on_error(result, appended)?;
+
+        let ids: HashSet<String> = updates.into_iter().map(|(id, _)| id).collect();
+        self.reindex_any(relation.collection, Some(&ids))?;
+        Ok(ids.len())
+    }
+
+    /// Delete all records matching an indexed field value.
+    /// Returns the number of records deleted.
+    pub fn delete_by_index<T: Record>(&mut self, field: &str, value: IndexValue) -> Result<usize> {
+        // First list the matching records
+        let filters = vec![Filter {
+            field: field.to_string(),
+            op: FilterOp::Eq,
+            value,
+        }];
+        let records: Vec<T> = self.list(&filters)?;
+
+        // Delete each one
+        let count = records.len();
+        for record in records {
+            self.delete::<T>(record.id())?;
+        }
+
+        Ok(count)
+    }
+
+    /// List records with optional filtering
+    pub fn list<T: Record>(&self, filters: &[Filter]) -> Result<Vec<T>> {
+        self.list_with(filters, &ListOptions::default())
+    }
+
+    /// List records matching `filters` with ordering and pagination
+    ///
+    /// See `ListOptions`. Records that don't index the `order_by` field sort
+    /// last; it is an error if the collection has records but none index it.
+    pub fn list_with<T: Record>(&self, filters: &[Filter], options: &ListOptions) -> Result<Vec<T>> {
+        self.list_records(filters, options, false)
+    }
+
+    /// The matching record with the smallest `order_by` value, `None` if nothing matches
+    ///
+    /// Runs the `list_with` query with `LIMIT 1`, so only that record is read.
+    /// As there, records that don't index `order_by` come last and ties go to
+    /// the smaller ID.
+    pub fn first<T: Record>(&self, filters: &[Filter], order_by: &str) -> Result<Option<T>> {
+        self.first_by(filters, order_by, SortDir::Asc)
+    }
+
+    /// The matching record with the largest `order_by` value, see `first`
+    pub fn last<T: Record>(&self, filters: &[Filter], order_by: &str) -> Result<Option<T>> {
+        self.first_by(filters, order_by, SortDir::Desc)
+    }
+
+    fn first_by<T: Record>(&self, filters: &[Filter], order_by: &str, dir: SortDir) -> Result<Option<T>> {
+        let options = ListOptions {
+            order_by: Some((order_by.to_string(), dir)),
+            limit: Some(1),
+            offset: None,
+        };
+        Ok(self.list_with(filters, &options)?.into_iter().next())
+    }
+
+    /// List records matching a combination of filters joined by AND and OR
+    ///
+    /// `list(&filters)` is the same as `list_where(&FilterExpr::all(filters))`.
+    /// An empty `Or` matches nothing and an empty `And` everything. Groups may
+    /// nest up to 32 levels (nested groups of the same kind count as one).
+    /// Composite indexes are only used for a plain AND of filters.
+    pub fn list_where<T: Record>(&self, expr: &FilterExpr) -> Result<Vec<T>> {
+        self.list_where_with(expr, &ListOptions::default())
+    }
+
+    /// `list_where` with ordering and pagination, see `list_with`
+    pub fn list_where_with<T: Record>(&self, expr: &FilterExpr, options: &ListOptions) -> Result<Vec<T>> {
+        if let Some(filters) = expr.clone().flattened().as_conjunction() {
+            return self.list_with(&filters, options);
+        }
+        self.query_records(Self::expr_clause(expr)?, options, false)
+    }
+
+    /// Like `list`, but with `Record::heavy_fields` left out of every record
+    ///
+    /// The fields are dropped by SQLite before the JSON is returned, so they
+    /// are neither copied out nor deserialized. They must be optional when
+    /// deserializing (e.g. `#[serde(default)]`); use `get` for the full record.
+    pub fn list_lite<T: Record>(&self, filters: &[Filter]) -> Result<Vec<T>> {
+        self.list_records(filters, &ListOptions::default(), true)
+    }
+
+    /// List every record, followed by the deleted records the JSONL still has tombstones for
+    ///
+    /// Live records come first, in `list` order, then deleted ones, most
+    /// recently deleted first. A deleted record is rebuilt from its tombstone,
+    /// which keeps its last fields; its `updated_at` is the deletion time.
+    /// Tombstones written before they kept the fields, and those of records
+    /// removed by `prune_expired`, can't be rebuilt and are left out. To restore a record, write it back with `update`.
+    pub fn list_including_deleted<T: Record>(&self) -> Result<Vec<MaybeDeleted<T>>> {
+        let mut records: Vec<MaybeDeleted<T>> = self
+            .list::<T>(&[])?
+            .into_iter()
+            .map(|record| MaybeDeleted {
+                record,
+                deleted_at: None,
+            })
+            .collect();
+
+        let Some(path) = self.jsonl_path(T::collection_name()) else {
+            return Ok(records);
+        };
+        let live: HashSet<String> = records.iter().map(|r| r.record.id().to_string()).collect();
+        let mut deleted = Vec::new();
+        for (id, mut tombstone) in jsonl::read_jsonl_latest(&path)? {
+            if live.contains(&id) || !tombstone.get("deleted").and_then(|v| v.as_bool()).unwrap_or(false) {
+                continue;
+            }
+            let Some(fields) = tombstone.as_object_mut() else {
+                continue;
+            };
+            fields.remove("deleted");
+            let deleted_at = fields
+                .remove("deleted_at")
+                .and_then(|v| v.as_i64())
+                .unwrap_or_else(|| jsonl::updated_at(&tombstone));
+            match serde_json::from_value::<T>(tombstone) {
+                Ok(record) => deleted.push(MaybeDeleted {
+                    record,
+                    deleted_at: Some(deleted_at),
+                }),
+                Err(e) => debug!(id, error = %e, "Tombstone doesn't hold the deleted record, skipping"),
+            }
+        }
+        deleted.sort_by(|a, b| (b.deleted_at, b.record.id()).cmp(&(a.deleted_at, a.record.id())));
+        records.extend(deleted);
+        Ok(records)
+    }
+
+    fn list_records<T: Record>(&self, filters: &[Filter], options: &ListOptions, lite: bool) -> Result<Vec<T>> {
+        let clause = Self::filter_clause(filters, &self.ready_composites::<T>(filters)?)?;
+        self.query_records(clause, options, lite)
+    }
+
+    /// Run a list query with an already built filter clause, see `list_query`
+    fn query_records<T: Record>(
+        &self,
+        clause: (String, Vec<Box<dyn rusqlite::ToSql>>),
+        options: &ListOptions,
+        lite: bool,
+    ) -> Result<Vec<T>> {
+        let timer = self.metrics.start();
+        let collection = T::collection_name();
+        let (query, params) = self.list_query::<T>(clause, options, lite)?;
+
+        let mut stmt = self.db.prepare(&query)?;
+        let params_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+        let rows = stmt.query_map(params_refs.as_slice(), |row| row.get::<_, String>(0))?;
+
+        let mut results = Vec::new();
+        for row_result in rows {
+            let data_json = row_result?;
+            let record: T = serde_json::from_str(&data_json)?;
+            results.push(record);
+        }
+
+        self.metrics.record(Op::Read, Some(collection), timer);
+        Ok(results)
+    }
+
+    /// SQL and parameters `list_with` runs for a filter clause and options
+    ///
+    /// `clause` comes from `filter_clause` or `expr_clause`. With `lite`, the
+    /// selected JSON leaves out `Record::heavy_fields`.
+    fn list_query<T: Record>(
+        &self,
+        clause: (String, Vec<Box<dyn rusqlite::ToSql>>),
+        options: &ListOptions,
+        lite: bool,
+    ) -> Result<(String, Vec<Box<dyn rusqlite::ToSql>>)> {
+        let collection = T::collection_name();
+
+        let mut data_sql = "r.data_json".to_string();
+        if lite && !T::heavy_fields().is_empty() {
+            let mut paths = Vec::new();
+            for field in T::heavy_fields() {
+                // Validated names are safe to quote into the JSON path
+                Self::validate_field_name(field)?;
+                paths.push(format!("'$.{}'", field));
+            }
+            data_sql = format!("json_remove(r.data_json, {})", paths.join(", "));
+        }
+
+        let (filter_sql, filter_params) = clause;
+        let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+        params.push(Box::new(collection.to_string()));
+        params.extend(filter_params);
+
+        let mut join_sql = String::new();
+        let order_sql = match &options.order_by {
+            None => "r.updated_at DESC, r.id ASC".to_string(),
+            Some((field, dir))
+                if field == "updated_at" || (field == "id" && !self.indexes_field(collection, "id")?) =>
+            {
+                format!("r.{} {}, r.id ASC", field, dir.to_sql())
+            }
+            Some((field, dir)) => {
+                Self::validate_field_name(field)?;
+                self.ensure_indexed(collection, field)?;
+                params.push(Box::new(field.clone()));
+                join_sql = format!(
+                    " LEFT JOIN record_indexes o ON o.collection = r.collection AND o.id = r.id AND o.field_name = ?{}",
+                    params.len()
+                );
+                format!(
+                    "o.id IS NULL, COALESCE(o.field_value_int, o.field_value_real, o.field_value_bool, o.field_value_str) {}, r.id ASC",
+                    dir.to_sql()
+                )
+            }
+        };
+
+        let mut limit_sql = String::new();
+        if options.limit.is_some() || options.offset.is_some() {
+            // SQLite only accepts OFFSET after a LIMIT; -1 means unbounded
+            params.push(Box::new(options.limit.map_or(-1, |n| n as i64)));
+            params.push(Box::new(options.offset.unwrap_or(0) as i64));
+            limit_sql = format!(" LIMIT ?{} OFFSET ?{}", params.len() - 1, params.len());
+        }
+
+        let query = format!(
+            "SELECT {}
+             FROM records r{}
+             WHERE r.collection = ?1{}
+             ORDER BY {}{}",
+            data_sql, join_sql, filter_sql, order_sql, limit_sql
+        );
+
+        Ok((query, params))
+    }
+
+    /// Show how SQLite would run `list` with these filters, without running it
+    ///
+    /// Returns the generated SQL followed by the `EXPLAIN QUERY PLAN` output as
+    /// an indented tree, e.g. to check that a filter hits an index (`SEARCH ...
+    /// USING INDEX`) rather than scanning, or that a composite index is used.
+    pub fn explain<T: Record>(&self, filters: &[Filter]) -> Result<String> {
+        let clause = Self::filter_clause(filters, &self.ready_composites::<T>(filters)?)?;
+        let (query, params) = self.list_query::<T>(clause, &ListOptions::default(), false)?;
+
+        let mut stmt = self.db.prepare(&format!("EXPLAIN QUERY PLAN {}", query))?;
+        let params_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+        let rows = stmt.query_map(params_refs.as_slice(), |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?, row.get::<_, String>(3)?))
+        })?;
+
+        let mut out = format!("{}\n\nQUERY PLAN\n", query);
+        let mut depths: HashMap<i64, usize> = HashMap::new();
+        for row in rows {
+            let (id, parent, detail) = row?;
+            let depth = depths.get(&parent).map_or(0, |d| d + 1);
+            depths.insert(id, depth);
+            out.push_str(&format!("{}`--{}\n", "   ".repeat(depth), detail));
+        }
+        Ok(out)
+    }
+
+    /// True if any record in the collection indexes `field`
+    fn indexes_field(&self, collection: &str, field: &str) -> Result<bool> {
+        Ok(self.db.query_row(
+            "SELECT EXISTS (SELECT 1 FROM record_indexes WHERE collection = ?1 AND field_name = ?2)",
+            [collection, field],
+            |row| row.get(0),
+        )?)
+    }
+
+    /// `filters` with `DateTime` values written as strings where the field is only indexed as strings
+    ///
+    /// A `DateTime` is compared with int index rows, so on a date kept as a
+    /// string it would otherwise match nothing.
+    fn dates_as_strings(&self, collection: &str, filters: &[Filter]) -> Result<Vec<Filter>> {
+        let mut filters = filters.to_vec();
+        for filter in &mut filters {
+            let Some(value) = filter.value.datetime_as_string() else {
+                continue;
+            };
+            let (has_str, has_int): (bool, bool) = self.db.query_row(
+                "SELECT EXISTS (SELECT 1 FROM record_indexes
+                                WHERE collection = ?1 AND field_name = ?2 AND field_value_str IS NOT NULL),
+                        EXISTS (SELECT 1 FROM record_indexes
+                                WHERE collection = ?1 AND field_name = ?2 AND field_value_int IS NOT NULL)",
+                rusqlite::params![collection, filter.field],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )?;
+            if has_str && !has_int {
+                filter.value = value;
+            }
+        }
+        Ok(filters)
+    }
+
+    /// Fail if the collection has records but none of them index `field`
+    fn ensure_indexed(&self, collection: &str, field: &str) -> Result<()> {
+        let (has_records, has_field): (bool, bool) = self.db.query_row(
+            "SELECT EXISTS (SELECT 1 FROM records WHERE collection = ?1),
+                    EXISTS (SELECT 1 FROM record_indexes WHERE collection = ?1 AND field_name = ?2)",
+            rusqlite::params![collection, field],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        if has_records && !has_field {
+            return Err(StoreError::Schema(format!(
+                "Field '{}' is not indexed for collection '{}'",
+                field, collection
+            )));
+        }
+        Ok(())
+    }
+
+    /// List the IDs of records matching `filters`
+    ///
+    /// Same filtering and order as `list`, but only reads the `id` column, so no
+    /// record JSON is loaded or deserialized.
+    pub fn list_ids<T: Record>(&self, filters: &[Filter]) -> Result<Vec<String>> {
+        let timer = self.metrics.start();
+        let collection = T::collection_name();
+
+        let (filter_sql, filter_params) = Self::filter_clause(filters, &self.ready_composites::<T>(filters)?)?;
+        let query = format!(
+            "SELECT r.id
+             FROM records r
+             WHERE r.collection = ?1{}
+             ORDER BY r.updated_at DESC, r.id ASC",
+            filter_sql
+        );
+
+        let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+        params.push(Box::new(collection.to_string()));
+        params.extend(filter_params);
+        let params_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+
+        let mut stmt = self.db.prepare(&query)?;
+        let rows = stmt.query_map(params_refs.as_slice(), |row| row.get::<_, String>(0))?;
+        let ids = rows.collect::<rusqlite::Result<_>>()?;
+
+        self.metrics.record(Op::Read, Some(collection), timer);
+        Ok(ids)
+    }
+
+    /// List records of any collection as JSON, filtered like `list`
+    ///
+    /// Meant for tools such as the CLI that don't have the record's Rust type.
+    /// Newest first. Fails with `Schema` if the collection has records but
+    /// none of them index a filtered field. A `DateTime` value, such as an
+    /// unquoted date parsed by `Filter::from_str`, is compared as a string on
+    /// a field only indexed as strings, see `IndexValue::datetime_as_string`.
+    pub fn list_json(&self, collection: &str, filters: &[Filter]) -> Result<Vec<serde_json::Value>> {
+        Self::validate_collection_name(collection)?;
+        let timer = self.metrics.start();
+        for filter in filters.iter().filter(|f| f.field != "updated_at") {
+            self.ensure_indexed(collection, &filter.field)?;
+        }
+        let filters = self.dates_as_strings(collection, filters)?;
+
+        let (filter_sql, filter_params) = Self::filter_clause(&filters, &[])?;
+        let query = format!(
+            "SELECT r.data_json
+             FROM records r
+             WHERE r.collection = ?1{}
+             ORDER BY r.updated_at DESC, r.id ASC",
+            filter_sql
+        );
+
+        let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+        params.push(Box::new(collection.to_string()));
+        params.extend(filter_params);
+        let params_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+
+        let mut stmt = self.db.prepare(&query)?;
+        let rows = stmt.query_map(params_refs.as_slice(), |row| row.get::<_, String>(0))?;
+        let mut records = Vec::new();
+        for row in rows {
+            records.push(serde_json::from_str(&row?)?);
+        }
+
+        self.metrics.record(Op::Read, Some(collection), timer);
+        Ok(records)
+    }
+
+    /// Count the records matching `filters`
+    ///
+    /// Same filtering as `list`, but runs `SELECT COUNT(*)`, so no record JSON
+    /// is loaded or deserialized. With no filters only the records table is read.
+    pub fn count<T: Record>(&self, filters: &[Filter]) -> Result<u64> {
+        let clause = Self::filter_clause(filters, &self.ready_composites::<T>(filters)?)?;
+        self.count_records::<T>(clause)
+    }
+
+    /// Count the records matching a filter expression, see `list_where`
+    pub fn count_where<T: Record>(&self, expr: &FilterExpr) -> Result<u64> {
+        if let Some(filters) = expr.clone().flattened().as_conjunction() {
+            return self.count::<T>(&filters);
+        }
+        self.count_records::<T>(Self::expr_clause(expr)?)
+    }
+
+    fn count_records<T: Record>(&self, clause: (String, Vec<Box<dyn rusqlite::ToSql>>)) -> Result<u64> {
+        let timer = self.metrics.start();
+        let collection = T::collection_name();
+        let (filter_sql, filter_params) = clause;
+        let query = format!("SELECT COUNT(*) FROM records r WHERE r.collection = ?1{}", filter_sql);
+
+        let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+        params.push(Box::new(collection.to_string()));
+        params.extend(filter_params);
+        let params_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+        let count: i64 = self.db.query_row(&query, params_refs.as_slice(), |row| row.get(0))?;
+
+        self.metrics.record(Op::Read, Some(collection), timer);
+        Ok(count as u64)
+    }
+
+    /// Iterate over records matching `filters` without loading them all at once
+    ///
+    /// Yields records in the same order as `list` (most recently updated first),
+    /// fetching them from SQLite in pages of `LIST_ITER_PAGE_SIZE`, so peak memory
+    /// stays bounded regardless of result size. The iterator borrows the store for
+    /// its whole lifetime, which rules out writes while iterating; no statement or
+    /// file handle is held between pages. A deserialization or query error is
+    /// yielded once and ends the iteration.
+    pub fn list_iter<T: Record>(&self, filters: &[Filter]) -> Result<ListIter<'_, T>> {
+        // Validate eagerly so a bad filter fails here rather than on first `next()`
+        Self::filter_clause(filters, &[])?;
+        let composites = self.ready_composites::<T>(filters)?;
+
+        Ok(ListIter {
+            store: self,
+            filters: filters.to_vec(),
+            composites,
+            buffer: VecDeque::new(),
+            last_key: None,
+            done: false,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Fetch the next page of `(updated_at, id, data_json)` rows for a `ListIter`
+    fn list_page(
+        &self,
+        collection: &str,
+        filters: &[Filter],
+        composites: &[&'static [&'static str]],
+        after: Option<&(i64, String)>,
+    ) -> Result<Vec<(i64, String, String)>> {
+        let (filter_sql, filter_params) = Self::filter_clause(filters, composites)?;
+        let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+        params.push(Box::new(collection.to_string()));
+        params.extend(filter_params);
+
+        // Keyset pagination on (updated_at DESC, id ASC)
+        let mut keyset_sql = String::new();
+        if let Some((updated_at, id)) = after {
+            params.push(Box::new(*updated_at));
+            params.push(Box::new(id.clone()));
+            keyset_sql = format!(
+                " AND (r.updated_at < ?{ts} OR (r.updated_at = ?{ts} AND r.id > ?{id}))",
+                ts = params.len() - 1,
+                id = params.len()
+            );
+        }
+
+        let query = format!(
+            "SELECT r.updated_at, r.id, r.data_json
+             FROM records r
+             WHERE r.collection = ?1{}{}
+             ORDER BY r.updated_at DESC, r.id ASC
+             LIMIT {}",
+            filter_sql, keyset_sql, LIST_ITER_PAGE_SIZE
+        );
+
+        let mut stmt = self.db.prepare(&query)?;
+        let params_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+        let rows = stmt.query_map(params_refs.as_slice(), |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })?;
+
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Iterate over records matching `filters` one SQLite row at a time
+    ///
+    /// Yields records in the same order as `list`, deserializing each as it is
+    /// stepped to, over a single statement that lives as long as the iterator.
+    /// Unlike `list_iter`, that statement keeps a read lock on the database
+    /// until the iterator is dropped: the records come from one consistent
+    /// snapshot, but writes from other connections wait on the lock and may
+    /// fail with `StoreError::Locked` once `busy_timeout_ms` runs out. Writes
+    /// through this store are ruled out by the borrow. A deserialization or
+    /// query error is yielded once and ends the iteration.
+    pub fn iter<T: Record>(&self, filters: &[Filter]) -> Result<RowIter<'_, T>> {
+        let composites = self.ready_composites::<T>(filters)?;
+        let (filter_sql, filter_params) = Self::filter_clause(filters, &composites)?;
+        let query = format!(
+            "SELECT r.data_json
+             FROM records r
+             WHERE r.collection = ?1{}
+             ORDER BY r.updated_at DESC, r.id ASC",
+            filter_sql
+        );
+
+        let mut stmt = self.db.prepare(&query)?;
+        stmt.raw_bind_parameter(1, T::collection_name())?;
+        for (i, param) in filter_params.iter().enumerate() {
+            stmt.raw_bind_parameter(i + 2, param.as_ref())?;
+        }
+
+        Ok(RowIter {
+            rows: RowCursorBuilder {
+                stmt,
+                rows_builder: |stmt| stmt.raw_query(),
+            }
+            .build(),
+            done: false,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Get the most recently updated record matching `filters`
+    ///
+    /// With `order_field`, returns the record with the highest value of that
+    /// indexed field instead. Same as `last` by `order_field` or `updated_at`.
+    pub fn latest<T: Record>(&self, filters: &[Filter], order_field: Option<&str>) -> Result<Option<T>> {
+        self.last(filters, order_field.unwrap_or("updated_at"))
+    }
+
+    /// Get the least recently updated record matching `filters`
+    ///
+    /// With `order_field`, returns the record with the lowest value of that
+    /// indexed field instead. Same as `first` by `order_field` or `updated_at`.
+    pub fn earliest<T: Record>(&self, filters: &[Filter], order_field: Option<&str>) -> Result<Option<T>> {
+        self.first(filters, order_field.unwrap_or("updated_at"))
+    }
+
+    /// List records with `updated_at >= since_ms`, most recently updated first
+    ///
+    /// `updated_at` can also be used in any `Filter` without being indexed.
+    pub fn list_updated_since<T: Record>(&self, since_ms: i64) -> Result<Vec<T>> {
+        self.list(Query::new().gte("updated_at", since_ms).filters())
+    }
+
+    /// List records with `start_ms <= updated_at < end_ms`, most recently updated first
+    pub fn list_updated_between<T: Record>(&self, start_ms: i64, end_ms: i64) -> Result<Vec<T>> {
+        self.list(
+            Query::new()
+                .gte("updated_at", start_ms)
+                .lt("updated_at", end_ms)
+                .filters(),
+        )
+    }
+
+    /// Run several reads against one consistent snapshot of the database
+    ///
+    /// All reads made through the `Batch` happen in a single read transaction,
+    /// so a page that lists several collections sees them as of one moment
+    /// rather than interleaved with other `Store`s' writes. The closure returns whatever bundle of
+    /// results it needs:
+    ///
+    /// ```ignore
+    /// let (users, active) = store.batch(|b| {
+    ///     Ok((b.list::<User>(&[])?, b.query::<Task>(&Query::new().eq("status", "active").limit(5))?))
+    /// })?;
+    /// ```
+    ///
+    /// Writes from other `Store`s wait for the batch to finish (up to
+    /// `busy_timeout_ms`), so keep the closure to reads. Fails if the
+    /// connection is already inside a transaction.
+    pub fn batch<R>(&self, f: impl FnOnce(&Batch<'_>) -> Result<R>) -> Result<R> {
+        let tx = self.db.unchecked_transaction()?;
+        let result = f(&Batch { store: self })?;
+        tx.commit()?;
+        Ok(result)
+    }
+
+    /// Run several writes as one atomic unit
+    ///
+    /// The `Txn`'s writes go to SQLite inside a single write transaction, and
+    /// their JSONL lines are buffered: they are only appended once the closure
+    /// returns `Ok`, just before the commit. If the closure fails (or panics),
+    /// SQLite is rolled back and the buffered lines are discarded, so a plan and
+    /// its tasks are either all written or not at all:
+    ///
+    /// ```ignore
+    /// store.transaction(|txn| {
+    ///     txn.create(plan)?;
+    ///     for task in tasks {
+    ///         txn.create(task)?;
+    ///     }
+    ///     Ok(())
+    /// })?;
+    /// ```
+    ///
+    /// Reads through the `Txn` see its own writes. Other `Store`s' writes wait
+    /// for the transaction (up to `busy_timeout_ms`), so keep the closure short.
+    /// `on_change` callbacks run once everything has committed.
+    pub fn transaction<R>(&mut self, f: impl FnOnce(&mut Txn<'_>) -> Result<R>) -> Result<R> {
+        self.ensure_writable()?;
+        self.hooks.take_changes();
+        let result = self.run_transaction(f);
+        self.finish_write(result.is_ok());
+        let (output, written) = result?;
+        for (collection, op) in written {
+            self.metrics.count(op.into(), &collection);
+        }
+        Ok(output)
+    }
+
+    /// The body of `transaction`, returning the closure's output and the collection and kind of each write
+    fn run_transaction<R>(&self, f: impl FnOnce(&mut Txn<'_>) -> Result<R>) -> Result<(R, Vec<(String, WriteOp)>)> {
+        let mut attempt = 0;
+        let tx = loop {
+            match rusqlite::Transaction::new_unchecked(&self.db, TransactionBehavior::Immediate)
+                .map_err(StoreError::from)
+            {
+                Err(e @ StoreError::Locked(_)) if attempt < BUSY_RETRIES => {
+                    attempt += 1;
+                    debug!(attempt, error = %e, "Database busy, retrying");
+                    std::thread::sleep(std::time::Duration::from_millis(10 << attempt));
+                }
+                result => break result?,
+            }
+        };
+        let mut txn = Txn {
+            store: self,
+            tx,
+            writes: Vec::new(),
+        };
+        let output = f(&mut txn)?;
+        let Txn { tx, writes, .. } = txn;
+
+        // One append per file, in the order the files were first written
+        let mut files: Vec<(PathBuf, Vec<&str>)> = Vec::new();
+        for write in &writes {
+            let Some(path) = self.jsonl_path(&write.collection) else {
+                continue;
+            };
+            match files.iter_mut().find(|(p, _)| *p == path) {
+                Some((_, lines)) => lines.push(&write.line),
+                None => files.push((path, vec![&write.line])),
+            }
+        }
+
+        let mut appended = Vec::new();
+        let commit = || {
+            for (path, lines) in &files {
+                appended.push(jsonl::append_line(path, &lines.join("\n"), &self.fsync)?);
+            }
+            for write in &writes {
+                self.hooks
+                    .after_line(&write.collection, &write.id, write.op, &write.line)?;
+            }
+            tx.commit()?;
+            Ok(())
+        };
+        let result = commit();
+        Self::undo_appends_on_error(result, appended)?;
+
+        Ok((output, writes.into_iter().map(|w| (w.collection, w.op)).collect()))
+    }
+
+    /// List records whose `time_field` is in `from <= t < to`, oldest first
+    ///
+    /// The canonical event-log read: `filters` narrow it down (e.g. equality
+    /// on `entity_id`) and the range bounds `time_field`, which must be indexed
+    /// as an `Int` (or be `updated_at`). The start is inclusive and the end
+    /// exclusive, so adjacent windows don't overlap; pass `i64::MIN` and
+    /// `i64::MAX` for an open range. Ties on the time field are ordered by ID.
+    /// A composite index on the filter fields followed by `time_field` lets
+    /// SQLite answer it from the index.
+    pub fn list_timeline<T: Record>(&self, filters: &[Filter], time_field: &str, from: i64, to: i64) -> Result<Vec<T>> {
+        let query = Query::new()
+            .gte(time_field, from)
+            .lt(time_field, to)
+            .order_by(time_field);
+        let mut all_filters = filters.to_vec();
+        all_filters.extend_from_slice(query.filters());
+        self.list_with(&all_filters, query.options())
+    }
+
+    /// Count records grouped by the value of an indexed field
+    ///
+    /// Returns `(value, count)` pairs sorted by count descending (ties by value).
+    /// Records that don't index the field are not counted. Fails if the
+    /// collection has records but none of them index `field`.
+    pub fn count_by<T: Record>(&self, field: &str) -> Result<Vec<(IndexValue, usize)>> {
+        let collection = T::collection_name();
+        Self::validate_field_name(field)?;
+
+        let counts: Vec<(IndexValue, usize)> = {
+            let mut stmt = self.db.prepare(
+                "SELECT i.field_value_str, i.field_value_int, i.field_value_real, i.field_value_bool, COUNT(*) AS n
+                 FROM record_indexes i
+                 JOIN records r ON r.collection = i.collection AND r.id = i.id
+                 WHERE i.collection = ?1 AND i.field_name = ?2
+                 GROUP BY i.field_value_str, i.field_value_int, i.field_value_real, i.field_value_bool
+                 ORDER BY n DESC, i.field_value_str, i.field_value_int, i.field_value_real, i.field_value_bool",
+            )?;
+            let rows = stmt.query_map(rusqlite::params![collection, field], |row| {
+                let value = Self::index_value_from_columns(row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?);
+                Ok((value, row.get::<_, i64>(4)? as usize))
+            })?;
+
+            let mut counts = Vec::new();
+            for row in rows {
+                let (value, count) = row?;
+                if let Some(value) = value {
+                    counts.push((value, count));
+                }
+            }
+            counts
+        };
+
+        if counts.is_empty() {
+            let has_records: bool = self.db.query_row(
+                "SELECT EXISTS (SELECT 1 FROM records WHERE collection = ?1)",
+                [collection],
+                |row| row.get(0),
+            )?;
+            if has_records {
+                return Err(StoreError::Schema(format!(
+                    "Field '{}' is not indexed for collection '{}'",
+                    field, collection
+                )));
+            }
+        }
+
+        Ok(counts)
+    }
+
+    /// Aggregate a numeric indexed field over the records matching `filters`
+    ///
+    /// `Sum`, `Avg`, `Min`, and `Max` require an `Int` or `Float` field and fail if the field
+    /// holds `String` or `Bool` values; they return `None` when no matching record
+    /// indexes the field. `Count` works on any field type and counts matching
+    /// records that index it, returning `Some(0.0)` when there are none.
+    pub fn aggregate<T: Record>(&self, field: &str, agg: Agg, filters: &[Filter]) -> Result<Option<f64>> {
+        let collection = T::collection_name();
+        Self::validate_field_name(field)?;
+
+        if agg != Agg::Count {
+            let non_numeric: bool = self.db.query_row(
+                "SELECT EXISTS (
+                     SELECT 1 FROM record_indexes
+                     WHERE collection = ?1 AND field_name = ?2
+                       AND (field_value_str IS NOT NULL OR field_value_bool IS NOT NULL)
+                 )",
+                rusqlite::params![collection, field],
+                |row| row.get(0),
+            )?;
+            if non_numeric {
+                return Err(StoreError::Schema(format!(
+                    "Cannot {} non-numeric field '{}' in collection '{}'",
+                    agg.to_sql(),
+                    field,
+                    collection
+                )));
+            }
+        }
+
+        let (filter_sql, filter_params) = Self::filter_clause(filters, &self.ready_composites::<T>(filters)?)?;
+        let field_param = filter_params.len() + 2;
+        let query = format!(
+            "SELECT {}(COALESCE(a.field_value_int, a.field_value_real)), COUNT(a.id)
+             FROM records r
+             JOIN record_indexes a ON a.collection = r.collection AND a.id = r.id
+             WHERE r.collection = ?1{} AND a.field_name = ?{}",
+            agg.to_sql(),
+            filter_sql,
+            field_param
+        );
+
+        let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+        params.push(Box::new(collection.to_string()));
+        params.extend(filter_params);
+        params.push(Box::new(field.to_string()));
+        let params_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+
+        let (value, count): (Option<f64>, i64) = self
+            .db
+            .query_row(&query, params_refs.as_slice(), |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+        Ok(match agg {
+            Agg::Count => Some(count as f64),
+            _ => value,
+        })
+    }
+
+    /// Find records whose foreign-key field doesn't resolve to an existing parent
+    ///
+    /// Returns the IDs (sorted) of records in `child_collection` whose `fk_field`
+    /// holds an ID with no matching record in `parent_collection`. The field is
+    /// read from the stored JSON rather than the index, so it works right after
+    /// `sync()` before indexes are rebuilt. Records where the field is missing
+    /// or null are not considered orphans.
+    pub fn find_orphans(&self, child_collection: &str, fk_field: &str, parent_collection: &str) -> Result<Vec<String>> {
+        Self::validate_collection_name(child_collection)?;
+        Self::validate_collection_name(parent_collection)?;
+        Self::validate_field_name(fk_field)?;
+
+        let mut stmt = self.db.prepare(
+            "SELECT c.id FROM records c
+             WHERE c.collection = ?1
+               AND json_extract(c.data_json, '$.' || ?2) IS NOT NULL
+               AND NOT EXISTS (
+                   SELECT 1 FROM records p
+                   WHERE p.collection = ?3
+                     AND p.id = CAST(json_extract(c.data_json, '$.' || ?2) AS TEXT)
+               )
+             ORDER BY c.id",
+        )?;
+
+        let rows = stmt.query_map(
+            rusqlite::params![child_collection, fk_field, parent_collection],
+            |row| row.get::<_, String>(0),
+        )?;
+
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    // ========================================================================
+    // Helper methods
+    // ========================================================================
+
+    /// Path of a collection's JSONL file, or `None` for an in-memory store
+    fn jsonl_path(&self, collection: &str) -> Option<PathBuf> {
+        if self.in_memory {
+            return None;
+        }
+        Some(Self::jsonl_file(&self.jsonl_dir, collection))
+    }
+
+    /// Path of a collection's JSONL file in `jsonl_dir`
+    fn jsonl_file(jsonl_dir: &Path, collection: &str) -> PathBuf {
+        jsonl_dir.join(format!("{}.jsonl", collection))
+    }
+
+    /// Append a raw JSON line to a collection's JSONL file, `None` for an in-memory store
+    fn append_jsonl_raw(&self, collection: &str, value: &serde_json::Value) -> Result<Option<JsonlAppend>> {
+        let Some(jsonl_path) = self.jsonl_path(collection) else {
+            return Ok(None);
+        };
+        Ok(Some(jsonl::append_line(
+            &jsonl_path,
+            &jsonl::to_canonical_json(value)?,
+            &self.fsync,
+        )?))
+    }
+
+    /// Run a SQLite write, retrying with backoff on transient SQLITE_BUSY/SQLITE_LOCKED
+    ///
+    /// `busy_timeout` already makes SQLite wait on locks, but some contention (e.g. a
+    /// lock upgrade that would deadlock) is reported immediately; retrying keeps brief
+    /// contention from surfacing as an error to callers.
+    ///
+    /// Changes recorded by a successful write are then passed to the `on_change`
+    /// callbacks; those of a failed attempt are dropped.
+    fn with_busy_retry<T>(&mut self, mut f: impl FnMut(&mut Connection) -> Result<T>) -> Result<T> {
+        let mut attempt = 0;
+        let result = loop {
+            self.hooks.take_changes();
+            match f(&mut self.db) {
+                Err(e @ StoreError::Locked(_)) if attempt < BUSY_RETRIES => {
+                    attempt += 1;
+                    debug!(attempt, error = %e, "Database busy, retrying");
+                    std::thread::sleep(std::time::Duration::from_millis(10 << attempt));
+                }
+                result => break result,
+            }
+        };
+
+        self.finish_write(result.is_ok());
+        result
+    }
+
+    /// Pass a write's recorded changes to the `on_change` callbacks if it committed, then rotate oversized files
+    fn finish_write(&self, committed: bool) {
+        let changes = self.hooks.take_changes();
+        if committed {
+            for change in &changes {
+                for callback in &self.change_callbacks {
+                    if let Err(e) = callback(change) {
+                        warn!(collection = change.collection, id = change.id, error = %e, "Change callback failed");
+                    }
+                }
+            }
+        }
+        // After the write, so a rotation never moves lines a failed write undoes
+        for path in self.fsync.take_oversized() {
+            if let Err(e) = jsonl::rotate_jsonl(&path) {
+                warn!(file = ?path, error = %e, "Failed to rotate JSONL file");
+            }
+        }
+    }
+
+    /// Fail with `StoreError::Validation` if a reference of `record` matches no record
+    ///
+    /// Each reference is a `(field, collection, key)` as in `Reference`; the
+    /// caller leaves out those allowed to dangle.
+    fn check_references<'a>(
+        conn: &Connection,
+        collection: &str,
+        id: &str,
+        record: &serde_json::Value,
+        references: impl IntoIterator<Item = (&'a str, &'a str, &'a str)>,
+    ) -> Result<()> {
+        for (field, target, key) in references {
+            let value = &record[field];
+            if value.is_null() || Self::reference_target(conn, target, key, value)?.is_some() {
+                continue;
+            }
+            return Err(ValidationError::new(format!(
+                "{}/{}: {} {} refers to no record in {} by {}",
+                collection, id, field, value, target, key
+            ))
+            .into());
+        }
+        Ok(())
+    }
+
+    /// ID of the record in `collection` whose `key` is `value`, if there is one
+    fn reference_target(
+        conn: &Connection,
+        collection: &str,
+        key: &str,
+        value: &serde_json::Value,
+    ) -> Result<Option<String>> {
+        if key == "id" {
+            let id = match value {
+                serde_json::Value::String(s) => s.clone(),
+                serde_json::Value::Number(n) => n.to_string(),
+                _ => return Ok(None),
+            };
+            return Ok(conn
+                .query_row(
+                    "SELECT id FROM records WHERE collection = ?1 AND id = ?2",
+                    rusqlite::params![collection, id],
+                    |row| row.get(0),
+                )
+                .optional()?);
+        }
+
+        let (column, param): (&str, Box<dyn rusqlite::ToSql>) = match value {
+            serde_json::Value::String(s) => ("field_value_str", Box::new(s.clone())),
+            serde_json::Value::Bool(b) => ("field_value_bool", Box::new(*b as i64)),
+            serde_json::Value::Number(n) => match (n.as_i64(), n.as_f64()) {
+                (Some(i), _) => ("field_value_int", Box::new(i)),
+                (None, Some(x)) => ("field_value_real", Box::new(x)),
+                (None, None) => return Ok(None),
+            },
+            _ => return Ok(None),
+        };
+        Ok(conn
+            .query_row(
+                &format!(
+                    "SELECT id FROM record_indexes
+                     WHERE collection = ?1 AND field_name = ?2 AND {} = ?3
+                     ORDER BY id LIMIT 1",
+                    column
+                ),
+                rusqlite::params![collection, key, param],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    /// Fail with `DuplicateKeyError` if another record in the collection has the
+    /// same indexed value for any of the unique fields
+    fn check_unique_tx(
+        tx: &rusqlite::Transaction,
+        collection: &str,
+        id: &str,
+        fields: &HashMap<String, IndexValue>,
+        unique_fields: &[&str],
+    ) -> Result<()> {
+        for field in unique_fields {
+            // Absent values (e.g. an unset optional field) can't collide
+            let Some(value) = fields.get(*field) else {
+                continue;
+            };
+
+            let (column, param): (&str, Box<dyn rusqlite::ToSql>) = match value {
+                IndexValue::String(s) => ("field_value_str", Box::new(s.clone())),
+                IndexValue::Int(i) | IndexValue::DateTime(i) => ("field_value_int", Box::new(*i)),
+                IndexValue::Float(x) => ("field_value_real", Box::new(*x)),
+                IndexValue::Bool(b) => ("field_value_bool", Box::new(*b as i64)),
+                IndexValue::Range(..) => continue,
+            };
+
+            let existing_id: Option<String> = tx
+                .query_row(
+                    &format!(
+                        "SELECT id FROM record_indexes
+                         WHERE collection = ?1 AND field_name = ?2 AND {} = ?3 AND id != ?4
+                         LIMIT 1",
+                        column
+                    ),
+                    rusqlite::params![collection, field, param, id],
+                    |row| row.get(0),
+                )
+                .optional()?;
+
+            if let Some(existing_id) = existing_id {
+                return Err(DuplicateKeyError {
+                    collection: collection.to_string(),
+                    field: field.to_string(),
+                    value: value.to_string(),
+                    existing_id,
+                }
+                .into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Fail if a record indexes a field as `IndexValue::Range`, which is only a filter value
+    fn check_indexable(collection: &str, id: &str, fields: &HashMap<String, IndexValue>) -> Result<()> {
+        match fields.iter().find(|(_, value)| matches!(value, IndexValue::Range(..))) {
+            Some((field, value)) => Err(StoreError::Schema(format!(
+                "Field '{}' of {}/{} is indexed as the range {}; ranges are only filter values",
+                field, collection, id, value
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    fn update_indexes_tx(
+        tx: &rusqlite::Transaction,
+        collection: &str,
+        id: &str,
+        fields: &HashMap<String, IndexValue>,
+        composites: &[&[&str]],
+    ) -> Result<()> {
+        debug!(collection, id, field_count = fields.len(), "update_indexes_tx: called");
+        Self::check_indexable(collection, id, fields)?;
+
+        // Delete old indexes
+        tx.execute(
+            "DELETE FROM record_indexes WHERE collection = ?1 AND id = ?2",
+            rusqlite::params![collection, id],
+        )?;
+
+        // Insert new indexes
+        for (field_name, value) in fields {
+            debug!(collection, id, field_name, ?value, "update_indexes_tx: inserting index");
+            Self::validate_field_name(field_name)?;
+
+            match value {
+                IndexValue::String(s) => {
+                    tx.execute(
+                        "INSERT INTO record_indexes (collection, id, field_name, field_value_str, field_value_int, field_value_bool)
+                         VALUES (?1, ?2, ?3, ?4, NULL, NULL)",
+                        rusqlite::params![collection, id, field_name, s],
+                    )?;
+                }
+                IndexValue::Int(i) | IndexValue::DateTime(i) => {
+                    tx.execute(
+                        "INSERT INTO record_indexes (collection, id, field_name, field_value_str, field_value_int, field_value_bool)
+                         VALUES (?1, ?2, ?3, NULL, ?4, NULL)",
+                        rusqlite::params![collection, id, field_name, i],
+                    )?;
+                }
+                IndexValue::Bool(b) => {
+                    tx.execute(
+                        "INSERT INTO record_indexes (collection, id, field_name, field_value_str, field_value_int, field_value_bool)
+                         VALUES (?1, ?2, ?3, NULL, NULL, ?4)",
+                        rusqlite::params![collection, id, field_name, *b as i64],
+                    )?;
+                }
+                // NaN compares unequal to everything, so it isn't indexed at all
+                IndexValue::Float(x) if x.is_nan() => {}
+                // Rejected by `check_indexable`
+                IndexValue::Range(..) => {}
+                IndexValue::Float(x) => {
+                    tx.execute(
+                        "INSERT INTO record_indexes (collection, id, field_name, field_value_real)
+                         VALUES (?1, ?2, ?3, ?4)",
+                        rusqlite::params![collection, id, field_name, x],
+                    )?;
+                }
+            }
+            tx.prepare_cached("INSERT OR IGNORE INTO indexed_field_names (collection, field_name) VALUES (?1, ?2)")?
+                .execute(rusqlite::params![collection, field_name])?;
+        }
+
+        tx.execute(
+            "DELETE FROM record_composite_indexes WHERE collection = ?1 AND id = ?2",
+            rusqlite::params![collection, id],
+        )?;
+        Self::update_composites_tx(tx, collection, id, fields, composites)
+    }
+
+    /// Rewrite a record's rows in `composites`; a field the record doesn't index is stored as NULL
+    ///
+    /// Rows in other composites are left alone.
+    fn update_composites_tx(
+        tx: &rusqlite::Transaction,
+        collection: &str,
+        id: &str,
+        fields: &HashMap<String, IndexValue>,
+        composites: &[&[&str]],
+    ) -> Result<()> {
+        for composite in composites {
+            Self::validate_composite(composite)?;
+            tx.execute(
+                "DELETE FROM record_composite_indexes WHERE collection = ?1 AND id = ?2 AND index_name = ?3",
+                rusqlite::params![collection, id, composite.join(",")],
+            )?;
+
+            let mut values: Vec<Box<dyn rusqlite::ToSql>> = composite
+                .iter()
+                .map(|field| Self::composite_value(fields.get(*field)))
+                .collect();
+            values.resize_with(MAX_COMPOSITE_FIELDS, || Box::new(rusqlite::types::Null));
+
+            let mut params: Vec<Box<dyn rusqlite::ToSql>> = vec![
+                Box::new(collection.to_string()),
+                Box::new(composite.join(",")),
+                Box::new(id.to_string()),
+            ];
+            params.extend(values);
+            let params_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+            tx.execute(
+                "INSERT INTO record_composite_indexes (collection, index_name, id, v0, v1, v2, v3)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params_refs.as_slice(),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Index existing records under any of `T`'s composites that aren't complete yet
+    ///
+    /// A composite is only used for queries once every record has a row in it,
+    /// so the first write after one is declared indexes the whole collection.
+    fn backfill_composites_tx<T: Record>(tx: &rusqlite::Transaction) -> Result<()> {
+        let collection = T::collection_name();
+
+        for composite in T::composite_indexes() {
+            let index_name = composite.join(",");
+            let ready: bool = tx.query_row(
+                "SELECT EXISTS (SELECT 1 FROM composite_index_meta WHERE collection = ?1 AND index_name = ?2)",
+                rusqlite::params![collection, index_name],
+                |row| row.get(0),
+            )?;
+            if ready {
+                continue;
+            }
+
+            let records_data: Vec<(String, String)> = {
+                let mut stmt = tx.prepare("SELECT id, data_json FROM records WHERE collection = ?1")?;
+                let rows = stmt.query_map([collection], |row| Ok((row.get(0)?, row.get(1)?)))?;
+                rows.collect::<rusqlite::Result<_>>()?
+            };
+            debug!(
+                collection,
+                index_name,
+                records = records_data.len(),
+                "Backfilling composite index"
+            );
+
+            for (id, data_json) in records_data {
+                match serde_json::from_str::<T>(&data_json) {
+                    Ok(record) => {
+                        Self::update_composites_tx(tx, collection, &id, &index_fields(&record), &[composite])?
+                    }
+                    Err(e) => warn!(collection, id = &id, error = ?e, "Skipping record that doesn't match type"),
+                }
+            }
+
+            tx.execute(
+                "INSERT OR IGNORE INTO composite_index_meta (collection, index_name) VALUES (?1, ?2)",
+                rusqlite::params![collection, index_name],
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// The subset of `T`'s composites that are complete and could serve `filters`
+    fn ready_composites<T: Record>(&self, filters: &[Filter]) -> Result<Vec<&'static [&'static str]>> {
+        let collection = T::collection_name();
+        let mut ready = Vec::new();
+        if filters.len() < 2 {
+            return Ok(ready);
+        }
+
+        for composite in T::composite_indexes() {
+            let exists: bool = self.db.query_row(
+                "SELECT EXISTS (SELECT 1 FROM composite_index_meta WHERE collection = ?1 AND index_name = ?2)",
+                rusqlite::params![collection, composite.join(",")],
+                |row| row.get(0),
+            )?;
+            if exists {
+                ready.push(*composite);
+            }
+        }
+        Ok(ready)
+    }
+
+    fn validate_composite(fields: &[&str]) -> Result<()> {
+        if fields.len() < 2 || fields.len() > MAX_COMPOSITE_FIELDS {
+            return Err(StoreError::Schema(format!(
+                "Composite index must have 2 to {} fields, got {:?}",
+                MAX_COMPOSITE_FIELDS, fields
+            )));
+        }
+        for field in fields {
+            Self::validate_field_name(field)?;
+        }
+        Ok(())
+    }
+
+    /// Value stored in (or compared against) a composite index column
+    fn composite_value(value: Option<&IndexValue>) -> Box<dyn rusqlite::ToSql> {
+        match value {
+            Some(IndexValue::String(s)) => Box::new(s.clone()),
+            Some(IndexValue::Int(i) | IndexValue::DateTime(i)) => Box::new(*i),
+            // SQLite binds NaN as NULL, which no comparison matches
+            Some(IndexValue::Float(x)) => Box::new(*x),
+            Some(IndexValue::Bool(b)) => Box::new(*b as i64),
+            Some(IndexValue::Range(..)) | None => Box::new(rusqlite::types::Null),
+        }
+    }
+
+    /// Pick the composite index covering the longest prefix of fields constrained by `filters`
+    ///
+    /// Every field of the prefix but the last needs an `Eq` filter; the last may
+    /// also be a range. Returns the composite and, per prefix field, the filter
+    /// to apply. Prefixes shorter than two fields are no better than the
+    /// single-field indexes and are ignored.
+    fn match_composite<'a>(
+        filters: &'a [Filter],
+        composites: &[&'static [&'static str]],
+    ) -> Option<(&'static [&'static str], Vec<&'a Filter>)> {
+        let mut best: Option<(&'static [&'static str], Vec<&'a Filter>)> = None;
+
+        for composite in composites {
+            let mut prefix = Vec::new();
+            for field in composite.iter() {
+                let on_field = || filters.iter().filter(move |f| f.field == *field);
+                if let Some(eq) = on_field().find(|f| f.op == FilterOp::Eq) {
+                    prefix.push(eq);
+                    continue;
+                }
+                if let Some(range) =
+                    on_field().find(|f| matches!(f.op, FilterOp::Gt | FilterOp::Gte | FilterOp::Lt | FilterOp::Lte))
+                {
+                    prefix.push(range);
+                }
+                break;
+            }
+
+            if prefix.len() >= 2 && best.as_ref().is_none_or(|(_, b)| prefix.len() > b.len()) {
+                best = Some((composite, prefix));
+            }
+        }
+
+        best
+    }
+
+    /// Build the ` AND EXISTS (...)` conditions that apply `filters` to records aliased `r`
+    ///
+    /// Parameters are numbered from `?2` (`?1` is reserved for the collection).
+    /// Returns the SQL fragment and the parameters to bind after the collection.
+    ///
+    /// When one of `composites` matches the filters (see `match_composite`), an extra
+    /// ` AND r.id IN (...)` condition lets SQLite narrow candidates through the
+    /// compound index. The per-field conditions are kept, so results are identical.
+    fn filter_clause(
+        filters: &[Filter],
+        composites: &[&'static [&'static str]],
+    ) -> Result<(String, Vec<Box<dyn rusqlite::ToSql>>)> {
+        let mut query = String::new();
+        let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+
+        for filter in filters {
+            query.push_str(" AND ");
+            query.push_str(&Self::filter_condition(filter, &mut params)?);
+        }
+
+        if let Some((composite, prefix)) = Self::match_composite(filters, composites) {
+            Self::validate_composite(composite)?;
+
+            params.push(Box::new(composite.join(",")));
+            query.push_str(&format!(
+                " AND r.id IN (
+                    SELECT c.id FROM record_composite_indexes c
+                    WHERE c.collection = ?1 AND c.index_name = ?{}",
+                params.len() + 1
+            ));
+            for (slot, filter) in prefix.iter().enumerate() {
+                params.push(Self::composite_value(Some(&filter.value)));
+                query.push_str(&format!(
+                    " AND c.v{} {} ?{}",
+                    slot,
+                    filter.op.to_sql(),
+                    params.len() + 1
+                ));
+            }
+            query.push(')');
+        }
+
+        Ok((query, params))
+    }
+
+    /// SQL condition applying one filter to records aliased `r`, pushing its parameters
+    ///
+    /// Parameters are numbered after those already in `params`, counting `?1`
+    /// (the collection) as taken.
+    fn filter_condition(filter: &Filter, params: &mut Vec<Box<dyn rusqlite::ToSql>>) -> Result<String> {
+        Self::validate_field_name(&filter.field)?;
+
+        if filter.op.requires_string() && !matches!(filter.value, IndexValue::String(_)) {
+            return Err(StoreError::Schema(format!(
+                "Filter {} on '{}' requires a string value, got {:?}",
+                filter.op, filter.field, filter.value
+            )));
+        }
+
+        let bounds = match (&filter.value, filter.op) {
+            (IndexValue::Range(low, high), FilterOp::Between)
+                if IndexKind::of(low) == IndexKind::of(high)
+                    && matches!(
+                        **low,
+                        IndexValue::String(_) | IndexValue::Int(_) | IndexValue::DateTime(_) | IndexValue::Float(_)
+                    ) =>
+            {
+                Some((low.as_ref(), high.as_ref()))
+            }
+            (IndexValue::Range(..), _) | (_, FilterOp::Between) => {
+                return Err(StoreError::Schema(format!(
+                    "Filter {} on '{}' needs a range of two Int, Float, or String values, got {:?}",
+                    filter.op, filter.field, filter.value
+                )));
+            }
+            _ => None,
+        };
+
+        // NaN matches nothing, like a NaN comparison in Rust
+        let values = bounds.map_or(vec![&filter.value], |(low, high)| vec![low, high]);
+        if values
+            .iter()
+            .any(|value| matches!(value, IndexValue::Float(x) if x.is_nan()))
+        {
+            return Ok("0".to_string());
+        }
+
+        // `?1` is the collection, so the next parameter pushed is `?{len + 2}`
+        let value_param = params.len() + 2;
+        for value in values {
+            match value {
+                // LIKE wildcards in the needle match literally
+                IndexValue::String(s) if matches!(filter.op, FilterOp::ContainsIgnoreCase | FilterOp::StartsWith) => {
+                    params.push(Box::new(
+                        s.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_"),
+                    ))
+                }
+                value => params.push(Self::composite_value(Some(value))),
+            }
+        }
+        let operand = match bounds {
+            Some(_) => format!("BETWEEN ?{} AND ?{}", value_param, value_param + 1),
+            None => format!("{} ?{}", filter.op.to_sql(), value_param),
+        };
+
+        // Every record's updated_at is already a column of `records`, so
+        // time filters work without the field being in `indexed_fields`
+        if filter.field == "updated_at"
+            && IndexKind::of(&filter.value) == IndexKind::Int
+            && matches!(
+                filter.op,
+                FilterOp::Eq
+                    | FilterOp::Ne
+                    | FilterOp::Gt
+                    | FilterOp::Gte
+                    | FilterOp::Lt
+                    | FilterOp::Lte
+                    | FilterOp::Between
+            )
+        {
+            return Ok(format!("r.updated_at {}", operand));
+        }
+
+        params.push(Box::new(filter.field.clone()));
+        let name_param = params.len() + 1;
+        // Aliases only need to be unique within one query
+        let alias = format!("idx{}", name_param);
+        let column = match IndexKind::of(&filter.value) {
+            IndexKind::String => "field_value_str",
+            IndexKind::Int => "field_value_int",
+            IndexKind::Float => "field_value_real",
+            IndexKind::Bool => "field_value_bool",
+        };
+        let comparison = match filter.op {
+            FilterOp::EqIgnoreCase | FilterOp::NeIgnoreCase => format!(
+                "{}.{} {} ?{} COLLATE NOCASE",
+                alias,
+                column,
+                filter.op.to_sql(),
+                value_param
+            ),
+            FilterOp::ContainsIgnoreCase => {
+                format!("{}.{} LIKE '%' || ?{} || '%' ESCAPE '\\'", alias, column, value_param)
+            }
+            FilterOp::StartsWith => format!("{}.{} LIKE ?{} || '%' ESCAPE '\\'", alias, column, value_param),
+            _ => format!("{}.{} {}", alias, column, operand),
+        };
+
+        Ok(format!(
+            "EXISTS (
+                SELECT 1 FROM record_indexes {a}
+                WHERE {a}.collection = r.collection
+                  AND {a}.id = r.id
+                  AND {a}.field_name = ?{n}
+                  AND {c})",
+            a = alias,
+            n = name_param,
+            c = comparison
+        ))
+    }
+
+    /// Build the ` AND (...)` condition that applies `expr` to records aliased `r`
+    ///
+    /// Parameters are numbered as in `filter_clause`. Nested groups of the same
+    /// kind are flattened, and each group's terms are combined as a balanced
+    /// tree, so a long `Or` doesn't nest one level per term in SQLite's parser.
+    fn expr_clause(expr: &FilterExpr) -> Result<(String, Vec<Box<dyn rusqlite::ToSql>>)> {
+        let mut params = Vec::new();
+        let sql = Self::expr_sql(&expr.clone().flattened(), &mut params, 0)?;
+        Ok((format!(" AND {}", sql), params))
+    }
+
+    fn expr_sql(expr: &FilterExpr, params: &mut Vec<Box<dyn rusqlite::ToSql>>, depth: usize) -> Result<String> {
+        if depth > MAX_FILTER_DEPTH {
+            return Err(StoreError::Schema(format!(
+                "Filter expression nests deeper than {} groups",
+                MAX_FILTER_DEPTH
+            )));
+        }
+        let (terms, op, empty) = match expr {
+            FilterExpr::Cond(filter) => return Self::filter_condition(filter, params),
+            FilterExpr::And(terms) => (terms, "AND", "1"),
+            // An empty disjunction is false: it matches nothing
+            FilterExpr::Or(terms) => (terms, "OR", "0"),
+        };
+        let sql = terms
+            .iter()
+            .map(|term| Self::expr_sql(term, params, depth + 1))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self::balanced(&sql, op).unwrap_or_else(|| empty.to_string()))
+    }
+
+    /// `terms` joined by `op` as a balanced tree of parenthesized pairs
+    fn balanced(terms: &[String], op: &str) -> Option<String> {
+        match terms {
+            [] => None,
+            [term] => Some(term.clone()),
+            _ => {
+                let (left, right) = terms.split_at(terms.len() / 2);
+                Some(format!(
+                    "({} {} {})",
+                    Self::balanced(left, op)?,
+                    op,
+                    Self::balanced(right, op)?
+                ))
+            }
+        }
+    }
+
+    /// Reconstruct a typed index value from the `record_indexes` value columns
+    fn index_value_from_columns(
+        str_val: Option<String>,
+        int_val: Option<i64>,
+        real_val: Option<f64>,
+        bool_val: Option<i64>,
+    ) -> Option<IndexValue> {
+        str_val
+            .map(IndexValue::String)
+            .or(int_val.map(IndexValue::Int))
+            .or(real_val.map(IndexValue::Float))
+            .or(bool_val.map(|b| IndexValue::Bool(b != 0)))
+    }
+
+    /// Fail with `StoreError::Schema` unless `name` is a valid collection name
+    ///
+    /// Valid names are 1 to 64 alphanumeric characters, `_` or `-`, so they
+    /// are safe as JSONL file names. Meant for tools such as the CLI that build
+    /// a collection's path from user input.
+    pub fn validate_collection_name(name: &str) -> Result<()> {
+        if name.is_empty() {
+            return Err(StoreError::Schema("Collection name cannot be empty".to_string()));
+        }
+        if name.len() > 64 {
+            return Err(StoreError::Schema(format!(
+                "Collection name too long: {} (max 64 chars)",
+                name
+            )));
+        }
+        if !name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-') {
+            return Err(StoreError::Schema(format!(
+                "Invalid collection name: {} (must be alphanumeric with _/-)",
+                name
+            )));
+        }
+        Ok(())
+    }
+
+    fn validate_field_name(name: &str) -> Result<()> {
+        if name.is_empty() {
+            return Err(StoreError::Schema("Field name cannot be empty".to_string()));
+        }
+        if name.len() > 64 {
+            return Err(StoreError::Schema(format!(
+                "Field name too long: {} (max 64 chars)",
+                name
+            )));
+        }
+        if !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            return Err(StoreError::Schema(format!(
+                "Invalid field name: {} (must be alphanumeric with _)",
+                name
+            )));
+        }
+        Ok(())
+    }
+
+    /// Validate record ID
+    fn validate_id(id: &str) -> Result<()> {
+        // Check not empty or whitespace-only
+        if id.trim().is_empty() {
+            return Err(StoreError::Validation(ValidationError::new(
+                "Record ID cannot be empty or whitespace-only".to_string(),
+            )));
+        }
+
+        // Check reasonable length (prevent DoS via huge IDs)
+        if id.len() > 256 {
+            return Err(StoreError::Validation(ValidationError::new(format!(
+                "Record ID too long: {} chars (max 256)",
+                id.len()
+            ))));
+        }
+
+        Ok(())
+    }
+
+    // ========================================================================
+    // Sync operations
+    // ========================================================================
+
+    /// Sync SQLite database from JSONL files
+    ///
+    /// Every `*.jsonl` file is loaded, whatever its collection, keeping the
+    /// latest version of each record. Existing rows are replaced in one
+    /// transaction, so a failed sync leaves the previous contents in place.
+    /// Indexes are restored with `rebuild_all_indexes`; collections whose type
+    /// isn't registered (see `register`) are reindexed on a best-effort basis.
+    pub fn sync(&mut self) -> Result<()> {
+        self.ensure_writable()?;
+
+        if self.in_memory {
+            debug!("In-memory store has no JSONL files, nothing to sync");
+            return Ok(());
+        }
+
+        info!("Syncing database from JSONL files");
+        let timer = self.metrics.start();
+
+        // Clear and reload in one write transaction. Writers append to JSONL
+        // while holding the write lock, so the files read here can't be missing
+        // a version whose SQLite row a concurrent writer already committed.
+        let jsonl_dir = self.jsonl_dir.clone();
+        let mut marks = Vec::new();
+        self.with_busy_retry(|db| {
+            marks.clear();
+            let tx = db.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            for table in [
+                "record_indexes",
+                "record_composite_indexes",
+                "composite_index_meta",
+                "records",
+                "sync_metadata",
+            ] {
+                tx.execute(&format!("DELETE FROM {}", table), [])?;
+            }
+
+            for entry in fs::read_dir(&jsonl_dir)? {
+                let entry = entry?;
+                let path = entry.path();
+
+                if path.extension().and_then(|s| s.to_str()) != Some("jsonl") {
+                    continue;
+                }
+
+                let collection = path
+                    .file_stem()
+                    .and_then(|s| s.to_str())
+                    .ok_or_else(|| StoreError::Corrupt(format!("Invalid JSONL filename: {:?}", path)))?;
+
+                debug!("Syncing collection: {}", collection);
+                let mark = Self::load_collection(&tx, collection, &path)?;
+                marks.push((collection.to_string(), mark));
+            }
+            tx.commit()?;
+            Ok(())
+        })?;
+
+        self.rebuild_all_indexes()?;
+        self.mark_synced(&marks)?;
+
+        self.metrics.record(Op::Sync, None, timer);
+        info!("Sync complete");
+        Ok(())
+    }
+
+    /// Sync one collection from its JSONL file, leaving every other collection untouched
+    ///
+    /// Much cheaper than `sync` when only one file changed, e.g. after a git
+    /// merge that touched a single collection. If the collection's JSONL file no
+    /// longer exists, its records are removed from SQLite. The collection is
+    /// reindexed like `rebuild_all_indexes` would.
+    ///
+    /// If lines were only appended since the collection was last synced, just
+    /// those lines are read and only the records they touch are reindexed;
+    /// otherwise the collection is reloaded in full. Use `sync` to rebuild
+    /// SQLite from scratch.
+    pub fn sync_collection(&mut self, collection: &str) -> Result<()> {
+        self.ensure_writable()?;
+        Self::validate_collection_name(collection)?;
+
+        if self.in_memory {
+            debug!("In-memory store has no JSONL files, nothing to sync");
+            return Ok(());
+        }
+
+        let timer = self.metrics.start();
+        let path = self.jsonl_dir.join(format!("{}.jsonl", collection));
+        if path.exists() && self.sync_appended(collection, &path)? {
+            self.metrics.record(Op::Sync, None, timer);
+            return Ok(());
+        }
+
+        debug!(collection, "Syncing collection");
+        // One write transaction, so a concurrent writer's rows are never
+        // deleted or overwritten by an older snapshot of the file (see `sync`)
+        let mut mark = None;
+        self.with_busy_retry(|db| {
+            let tx = db.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            for table in [
+                "record_indexes",
+                "record_composite_indexes",
+                "composite_index_meta",
+                "records",
+                "sync_metadata",
+            ] {
+                tx.execute(&format!("DELETE FROM {} WHERE collection = ?1", table), [collection])?;
+            }
+            mark = if path.exists() {
+                Some(Self::load_collection(&tx, collection, &path)?)
+            } else {
+                None
+            };
+            tx.commit()?;
+            Ok(())
+        })?;
+
+        if let Some(mark) = mark {
+            self.reindex_any(collection, None)?;
+            self.mark_synced(&[(collection.to_string(), mark)])?;
+        }
+
+        self.metrics.record(Op::Sync, None, timer);
+        Ok(())
+    }
+
+    /// Bring a collection up to date by reading only the lines appended since its last sync
+    ///
+    /// The last sync recorded how far into the file it read and a hash of
+    /// those bytes. If they are unchanged, the new lines are applied like a
+    /// sync would (a version only replaces an older one, a tombstone removes the
+    /// record) and only the touched records are reindexed. Returns false,
+    /// having changed nothing, if that isn't possible: the collection was never
+    /// synced, or the synced bytes changed (a git merge or compaction rewrote
+    /// the file), so a full reload is needed.
+    ///
+    /// Tombstones aren't kept in SQLite, so an appended version of a record
+    /// with no row is checked against the whole file's latest versions (read
+    /// once, and only if needed); one older than the record's tombstone is
+    /// skipped, as a full reload would.
+    fn sync_appended(&mut self, collection: &str, path: &Path) -> Result<bool> {
+        let synced: Option<(i64, String)> = self
+            .db
+            .query_row(
+                "SELECT file_size, file_hash FROM sync_metadata WHERE collection = ?1",
+                [collection],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        let Some((offset, synced_hash)) = synced else {
+            return Ok(false);
+        };
+        let offset = offset as u64;
+        if synced_hash.is_empty() || fs::metadata(path)?.len() < offset || !jsonl::is_line_start(path, offset)? {
+            return Ok(false);
+        }
+
+        let mut tail = JsonlTail::from_offset(path, offset);
+        let appended = tail.poll()?;
+        let end = tail.position();
+
+        // Checked after reading, so a rewrite racing with the read is caught too
+        let hashes = jsonl::hash_prefixes(path, &[offset, end])?;
+        if hashes[0] != synced_hash {
+            debug!(
+                collection,
+                "JSONL was rewritten since the last sync, reloading it in full"
+            );
+            return Ok(false);
+        }
+
+        // Latest appended version per ID, resolved like `read_jsonl_latest`
+        let mut latest: HashMap<String, serde_json::Value> = HashMap::new();
+        for record in appended {
+            let Some(id) = record.get("id").and_then(|v| v.as_str()).map(str::to_string) else {
+                continue;
+            };
+            if latest
+                .get(&id)
+                .is_none_or(|existing| jsonl::supersedes(&record, existing))
+            {
+                latest.insert(id, record);
+            }
+        }
+
+        let (file_mtime, _) = Self::file_fingerprint(path)?;
+        let mut changed = HashSet::new();
+        let mut history: Option<HashMap<String, serde_json::Value>> = None;
+        self.with_busy_retry(|db| {
+            changed.clear();
+            let tx = db.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            for (id, record) in &latest {
+                let current: Option<String> = tx
+                    .query_row(
+                        "SELECT data_json FROM records WHERE collection = ?1 AND id = ?2",
+                        [collection, id],
+                        |row| row.get(0),
+                    )
+                    .optional()?;
+                let deleted = record.get("deleted").and_then(|v| v.as_bool()).unwrap_or(false);
+                match current {
+                    Some(current) if !jsonl::supersedes(record, &serde_json::from_str(&current)?) => continue,
+                    Some(_) => {}
+                    None if deleted => {}
+                    None => {
+                        if history.is_none() {
+                            history = Some(jsonl::read_jsonl_latest(path)?);
+                        }
+                        let tombstone = history
+                            .as_ref()
+                            .and_then(|h| h.get(id))
+                            .filter(|latest| latest.get("deleted").and_then(|v| v.as_bool()).unwrap_or(false));
+                        if tombstone.is_some_and(|tombstone| jsonl::supersedes(tombstone, record)) {
+                            continue;
+                        }
+                    }
+                }
+
+                if deleted {
+                    for table in ["record_indexes", "record_composite_indexes", "records"] {
+                        tx.execute(
+                            &format!("DELETE FROM {} WHERE collection = ?1 AND id = ?2", table),
+                            [collection, id],
+                        )?;
+                    }
+                } else {
+                    tx.execute(
+                        "INSERT OR REPLACE INTO records (collection, id, data_json, updated_at)
+                         VALUES (?1, ?2, ?3, ?4)",
+                        rusqlite::params![
+                            collection,
+                            id,
+                            serde_json::to_string(record)?,
+                            jsonl::updated_at(record)
+                        ],
+                    )?;
+                    changed.insert(id.clone());
+                }
+            }
+            tx.execute(
+                "INSERT OR REPLACE INTO sync_metadata (collection, last_sync_time, file_mtime, file_size, file_hash)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                rusqlite::params![collection, now_ms(), file_mtime, end as i64, hashes[1]],
+            )?;
+            tx.commit()?;
+            Ok(())
+        })?;
+
+        self.reindex_any(collection, Some(&changed))?;
+        debug!(
+            collection,
+            from = offset,
+            to = end,
+            changed = changed.len(),
+            "Synced appended JSONL lines"
+        );
+        Ok(true)
+    }
+
+    /// Insert the latest version of each live record in a JSONL file
+    ///
+    /// Expects the collection's existing rows to have been cleared in the same
+    /// transaction. Returns the file's sync metadata (mtime, size, hash) for
+    /// `mark_synced`, which is only recorded once the records are indexed.
+    fn load_collection(tx: &rusqlite::Transaction, collection: &str, path: &Path) -> Result<(i64, i64, String)> {
+        // Get file fingerprint and content hash for staleness tracking
+        let (file_mtime, file_size) = Self::file_fingerprint(path)?;
+        let file_hash = jsonl::hash_file(path)?;
+
+        // Read records from JSONL
+        let records = jsonl::read_jsonl_latest(path)?;
+
+        // Insert into SQLite
+        for (id, record) in records {
+            // Skip tombstones
+            if record.get("deleted").and_then(|v| v.as_bool()).unwrap_or(false) {
+                continue;
+            }
+
+            let data_json = serde_json::to_string(&record)?;
+            let updated_at = record.get("updated_at").and_then(|v| v.as_i64()).unwrap_or(0);
+
+            tx.execute(
+                "INSERT OR REPLACE INTO records (collection, id, data_json, updated_at)
+                 VALUES (?1, ?2, ?3, ?4)",
+                rusqlite::params![collection, &id, data_json, updated_at],
+            )?;
+        }
+
+        Ok((file_mtime, file_size, file_hash))
+    }
+
+    /// Record sync metadata for collections that were loaded and reindexed
+    ///
+    /// Kept out of the load transaction: if the process dies before the
+    /// records are indexed, the collection has no metadata, so it is still
+    /// stale and the next `open` syncs it again.
+    fn mark_synced(&mut self, marks: &[(String, (i64, i64, String))]) -> Result<()> {
+        self.with_busy_retry(|db| {
+            let tx = db.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            for (collection, (file_mtime, file_size, file_hash)) in marks {
+                tx.execute(
+                    "INSERT OR REPLACE INTO sync_metadata (collection, last_sync_time, file_mtime, file_size, file_hash)
+                     VALUES (?1, ?2, ?3, ?4, ?5)",
+                    rusqlite::params![collection, now_ms(), file_mtime, file_size, file_hash],
+                )?;
+            }
+            tx.commit()?;
+            Ok(())
+        })
+    }
+
+    /// Compute what `sync()` would change without modifying the database or JSONL files
+    ///
+    /// Compares the latest version of each record in JSONL against the records
+    /// currently in SQLite and reports per-collection counts of additions,
+    /// updates, and removals.
+    pub fn sync_dry_run(&self) -> Result<SyncPlan> {
+        if self.in_memory {
+            return Ok(SyncPlan::default());
+        }
+
+        let mut plans: HashMap<String, CollectionSyncPlan> = HashMap::new();
+
+        // Compare each JSONL file against SQLite
+        for entry in fs::read_dir(&self.jsonl_dir)? {
+            let entry = entry?;
+            let path = entry.path();
+
+            if path.extension().and_then(|s| s.to_str()) != Some("jsonl") {
+                continue;
+            }
+
+            let collection = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .ok_or_else(|| StoreError::Corrupt(format!("Invalid JSONL filename: {:?}", path)))?;
+
+            let mut indexed = self.indexed_records(collection)?;
+            let plan = plans
+                .entry(collection.to_string())
+                .or_insert_with(|| CollectionSyncPlan {
+                    collection: collection.to_string(),
+                    ..Default::default()
+                });
+
+            for (id, record) in jsonl::read_jsonl_latest(&path)? {
+                // Tombstones are not indexed; they are removals if still present
+                if record.get("deleted").and_then(|v| v.as_bool()).unwrap_or(false) {
+                    continue;
+                }
+
+                match indexed.remove(&id) {
+                    None => plan.added += 1,
+                    Some(existing) if existing != record => plan.updated += 1,
+                    Some(_) => {}
+                }
+            }
+
+            plan.removed += indexed.len();
+        }
+
+        // Collections indexed in SQLite whose JSONL file no longer exists
+        let db_collections: Vec<String> = {
+            let mut stmt = self.db.prepare("SELECT DISTINCT collection FROM records")?;
+            let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+            rows.collect::<rusqlite::Result<_>>()?
+        };
+        for collection in db_collections {
+            if plans.contains_key(&collection) {
+                continue;
+            }
+            let removed = self.indexed_records(&collection)?.len();
+            plans.insert(
+                collection.clone(),
+                CollectionSyncPlan {
+                    collection,
+                    removed,
+                    ..Default::default()
+                },
+            );
+        }
+
+        let mut collections: Vec<CollectionSyncPlan> = plans.into_values().collect();
+        collections.sort_by(|a, b| a.collection.cmp(&b.collection));
+
+        Ok(SyncPlan { collections })
+    }
+
+    /// Load the indexed JSON of every record in a collection, keyed by ID
+    fn indexed_records(&self, collection: &str) -> Result<HashMap<String, serde_json::Value>> {
+        let mut stmt = self
+            .db
+            .prepare("SELECT id, data_json FROM records WHERE collection = ?1")?;
+        let rows = stmt.query_map([collection], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+
+        let mut records = HashMap::new();
+        for row in rows {
+            let (id, data_json) = row?;
+            let value: serde_json::Value = serde_json::from_str(&data_json)?;
+            records.insert(id, value);
+        }
+        Ok(records)
+    }
+
+    /// Rebuild indexes for a specific record type after sync
+    ///
+    /// Needed after `sync()` only for types that aren't registered (see
+    /// `register`) or whose indexes aren't plain top-level fields. The method:
+    /// - Reads all records from SQLite for the collection
+    /// - Deserializes each to type T to extract `indexed_fields()`
+    /// - Rebuilds the `record_indexes` table entries
+    ///
+    /// Returns the number of records successfully indexed.
+    ///
+    /// # Edge case handling
+    /// If records in the collection don't deserialize to type T (e.g., wrong type
+    /// passed), those records are skipped with a warning log. This prevents crashes
+    /// while alerting to potential misconfiguration.
+    ///
+    /// If JSONL contains several records with the same value for one of
+    /// `T::unique_fields()` (e.g. created on different branches and merged), all of
+    /// them are indexed and each duplicate is reported with a warning log. Later
+    /// writes to those records fail with `DuplicateKeyError` until it's resolved.
+    pub fn rebuild_indexes<T: Record>(&mut self) -> Result<usize> {
+        self.ensure_writable()?;
+        self.reindex_collection(T::collection_name(), Registration::of::<T>(), None)
+    }
+
+    /// Register a record type so `rebuild_all_indexes` (and therefore `sync`) can reindex its collection
+    ///
+    /// Registering again replaces the previous registration for the collection.
+    pub fn register<T: Record>(&mut self) {
+        self.registry.insert(T::collection_name(), Registration::of::<T>());
+    }
+
+    /// Rebuild indexes for every collection in the database, returning per-collection counts
+    ///
+    /// Registered collections (see `register`) are reindexed through their type,
+    /// exactly like `rebuild_indexes::<T>()`. Other collections, e.g. ones only
+    /// ever touched by the CLI, are reindexed from raw JSON using the field names
+    /// they were indexed by before: top-level string, integer, and bool values
+    /// with those names are indexed, anything else (computed or nested fields) is
+    /// skipped until the type is registered. Collections with neither are left
+    /// unindexed and absent from the result.
+    ///
+    /// `sync` calls this, so manual reindexing is rarely needed.
+    pub fn rebuild_all_indexes(&mut self) -> Result<HashMap<String, usize>> {
+        self.ensure_writable()?;
+
+        let mut collections: Vec<String> = {
+            let mut stmt = self.db.prepare("SELECT DISTINCT collection FROM records")?;
+            let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+            rows.collect::<rusqlite::Result<_>>()?
+        };
+        for name in self.registry.keys() {
+            if !collections.iter().any(|c| c == name) {
+                collections.push(name.to_string());
+            }
+        }
+        collections.sort();
+
+        let mut counts = HashMap::new();
+        for collection in collections {
+            if let Some(count) = self.reindex_any(&collection, None)? {
+                counts.insert(collection, count);
+            }
+        }
+        Ok(counts)
+    }
+
+    /// Reindex a collection through its registered type, or from raw JSON if it has none
+    ///
+    /// Raw JSON is indexed by the collection's specs in `schema.json` if it has
+    /// any, else by the field names it was indexed by before. With `only`, just those records are reindexed. Returns `None` if the
+    /// collection was left unindexed.
+    fn reindex_any(&mut self, collection: &str, only: Option<&HashSet<String>>) -> Result<Option<usize>> {
+        if let Some(registration) = self.registry.get(collection).copied() {
+            return self.reindex_collection(collection, registration, only).map(Some);
+        }
+        match self.schema.get(collection).cloned() {
+            Some(specs) => self.reindex_with_specs(collection, &specs, only).map(Some),
+            None => self.reindex_collection_raw(collection, only),
+        }
+    }
+
+    /// Rebuild a collection's indexes from raw JSON, without its Rust type
+    ///
+    /// Each `IndexSpec` names a JSON key and the `IndexValue` kind to index it
+    /// as. The specs replace whatever the collection was indexed by before.
+    /// Records missing a field, or holding a value of another kind, get no index
+    /// row for it. Returns the number of records reindexed.
+    pub fn rebuild_indexes_raw(&mut self, collection: &str, specs: &[IndexSpec]) -> Result<usize> {
+        self.ensure_writable()?;
+        Self::validate_collection_name(collection)?;
+
+        self.db
+            .execute("DELETE FROM indexed_field_names WHERE collection = ?1", [collection])?;
+        self.reindex_with_specs(collection, specs, None)
+    }
+
+    /// Reindex a collection (or just the `only` records) from raw JSON by `specs`
+    fn reindex_with_specs(
+        &mut self,
+        collection: &str,
+        specs: &[IndexSpec],
+        only: Option<&HashSet<String>>,
+    ) -> Result<usize> {
+        for spec in specs {
+            Self::validate_field_name(&spec.field)?;
+        }
+
+        let mut mismatched: HashMap<&str, usize> = HashMap::new();
+        let mut indexable = Vec::new();
+        for (id, data_json) in self.collection_json(collection, only)? {
+            let value: serde_json::Value = match serde_json::from_str(&data_json) {
+                Ok(v) => v,
+                Err(e) => {
+                    warn!(collection, id = &id, error = ?e, "Skipping record with invalid JSON");
+                    continue;
+                }
+            };
+            let mut fields = HashMap::new();
+            for spec in specs {
+                match spec.value_from(&value) {
+                    Some(index_value) => {
+                        fields.insert(spec.field.clone(), index_value);
+                    }
+                    None if !value.get(&spec.field).is_none_or(|v| v.is_null()) => {
+                        *mismatched.entry(spec.field.as_str()).or_default() += 1;
+                    }
+                    None => {}
+                }
+            }
+            indexable.push((id, fields));
+        }
+        for (field, count) in mismatched {
+            warn!(
+                collection,
+                field, count, "Field has values of another kind than its index spec, not indexed"
+            );
+        }
+
+        let count = self.write_indexes(collection, &indexable, &[])?;
+        debug!(
+            collection,
+            count,
+            fields = specs.len(),
+            "Rebuilt indexes from index specs"
+        );
+        Ok(count)
+    }
+
+    /// Register a record type and record its index fields in `schema.json`
+    ///
+    /// Like `register`, and also stores the fields and kinds the collection's
+    /// records index (as seen in the records stored now) in `schema.json`.
+    /// Stores opened later, including the CLI, then index the collection the
+    /// same way on sync without the Rust type. Nothing is written if no stored
+    /// record indexes any field.
+    pub fn register_collection<T: Record>(&mut self) -> Result<()> {
+        self.ensure_writable()?;
+        self.register::<T>();
+        let collection = T::collection_name();
+
+        let mut kinds: BTreeMap<String, IndexKind> = BTreeMap::new();
+        for (id, data_json) in self.collection_json(collection, None)? {
+            let record: T = match serde_json::from_str(&data_json) {
+                Ok(record) => record,
+                Err(e) => {
+                    warn!(collection, id = &id, error = ?e, "Skipping record that doesn't deserialize");
+                    continue;
+                }
+            };
+            let fields = index_fields(&record);
+            Self::check_indexable(collection, &id, &fields)?;
+            for (field, value) in fields {
+                kinds.entry(field).or_insert_with(|| IndexKind::of(&value));
+            }
+        }
+        if kinds.is_empty() {
+            debug!(collection, "No indexed fields seen, leaving schema unchanged");
+            return Ok(());
+        }
+
+        let specs: Vec<IndexSpec> = kinds
+            .into_iter()
+            .map(|(field, kind)| IndexSpec::new(field, kind))
+            .collect();
+        if self.schema.get(collection) == Some(&specs) {
+            return Ok(());
+        }
+        self.schema.insert(collection.to_string(), specs);
+        self.save_schema()?;
+        info!(collection, "Recorded index fields in schema");
+        Ok(())
+    }
+
+    /// Index specs `schema.json` holds for a collection
+    pub fn index_specs(&self, collection: &str) -> Option<&[IndexSpec]> {
+        self.schema.get(collection).map(Vec::as_slice)
+    }
+
+    fn load_schema(&self) -> Result<BTreeMap<String, Vec<IndexSpec>>> {
+        let path = self.base_path.join(SCHEMA_FILENAME);
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+            Err(e) => return Err(StoreError::io("Failed to read schema file", e)),
+        };
+        serde_json::from_str(&content).map_err(|e| StoreError::Corrupt(format!("Invalid {}: {}", path.display(), e)))
+    }
+
+    /// Write `schema.json` (skipped for an in-memory store)
+    fn save_schema(&self) -> Result<()> {
+        if self.in_memory {
+            return Ok(());
+        }
+        let path = self.base_path.join(SCHEMA_FILENAME);
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(&self.schema)? + "\n")
+            .map_err(|e| StoreError::io("Failed to write schema file", e))?;
+        fs::rename(&tmp, &path).map_err(|e| StoreError::io("Failed to replace schema file", e))?;
+        Ok(())
+    }
+
+    /// Reindex a collection with no registered type from the field names it was indexed by before
+    ///
+    /// Returns `None` if no field names are known for the collection.
+    fn reindex_collection_raw(&mut self, collection: &str, only: Option<&HashSet<String>>) -> Result<Option<usize>> {
+        let field_names: Vec<String> = {
+            let mut stmt = self
+                .db
+                .prepare("SELECT field_name FROM indexed_field_names WHERE collection = ?1 ORDER BY field_name")?;
+            let rows = stmt.query_map([collection], |row| row.get::<_, String>(0))?;
+            rows.collect::<rusqlite::Result<_>>()?
+        };
+        if field_names.is_empty() {
+            debug!(
+                collection,
+                "No registered type or known indexed fields, skipping reindex"
+            );
+            // The records' composite rows are gone all the same, see `write_indexes`
+            if only.is_none_or(|ids| !ids.is_empty()) {
+                for table in ["record_composite_indexes", "composite_index_meta"] {
+                    self.db
+                        .execute(&format!("DELETE FROM {} WHERE collection = ?1", table), [collection])?;
+                }
+            }
+            return Ok(None);
+        }
+
+        let records_data = self.collection_json(collection, only)?;
+        let indexable: Vec<(String, HashMap<String, IndexValue>)> = records_data
+            .into_iter()
+            .filter_map(|(id, data_json)| {
+                let value: serde_json::Value = match serde_json::from_str(&data_json) {
+                    Ok(v) => v,
+                    Err(e) => {
+                        warn!(collection, id = &id, error = ?e, "Skipping record with invalid JSON");
+                        return None;
+                    }
+                };
+                let fields = field_names
+                    .iter()
+                    .filter_map(|name| {
+                        let index_value = match value.get(name)? {
+                            // Only a non-string `id_index_value` puts `id` in the index, see `index_fields`
+                            serde_json::Value::String(s) if name == "id" => IndexValue::Int(s.parse().ok()?),
+                            serde_json::Value::String(s) => IndexValue::String(s.clone()),
+                            serde_json::Value::Bool(b) => IndexValue::Bool(*b),
+                            serde_json::Value::Number(n) => match n.as_i64() {
+                                Some(i) => IndexValue::Int(i),
+                                None => IndexValue::Float(n.as_f64()?),
+                            },
+                            _ => return None,
+                        };
+                        Some((name.clone(), index_value))
+                    })
+                    .collect();
+                Some((id, fields))
+            })
+            .collect();
+
+        let count = self.write_indexes(collection, &indexable, &[])?;
+        debug!(collection, count, "Rebuilt indexes from raw JSON");
+        Ok(Some(count))
+    }
+
+    /// Reindex every record in a collection through a registration's `indexed_fields`
+    fn reindex_collection(
+        &mut self,
+        collection: &str,
+        registration: Registration,
+        only: Option<&HashSet<String>>,
+    ) -> Result<usize> {
+        // Get raw JSON from SQLite (bypass list<T> to handle deserialization errors)
+        let records_data = self.collection_json(collection, only)?;
+
+        let mut indexable = Vec::with_capacity(records_data.len());
+        for (id, data_json) in records_data {
+            // Attempt deserialization - skip records that don't match type T
+            let fields = match (registration.indexed_fields)(&data_json) {
+                Ok(fields) => fields,
+                Err(e) => {
+                    warn!(
+                        collection = collection,
+                        id = &id,
+                        error = ?e,
+                        "Skipping record that doesn't match type"
+                    );
+                    continue;
+                }
+            };
+            indexable.push((id, fields));
+        }
+
+        // Report (rather than silently pick between) records that share a unique value
+        for field in registration.unique_fields {
+            let mut seen: HashMap<String, Vec<&str>> = HashMap::new();
+            for (id, fields) in &indexable {
+                if let Some(value) = fields.get(*field) {
+                    seen.entry(value.to_string()).or_default().push(id);
+                }
+            }
+            for (value, ids) in seen.into_iter().filter(|(_, ids)| ids.len() > 1) {
+                warn!(
+                    collection = collection,
+                    field = field,
+                    value = value,
+                    ids = ?ids,
+                    "Duplicate values for unique field; all records were indexed, resolve manually"
+                );
+            }
+        }
+
+        let count = self.write_indexes(collection, &indexable, registration.composite_indexes)?;
+        debug!(collection = collection, count = count, "Rebuilt indexes for collection");
+        Ok(count)
+    }
+
+    /// `(id, data_json)` for every record in a collection, or just the records in `only`
+    fn collection_json(&self, collection: &str, only: Option<&HashSet<String>>) -> Result<Vec<(String, String)>> {
+        if let Some(ids) = only {
+            let mut stmt = self
+                .db
+                .prepare("SELECT data_json FROM records WHERE collection = ?1 AND id = ?2")?;
+            let mut records = Vec::with_capacity(ids.len());
+            for id in ids {
+                if let Some(data_json) = stmt.query_row([collection, id], |row| row.get(0)).optional()? {
+                    records.push((id.clone(), data_json));
+                }
+            }
+            return Ok(records);
+        }
+
+        let mut stmt = self
+            .db
+            .prepare("SELECT id, data_json FROM records WHERE collection = ?1")?;
+        let rows = stmt.query_map([collection], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+        Ok(rows.filter_map(|r| r.ok()).collect())
+    }
+
+    /// Replace the index rows of the given records in one transaction, marking `composites` complete
+    ///
+    /// Without `composites` (no Rust type to compute them from), the records
+    /// lose their composite rows, so the collection's composites are dropped as
+    /// incomplete; the next typed write backfills them.
+    fn write_indexes(
+        &mut self,
+        collection: &str,
+        indexable: &[(String, HashMap<String, IndexValue>)],
+        composites: &[&[&str]],
+    ) -> Result<usize> {
+        self.with_busy_retry(|db| {
+            let tx = db.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            for (id, fields) in indexable {
+                Self::update_indexes_tx(&tx, collection, id, fields, composites)?;
+            }
+            if composites.is_empty() && !indexable.is_empty() {
+                for table in ["record_composite_indexes", "composite_index_meta"] {
+                    tx.execute(&format!("DELETE FROM {} WHERE collection = ?1", table), [collection])?;
+                }
+            }
+            for composite in composites {
+                tx.execute(
+                    "INSERT OR IGNORE INTO composite_index_meta (collection, index_name) VALUES (?1, ?2)",
+                    rusqlite::params![collection, composite.join(",")],
+                )?;
+            }
+            tx.commit()?;
+            Ok(())
+        })?;
+        Ok(indexable.len())
+    }
+
+    // ========================================================================
+    // Maintenance
+    // ========================================================================
+
+    /// Operation counts, latency histograms, and JSONL file sizes for this store
+    ///
+    /// Counts cover this `Store` value since it was opened: creates, updates,
+    /// and deletes (including cascaded ones) per collection, reads from `get`,
+    /// `list`, `list_with`, and `list_ids`, plus latency per operation including
+    /// syncs. Taking a snapshot copies the counters and stats the JSONL files.
+    #[cfg(feature = "metrics")]
+    pub fn metrics_snapshot(&self) -> crate::metrics::Metrics {
+        let mut metrics = self.metrics.snapshot();
+        if !self.in_memory
+            && let Ok(entries) = fs::read_dir(&self.jsonl_dir)
+        {
+            for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+                if path.extension().and_then(|s| s.to_str()) != Some("jsonl") {
+                    continue;
+                }
+                if let (Some(collection), Ok(meta)) = (path.file_stem().and_then(|s| s.to_str()), fs::metadata(&path)) {
+                    metrics.jsonl_bytes.insert(collection.to_string(), meta.len());
+                }
+            }
+        }
+        metrics
+    }
+
+    /// fsync JSONL appends that the store's `FsyncPolicy` hasn't yet
+    ///
+    /// A no-op with `FsyncPolicy::EveryWrite`. With `Batched` or `OnFlush`,
+    /// call this after a bulk load or at any point writes must survive a crash.
+    /// Dropping the store flushes too, on a best-effort basis.
+    pub fn flush(&self) -> Result<()> {
+        self.fsync.flush()
+    }
+
+    /// Reclaim unused space in the SQLite database and refresh planner statistics
+    ///
+    /// Runs `VACUUM` to rebuild the database file (SQLite otherwise keeps freed
+    /// pages after deletes) followed by `ANALYZE`.
+    ///
+    /// `VACUUM` writes a full copy of the database, so it needs free disk space
+    /// roughly equal to the current database size, and it holds a write lock for
+    /// its whole duration, blocking other writers.
+    pub fn vacuum(&mut self) -> Result<()> {
+        self.ensure_writable()?;
+
+        info!("Vacuuming database");
+        self.db.execute_batch("VACUUM; ANALYZE;")?;
+        info!("Vacuum complete");
+        Ok(())
+    }
+
+    /// Collections that have a JSONL file, sorted by name (empty for an in-memory store)
+    pub fn jsonl_collections(&self) -> Result<Vec<String>> {
+        if self.in_memory {
+            return Ok(Vec::new());
+        }
+        let mut collections = Vec::new();
+        for entry in fs::read_dir(&self.jsonl_dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|s| s.to_str()) != Some("jsonl") {
+                continue;
+            }
+            if let Some(collection) = path.file_stem().and_then(|s| s.to_str()) {
+                collections.push(collection.to_string());
+            }
+        }
+        collections.sort();
+        Ok(collections)
+    }
+
+    /// Per-collection JSONL and index sizes, sorted by collection name
+    ///
+    /// Covers every collection with a JSONL file or rows in SQLite. The stale
+    /// ratio is 0 for a collection with no live records.
+    pub fn sizes(&self) -> Result<Vec<CollectionSizes>> {
+        let mut collections: Vec<String> = self.jsonl_collections()?;
+        {
+            let mut stmt = self.db.prepare("SELECT DISTINCT collection FROM records")?;
+            for collection in stmt.query_map([], |row| row.get::<_, String>(0))? {
+                collections.push(collection?);
+            }
+        }
+        collections.sort();
+        collections.dedup();
+
+        let mut sizes = Vec::with_capacity(collections.len());
+        for collection in collections {
+            let (jsonl_bytes, scan) = match self.jsonl_path(&collection) {
+                Some(path) => {
+                    let bytes = std::iter::once(path.clone())
+                        .chain(jsonl::archive_paths(&path)?)
+                        .map(|p| fs::metadata(p).map(|m| m.len()).unwrap_or(0))
+                        .sum();
+                    (bytes, jsonl::scan_jsonl(&path)?)
+                }
+                None => (0, jsonl::JsonlScan::default()),
+            };
+            let index_rows: i64 = self.db.query_row(
+                "SELECT (SELECT COUNT(*) FROM record_indexes WHERE collection = ?1)
+                      + (SELECT COUNT(*) FROM record_composite_indexes WHERE collection = ?1)",
+                [&collection],
+                |row| row.get(0),
+            )?;
+            let stale_ratio = if scan.live_records == 0 {
+                0.0
+            } else {
+                scan.total_lines as f64 / scan.live_records as f64
+            };
+            sizes.push(CollectionSizes {
+                collection,
+                jsonl_bytes,
+                total_lines: scan.total_lines,
+                live_records: scan.live_records,
+                index_rows: index_rows as usize,
+                stale_ratio,
+            });
+        }
+        Ok(sizes)
+    }
+
+    /// Rewrite a collection's JSONL file keeping only the latest line per ID
+    ///
+    /// See `jsonl::compact_jsonl`. SQLite is unaffected: the surviving lines are
+    /// exactly the versions it already holds.
+    pub fn compact_collection(&mut self, collection: &str) -> Result<CompactStats> {
+        self.ensure_writable()?;
+        Self::validate_collection_name(collection)?;
+
+        let Some(path) = self.jsonl_path(collection) else {
+            return Ok(CompactStats::default());
+        };
+        jsonl::compact_jsonl(&path)
+    }
+
+    /// Compact `T`'s JSONL file, see `compact_collection`
+    pub fn compact<T: Record>(&mut self) -> Result<CompactStats> {
+        self.compact_collection(T::collection_name())
+    }
+
+    /// Compact every collection's JSONL file, see `compact_collection`
+    ///
+    /// Each file is rewritten under its own lock, so collections compacted
+    /// before a failure stay compacted and a rerun picks up the rest.
+    pub fn compact_all(&mut self) -> Result<BTreeMap<String, CompactStats>> {
+        let mut stats = BTreeMap::new();
+        for collection in self.jsonl_collections()? {
+            let collection_stats = self.compact_collection(&collection)?;
+            info!(
+                collection,
+                lines_before = collection_stats.lines_before,
+                lines_after = collection_stats.lines_after,
+                "Compacted JSONL"
+            );
+            stats.insert(collection, collection_stats);
+        }
+        Ok(stats)
+    }
+
+    /// Create a record from raw JSON, without its Rust type
+    ///
+    /// Meant for tools such as the CLI. `record` must be a JSON object with a
+    /// string `id` that isn't stored yet; a missing `updated_at` is set to now.
+    /// The record is indexed the way a sync would index it (see
+    /// `register_collection`). `Record::validate`, unique fields, references,
+    /// and write hooks don't apply. Returns the ID.
+    pub fn create_json(&mut self, collection: &str, record: serde_json::Value) -> Result<String> {
+        self.create_json_with_refs(collection, record, &[])
+    }
+
+    /// Create a record from raw JSON like `create_json`, checking `references` as a typed create would
+    ///
+    /// Each reference is a `(field, parent)` pair: the record's `field` must
+    /// hold the ID of a record in the `parent` collection, unless it is
+    /// missing or null. The references are checked inside the write
+    /// transaction, so a parent can't be deleted between the check and the
+    /// write. Fails with `StoreError::Validation` if one points at no record.
+    pub fn create_json_with_refs(
+        &mut self,
+        collection: &str,
+        mut record: serde_json::Value,
+        references: &[(&str, &str)],
+    ) -> Result<String> {
+        self.ensure_writable()?;
+        Self::validate_collection_name(collection)?;
+        let timer = self.metrics.start();
+
+        let Some(fields) = record.as_object_mut() else {
+            return Err(StoreError::Validation(ValidationError::new(
+                "Record must be a JSON object",
+            )));
+        };
+        let id = match fields.get("id") {
+            Some(serde_json::Value::String(id)) => id.clone(),
+            _ => {
+                return Err(StoreError::Validation(ValidationError::new(
+                    "Record is missing a string 'id' field",
+                )));
+            }
+        };
+        Self::validate_id(&id)?;
+        match fields.get("updated_at") {
+            None => {
+                fields.insert("updated_at".to_string(), now_ms().into());
+            }
+            Some(value) if !value.is_i64() => {
+                return Err(StoreError::Validation(ValidationError::new(
+                    "'updated_at' must be an integer (milliseconds since epoch)",
+                )));
+            }
+            Some(_) => {}
+        }
+        let data_json = jsonl::to_canonical_json(&record)?;
+        let updated_at = jsonl::updated_at(&record);
+
+        let jsonl_path = self.jsonl_path(collection);
+        let hooks = Arc::clone(&self.hooks);
+        let fsync = Arc::clone(&self.fsync);
+        let mut appended = None;
+        let result = self.with_busy_retry(|db| {
+            let tx = db.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            let exists: bool = tx.query_row(
+                "SELECT EXISTS (SELECT 1 FROM records WHERE collection = ?1 AND id = ?2)",
+                rusqlite::params![collection, id],
+                |row| row.get(0),
+            )?;
+            if exists {
+                return Err(StoreError::Conflict(DuplicateKeyError {
+                    collection: collection.to_string(),
+                    field: "id".to_string(),
+                    value: id.clone(),
+                    existing_id: id.clone(),
+                }));
+            }
+            let references = references.iter().map(|&(field, parent)| (field, parent, "id"));
+            Self::check_references(&tx, collection, &id, &record, references)?;
+            if appended.is_none()
+                && let Some(jsonl_path) = &jsonl_path
+            {
+                appended = Some(jsonl::append_line(jsonl_path, &data_json, &fsync)?);
+            }
+            tx.execute(
+                "INSERT INTO records (collection, id, data_json, updated_at) VALUES (?1, ?2, ?3, ?4)",
+                rusqlite::params![collection, id, data_json, updated_at],
+            )?;
+            hooks.record(collection, &id, WriteOp::Create);
+            tx.commit()?;
+            Ok(())
+        });
+        Self::undo_appends_on_error(result, appended)?;
+
+        self.reindex_any(collection, Some(&HashSet::from([id.clone()])))?;
+        self.metrics.record(Op::Create, Some(collection), timer);
+        Ok(id)
+    }
+
+    /// Append records from JSONL (e.g. another store's export) to a collection
+    ///
+    /// Each line must be a JSON object with a string `id`; other lines are
+    /// rejected and reported, not fatal. `strategy` decides what happens to IDs
+    /// that already exist (as a live record or a tombstone). A later line for
+    /// the same ID is judged against the earlier one. Accepted lines are
+    /// appended to the collection's JSONL file, then the collection is synced
+    /// and reindexed from it. Records are written as-is: `Record::validate`,
+    /// unique indexes, and write hooks don't apply.
+    pub fn import_jsonl(
+        &mut self,
+        collection: &str,
+        reader: impl BufRead,
+        strategy: ImportStrategy,
+    ) -> Result<ImportReport> {
+        self.ensure_writable()?;
+        Self::validate_collection_name(collection)?;
+        let Some(path) = self.jsonl_path(collection) else {
+            return Err(StoreError::Io(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "An in-memory store has no JSONL to import into",
+            )));
+        };
+
+        let mut latest = jsonl::read_jsonl_latest(&path)?;
+        let mut report = ImportReport::default();
+        for (line_num, line) in reader.lines().enumerate() {
+            let line = line.map_err(|e| StoreError::io("Failed to read import input", e))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let mut record = match serde_json::from_str::<serde_json::Value>(&line) {
+                Ok(record @ serde_json::Value::Object(_)) => record,
+                Ok(_) => {
+                    report.rejected.push((line_num + 1, "not a JSON object".to_string()));
+                    continue;
+                }
+                Err(e) => {
+                    report.rejected.push((line_num + 1, format!("invalid JSON: {}", e)));
+                    continue;
+                }
+            };
+            let Some(id) = record.get("id").and_then(|v| v.as_str()).map(str::to_string) else {
+                report
+                    .rejected
+                    .push((line_num + 1, "missing string 'id' field".to_string()));
+                continue;
+            };
+            if let Err(e) = Self::validate_id(&id) {
+                report.rejected.push((line_num + 1, e.to_string()));
+                continue;
+            }
+
+            let updated_at = |r: &serde_json::Value| r.get("updated_at").and_then(|v| v.as_i64()).unwrap_or(0);
+            let existing = latest.get(&id);
+            let live = existing.is_some_and(|r| !r.get("deleted").and_then(|v| v.as_bool()).unwrap_or(false));
+            match (strategy, existing) {
+                (_, None) => {}
+                (ImportStrategy::SkipExisting, Some(_)) if live => {
+                    report.skipped += 1;
+                    continue;
+                }
+                (ImportStrategy::KeepNewer, Some(existing)) if updated_at(&record) <= updated_at(existing) => {
+                    report.skipped += 1;
+                    continue;
+                }
+                (ImportStrategy::Overwrite, Some(existing)) if updated_at(&record) <= updated_at(existing) => {
+                    record["updated_at"] = (updated_at(existing) + 1).max(now_ms()).into();
+                }
+                _ => {}
+            }
+
+            jsonl::append_line(&path, &jsonl::to_canonical_json(&record)?, &self.fsync)?;
+            if live {
+                report.updated += 1;
+            } else {
+                report.imported += 1;
+            }
+            latest.insert(id, record);
+        }
+
+        if report.imported + report.updated > 0 {
+            self.sync_collection(collection)?;
+        }
+        info!(
+            collection,
+            imported = report.imported,
+            updated = report.updated,
+            skipped = report.skipped,
+            rejected = report.rejected.len(),
+            "Imported JSONL"
+        );
+        Ok(report)
+    }
+
+    /// Write a record and everything under it into a directory of JSONL files
+    ///
+    /// The bundle holds the record plus the children declared in
+    /// `Record::cascade_deletes` (recursively), one `<collection>.jsonl` per
+    /// collection with the latest version of each record. `dest` is created and
+    /// must be empty. Each `(child_collection, fk_field, parent_collection)` in
+    /// `refs` is checked across the bundled records; references to records the
+    /// bundle doesn't contain are reported rather than silently dropped.
+    pub fn export_bundle<T: Record>(&self, id: &str, dest: &Path, refs: &[(&str, &str, &str)]) -> Result<BundleReport> {
+        let collection = T::collection_name();
+        Self::validate_collection_name(collection)?;
+        if !self.record_exists(collection, id)? {
+            return Err(StoreError::NotFound {
+                collection: collection.to_string(),
+                id: id.to_string(),
+            });
+        }
+
+        let mut targets = vec![(collection.to_string(), id.to_string())];
+        Self::collect_cascade_targets(&self.db, id, T::cascade_deletes(), &mut targets)?;
+
+        let mut bundle: BTreeMap<String, BTreeMap<String, serde_json::Value>> = BTreeMap::new();
+        for (collection, id) in targets {
+            let data_json: String = self.db.query_row(
+                "SELECT data_json FROM records WHERE collection = ?1 AND id = ?2",
+                rusqlite::params![collection, id],
+                |row| row.get(0),
+            )?;
+            bundle
+                .entry(collection)
+                .or_default()
+                .insert(id, serde_json::from_str(&data_json)?);
+        }
+
+        let mut report = BundleReport::default();
+        for (child, fk_field, parent) in refs {
+            Self::validate_field_name(fk_field)?;
+            let Some(records) = bundle.get(*child) else {
+                continue;
+            };
+            for (id, record) in records {
+                let parent_id = match &record[*fk_field] {
+                    serde_json::Value::Null => continue,
+                    serde_json::Value::String(s) => s.clone(),
+                    value => value.to_string(),
+                };
+                if !bundle
+                    .get(*parent)
+                    .is_some_and(|parents| parents.contains_key(&parent_id))
+                {
+                    warn!(
+                        collection = child,
+                        id, fk_field, parent, parent_id, "Bundled record references a record outside the bundle"
+                    );
+                    report.dangling.push(DanglingReference {
+                        collection: child.to_string(),
+                        id: id.clone(),
+                        fk_field: fk_field.to_string(),
+                        parent_collection: parent.to_string(),
+                        parent_id,
+                    });
+                }
+            }
+        }
+
+        fs::create_dir_all(dest).map_err(|e| StoreError::io("Failed to create bundle directory", e))?;
+        if fs::read_dir(dest)?.next().is_some() {
+            return Err(StoreError::Io(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("Bundle directory isn't empty: {}", dest.display()),
+            )));
+        }
+        for (collection, records) in &bundle {
+            let mut content = String::new();
+            for record in records.values() {
+                content.push_str(&jsonl::to_canonical_json(record)?);
+                content.push('\n');
+            }
+            fs::write(dest.join(format!("{}.jsonl", collection)), content)
+                .map_err(|e| StoreError::io("Failed to write bundle file", e))?;
+            report.records.insert(collection.clone(), records.len());
+        }
+
+        info!(collection, id, dest = ?dest, records = ?report.records, dangling = report.dangling.len(), "Exported bundle");
+        Ok(report)
+    }
+
+    /// Import every `<collection>.jsonl` in a bundle directory, see `export_bundle`
+    ///
+    /// Each file goes through `import_jsonl` with `strategy`; reports are keyed
+    /// by collection.
+    pub fn import_bundle(&mut self, dir: &Path, strategy: ImportStrategy) -> Result<BTreeMap<String, ImportReport>> {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(dir).map_err(|e| StoreError::io("Failed to read bundle directory", e))? {
+            let path = entry?.path();
+            if path.extension().and_then(|s| s.to_str()) != Some("jsonl") {
+                continue;
+            }
+            if let Some(collection) = path.file_stem().and_then(|s| s.to_str()) {
+                files.push((collection.to_string(), path.clone()));
+            }
+        }
+        files.sort();
+
+        let mut reports = BTreeMap::new();
+        for (collection, path) in files {
+            let file = fs::File::open(&path).map_err(|e| StoreError::io("Failed to open bundle file", e))?;
+            let report = self.import_jsonl(&collection, std::io::BufReader::new(file), strategy)?;
+            reports.insert(collection, report);
+        }
+        Ok(reports)
+    }
+
+    /// Write every live record to one JSON document, for backups and moving a store
+    ///
+    /// The document maps each collection to its records, next to the store
+    /// version and the index specs in `schema.json`. Records are read from
+    /// SQLite as raw JSON, so collections need no registered type; sync first if
+    /// JSONL may have changed. Tombstones and older versions aren't included.
+    pub fn export_snapshot(&self, writer: impl Write) -> Result<()> {
+        let mut snapshot = Snapshot {
+            version: CURRENT_VERSION,
+            schema: self.schema.clone(),
+            collections: BTreeMap::new(),
+        };
+        let mut stmt = self
+            .db
+            .prepare("SELECT collection, data_json FROM records ORDER BY collection, id")?;
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+        for row in rows {
+            let (collection, data_json) = row?;
+            snapshot
+                .collections
+                .entry(collection)
+                .or_default()
+                .push(serde_json::from_str(&data_json)?);
+        }
+
+        let mut writer = std::io::BufWriter::new(writer);
+        serde_json::to_writer(&mut writer, &snapshot)?;
+        writer.flush()?;
+        info!(collections = snapshot.collections.len(), "Exported snapshot");
+        Ok(())
+    }
+
+    /// Fill an empty store from a document written by `export_snapshot`
+    ///
+    /// Writes each collection's JSONL file and syncs it into SQLite. The
+    /// snapshot's index specs are added to `schema.json` (specs already there
+    /// win), so collections are indexed like in the exported store even if no
+    /// process registers their type. Fails if the store already has records
+    /// or JSONL files, or if the snapshot comes from a newer store version.
+    /// Returns the number of records imported per collection.
+    pub fn import_snapshot(&mut self, reader: impl Read) -> Result<BTreeMap<String, usize>> {
+        self.ensure_writable()?;
+        if self.in_memory {
+            return Err(StoreError::Io(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "An in-memory store has no JSONL to import into",
+            )));
+        }
+
+        let snapshot: Snapshot = serde_json::from_reader(std::io::BufReader::new(reader))?;
+        if snapshot.version > CURRENT_VERSION {
+            return Err(StoreError::Schema(format!(
+                "Snapshot is from store version {}, newer than this build's {}",
+                snapshot.version, CURRENT_VERSION
+            )));
+        }
+        let has_records: bool = self
+            .db
+            .query_row("SELECT EXISTS(SELECT 1 FROM records)", [], |row| row.get(0))?;
+        if has_records || !self.jsonl_collections()?.is_empty() {
+            return Err(StoreError::Io(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("Store isn't empty: {}", self.base_path.display()),
+            )));
+        }
+
+        // Check every record before writing anything
+        let mut files = Vec::new();
+        for (collection, records) in &snapshot.collections {
+            Self::validate_collection_name(collection)?;
+            let mut ids = HashSet::new();
+            let mut content = String::new();
+            for record in records {
+                let id = record.get("id").and_then(|v| v.as_str()).ok_or_else(|| {
+                    StoreError::Corrupt(format!("Snapshot record in {} has no string 'id' field", collection))
+                })?;
+                Self::validate_id(id)?;
+                if !ids.insert(id) {
+                    return Err(StoreError::Corrupt(format!(
+                        "Snapshot holds {}/{} more than once",
+                        collection, id
+                    )));
+                }
+                content.push_str(&jsonl::to_canonical_json(record)?);
+                content.push('\n');
+            }
+            files.push((collection, content, records.len()));
+        }
+
+        for (collection, specs) in &snapshot.schema {
+            self.schema.entry(collection.clone()).or_insert_with(|| specs.clone());
+        }
+        self.save_schema()?;
+
+        let mut counts = BTreeMap::new();
+        for (collection, content, count) in files {
+            let path = self.jsonl_dir.join(format!("{}.jsonl", collection));
+            let mut file = fs::File::create(&path).map_err(|e| StoreError::io("Failed to create JSONL file", e))?;
+            file.write_all(content.as_bytes())?;
+            file.sync_all()?;
+            self.sync_collection(collection)?;
+            counts.insert(collection.clone(), count);
+        }
+        info!(records = ?counts, "Imported snapshot");
+        Ok(counts)
+    }
+
+    /// Report on the store's health without modifying anything
+    ///
+    /// Combines staleness, drift between SQLite and JSONL (as `sync_dry_run`
+    /// computes it), and JSONL lines that reads skip or have to tie-break.
+    /// Use `check_with_refs` to also look for orphaned foreign keys.
+    pub fn check(&self) -> Result<HealthReport> {
+        self.check_with_refs(&[])
+    }
+
+    /// Like `check`, also running `find_orphans` for each
+    /// `(child_collection, fk_field, parent_collection)` reference
+    pub fn check_with_refs(&self, refs: &[(&str, &str, &str)]) -> Result<HealthReport> {
+        let mut report = HealthReport {
+            stale: self.is_stale()?,
+            drift: self
+                .sync_dry_run()?
+                .collections
+                .into_iter()
+                .filter(|c| c.added + c.updated + c.removed > 0)
+                .collect(),
+            ..Default::default()
+        };
+
+        if !self.in_memory {
+            for entry in fs::read_dir(&self.jsonl_dir)? {
+                let path = entry?.path();
+                if path.extension().and_then(|s| s.to_str()) != Some("jsonl") {
+                    continue;
+                }
+                let Some(collection) = path.file_stem().and_then(|s| s.to_str()) else {
+                    continue;
+                };
+
+                let scan = jsonl::scan_jsonl(&path)?;
+                if !scan.is_clean() {
+                    report.jsonl.push(CollectionJsonlHealth {
+                        collection: collection.to_string(),
+                        unparseable_lines: scan.unparseable_lines,
+                        archived_unparseable_lines: scan.archived_unparseable_lines,
+                        ambiguous_ids: scan.ambiguous_ids,
+                    });
+                }
+            }
+            report.jsonl.sort_by(|a, b| a.collection.cmp(&b.collection));
+        }
+
+        for (child, field, parent) in refs {
+            let ids = self.find_orphans(child, field, parent)?;
+            if !ids.is_empty() {
+                report.orphans.push(OrphanedRecords {
+                    child_collection: child.to_string(),
+                    fk_field: field.to_string(),
+                    parent_collection: parent.to_string(),
+                    ids,
+                });
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Compare the latest live records in this store's JSONL files against another store's
+    ///
+    /// Reads JSONL only, so neither SQLite index needs to be up to date and
+    /// nothing is written. Tombstoned records count as absent. With
+    /// `collection`, only that collection is compared; otherwise every
+    /// collection with a JSONL file in either store is.
+    pub fn diff(&self, other: &Store, collection: Option<&str>) -> Result<StoreDiff> {
+        let collections = match collection {
+            Some(collection) => {
+                Self::validate_collection_name(collection)?;
+                vec![collection.to_string()]
+            }
+            None => {
+                let mut all = self.jsonl_collections()?;
+                all.extend(other.jsonl_collections()?);
+                all.sort();
+                all.dedup();
+                all
+            }
+        };
+
+        let mut diff = StoreDiff::default();
+        for collection in collections {
+            let mut theirs = other.live_jsonl_records(&collection)?;
+            let mut c = CollectionDiff {
+                collection,
+                ..Default::default()
+            };
+            for (id, record) in self.live_jsonl_records(&c.collection)? {
+                match theirs.remove(&id) {
+                    None => c.only_in_self.push(id),
+                    Some(their_record) if their_record != record => c.changed.push(id),
+                    Some(_) => {}
+                }
+            }
+            c.only_in_other.extend(theirs.into_keys());
+
+            if !(c.only_in_self.is_empty() && c.only_in_other.is_empty() && c.changed.is_empty()) {
+                c.only_in_self.sort();
+                c.only_in_other.sort();
+                c.changed.sort();
+                diff.collections.push(c);
+            }
+        }
+        Ok(diff)
+    }
+
+    /// Latest non-tombstone version of each record in a collection's JSONL file
+    fn live_jsonl_records(&self, collection: &str) -> Result<HashMap<String, serde_json::Value>> {
+        let Some(path) = self.jsonl_path(collection) else {
+            return Ok(HashMap::new());
+        };
+        let mut records = jsonl::read_jsonl_latest(&path)?;
+        records.retain(|_, record| !record.get("deleted").and_then(|v| v.as_bool()).unwrap_or(false));
+        Ok(records)
+    }
+
+    // ========================================================================
+    // Git Integration
+    // ========================================================================
+
+    /// Install git hooks for automatic sync
+    pub fn install_git_hooks(&self) -> Result<()> {
+        self.ensure_writable()?;
+
+        info!("Installing git hooks");
+
+        // Find git directory
+        let git_dir = self.find_git_dir()?;
+        let hooks_dir = git_dir.join("hooks");
+
+        // Create hooks directory if it doesn't exist
+        fs::create_dir_all(&hooks_dir).map_err(|e| StoreError::io("Failed to create hooks directory", e))?;
+
+        // Install all hooks
+        self.install_hook(&hooks_dir, "pre-commit", "taskstore sync")?;
+        self.install_hook(&hooks_dir, "post-merge", "taskstore sync")?;
+        self.install_hook(&hooks_dir, "post-rebase", "taskstore sync")?;
+        self.install_hook(&hooks_dir, "pre-push", "taskstore sync")?;
+        self.install_hook(&hooks_dir, "post-checkout", "taskstore sync")?;
+
+        // Install .gitattributes for merge driver
+        self.install_gitattributes()?;
+
+        info!("Git hooks installed successfully");
+        Ok(())
+    }
+
+    fn find_git_dir(&self) -> Result<PathBuf> {
+        if self.in_memory {
+            return Err(StoreError::Git(
+                "Git integration is not available for an in-memory store".to_string(),
+            ));
+        }
+
+        let mut current = self.base_path.clone();
+
+        // Walk up to find .git
+        loop {
+            let git_path = current.join(".git");
+            if git_path.exists() {
+                if git_path.is_dir() {
+                    return Ok(git_path);
+                } else {
+                    // Worktree - read .git file
+                    let content = fs::read_to_string(&git_path)?;
+                    let gitdir = content
+                        .strip_prefix("gitdir: ")
+                        .ok_or_else(|| StoreError::Git("Invalid .git file format".to_string()))?
+                        .trim();
+                    return Ok(PathBuf::from(gitdir));
+                }
+            }
+
+            if !current.pop() {
+                break;
+            }
+        }
+
+        Err(StoreError::Git("Not in a git repository".to_string()))
+    }
+
+    fn install_hook(&self, hooks_dir: &Path, hook_name: &str, command: &str) -> Result<()> {
+        let hook_path = hooks_dir.join(hook_name);
+        let hook_content = format!("#!/bin/sh\n# Auto-generated by taskstore\n{}\n", command);
+
+        if hook_path.exists() {
+            let existing = fs::read_to_string(&hook_path)?;
+            if existing.contains(command) {
+                debug!("Hook {} already contains command", hook_name);
+                return Ok(());
+            }
+            // Append to existing hook
+            fs::write(&hook_path, format!("{}\n{}", existing, command))?;
+        } else {
+            fs::write(&hook_path, hook_content)?;
+        }
+
+        // Make executable (Unix only)
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mut perms = fs::metadata(&hook_path)?.permissions();
+            perms.set_mode(0o755);
+            fs::set_permissions(&hook_path, perms)?;
+        }
+
+        Ok(())
+    }
+
+    fn install_gitattributes(&self) -> Result<()> {
+        // Find repo root
+        let mut repo_root = self.base_path.clone();
+        while !repo_root.join(".git").exists() && repo_root.pop() {}
+
+        let gitattributes_path = repo_root.join(".gitattributes");
+        let jsonl_dir = self
+            .jsonl_dir
+            .strip_prefix(&repo_root)
+            .ok()
+            .filter(|rel| !rel.as_os_str().is_empty())
+            .map(|rel| rel.to_string_lossy().into_owned())
+            .unwrap_or_else(|| ".taskstore".to_string());
+        let merge_rule = format!("{}/*.jsonl merge=taskstore-merge", jsonl_dir);
+
+        if gitattributes_path.exists() {
+            let existing = fs::read_to_string(&gitattributes_path)?;
+            if existing.contains(&merge_rule) {
+                info!(".gitattributes already configured");
+                return Ok(());
+            }
+
+            // Append rule
+            let mut file = fs::OpenOptions::new().append(true).open(&gitattributes_path)?;
+            use std::io::Write;
+            writeln!(file, "\n{}", merge_rule)?;
+        } else {
+            // Create new
+            fs::write(&gitattributes_path, format!("{}\n", merge_rule))?;
+        }
+
+        // Configure git merge driver
+        self.configure_merge_driver()?;
+
+        info!(".gitattributes configured");
+        Ok(())
+    }
+
+    fn configure_merge_driver(&self) -> Result<()> {
+        use std::process::Command;
+
+        let output = Command::new("git")
+            .args([
+                "config",
+                "--local",
+                "merge.taskstore-merge.name",
+                "TaskStore JSONL merge driver",
+            ])
+            .output()?;
+
+        if !output.status.success() {
+            return Err(StoreError::Schema("Failed to configure merge driver name".to_string()));
+        }
+
+        let output = Command::new("git")
             .args([
                 "config",
                 "--local",
                 "merge.taskstore-merge.driver",
                 "taskstore-merge %O %A %B %P",
             ])
-            .output()?;
+            .output()?;
+
+        if !output.status.success() {
+            return Err(StoreError::Schema(
+                "Failed to configure merge driver command".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for Store {
+    fn drop(&mut self) {
+        if let Err(e) = self.fsync.flush() {
+            warn!(error = %e, "Failed to fsync JSONL appends when closing the store");
+        }
+    }
+}
+
+/// Read-only view of a `Store` inside `Store::batch`
+pub struct Batch<'a> {
+    store: &'a Store,
+}
+
+impl Batch<'_> {
+    /// Get a record by ID, see `Store::get`
+    pub fn get<T: Record>(&self, id: &str) -> Result<Option<T>> {
+        self.store.get(id)
+    }
+
+    /// Get several records by ID, see `Store::get_many`
+    pub fn get_many<T: Record>(&self, ids: &[&str]) -> Result<Vec<Option<T>>> {
+        self.store.get_many(ids)
+    }
+
+    /// Check whether a record exists, see `Store::exists`
+    pub fn exists<T: Record>(&self, id: &str) -> Result<bool> {
+        self.store.exists::<T>(id)
+    }
+
+    /// List records with optional filtering, see `Store::list`
+    pub fn list<T: Record>(&self, filters: &[Filter]) -> Result<Vec<T>> {
+        self.store.list(filters)
+    }
+
+    /// List records with ordering and pagination, see `Store::list_with`
+    pub fn list_with<T: Record>(&self, filters: &[Filter], options: &ListOptions) -> Result<Vec<T>> {
+        self.store.list_with(filters, options)
+    }
+
+    /// Run a `Query`'s filters and options
+    pub fn query<T: Record>(&self, query: &Query) -> Result<Vec<T>> {
+        self.store.list_with(query.filters(), query.options())
+    }
+
+    /// The matching record with the smallest `order_by` value, see `Store::first`
+    pub fn first<T: Record>(&self, filters: &[Filter], order_by: &str) -> Result<Option<T>> {
+        self.store.first(filters, order_by)
+    }
+
+    /// The matching record with the largest `order_by` value, see `Store::last`
+    pub fn last<T: Record>(&self, filters: &[Filter], order_by: &str) -> Result<Option<T>> {
+        self.store.last(filters, order_by)
+    }
+
+    /// Count records matching `filters`, see `Store::count`
+    pub fn count<T: Record>(&self, filters: &[Filter]) -> Result<u64> {
+        self.store.count::<T>(filters)
+    }
+}
+
+/// Writes inside `Store::transaction`
+///
+/// Creates, updates, and deletes hit SQLite right away, under the
+/// transaction, and queue their JSONL lines until it commits.
+pub struct Txn<'a> {
+    store: &'a Store,
+    tx: rusqlite::Transaction<'a>,
+    writes: Vec<TxnWrite>,
+}
+
+/// A write buffered by a `Txn`: its collection, record ID, kind, and JSONL line
+struct TxnWrite {
+    collection: String,
+    id: String,
+    op: WriteOp,
+    line: String,
+}
+
+impl Txn<'_> {
+    /// Create or replace a record, see `Store::create`
+    pub fn create<T: Record>(&mut self, record: T) -> Result<String> {
+        let collection = T::collection_name();
+        Store::validate_collection_name(collection)?;
+        Store::validate_id(record.id())?;
+        record.validate()?;
+
+        let op = if self.store.record_exists(collection, record.id())? {
+            WriteOp::Update
+        } else {
+            WriteOp::Create
+        };
+        let record = Store::before_write_with(&self.store.hooks, record, op)?;
+        let line = Store::insert_record_tx(&self.tx, &record)?;
+        let id = record.id().to_string();
+        self.writes.push(TxnWrite {
+            collection: collection.to_string(),
+            id: id.clone(),
+            op,
+            line,
+        });
+        Ok(id)
+    }
+
+    /// Update a record, see `Store::update`
+    pub fn update<T: Record>(&mut self, record: T) -> Result<()> {
+        self.create(record)?;
+        Ok(())
+    }
+
+    /// Delete a record and its `cascade_deletes` children, see `Store::delete`
+    pub fn delete<T: Record>(&mut self, id: &str) -> Result<()> {
+        let mut targets = vec![(T::collection_name().to_string(), id.to_string())];
+        Store::collect_cascade_targets(&self.tx, id, T::cascade_deletes(), &mut targets)?;
+
+        for (collection, id) in targets {
+            let stored: Option<String> = self
+                .tx
+                .query_row(
+                    "SELECT data_json FROM records WHERE collection = ?1 AND id = ?2",
+                    rusqlite::params![collection, id],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            let mut tombstone = Store::tombstone(&id, stored.as_deref())?;
+            self.store.hooks.before(&collection, WriteOp::Delete, &mut tombstone)?;
+            Store::delete_record_tx(&self.tx, &collection, &id)?;
+            self.writes.push(TxnWrite {
+                line: jsonl::to_canonical_json(&tombstone)?,
+                collection,
+                id,
+                op: WriteOp::Delete,
+            });
+        }
+        Ok(())
+    }
+
+    /// Get a record by ID, including this transaction's writes, see `Store::get`
+    pub fn get<T: Record>(&self, id: &str) -> Result<Option<T>> {
+        self.store.get(id)
+    }
+
+    /// List records, including this transaction's writes, see `Store::list`
+    pub fn list<T: Record>(&self, filters: &[Filter]) -> Result<Vec<T>> {
+        self.store.list(filters)
+    }
+}
+
+/// Streaming iterator returned by `Store::list_iter`
+pub struct ListIter<'a, T: Record> {
+    store: &'a Store,
+    filters: Vec<Filter>,
+    composites: Vec<&'static [&'static str]>,
+    buffer: VecDeque<String>,
+    last_key: Option<(i64, String)>,
+    done: bool,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Record> Iterator for ListIter<'_, T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.buffer.is_empty() && !self.done {
+            match self.store.list_page(
+                T::collection_name(),
+                &self.filters,
+                &self.composites,
+                self.last_key.as_ref(),
+            ) {
+                Ok(page) => {
+                    if page.len() < LIST_ITER_PAGE_SIZE {
+                        self.done = true;
+                    }
+                    if let Some((updated_at, id, _)) = page.last() {
+                        self.last_key = Some((*updated_at, id.clone()));
+                    }
+                    self.buffer.extend(page.into_iter().map(|(_, _, data_json)| data_json));
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+
+        let data_json = self.buffer.pop_front()?;
+        match serde_json::from_str(&data_json) {
+            Ok(record) => Some(Ok(record)),
+            Err(e) => {
+                self.done = true;
+                self.buffer.clear();
+                Some(Err(e.into()))
+            }
+        }
+    }
+}
+
+/// Row-by-row iterator returned by `Store::iter`
+pub struct RowIter<'a, T: Record> {
+    rows: RowCursor<'a>,
+    done: bool,
+    _marker: PhantomData<T>,
+}
+
+/// A prepared statement and the rows it is stepping through
+#[ouroboros::self_referencing]
+struct RowCursor<'a> {
+    stmt: rusqlite::Statement<'a>,
+    #[borrows(mut stmt)]
+    #[not_covariant]
+    rows: rusqlite::Rows<'this>,
+}
+
+impl<T: Record> Iterator for RowIter<'_, T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let data_json = self
+            .rows
+            .with_rows_mut(|rows| Ok::<_, StoreError>(rows.next()?.map(|row| row.get::<_, String>(0)).transpose()?));
+        let result = match data_json {
+            Ok(Some(data_json)) => serde_json::from_str(&data_json).map_err(StoreError::from),
+            Ok(None) => {
+                self.done = true;
+                return None;
+            }
+            Err(e) => Err(e),
+        };
+        self.done = result.is_err();
+        Some(result)
+    }
+}
+
+/// Last value `now_ms` returned in this process
+static LAST_NOW_MS: AtomicI64 = AtomicI64::new(0);
+
+/// Current time in milliseconds since the Unix epoch, for `updated_at`
+///
+/// Strictly increasing within a process: a call in the same millisecond as the
+/// previous one (or after the clock stepped back) returns the previous value
+/// plus one. Two writes from one process therefore never share an `updated_at`,
+/// which latest-wins reads and the merge driver rely on to order them. A burst
+/// of more than one call per millisecond runs slightly ahead of the wall clock
+/// until it catches up.
+pub fn now_ms() -> i64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    let wall = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("System time before Unix epoch")
+        .as_millis() as i64;
+    let previous = LAST_NOW_MS
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |last| Some(wall.max(last + 1)))
+        .expect("closure always returns Some");
+    wall.max(previous + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::Query;
+    use crate::record::Reference;
+    use serde::{Deserialize, Serialize};
+    use tempfile::TempDir;
+
+    // Test record type
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    struct TestRecord {
+        id: String,
+        name: String,
+        status: String,
+        count: i64,
+        active: bool,
+        updated_at: i64,
+    }
+
+    impl Record for TestRecord {
+        fn id(&self) -> &str {
+            &self.id
+        }
+
+        fn updated_at(&self) -> i64 {
+            self.updated_at
+        }
+
+        fn collection_name() -> &'static str {
+            "test_records"
+        }
+
+        fn indexed_fields(&self) -> HashMap<String, IndexValue> {
+            let mut fields = HashMap::new();
+            fields.insert("status".to_string(), IndexValue::String(self.status.clone()));
+            fields.insert("count".to_string(), IndexValue::Int(self.count));
+            fields.insert("active".to_string(), IndexValue::Bool(self.active));
+            fields
+        }
+    }
+
+    #[test]
+    fn test_store_open_creates_directory() {
+        let temp = TempDir::new().unwrap();
+
+        let _store = Store::open(temp.path()).unwrap();
+        let store_path = temp.path().join(".taskstore");
+        assert!(store_path.exists());
+        assert!(store_path.join("taskstore.db").exists());
+        assert!(store_path.join(".gitignore").exists());
+        assert!(store_path.join(".version").exists());
+    }
+
+    #[test]
+    fn test_generic_create() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+
+        let record = TestRecord {
+            id: "rec1".to_string(),
+            name: "Test Record 1".to_string(),
+            status: "active".to_string(),
+            count: 42,
+            active: true,
+            updated_at: now_ms(),
+        };
+
+        let id = store.create(record.clone()).unwrap();
+        assert_eq!(id, "rec1");
+
+        // Verify JSONL file was created
+        let jsonl_path = temp.path().join(".taskstore/test_records.jsonl");
+        assert!(jsonl_path.exists());
+
+        // Verify record in SQLite
+        let retrieved: Option<TestRecord> = store.get("rec1").unwrap();
+        assert!(retrieved.is_some());
+        let retrieved = retrieved.unwrap();
+        assert_eq!(retrieved.name, "Test Record 1");
+        assert_eq!(retrieved.status, "active");
+        assert_eq!(retrieved.count, 42);
+        assert!(retrieved.active);
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+    struct StampedRecord {
+        id: String,
+        created_at: i64,
+        updated_at: i64,
+    }
+
+    impl Record for StampedRecord {
+        fn id(&self) -> &str {
+            &self.id
+        }
+
+        fn updated_at(&self) -> i64 {
+            self.updated_at
+        }
+
+        fn created_at(&self) -> Option<i64> {
+            Some(self.created_at)
+        }
+
+        fn collection_name() -> &'static str {
+            "stamped_records"
+        }
+
+        fn set_id(&mut self, id: String) {
+            self.id = id;
+        }
+
+        fn set_created_at(&mut self, created_at: i64) {
+            self.created_at = created_at;
+        }
+
+        fn set_updated_at(&mut self, updated_at: i64) {
+            self.updated_at = updated_at;
+        }
+    }
+
+    #[test]
+    fn test_create_with_generated_id() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+
+        let before = now_ms();
+        let id = store
+            .create_with_generated_id(StampedRecord {
+                id: String::new(),
+                created_at: 0,
+                updated_at: 0,
+            })
+            .unwrap();
+        assert!(uuid::Uuid::parse_str(&id).is_ok());
+
+        let retrieved: StampedRecord = store.get(&id).unwrap().unwrap();
+        assert!(retrieved.created_at >= before);
+        assert_eq!(retrieved.created_at, retrieved.updated_at);
+
+        // An explicit ID is kept
+        let id = store
+            .create_with_generated_id(StampedRecord {
+                id: "explicit".to_string(),
+                created_at: 0,
+                updated_at: 0,
+            })
+            .unwrap();
+        assert_eq!(id, "explicit");
+    }
+
+    #[test]
+    fn test_created_at_is_indexed_for_ordering() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        for (id, created_at, updated_at) in [("old", 1000, 5000), ("new", 3000, 4000), ("mid", 2000, 6000)] {
+            store
+                .create(StampedRecord {
+                    id: id.to_string(),
+                    created_at,
+                    updated_at,
+                })
+                .unwrap();
+        }
+
+        let ids = |query: Query| -> Vec<String> {
+            let records: Vec<StampedRecord> = store.list_with(query.filters(), query.options()).unwrap();
+            records.into_iter().map(|r| r.id).collect()
+        };
+        assert_eq!(ids(Query::new().order_by("created_at")), vec!["old", "mid", "new"]);
+        assert_eq!(
+            ids(Query::new().gte("created_at", 2000).order_by_desc("created_at")),
+            vec!["new", "mid"]
+        );
+        assert_eq!(ids(Query::new()), vec!["mid", "old", "new"]);
+    }
+
+    #[test]
+    fn test_get_or_create() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+
+        let make = |name: &str| TestRecord {
+            id: "config".to_string(),
+            name: name.to_string(),
+            status: "active".to_string(),
+            count: 0,
+            active: true,
+            updated_at: now_ms(),
+        };
+
+        let created = store.get_or_create("config", || make("Defaults")).unwrap();
+        assert_eq!(created.name, "Defaults");
+
+        // Existing record is returned and the default never runs
+        let existing = store
+            .get_or_create::<TestRecord>("config", || panic!("default must not run"))
+            .unwrap();
+        assert_eq!(existing, created);
+
+        let content = fs::read_to_string(temp.path().join(".taskstore/test_records.jsonl")).unwrap();
+        assert_eq!(content.lines().count(), 1);
+
+        // Default with the wrong ID is rejected
+        assert!(store.get_or_create("other", || make("Wrong ID")).is_err());
+    }
+
+    #[test]
+    fn test_open_read_only() {
+        let temp = TempDir::new().unwrap();
+
+        // Nothing to open yet, and nothing gets created
+        assert!(Store::open_read_only(temp.path()).is_err());
+        assert!(!temp.path().join(".taskstore").exists());
+
+        let record = TestRecord {
+            id: "rec1".to_string(),
+            name: "Read Only".to_string(),
+            status: "active".to_string(),
+            count: 1,
+            active: true,
+            updated_at: 1000,
+        };
+        {
+            let mut store = Store::open(temp.path()).unwrap();
+            store.create(record.clone()).unwrap();
+            store.sync().unwrap();
+        }
+
+        let mut store = Store::open_read_only(temp.path()).unwrap();
+        assert!(store.is_read_only());
+        let retrieved: TestRecord = store.get("rec1").unwrap().unwrap();
+        assert_eq!(retrieved, record);
+
+        // Writes fail without touching JSONL
+        let jsonl_path = temp.path().join(".taskstore/test_records.jsonl");
+        let before = fs::read_to_string(&jsonl_path).unwrap();
+        assert!(store.create(record.clone()).is_err());
+        assert!(store.delete::<TestRecord>("rec1").is_err());
+        assert!(store.sync().is_err());
+        assert_eq!(fs::read_to_string(&jsonl_path).unwrap(), before);
+
+        // A stale store can't be opened read-only
+        let mut content = before.clone();
+        content.push_str("{\"id\":\"rec2\",\"updated_at\":2000}\n");
+        fs::write(&jsonl_path, content).unwrap();
+        assert!(Store::open_read_only(temp.path()).is_err());
+    }
+
+    #[test]
+    fn test_open_in_memory() {
+        let mut store = Store::open_in_memory().unwrap();
+        assert!(store.is_in_memory());
+
+        for (i, status) in ["active", "draft", "active"].iter().enumerate() {
+            store
+                .create(TestRecord {
+                    id: format!("rec{}", i),
+                    name: "In Memory".to_string(),
+                    status: status.to_string(),
+                    count: i as i64,
+                    active: true,
+                    updated_at: 1000 + i as i64,
+                })
+                .unwrap();
+        }
+
+        let active = vec![Filter {
+            field: "status".to_string(),
+            op: FilterOp::Eq,
+            value: IndexValue::String("active".to_string()),
+        }];
+        let records: Vec<TestRecord> = store.list(&active).unwrap();
+        assert_eq!(records.len(), 2);
+
+        store.delete::<TestRecord>("rec0").unwrap();
+        assert!(store.get::<TestRecord>("rec0").unwrap().is_none());
+
+        assert_eq!(store.rebuild_indexes::<TestRecord>().unwrap(), 2);
+        assert_eq!(store.list::<TestRecord>(&active).unwrap().len(), 1);
+        store
+            .create_json("test_records", serde_json::json!({"id": "raw", "status": "active"}))
+            .unwrap();
+        assert_eq!(store.list_json("test_records", &active).unwrap().len(), 2);
+        store.delete::<TestRecord>("raw").unwrap();
+
+        // Sync must not wipe the only copy of the data
+        assert!(!store.is_stale().unwrap());
+        store.sync().unwrap();
+        assert_eq!(store.list::<TestRecord>(&[]).unwrap().len(), 2);
+        assert!(store.install_git_hooks().is_err());
+    }
+
+    #[test]
+    fn test_migrate_schema_upgrades_existing_store() {
+        fn add_priority(db: &Connection) -> Result<()> {
+            let exists: bool = db.query_row(
+                "SELECT EXISTS (SELECT 1 FROM pragma_table_info('records') WHERE name = 'priority')",
+                [],
+                |row| row.get(0),
+            )?;
+            if !exists {
+                db.execute("ALTER TABLE records ADD COLUMN priority INTEGER NOT NULL DEFAULT 0", [])?;
+            }
+            Ok(())
+        }
+        fn fail(_: &Connection) -> Result<()> {
+            Err(StoreError::Schema("interrupted".to_string()))
+        }
+        let v1_to_v2 = Migration {
+            from: 1,
+            to: 2,
+            up: add_priority,
+        };
+        let v2_to_v3 = Migration {
+            from: 2,
+            to: 3,
+            up: fail,
+        };
+
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        let version_path = temp.path().join(".taskstore/.version");
+        assert_eq!(fs::read_to_string(&version_path).unwrap(), "1");
+
+        // A failing step keeps the versions before it, and a rerun resumes from there
+        let err = store.migrate_schema(&[v1_to_v2, v2_to_v3], 3).unwrap_err();
+        assert!(matches!(err, StoreError::Schema(_)));
+        assert_eq!(fs::read_to_string(&version_path).unwrap(), "2");
+        let has_priority: bool = store
+            .db
+            .query_row(
+                "SELECT EXISTS (SELECT 1 FROM pragma_table_info('records') WHERE name = 'priority')",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(has_priority);
+
+        // Rerunning an already applied step is harmless
+        fs::write(&version_path, "1").unwrap();
+        store.migrate_schema(&[v1_to_v2], 2).unwrap();
+        assert_eq!(fs::read_to_string(&version_path).unwrap(), "2");
+
+        // A store from a newer version, or with no path forward, is refused
+        assert!(matches!(store.migrate_schema(&[], 1), Err(StoreError::Schema(_))));
+        fs::write(&version_path, "1").unwrap();
+        assert!(matches!(store.migrate_schema(&[], 2), Err(StoreError::Schema(_))));
+    }
+
+    #[test]
+    fn test_open_with_custom_paths() {
+        let temp = TempDir::new().unwrap();
+        let options = StoreOptions {
+            jsonl_dir: Some(temp.path().join("data")),
+            db_filename: Some("cache.sqlite".to_string()),
+            ..Default::default()
+        };
+
+        let mut store = Store::open_with(temp.path(), options.clone()).unwrap();
+        store
+            .create(TestRecord {
+                id: "rec1".to_string(),
+                name: "Relocated".to_string(),
+                status: "active".to_string(),
+                count: 1,
+                active: true,
+                updated_at: 1000,
+            })
+            .unwrap();
+        drop(store);
+
+        let base = temp.path().join(".taskstore");
+        assert!(temp.path().join("data/test_records.jsonl").exists());
+        assert!(base.join("cache.sqlite").exists());
+        assert!(!base.join("taskstore.db").exists());
+        let gitignore = fs::read_to_string(base.join(".gitignore")).unwrap();
+        assert!(gitignore.lines().any(|l| l == "cache.sqlite"));
+        assert!(gitignore.lines().any(|l| l == "cache.sqlite-wal"));
+
+        // A fresh database elsewhere rebuilds from the relocated JSONL
+        let db_path = temp.path().join("elsewhere/index.db");
+        let store = Store::open_with(
+            temp.path(),
+            StoreOptions {
+                db_path: Some(db_path.clone()),
+                ..options
+            },
+        )
+        .unwrap();
+        assert!(db_path.exists());
+        assert_eq!(store.jsonl_dir(), temp.path().join("data"));
+        assert!(store.get::<TestRecord>("rec1").unwrap().is_some());
+    }
+
+    #[test]
+    fn test_generic_get_nonexistent() {
+        let temp = TempDir::new().unwrap();
+        let store = Store::open(temp.path()).unwrap();
+
+        let result: Option<TestRecord> = store.get("nonexistent").unwrap();
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn test_create_many_and_upsert_many() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        let record = |id: &str, count: i64, updated_at: i64| TestRecord {
+            id: id.to_string(),
+            name: "Bulk".to_string(),
+            status: "pending".to_string(),
+            count,
+            active: true,
+            updated_at,
+        };
+
+        let ids = store
+            .create_many(vec![record("b", 1, 1000), record("a", 2, 1000), record("c", 3, 1000)])
+            .unwrap();
+        assert_eq!(ids, vec!["b", "a", "c"]);
+        let jsonl = fs::read_to_string(temp.path().join(".taskstore/test_records.jsonl")).unwrap();
+        let written: Vec<String> = jsonl
+            .lines()
+            .map(|line| serde_json::from_str::<TestRecord>(line).unwrap().id)
+            .collect();
+        assert_eq!(written, vec!["b", "a", "c"]);
+
+        // An existing or repeated ID rejects the whole batch
+        for batch in [
+            vec![record("d", 4, 1000), record("a", 5, 2000)],
+            vec![record("d", 4, 1000), record("d", 5, 2000)],
+        ] {
+            let err = store.create_many(batch).unwrap_err();
+            assert!(matches!(err, StoreError::Conflict(_)));
+        }
+        assert!(!store.exists::<TestRecord>("d").unwrap());
+        assert_eq!(
+            fs::read_to_string(temp.path().join(".taskstore/test_records.jsonl")).unwrap(),
+            jsonl
+        );
+
+        store
+            .upsert_many(vec![record("a", 20, 2000), record("d", 4, 2000)])
+            .unwrap();
+        assert_eq!(store.count::<TestRecord>(&[]).unwrap(), 4);
+        assert_eq!(store.get::<TestRecord>("a").unwrap().unwrap().count, 20);
+        assert_eq!(
+            store
+                .list::<TestRecord>(Query::new().eq("count", 20).filters())
+                .unwrap()
+                .len(),
+            1
+        );
+
+        // The JSONL stays the source of truth
+        store.sync().unwrap();
+        assert_eq!(store.get::<TestRecord>("a").unwrap().unwrap().count, 20);
+        assert_eq!(store.count::<TestRecord>(&[]).unwrap(), 4);
+    }
+
+    #[test]
+    fn test_transaction_commits_or_discards_all_writes() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        let dir = temp.path().join(".taskstore");
+        let record = |id: &str| TestRecord {
+            id: id.to_string(),
+            name: "Task".to_string(),
+            status: "pending".to_string(),
+            count: 0,
+            active: true,
+            updated_at: 1000,
+        };
+        let user = |id: &str, email: &str| User {
+            id: id.to_string(),
+            email: email.to_string(),
+            updated_at: 1000,
+        };
+        store.create(user("u1", "taken@example.com")).unwrap();
+        let users_jsonl = fs::read_to_string(dir.join("users.jsonl")).unwrap();
+
+        // A failure halfway leaves neither SQLite nor JSONL touched
+        let err = store
+            .transaction(|txn| {
+                txn.create(record("t1"))?;
+                assert!(txn.get::<TestRecord>("t1")?.is_some());
+                txn.create(user("u2", "taken@example.com"))
+            })
+            .unwrap_err();
+        assert!(matches!(err, StoreError::Conflict(_)));
+        assert!(!store.exists::<TestRecord>("t1").unwrap());
+        assert!(!dir.join("test_records.jsonl").exists());
+        assert_eq!(fs::read_to_string(dir.join("users.jsonl")).unwrap(), users_jsonl);
+
+        let count = store
+            .transaction(|txn| {
+                txn.create(record("t1"))?;
+                txn.create(record("t2"))?;
+                txn.create(user("u2", "new@example.com"))?;
+                txn.delete::<User>("u1")?;
+                Ok(txn.list::<TestRecord>(&[])?.len())
+            })
+            .unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(
+            fs::read_to_string(dir.join("test_records.jsonl"))
+                .unwrap()
+                .lines()
+                .count(),
+            2
+        );
+
+        // Everything landed in JSONL too
+        store.sync().unwrap();
+        assert_eq!(store.count::<TestRecord>(&[]).unwrap(), 2);
+        assert!(store.get::<User>("u1").unwrap().is_none());
+        assert_eq!(store.get::<User>("u2").unwrap().unwrap().email, "new@example.com");
+    }
+
+    #[test]
+    fn test_delete_writes_tombstone_with_last_fields() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        let record = |id: &str, updated_at: i64| TestRecord {
+            id: id.to_string(),
+            name: format!("Record {}", id),
+            status: "active".to_string(),
+            count: 1,
+            active: true,
+            updated_at,
+        };
+
+        store.create(record("kept", 1000)).unwrap();
+        // A future timestamp still loses to its tombstone
+        let future = now_ms() + 60_000;
+        store.create(record("gone", future)).unwrap();
+        store.delete::<TestRecord>("gone").unwrap();
+
+        let jsonl = fs::read_to_string(temp.path().join(".taskstore/test_records.jsonl")).unwrap();
+        let tombstone: serde_json::Value = serde_json::from_str(jsonl.lines().last().unwrap()).unwrap();
+        assert_eq!(tombstone["deleted"], true);
+        assert_eq!(tombstone["name"], "Record gone");
+        assert!(tombstone["deleted_at"].as_i64().unwrap() <= future);
+        assert!(tombstone["updated_at"].as_i64().unwrap() > future);
+
+        store.sync().unwrap();
+        assert!(store.get::<TestRecord>("gone").unwrap().is_none());
+        assert_eq!(store.list::<TestRecord>(&[]).unwrap().len(), 1);
+
+        let all = store.list_including_deleted::<TestRecord>().unwrap();
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[0].record.id, "kept");
+        assert!(!all[0].is_deleted());
+        assert_eq!(all[1].record.name, "Record gone");
+        assert!(all[1].is_deleted());
+
+        // Writing the record back restores it
+        let mut restored = all[1].record.clone();
+        restored.updated_at += 1;
+        store.update(restored).unwrap();
+        assert!(store.get::<TestRecord>("gone").unwrap().is_some());
+        assert!(
+            store
+                .list_including_deleted::<TestRecord>()
+                .unwrap()
+                .iter()
+                .all(|r| !r.is_deleted())
+        );
+    }
+
+    #[test]
+    fn test_update_if_unchanged_is_compare_and_swap() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        store
+            .create(TestRecord {
+                id: "rec1".to_string(),
+                name: "Counter".to_string(),
+                status: "active".to_string(),
+                count: 0,
+                active: true,
+                updated_at: 1000,
+            })
+            .unwrap();
+
+        let read: TestRecord = store.get("rec1").unwrap().unwrap();
+        let first = TestRecord {
+            count: 1,
+            updated_at: 2000,
+            ..read.clone()
+        };
+        assert!(store.update_if_unchanged(first, read.updated_at).unwrap());
+
+        // A second writer holding the same stale read loses
+        let stale = TestRecord {
+            count: 100,
+            updated_at: 3000,
+            ..read.clone()
+        };
+        assert!(!store.update_if_unchanged(stale, read.updated_at).unwrap());
+        assert_eq!(store.get::<TestRecord>("rec1").unwrap().unwrap().count, 1);
+
+        store.sync().unwrap();
+        assert_eq!(store.get::<TestRecord>("rec1").unwrap().unwrap().count, 1);
+
+        let missing = TestRecord {
+            id: "missing".to_string(),
+            ..read
+        };
+        assert!(store.update_if_unchanged(missing, 1000).unwrap_err().is_not_found());
+    }
+
+    #[test]
+    fn test_exists() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+
+        assert!(!store.exists::<TestRecord>("rec1").unwrap());
+        store
+            .create(TestRecord {
+                id: "rec1".to_string(),
+                name: "Present".to_string(),
+                status: "active".to_string(),
+                count: 1,
+                active: true,
+                updated_at: 1000,
+            })
+            .unwrap();
+        assert!(store.exists::<TestRecord>("rec1").unwrap());
+        assert!(!store.exists::<User>("rec1").unwrap());
+
+        store.delete::<TestRecord>("rec1").unwrap();
+        assert!(!store.exists::<TestRecord>("rec1").unwrap());
+    }
+
+    #[test]
+    fn test_generic_update() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+
+        // Create initial record
+        let mut record = TestRecord {
+            id: "rec1".to_string(),
+            name: "Original".to_string(),
+            status: "draft".to_string(),
+            count: 1,
+            active: false,
+            updated_at: 1000,
+        };
+        store.create(record.clone()).unwrap();
+
+        // Update record
+        record.name = "Updated".to_string();
+        record.status = "active".to_string();
+        record.count = 2;
+        record.active = true;
+        record.updated_at = 2000;
+        store.update(record.clone()).unwrap();
+
+        // Verify update
+        let retrieved: Option<TestRecord> = store.get("rec1").unwrap();
+        assert!(retrieved.is_some());
+        let retrieved = retrieved.unwrap();
+        assert_eq!(retrieved.name, "Updated");
+        assert_eq!(retrieved.status, "active");
+        assert_eq!(retrieved.count, 2);
+        assert!(retrieved.active);
+        assert_eq!(retrieved.updated_at, 2000);
+    }
+
+    #[test]
+    fn test_generic_delete() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+
+        // Create record
+        let record = TestRecord {
+            id: "rec1".to_string(),
+            name: "To Delete".to_string(),
+            status: "active".to_string(),
+            count: 1,
+            active: true,
+            updated_at: now_ms(),
+        };
+        store.create(record).unwrap();
+
+        // Delete record
+        store.delete::<TestRecord>("rec1").unwrap();
+
+        // Verify deleted from SQLite
+        let retrieved: Option<TestRecord> = store.get("rec1").unwrap();
+        assert!(retrieved.is_none());
+
+        // Verify tombstone in JSONL
+        let jsonl_path = temp.path().join(".taskstore/test_records.jsonl");
+        let content = fs::read_to_string(jsonl_path).unwrap();
+        assert!(content.contains("\"deleted\":true"));
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    struct ValidatedRecord {
+        id: String,
+        title: String,
+        updated_at: i64,
+    }
+
+    impl Record for ValidatedRecord {
+        fn id(&self) -> &str {
+            &self.id
+        }
+
+        fn updated_at(&self) -> i64 {
+            self.updated_at
+        }
+
+        fn collection_name() -> &'static str {
+            "validated_records"
+        }
+
+        fn validate(&self) -> std::result::Result<(), ValidationError> {
+            if self.title.trim().is_empty() {
+                return Err(ValidationError::new("title cannot be empty"));
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_validate_rejects_before_write() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+
+        let record = ValidatedRecord {
+            id: "rec1".to_string(),
+            title: "  ".to_string(),
+            updated_at: now_ms(),
+        };
+        let err = store.create(record).unwrap_err();
+        assert!(matches!(err, StoreError::Validation(_)));
+
+        // Nothing reached JSONL or SQLite
+        assert!(!temp.path().join(".taskstore/validated_records.jsonl").exists());
+        let retrieved: Option<ValidatedRecord> = store.get("rec1").unwrap();
+        assert!(retrieved.is_none());
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    struct User {
+        id: String,
+        email: String,
+        updated_at: i64,
+    }
+
+    impl Record for User {
+        fn id(&self) -> &str {
+            &self.id
+        }
+
+        fn updated_at(&self) -> i64 {
+            self.updated_at
+        }
+
+        fn collection_name() -> &'static str {
+            "users"
+        }
+
+        fn indexed_fields(&self) -> HashMap<String, IndexValue> {
+            let mut fields = HashMap::new();
+            fields.insert("email".to_string(), IndexValue::String(self.email.clone()));
+            fields
+        }
+
+        fn unique_fields() -> &'static [&'static str] {
+            &["email"]
+        }
+    }
+
+    #[test]
+    fn test_hooks_stamp_veto_and_roll_back() {
+        use std::sync::Mutex;
+
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        let jsonl_path = temp.path().join(".taskstore/test_records.jsonl");
+        let record = |id: &str, status: &str| TestRecord {
+            id: id.to_string(),
+            name: "Original".to_string(),
+            status: status.to_string(),
+            count: 1,
+            active: true,
+            updated_at: now_ms(),
+        };
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let seen_by_hook = Arc::clone(&seen);
+        store.set_hooks(StoreHooks {
+            before_write: Some(Box::new(|_, op, json| {
+                if json["status"] == "blocked" {
+                    return Err(StoreError::Validation(ValidationError::new("blocked by quota")));
+                }
+                if op != WriteOp::Delete {
+                    json["name"] = format!("{:?}", op).into();
+                }
+                Ok(())
+            })),
+            after_write: Some(Box::new(move |collection, op, json| {
+                seen_by_hook.lock().unwrap().push((collection.to_string(), op));
+                if json["status"] == "poison" {
+                    return Err(StoreError::Validation(ValidationError::new("after_write failed")));
+                }
+                Ok(())
+            })),
+        });
+
+        // before_write stamps the record
+        store.create(record("rec1", "active")).unwrap();
+        store.update(record("rec1", "active")).unwrap();
+        assert_eq!(store.get::<TestRecord>("rec1").unwrap().unwrap().name, "Update");
+
+        // before_write veto: nothing written, after_write never runs
+        let len = fs::metadata(&jsonl_path).unwrap().len();
+        assert!(matches!(
+            store.create(record("rec2", "blocked")),
+            Err(StoreError::Validation(_))
+        ));
+
+        // after_write failure: SQLite rolled back and the JSONL line taken back
+        assert!(store.update(record("rec1", "poison")).is_err());
+        assert_eq!(fs::metadata(&jsonl_path).unwrap().len(), len);
+        assert_eq!(store.get::<TestRecord>("rec1").unwrap().unwrap().status, "active");
+        store.sync().unwrap();
+        assert_eq!(store.get::<TestRecord>("rec1").unwrap().unwrap().status, "active");
+
+        store.delete::<TestRecord>("rec1").unwrap();
+        assert!(store.get::<TestRecord>("rec1").unwrap().is_none());
+
+        let ops: Vec<WriteOp> = seen.lock().unwrap().iter().map(|(_, op)| *op).collect();
+        assert_eq!(
+            ops,
+            vec![WriteOp::Create, WriteOp::Update, WriteOp::Update, WriteOp::Delete]
+        );
+    }
+
+    #[test]
+    fn test_on_change_fires_after_commit_in_order() {
+        use crate::hooks::{ChangeEvent, ChangeKind};
+        use std::sync::Mutex;
+
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        for name in ["first", "second"] {
+            let seen = Arc::clone(&seen);
+            store.on_change(Box::new(move |change: &ChangeEvent| {
+                seen.lock()
+                    .unwrap()
+                    .push((name, change.collection.clone(), change.id.clone(), change.kind));
+                Err(StoreError::Schema("callback errors are only logged".to_string()))
+            }));
+        }
+        let take = || std::mem::take(&mut *seen.lock().unwrap());
+
+        store
+            .create(Project {
+                id: "p1".to_string(),
+                updated_at: 1000,
+            })
+            .unwrap();
+        assert_eq!(
+            take(),
+            vec![
+                ("first", "projects".to_string(), "p1".to_string(), ChangeKind::Created),
+                ("second", "projects".to_string(), "p1".to_string(), ChangeKind::Created),
+            ]
+        );
+
+        // A failed write reports nothing
+        let duplicate = store.create_many(vec![
+            Project {
+                id: "p2".to_string(),
+                updated_at: 1000,
+            },
+            Project {
+                id: "p1".to_string(),
+                updated_at: 1000,
+            },
+        ]);
+        assert!(duplicate.is_err());
+        assert!(take().is_empty());
+
+        // A cascade reports every deleted record
+        store.create(member("m1", "p1")).unwrap();
+        take();
+        store.delete::<Project>("p1").unwrap();
+        let deleted: Vec<(String, ChangeKind)> = take()
+            .into_iter()
+            .filter(|(name, ..)| *name == "first")
+            .map(|(_, collection, id, kind)| (format!("{}/{}", collection, id), kind))
+            .collect();
+        assert_eq!(
+            deleted,
+            vec![
+                ("projects/p1".to_string(), ChangeKind::Deleted),
+                ("members/m1".to_string(), ChangeKind::Deleted),
+            ]
+        );
+
+        // Hooks set afterwards keep reporting changes
+        store.set_hooks(StoreHooks::default());
+        store
+            .create(Project {
+                id: "p3".to_string(),
+                updated_at: 1000,
+            })
+            .unwrap();
+        assert_eq!(take().len(), 2);
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn test_metrics_snapshot_counts_operations() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        let record = TestRecord {
+            id: "rec1".to_string(),
+            name: "Record 1".to_string(),
+            status: "active".to_string(),
+            count: 1,
+            active: true,
+            updated_at: 1000,
+        };
+        store.create(record.clone()).unwrap();
+        store.update(record).unwrap();
+        store.get::<TestRecord>("rec1").unwrap();
+        store.list::<TestRecord>(&[]).unwrap();
+        store.delete::<TestRecord>("rec1").unwrap();
+        store.sync().unwrap();
+
+        let metrics = store.metrics_snapshot();
+        let counts = &metrics.collections["test_records"];
+        assert_eq!(
+            (counts.creates, counts.updates, counts.deletes, counts.reads),
+            (1, 1, 1, 2)
+        );
+        assert_eq!(metrics.latency["read"].count, 2);
+        assert_eq!(metrics.latency["sync"].count, 1);
+        assert!(metrics.jsonl_bytes["test_records"] > 0);
+        assert!(
+            metrics
+                .to_prometheus()
+                .contains(r#"taskstore_operations_total{collection="test_records",op="read"} 2"#)
+        );
+    }
+
+    #[test]
+    fn test_unique_field_rejects_duplicate() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+
+        let alice = User {
+            id: "u1".to_string(),
+            email: "alice@example.com".to_string(),
+            updated_at: now_ms(),
+        };
+        store.create(alice.clone()).unwrap();
+
+        // Updating the same record with the same value is fine
+        store.update(alice).unwrap();
+
+        let err = store
+            .create(User {
+                id: "u2".to_string(),
+                email: "alice@example.com".to_string(),
+                updated_at: now_ms(),
+            })
+            .unwrap_err();
+        let StoreError::Conflict(dup) = err else {
+            panic!("expected a duplicate key conflict, got {:?}", err);
+        };
+        assert_eq!(dup.field, "email");
+        assert_eq!(dup.existing_id, "u1");
+
+        // The rejected record never reached JSONL
+        let content = fs::read_to_string(temp.path().join(".taskstore/users.jsonl")).unwrap();
+        assert!(!content.contains("\"u2\""));
+        assert!(store.get::<User>("u2").unwrap().is_none());
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    struct Project {
+        id: String,
+        updated_at: i64,
+    }
+
+    impl Record for Project {
+        fn id(&self) -> &str {
+            &self.id
+        }
+
+        fn updated_at(&self) -> i64 {
+            self.updated_at
+        }
+
+        fn collection_name() -> &'static str {
+            "projects"
+        }
+
+        fn cascade_deletes() -> &'static [ChildRelation] {
+            // projects <- members.project_id <- notes.member_id
+            &[ChildRelation {
+                collection: "members",
+                fk_field: "project_id",
+                children: &[ChildRelation {
+                    collection: "notes",
+                    fk_field: "member_id",
+                    children: &[],
+                }],
+            }]
+        }
+    }
+
+    /// Child of `Project`
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Member {
+        id: String,
+        project_id: String,
+        updated_at: i64,
+    }
+
+    impl Record for Member {
+        fn id(&self) -> &str {
+            &self.id
+        }
+
+        fn updated_at(&self) -> i64 {
+            self.updated_at
+        }
+
+        fn collection_name() -> &'static str {
+            "members"
+        }
+
+        fn indexed_fields(&self) -> HashMap<String, IndexValue> {
+            HashMap::from([("project_id".to_string(), self.project_id.clone().into())])
+        }
+    }
+
+    fn member(id: &str, project_id: &str) -> Member {
+        Member {
+            id: id.to_string(),
+            project_id: project_id.to_string(),
+            updated_at: 1000,
+        }
+    }
+
+    /// Child of `Member`, also pointing straight at its project
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Note {
+        id: String,
+        member_id: String,
+        project_id: String,
+        updated_at: i64,
+    }
+
+    impl Record for Note {
+        fn id(&self) -> &str {
+            &self.id
+        }
+
+        fn updated_at(&self) -> i64 {
+            self.updated_at
+        }
+
+        fn collection_name() -> &'static str {
+            "notes"
+        }
+    }
+
+    fn note(id: &str, member_id: &str, project_id: &str) -> Note {
+        Note {
+            id: id.to_string(),
+            member_id: member_id.to_string(),
+            project_id: project_id.to_string(),
+            updated_at: 1000,
+        }
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    struct Assignment {
+        id: String,
+        owner_email: Option<String>,
+        reviewer_email: Option<String>,
+        updated_at: i64,
+    }
+
+    impl Record for Assignment {
+        fn id(&self) -> &str {
+            &self.id
+        }
+
+        fn updated_at(&self) -> i64 {
+            self.updated_at
+        }
+
+        fn collection_name() -> &'static str {
+            "assignments"
+        }
+
+        fn references() -> &'static [Reference] {
+            &[
+                Reference {
+                    field: "owner_email",
+                    collection: "users",
+                    key: "email",
+                    allow_dangling: false,
+                },
+                Reference {
+                    field: "reviewer_email",
+                    collection: "users",
+                    key: "email",
+                    allow_dangling: true,
+                },
+            ]
+        }
+    }
+
+    #[test]
+    fn test_references_are_checked_and_resolved() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        store
+            .create(User {
+                id: "u1".to_string(),
+                email: "a@example.com".to_string(),
+                updated_at: now_ms(),
+            })
+            .unwrap();
+        let assignment = |owner: Option<&str>, reviewer: Option<&str>| Assignment {
+            id: "as1".to_string(),
+            owner_email: owner.map(str::to_string),
+            reviewer_email: reviewer.map(str::to_string),
+            updated_at: now_ms(),
+        };
+
+        let err = store.create(assignment(Some("nobody@example.com"), None)).unwrap_err();
+        assert!(matches!(err, StoreError::Validation(_)), "{err}");
+        assert!(store.get::<Assignment>("as1").unwrap().is_none());
+        assert!(!store.jsonl_path("assignments").unwrap().exists());
+
+        // Null references and ones allowed to dangle aren't checked
+        store.create(assignment(None, Some("gone@example.com"))).unwrap();
+        store
+            .update(assignment(Some("a@example.com"), Some("gone@example.com")))
+            .unwrap();
+
+        let record = store.get::<Assignment>("as1").unwrap().unwrap();
+        let owner: User = store.resolve(&record, "owner_email").unwrap().unwrap();
+        assert_eq!(owner.id, "u1");
+        assert!(store.resolve::<_, User>(&record, "reviewer_email").unwrap().is_none());
+        assert!(matches!(
+            store.resolve::<_, Project>(&record, "owner_email"),
+            Err(StoreError::Schema(_))
+        ));
+    }
+
+    #[test]
+    fn test_delete_cascades_to_children() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+
+        for id in ["p1", "p2"] {
+            store
+                .create(Project {
+                    id: id.to_string(),
+                    updated_at: 1000,
+                })
+                .unwrap();
+        }
+        for (id, project) in [("m1", "p1"), ("m2", "p2")] {
+            store.create(member(id, project)).unwrap();
+        }
+        for (id, member_id, project) in [("n1", "m1", "p1"), ("n2", "m2", "p2")] {
+            store.create(note(id, member_id, project)).unwrap();
+        }
+
+        store.delete::<Project>("p1").unwrap();
+
+        assert!(store.get::<Member>("m1").unwrap().is_none());
+        assert!(store.get::<Note>("n1").unwrap().is_none());
+        assert!(store.get::<Member>("m2").unwrap().is_some());
+        assert!(store.get::<Note>("n2").unwrap().is_some());
+
+        // Tombstones reached JSONL, so a full rebuild agrees
+        store.sync().unwrap();
+        assert!(store.get::<Member>("m1").unwrap().is_none());
+        assert!(store.get::<Note>("n1").unwrap().is_none());
+        assert!(store.get::<Note>("n2").unwrap().is_some());
+    }
+
+    #[test]
+    fn test_delete_finds_children_added_while_waiting_for_the_write_lock() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        store
+            .create(Project {
+                id: "p1".to_string(),
+                updated_at: 1000,
+            })
+            .unwrap();
+
+        // Another writer holds the lock while it adds a child of p1
+        let writer = Connection::open(temp.path().join(".taskstore").join(DEFAULT_DB_FILENAME)).unwrap();
+        writer.execute_batch("BEGIN IMMEDIATE").unwrap();
+        writer
+            .execute(
+                "INSERT INTO records (collection, id, data_json, updated_at) VALUES (?1, 'm1', ?2, 1000)",
+                rusqlite::params![
+                    Member::collection_name(),
+                    serde_json::to_string(&member("m1", "p1")).unwrap()
+                ],
+            )
+            .unwrap();
+        let delete = std::thread::spawn(move || {
+            store.delete::<Project>("p1").unwrap();
+            store
+        });
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        writer.execute_batch("COMMIT").unwrap();
+
+        let store = delete.join().unwrap();
+        assert!(store.get::<Project>("p1").unwrap().is_none());
+        assert!(store.get::<Member>("m1").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_foreign_keys_are_enforced_in_sqlite() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        store
+            .create(TestRecord {
+                id: "rec1".to_string(),
+                name: "Parent".to_string(),
+                status: "active".to_string(),
+                count: 1,
+                active: true,
+                updated_at: 1000,
+            })
+            .unwrap();
+        let index_rows = |store: &Store| -> i64 {
+            store
+                .db
+                .query_row("SELECT COUNT(*) FROM record_indexes WHERE id = 'rec1'", [], |row| {
+                    row.get(0)
+                })
+                .unwrap()
+        };
+        assert_eq!(index_rows(&store), 3);
+
+        // An index row for a record that doesn't exist is rejected
+        let err = store.db.execute(
+            "INSERT INTO record_indexes (collection, id, field_name, field_value_str)
+             VALUES ('test_records', 'ghost', 'status', 'active')",
+            [],
+        );
+        assert!(err.is_err());
+
+        // Removing the records row cascades to its index rows
+        store
+            .db
+            .execute(
+                "DELETE FROM records WHERE collection = 'test_records' AND id = 'rec1'",
+                [],
+            )
+            .unwrap();
+        assert_eq!(index_rows(&store), 0);
+    }
+
+    #[test]
+    fn test_rename_id_moves_record_and_repoints_children() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+
+        for id in ["p1", "p2"] {
+            store
+                .create(Project {
+                    id: id.to_string(),
+                    updated_at: 1000,
+                })
+                .unwrap();
+        }
+        store.create(member("m1", "p1")).unwrap();
+
+        store.rename_id::<Project>("p1", "p9").unwrap();
+
+        assert!(store.get::<Project>("p1").unwrap().is_none());
+        assert!(store.get::<Project>("p9").unwrap().unwrap().updated_at > 1000);
+        assert_eq!(store.get::<Member>("m1").unwrap().unwrap().project_id, "p9");
+        let members: Vec<Member> = store.list(Query::new().eq("project_id", "p9").filters()).unwrap();
+        assert_eq!(members.len(), 1);
+
+        let err = store.rename_id::<Project>("p9", "p2").unwrap_err();
+        assert!(
+            matches!(err, StoreError::Conflict(ref dup) if dup.field == "id"),
+            "{:?}",
+            err
+        );
+        assert!(store.rename_id::<Project>("p1", "p3").unwrap_err().is_not_found());
+
+        // Both the tombstone and the copies reached JSONL, so a full rebuild agrees
+        store.sync().unwrap();
+        assert!(store.get::<Project>("p1").unwrap().is_none());
+        assert!(store.get::<Project>("p9").unwrap().is_some());
+        assert!(store.get::<Project>("p2").unwrap().is_some());
+        assert_eq!(store.get::<Member>("m1").unwrap().unwrap().project_id, "p9");
+    }
+
+    #[test]
+    fn test_list_updated_since_and_between_without_indexing_updated_at() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+
+        for (id, status, updated_at) in [("a", "active", 1000), ("b", "done", 2000), ("c", "active", 3000)] {
+            store
+                .create(TestRecord {
+                    id: id.to_string(),
+                    name: id.to_string(),
+                    status: status.to_string(),
+                    count: 0,
+                    active: true,
+                    updated_at,
+                })
+                .unwrap();
+        }
+        let ids = |records: Vec<TestRecord>| records.into_iter().map(|r| r.id).collect::<Vec<_>>();
+
+        assert_eq!(ids(store.list_updated_since(2000).unwrap()), vec!["c", "b"]);
+        assert_eq!(ids(store.list_updated_between(1000, 3000).unwrap()), vec!["b", "a"]);
+        assert!(store.list_updated_since::<TestRecord>(3001).unwrap().is_empty());
+
+        // Combines with indexed filters like any other field
+        let query = Query::new().eq("status", "active").gt("updated_at", 1000);
+        assert_eq!(ids(store.list(query.filters()).unwrap()), vec!["c"]);
+    }
+
+    #[test]
+    fn test_datetime_filters_compare_with_millisecond_ints() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        for (id, updated_at) in [("a", 1000), ("b", 2000), ("c", 3000)] {
+            store
+                .create(TestRecord {
+                    id: id.to_string(),
+                    name: id.to_string(),
+                    status: "active".to_string(),
+                    count: 0,
+                    active: true,
+                    updated_at,
+                })
+                .unwrap();
+        }
+        let ids = |records: Vec<TestRecord>| records.into_iter().map(|r| r.id).collect::<Vec<_>>();
+
+        // Indexed or not
+        let window = Query::new().after("updated_at", 1000).before("updated_at", 3000);
+        assert_eq!(ids(store.list(window.filters()).unwrap()), vec!["b"]);
+        let parsed: Filter = "updated_at<1970-01-01T00:00:02Z".parse().unwrap();
+        assert_eq!(ids(store.list(std::slice::from_ref(&parsed)).unwrap()), vec!["a"]);
+        assert_eq!(store.list_json("test_records", &[parsed]).unwrap().len(), 1);
+        assert_eq!(ids(store.list(&[Filter::after("count", -1)]).unwrap()).len(), 3);
+    }
+
+    #[test]
+    fn test_list_json_compares_dates_with_string_indexed_fields_as_strings() {
+        #[derive(Debug, Clone, Serialize, Deserialize)]
+        struct Milestone {
+            id: String,
+            due: String,
+            updated_at: i64,
+        }
+
+        impl Record for Milestone {
+            fn id(&self) -> &str {
+                &self.id
+            }
+
+            fn updated_at(&self) -> i64 {
+                self.updated_at
+            }
+
+            fn collection_name() -> &'static str {
+                "milestones"
+            }
+
+            fn indexed_fields(&self) -> HashMap<String, IndexValue> {
+                HashMap::from([("due".to_string(), IndexValue::String(self.due.clone()))])
+            }
+        }
+
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        for (id, due) in [
+            ("m1", "2024-01-15"),
+            ("m2", "2024-02-01"),
+            ("m3", "2024-02-01T09:00:00Z"),
+        ] {
+            store
+                .create(Milestone {
+                    id: id.to_string(),
+                    due: due.to_string(),
+                    updated_at: now_ms(),
+                })
+                .unwrap();
+        }
+        let ids = |filter: &str| {
+            let mut ids: Vec<String> = store
+                .list_json("milestones", &[filter.parse().unwrap()])
+                .unwrap()
+                .into_iter()
+                .map(|record| record["id"].as_str().unwrap().to_string())
+                .collect();
+            ids.sort();
+            ids
+        };
+
+        // An unquoted date parses as a DateTime, but the field only has string rows
+        assert_eq!(ids("due=2024-01-15"), ["m1"]);
+        assert_eq!(ids("due=\"2024-01-15\""), ["m1"]);
+        assert_eq!(ids("due>=2024-02-01"), ["m2", "m3"]);
+        assert_eq!(ids("due<2024-02-01"), ["m1"]);
+        assert_eq!(ids("due=2024-02-01T09:00:00Z"), ["m3"]);
+    }
+
+    #[test]
+    fn test_increment_is_atomic_across_stores() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        store
+            .create(TestRecord {
+                id: "c1".to_string(),
+                name: "Counter".to_string(),
+                status: "active".to_string(),
+                count: 0,
+                active: true,
+                updated_at: 1000,
+            })
+            .unwrap();
+
+        // Each thread has its own Store, so only SQLite's write lock serializes them
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let path = temp.path().to_path_buf();
+                std::thread::spawn(move || {
+                    let mut store = Store::open(&path).unwrap();
+                    for _ in 0..10 {
+                        store.increment::<TestRecord>("c1", "count", 1).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(store.increment::<TestRecord>("c1", "count", -5).unwrap(), 35);
+        let record = store.get::<TestRecord>("c1").unwrap().unwrap();
+        assert_eq!(record.count, 35);
+        assert!(record.updated_at > 1000);
+        let by_index: Vec<TestRecord> = store.list(Query::new().eq("count", 35).filters()).unwrap();
+        assert_eq!(by_index.len(), 1);
+
+        assert!(
+            store
+                .increment::<TestRecord>("missing", "count", 1)
+                .unwrap_err()
+                .is_not_found()
+        );
+        assert!(matches!(
+            store.increment::<TestRecord>("c1", "name", 1),
+            Err(StoreError::Schema(_))
+        ));
+
+        // Every increment reached JSONL
+        store.sync().unwrap();
+        assert_eq!(store.get::<TestRecord>("c1").unwrap().unwrap().count, 35);
+    }
+
+    #[test]
+    fn test_increment_rejects_fields_that_arent_ints() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        let record = TestRecord {
+            id: "c1".to_string(),
+            name: "Counter".to_string(),
+            status: "active".to_string(),
+            count: 0,
+            active: true,
+            updated_at: 1000,
+        };
+        store.create(record.clone()).unwrap();
+        let jsonl_path = temp.path().join(".taskstore/test_records.jsonl");
+        let jsonl = fs::read_to_string(&jsonl_path).unwrap();
+
+        // Indexed as `IndexValue::String` and `IndexValue::Bool`
+        for field in ["status", "active"] {
+            let err = store.increment::<TestRecord>("c1", field, 1).unwrap_err();
+            assert!(matches!(err, StoreError::Schema(_)), "{}: {:?}", field, err);
+        }
+        assert_eq!(store.get::<TestRecord>("c1").unwrap().unwrap(), record);
+        assert_eq!(fs::read_to_string(&jsonl_path).unwrap(), jsonl);
+    }
+
+    #[test]
+    fn test_update_where_updates_matching_records_in_one_go() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        for (id, status) in [("a", "running"), ("b", "running"), ("c", "done")] {
+            store
+                .create(TestRecord {
+                    id: id.to_string(),
+                    name: id.to_string(),
+                    status: status.to_string(),
+                    count: 0,
+                    active: true,
+                    updated_at: 1000,
+                })
+                .unwrap();
+        }
+        let jsonl = store.jsonl_path("test_records").unwrap();
+        let lines = || fs::read_to_string(&jsonl).unwrap().lines().count();
+
+        let updated = store
+            .update_where(Query::new().eq("status", "running").filters(), |r: &mut TestRecord| {
+                r.status = "stopped".to_string();
+                r.updated_at = 2000;
+            })
+            .unwrap();
+        assert_eq!(updated, 2);
+        let stopped: Vec<TestRecord> = store.list(Query::new().eq("status", "stopped").filters()).unwrap();
+        assert_eq!(stopped.len(), 2);
+        assert_eq!(store.get::<TestRecord>("c").unwrap().unwrap().status, "done");
+        assert_eq!(lines(), 5);
+
+        // Empty filters need update_all
+        let err = store.update_where(&[], |r: &mut TestRecord| r.count += 1).unwrap_err();
+        assert!(matches!(err, StoreError::Schema(_)), "{:?}", err);
+        assert_eq!(
+            store
+                .update_all(|r: &mut TestRecord| {
+                    r.count += 1;
+                    r.updated_at = 3000;
+                })
+                .unwrap(),
+            3
+        );
+        assert_eq!(lines(), 8);
+
+        // A failing record aborts the whole batch
+        let err = store
+            .update_where(Query::new().eq("status", "stopped").filters(), |r: &mut TestRecord| {
+                if r.id == "b" {
+                    r.id = "renamed".to_string();
+                }
+                r.count = 100;
+            })
+            .unwrap_err();
+        assert!(matches!(err, StoreError::Schema(_)), "{:?}", err);
+        assert_eq!(lines(), 8);
+        assert_eq!(store.get::<TestRecord>("a").unwrap().unwrap().count, 1);
+
+        store.sync().unwrap();
+        let stopped: Vec<TestRecord> = store.list(Query::new().eq("status", "stopped").filters()).unwrap();
+        assert_eq!(stopped.len(), 2);
+    }
+
+    #[test]
+    fn test_export_bundle_round_trips_record_tree() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        for id in ["p1", "p2"] {
+            store
+                .create(Project {
+                    id: id.to_string(),
+                    updated_at: 1000,
+                })
+                .unwrap();
+        }
+        for (id, project) in [("m1", "p1"), ("m2", "p2")] {
+            store.create(member(id, project)).unwrap();
+        }
+        // n1 belongs to m1 but its `project_id` points at p2, which the bundle won't contain
+        for (id, member_id) in [("n1", "m1"), ("n2", "m2")] {
+            store.create(note(id, member_id, "p2")).unwrap();
+        }
+
+        let dest = temp.path().join("bundle");
+        let report = store
+            .export_bundle::<Project>("p1", &dest, &[("notes", "project_id", "projects")])
+            .unwrap();
+        assert_eq!(
+            report.records,
+            BTreeMap::from([
+                ("members".to_string(), 1),
+                ("notes".to_string(), 1),
+                ("projects".to_string(), 1)
+            ])
+        );
+        assert_eq!(
+            report.dangling,
+            vec![DanglingReference {
+                collection: "notes".to_string(),
+                id: "n1".to_string(),
+                fk_field: "project_id".to_string(),
+                parent_collection: "projects".to_string(),
+                parent_id: "p2".to_string(),
+            }]
+        );
+
+        // The directory must be empty, and the root must exist
+        assert!(store.export_bundle::<Project>("p1", &dest, &[]).is_err());
+        assert!(
+            store
+                .export_bundle::<Project>("p9", &temp.path().join("other"), &[])
+                .unwrap_err()
+                .is_not_found()
+        );
+
+        let other = TempDir::new().unwrap();
+        let mut imported = Store::open(other.path()).unwrap();
+        let reports = imported.import_bundle(&dest, ImportStrategy::default()).unwrap();
+        assert_eq!(reports.len(), 3);
+        assert!(reports.values().all(|r| r.imported == 1 && r.rejected.is_empty()));
+        assert!(imported.get::<Project>("p1").unwrap().is_some());
+        assert_eq!(imported.get::<Member>("m1").unwrap().unwrap().project_id, "p1");
+        assert_eq!(imported.get::<Note>("n1").unwrap().unwrap().member_id, "m1");
+        assert!(imported.get::<Project>("p2").unwrap().is_none());
+        assert!(imported.get::<Note>("n2").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_snapshot_round_trips_every_collection() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        store
+            .create(Project {
+                id: "p1".to_string(),
+                updated_at: 1000,
+            })
+            .unwrap();
+        store.create(member("m1", "p1")).unwrap();
+        for i in 0..3 {
+            store
+                .create(TestRecord {
+                    id: format!("rec{}", i),
+                    name: "Snapshot".to_string(),
+                    status: if i == 0 { "done" } else { "open" }.to_string(),
+                    count: i,
+                    active: true,
+                    updated_at: 1000,
+                })
+                .unwrap();
+        }
+        store.delete::<TestRecord>("rec2").unwrap();
+        store.register_collection::<TestRecord>().unwrap();
+
+        let mut snapshot = Vec::new();
+        store.export_snapshot(&mut snapshot).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&snapshot).unwrap();
+        assert_eq!(json["version"], CURRENT_VERSION);
+        assert_eq!(json["collections"]["test_records"].as_array().unwrap().len(), 2);
+
+        let other = TempDir::new().unwrap();
+        let mut imported = Store::open(other.path()).unwrap();
+        let counts = imported.import_snapshot(snapshot.as_slice()).unwrap();
+        assert_eq!(
+            counts,
+            BTreeMap::from([
+                ("members".to_string(), 1),
+                ("projects".to_string(), 1),
+                ("test_records".to_string(), 2)
+            ])
+        );
+        for collection in ["members", "projects", "test_records"] {
+            assert_eq!(
+                imported.list_json(collection, &[]).unwrap(),
+                store.list_json(collection, &[]).unwrap()
+            );
+        }
+        assert_eq!(
+            imported.list::<TestRecord>(&[]).unwrap(),
+            store.list::<TestRecord>(&[]).unwrap()
+        );
+
+        // Indexed from the snapshot's schema, without TestRecord being registered
+        let done = imported
+            .list_json("test_records", &["status=done".parse().unwrap()])
+            .unwrap();
+        assert_eq!(done.len(), 1);
+        assert_eq!(done[0]["id"], "rec0");
+
+        // Only into an empty store
+        assert!(imported.import_snapshot(snapshot.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_rebuild_indexes_raw_uses_index_specs() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        let notes = [
+            serde_json::json!({"id": "n1", "status": "open", "priority": 3, "updated_at": 1000}),
+            serde_json::json!({"id": "n2", "status": "done", "priority": "high", "updated_at": 1000}),
+            serde_json::json!({"id": "n3", "updated_at": 1000}),
+        ];
+        let lines: String = notes.iter().map(|n| format!("{}\n", n)).collect();
+        store
+            .import_jsonl("notes", lines.as_bytes(), ImportStrategy::default())
+            .unwrap();
+
+        let specs = [
+            IndexSpec::new("status", crate::IndexKind::String),
+            "priority:int".parse().unwrap(),
+        ];
+        assert_eq!(store.rebuild_indexes_raw("notes", &specs).unwrap(), 3);
+
+        let rows: Vec<(String, String)> = {
+            let mut stmt = store
+                .db
+                .prepare("SELECT id, field_name FROM record_indexes WHERE collection = 'notes' ORDER BY id, field_name")
+                .unwrap();
+            stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+                .unwrap()
+                .collect::<rusqlite::Result<_>>()
+                .unwrap()
+        };
+        // n2's string priority and n3's missing fields get no rows
+        let expected = [("n1", "priority"), ("n1", "status"), ("n2", "status")];
+        assert_eq!(rows, expected.map(|(id, f)| (id.to_string(), f.to_string())));
+
+        assert!("priority".parse::<IndexSpec>().is_err());
+        assert!("priority:decimal".parse::<IndexSpec>().is_err());
+        assert_eq!(
+            "score:float".parse::<IndexSpec>().unwrap(),
+            IndexSpec::new("score", IndexKind::Float)
+        );
+    }
+
+    #[test]
+    fn test_schema_file_indexes_collections_without_their_type() {
+        let temp = TempDir::new().unwrap();
+        let schema_path = temp.path().join(".taskstore").join(SCHEMA_FILENAME);
+        {
+            let mut store = Store::open(temp.path()).unwrap();
+            store
+                .create(TestRecord {
+                    id: "rec1".to_string(),
+                    name: "Record".to_string(),
+                    status: "active".to_string(),
+                    count: 1,
+                    active: true,
+                    updated_at: 1000,
+                })
+                .unwrap();
+            store.register_collection::<TestRecord>().unwrap();
+        }
+        let schema: serde_json::Value = serde_json::from_str(&fs::read_to_string(&schema_path).unwrap()).unwrap();
+        assert_eq!(
+            schema["test_records"],
+            serde_json::json!([
+                {"field": "active", "kind": "bool"},
+                {"field": "count", "kind": "int"},
+                {"field": "status", "kind": "string"}
+            ])
+        );
+
+        // A collection only known through schema.json is indexed on open
+        let mut schema = schema;
+        schema["notes"] = serde_json::json!([{"field": "status", "kind": "string"}]);
+        fs::write(&schema_path, schema.to_string()).unwrap();
+        fs::write(
+            temp.path().join(".taskstore/notes.jsonl"),
+            "{\"id\":\"n1\",\"status\":\"open\",\"updated_at\":1}\n",
+        )
+        .unwrap();
+
+        let store = Store::open(temp.path()).unwrap();
+        assert_eq!(
+            store.index_specs("notes"),
+            Some(&[IndexSpec::new("status", IndexKind::String)][..])
+        );
+        let status: String = store
+            .db
+            .query_row(
+                "SELECT field_value_str FROM record_indexes WHERE collection = 'notes' AND id = 'n1' AND field_name = 'status'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(status, "open");
+
+        fs::write(&schema_path, "not json").unwrap();
+        assert!(matches!(Store::open(temp.path()), Err(StoreError::Corrupt(_))));
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    struct Doc {
+        id: String,
+        title: String,
+        #[serde(default)]
+        content: String,
+        updated_at: i64,
+    }
+
+    impl Record for Doc {
+        fn id(&self) -> &str {
+            &self.id
+        }
+
+        fn updated_at(&self) -> i64 {
+            self.updated_at
+        }
+
+        fn collection_name() -> &'static str {
+            "docs"
+        }
+
+        fn indexed_fields(&self) -> HashMap<String, IndexValue> {
+            HashMap::from([("title".to_string(), IndexValue::String(self.title.clone()))])
+        }
+
+        fn heavy_fields() -> &'static [&'static str] {
+            &["content"]
+        }
+    }
+
+    #[test]
+    fn test_list_lite_leaves_out_heavy_fields() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        for (id, title) in [("d1", "Intro"), ("d2", "Design")] {
+            store
+                .create(Doc {
+                    id: id.to_string(),
+                    title: title.to_string(),
+                    content: "x".repeat(10_000),
+                    updated_at: 1000,
+                })
+                .unwrap();
+        }
+
+        let docs: Vec<Doc> = store.list_lite(Query::new().eq("title", "Design").filters()).unwrap();
+        assert_eq!(docs.len(), 1);
+        assert_eq!(docs[0].id, "d2");
+        assert!(docs[0].content.is_empty());
+        assert_eq!(store.list_lite::<Doc>(&[]).unwrap().len(), 2);
+
+        // Full content is still there for get and list
+        assert_eq!(store.get::<Doc>("d2").unwrap().unwrap().content.len(), 10_000);
+        assert!(
+            store
+                .list::<Doc>(&[])
+                .unwrap()
+                .iter()
+                .all(|d| d.content.len() == 10_000)
+        );
+    }
+
+    #[test]
+    fn test_now_ms_is_strictly_increasing() {
+        let mut last = now_ms();
+        for _ in 0..10_000 {
+            let now = now_ms();
+            assert!(now > last);
+            last = now;
+        }
+
+        let handles: Vec<_> = (0..4)
+            .map(|_| std::thread::spawn(|| (0..1000).map(|_| now_ms()).collect::<Vec<_>>()))
+            .collect();
+        let mut all: Vec<i64> = handles.into_iter().flat_map(|h| h.join().unwrap()).collect();
+        let count = all.len();
+        all.sort();
+        all.dedup();
+        assert_eq!(all.len(), count);
+    }
+
+    #[test]
+    fn test_get_at_time_reads_history_from_jsonl() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        let record = |name: &str, updated_at: i64| TestRecord {
+            id: "rec1".to_string(),
+            name: name.to_string(),
+            status: "active".to_string(),
+            count: 0,
+            active: true,
+            updated_at,
+        };
+        store.create(record("First", 1000)).unwrap();
+        store.update(record("Second", 2000)).unwrap();
+        store.delete::<TestRecord>("rec1").unwrap();
+        let deleted_at = now_ms();
+
+        let name_at = |at: i64| store.get_at_time::<TestRecord>("rec1", at).unwrap().map(|r| r.name);
+        assert_eq!(name_at(999), None);
+        assert_eq!(name_at(1000).as_deref(), Some("First"));
+        assert_eq!(name_at(1999).as_deref(), Some("First"));
+        assert_eq!(name_at(2500).as_deref(), Some("Second"));
+        assert_eq!(name_at(deleted_at), None);
+        assert!(store.get_at_time::<TestRecord>("other", deleted_at).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_get_many_keeps_requested_order() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        let count = GET_MANY_CHUNK_SIZE + 10;
+        for i in 0..count {
+            store
+                .create(TestRecord {
+                    id: format!("rec{}", i),
+                    name: format!("Record {}", i),
+                    status: "active".to_string(),
+                    count: i as i64,
+                    active: true,
+                    updated_at: 1000,
+                })
+                .unwrap();
+        }
+
+        let records = store
+            .get_many::<TestRecord>(&["rec2", "missing", "rec0", "rec2"])
+            .unwrap();
+        let names: Vec<Option<&str>> = records.iter().map(|r| r.as_ref().map(|r| r.name.as_str())).collect();
+        assert_eq!(names, vec![Some("Record 2"), None, Some("Record 0"), Some("Record 2")]);
+
+        // More IDs than fit in one query
+        let ids: Vec<String> = (0..count).rev().map(|i| format!("rec{}", i)).collect();
+        let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+        let records = store.get_many::<TestRecord>(&ids).unwrap();
+        assert_eq!(records.len(), count);
+        assert!(records.iter().zip(&ids).all(|(r, id)| r.as_ref().unwrap().id == *id));
+        assert!(store.get_many::<TestRecord>(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_batch_reads_one_snapshot() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        let record = |id: &str| TestRecord {
+            id: id.to_string(),
+            name: id.to_string(),
+            status: "active".to_string(),
+            count: 0,
+            active: true,
+            updated_at: now_ms(),
+        };
+        store.create(record("rec1")).unwrap();
+        store
+            .create(User {
+                id: "u1".to_string(),
+                email: "a@example.com".to_string(),
+                updated_at: now_ms(),
+            })
+            .unwrap();
+
+        let mut writer = Store::open(temp.path()).unwrap();
+        let (users, before, after, handle) = store
+            .batch(|b| {
+                let users: Vec<User> = b.list(&[])?;
+                let before: Vec<TestRecord> = b.query(&Query::new().eq("status", "active"))?;
+                // A concurrent write doesn't land in the middle of the batch
+                let handle = std::thread::spawn(move || writer.create(record("rec2")).unwrap());
+                std::thread::sleep(std::time::Duration::from_millis(50));
+                let after: Vec<TestRecord> = b.list(&[])?;
+                Ok((users, before, after, handle))
+            })
+            .unwrap();
+        handle.join().unwrap();
+        assert_eq!(users.len(), 1);
+        assert_eq!(before.len(), 1);
+        assert_eq!(after.len(), 1);
+        assert_eq!(store.list::<TestRecord>(&[]).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_touch_bumps_updated_at_only() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        let record = TestRecord {
+            id: "rec1".to_string(),
+            name: "Touched".to_string(),
+            status: "active".to_string(),
+            count: 3,
+            active: true,
+            updated_at: 1000,
+        };
+        store.create(record.clone()).unwrap();
+
+        store.touch::<TestRecord>("rec1").unwrap();
+        let touched = store.get::<TestRecord>("rec1").unwrap().unwrap();
+        assert!(touched.updated_at > 1000);
+        assert_eq!(
+            TestRecord {
+                updated_at: 1000,
+                ..touched.clone()
+            },
+            record
+        );
+
+        // The new version is in JSONL, so it survives a rebuild
+        let lines = fs::read_to_string(store.jsonl_path("test_records").unwrap()).unwrap();
+        assert_eq!(lines.lines().count(), 2);
+        store.sync().unwrap();
+        assert_eq!(
+            store.get::<TestRecord>("rec1").unwrap().unwrap().updated_at,
+            touched.updated_at
+        );
+
+        assert!(store.touch::<TestRecord>("missing").unwrap_err().is_not_found());
+    }
+
+    #[test]
+    fn test_update_partial_patches_only_given_fields() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        store
+            .create(TestRecord {
+                id: "rec1".to_string(),
+                name: "Original".to_string(),
+                status: "active".to_string(),
+                count: 3,
+                active: true,
+                updated_at: 1000,
+            })
+            .unwrap();
+
+        // Another writer's change to a different field is kept
+        store.increment::<TestRecord>("rec1", "count", 1).unwrap();
+        let patched: TestRecord = store
+            .update_partial("rec1", serde_json::json!({ "status": "done", "id": "rec1" }))
+            .unwrap();
+        assert_eq!((patched.status.as_str(), patched.count), ("done", 4));
+        assert_eq!(patched.name, "Original");
+        assert!(patched.updated_at > 1000);
+        assert_eq!(store.get::<TestRecord>("rec1").unwrap().unwrap(), patched);
+        assert_eq!(
+            store
+                .list::<TestRecord>(&["status=done".parse().unwrap()])
+                .unwrap()
+                .len(),
+            1
+        );
+
+        let err = store
+            .update_partial::<TestRecord>("rec1", serde_json::json!({ "id": "rec2" }))
+            .unwrap_err();
+        assert!(matches!(err, StoreError::Schema(_)));
+        assert!(matches!(
+            store.update_partial::<TestRecord>("rec1", serde_json::json!([1])),
+            Err(StoreError::Schema(_))
+        ));
+        assert!(
+            store
+                .update_partial::<TestRecord>("missing", serde_json::json!({ "count": 1 }))
+                .unwrap_err()
+                .is_not_found()
+        );
+        assert_eq!(store.get::<TestRecord>("rec1").unwrap().unwrap(), patched);
+    }
+
+    #[test]
+    fn test_list_timeline_orders_range_ascending() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        for (id, status, count) in [
+            ("e1", "a", 30),
+            ("e2", "a", 10),
+            ("e3", "b", 20),
+            ("e4", "a", 20),
+            ("e5", "a", 40),
+        ] {
+            store
+                .create(TestRecord {
+                    id: id.to_string(),
+                    name: id.to_string(),
+                    status: status.to_string(),
+                    count,
+                    active: true,
+                    updated_at: now_ms(),
+                })
+                .unwrap();
+        }
+        let ids = |records: Vec<TestRecord>| records.into_iter().map(|r| r.id).collect::<Vec<_>>();
+        let entity = Query::new().eq("status", "a");
+
+        // From inclusive, to exclusive
+        let window = store.list_timeline(entity.filters(), "count", 10, 40).unwrap();
+        assert_eq!(ids(window), vec!["e2", "e4", "e1"]);
+        let all = store
+            .list_timeline(entity.filters(), "count", i64::MIN, i64::MAX)
+            .unwrap();
+        assert_eq!(ids(all), vec!["e2", "e4", "e1", "e5"]);
+        assert!(
+            store
+                .list_timeline::<TestRecord>(&[], "count", 40, 40)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_clear_collection_tombstones_every_record() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        for i in 0..3 {
+            store
+                .create(TestRecord {
+                    id: format!("rec{}", i),
+                    name: "Clear me".to_string(),
+                    status: "active".to_string(),
+                    count: i,
+                    active: true,
+                    updated_at: now_ms(),
+                })
+                .unwrap();
+        }
+        store
+            .create(User {
+                id: "u1".to_string(),
+                email: "a@example.com".to_string(),
+                updated_at: now_ms(),
+            })
+            .unwrap();
+
+        assert_eq!(
+            store
+                .clear_collection::<TestRecord>(ClearConfirm::DeleteAllRecords)
+                .unwrap(),
+            3
+        );
+        assert!(store.list::<TestRecord>(&[]).unwrap().is_empty());
+        assert!(
+            store
+                .list::<TestRecord>(Query::new().eq("status", "active").filters())
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(store.list::<User>(&[]).unwrap().len(), 1);
+
+        // Tombstones keep the records deleted through a rebuild from JSONL
+        let lines = fs::read_to_string(store.jsonl_path("test_records").unwrap()).unwrap();
+        assert_eq!(lines.lines().count(), 6);
+        store.sync().unwrap();
+        assert!(store.list::<TestRecord>(&[]).unwrap().is_empty());
+
+        assert_eq!(
+            store
+                .clear_collection::<TestRecord>(ClearConfirm::DeleteAllRecords)
+                .unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_with_connection_runs_custom_sql() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        for (id, status, count) in [("a", "open", 1), ("b", "open", 2), ("c", "done", 3)] {
+            store
+                .create(TestRecord {
+                    id: id.to_string(),
+                    name: id.to_string(),
+                    status: status.to_string(),
+                    count,
+                    active: true,
+                    updated_at: now_ms(),
+                })
+                .unwrap();
+        }
+
+        // Running total of `count` per status
+        let totals: Vec<(String, i64)> = store
+            .with_connection(|conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT s.id, SUM(c.field_value_int) OVER (PARTITION BY s.field_value_str ORDER BY s.id)
+                     FROM record_indexes s JOIN record_indexes c ON c.collection = s.collection AND c.id = s.id
+                     WHERE s.collection = 'test_records' AND s.field_name = 'status' AND c.field_name = 'count'
+                     ORDER BY s.id",
+                )?;
+                stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?.collect()
+            })
+            .unwrap();
+        assert_eq!(
+            totals,
+            vec![("a".to_string(), 1), ("b".to_string(), 3), ("c".to_string(), 3)]
+        );
+
+        store
+            .with_connection_mut(|tx| tx.execute("CREATE TABLE report_cache (total INTEGER)", []))
+            .unwrap();
+        let err = store
+            .with_connection(|conn| conn.execute("SELECT * FROM missing", []))
+            .unwrap_err();
+        assert!(matches!(err, StoreError::Sqlite(_)), "{err}");
+    }
+
+    #[test]
+    fn test_list_where_combines_and_or() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        for (id, status, count) in [
+            ("a", "pending", 1),
+            ("b", "running", 2),
+            ("c", "done", 3),
+            ("d", "pending", 4),
+        ] {
+            store
+                .create(TestRecord {
+                    id: id.to_string(),
+                    name: id.to_string(),
+                    status: status.to_string(),
+                    count,
+                    active: true,
+                    updated_at: now_ms(),
+                })
+                .unwrap();
+        }
+        let ids = |expr: &FilterExpr| {
+            let mut ids: Vec<String> = store
+                .list_where::<TestRecord>(expr)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.id)
+                .collect();
+            ids.sort();
+            ids
+        };
+        let statuses = Query::new().eq("status", "pending").eq("status", "running");
+
+        assert_eq!(ids(&FilterExpr::any(statuses.filters())), vec!["a", "b", "d"]);
+        let nested = FilterExpr::And(vec![
+            FilterExpr::any(statuses.filters()),
+            FilterExpr::Or(vec![
+                Query::new().gt("count", 3).filters()[0].clone().into(),
+                Query::new().eq("count", 2).filters()[0].clone().into(),
+            ]),
+        ]);
+        assert_eq!(ids(&nested), vec!["b", "d"]);
+        assert!(ids(&FilterExpr::Or(vec![])).is_empty());
+        assert_eq!(ids(&FilterExpr::And(vec![])).len(), 4);
+        assert_eq!(
+            ids(&FilterExpr::all(
+                Query::new().eq("status", "pending").gt("count", 1).filters()
+            )),
+            vec!["d"]
+        );
+
+        // Long groups don't nest one level per term
+        let many: Vec<Filter> = (0..1500)
+            .map(|i| Query::new().eq("count", i).filters()[0].clone())
+            .collect();
+        assert_eq!(ids(&FilterExpr::any(&many)).len(), 4);
+
+        // Same-kind nesting flattens; alternating nesting is capped
+        let mut deep = FilterExpr::any(statuses.filters());
+        for _ in 0..100 {
+            deep = FilterExpr::Or(vec![deep]);
+        }
+        assert_eq!(ids(&deep).len(), 3);
+        let mut alternating = FilterExpr::any(statuses.filters());
+        for i in 0..40 {
+            let extra = Query::new().eq("count", i).filters()[0].clone().into();
+            alternating = if i % 2 == 0 {
+                FilterExpr::And(vec![alternating, extra])
+            } else {
+                FilterExpr::Or(vec![alternating, extra])
+            };
+        }
+        assert!(matches!(
+            store.list_where::<TestRecord>(&alternating),
+            Err(StoreError::Schema(_))
+        ));
+    }
+
+    #[test]
+    fn test_generic_list_no_filters() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+
+        // Create multiple records
+        for i in 1..=3 {
+            let record = TestRecord {
+                id: format!("rec{}", i),
+                name: format!("Record {}", i),
+                status: "active".to_string(),
+                count: i,
+                active: true,
+                updated_at: now_ms(),
+            };
+            store.create(record).unwrap();
+        }
+
+        // List all records
+        let records: Vec<TestRecord> = store.list(&[]).unwrap();
+        assert_eq!(records.len(), 3);
+    }
+
+    #[test]
+    fn test_float_index_values() {
+        #[derive(Debug, Clone, Serialize, Deserialize)]
+        struct Score {
+            id: String,
+            score: f64,
+            updated_at: i64,
+        }
+
+        impl Record for Score {
+            fn id(&self) -> &str {
+                &self.id
+            }
+
+            fn updated_at(&self) -> i64 {
+                self.updated_at
+            }
+
+            fn collection_name() -> &'static str {
+                "scores"
+            }
+
+            fn indexed_fields(&self) -> HashMap<String, IndexValue> {
+                HashMap::from([("score".to_string(), IndexValue::Float(self.score))])
+            }
+        }
+
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        for (id, score) in [("a", 2.5), ("b", -1.0), ("c", 10.0), ("d", f64::NAN)] {
+            store
+                .create(Score {
+                    id: id.to_string(),
+                    score,
+                    updated_at: 1000,
+                })
+                .unwrap();
+        }
+
+        let ids = |store: &Store, query: Query| -> Vec<String> {
+            store
+                .list_with::<Score>(query.filters(), query.options())
+                .unwrap()
+                .into_iter()
+                .map(|s| s.id)
+                .collect()
+        };
+        assert_eq!(ids(&store, Query::new().eq("score", 2.5)), vec!["a"]);
+        assert_eq!(
+            ids(&store, Query::new().gt("score", 0.0).order_by("score")),
+            vec!["a", "c"]
+        );
+        assert_eq!(
+            ids(&store, Query::new().lte("score", 2.5).order_by_desc("score")),
+            vec!["a", "b"]
+        );
+        // Filters only match their own type, and NaN matches nothing
+        assert!(ids(&store, Query::new().eq("score", 10)).is_empty());
+        assert_eq!(ids(&store, Query::new().ne("score", 2.5)).len(), 2);
+        for query in [
+            Query::new().eq("score", f64::NAN),
+            Query::new().ne("score", f64::NAN),
+            Query::new().gt("score", f64::NAN),
+        ] {
+            assert!(ids(&store, query).is_empty());
+        }
+
+        let max = store.aggregate::<Score>("score", Agg::Max, &[]).unwrap();
+        assert_eq!(max, Some(10.0));
+        assert_eq!(store.count_by::<Score>("score").unwrap()[0].0, IndexValue::Float(-1.0));
+
+        // Raw reindexing keeps the float kind
+        store.sync().unwrap();
+        assert_eq!(
+            ids(&store, Query::new().gt("score", 0.0).order_by("score")),
+            vec!["a", "c"]
+        );
+    }
+
+    #[test]
+    fn test_count_matches_list() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+
+        for i in 0..6 {
+            store
+                .create(TestRecord {
+                    id: format!("rec{}", i),
+                    name: "Counted".to_string(),
+                    status: if i % 3 == 0 { "done" } else { "pending" }.to_string(),
+                    count: i,
+                    active: i % 2 == 0,
+                    updated_at: 1000 + i,
+                })
+                .unwrap();
+        }
+
+        assert_eq!(store.count::<TestRecord>(&[]).unwrap(), 6);
+        let pending = Query::new().eq("status", "pending");
+        assert_eq!(store.count::<TestRecord>(pending.filters()).unwrap(), 4);
+        let pending_active = pending.eq("active", true);
+        assert_eq!(
+            store.count::<TestRecord>(pending_active.filters()).unwrap() as usize,
+            store.list::<TestRecord>(pending_active.filters()).unwrap().len()
+        );
+
+        let expr = FilterExpr::Or(vec![
+            Query::new().eq("status", "done").filters()[0].clone().into(),
+            Query::new().gte("count", 4).filters()[0].clone().into(),
+        ]);
+        assert_eq!(store.count_where::<TestRecord>(&expr).unwrap(), 4);
+        assert_eq!(
+            store.count_where::<TestRecord>(&expr).unwrap() as usize,
+            store.list_where::<TestRecord>(&expr).unwrap().len()
+        );
+        assert_eq!(store.count::<Doc>(&[]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_generic_list_with_filter() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+
+        // Create records with different statuses
+        let record1 = TestRecord {
+            id: "rec1".to_string(),
+            name: "Record 1".to_string(),
+            status: "active".to_string(),
+            count: 1,
+            active: true,
+            updated_at: now_ms(),
+        };
+        let record2 = TestRecord {
+            id: "rec2".to_string(),
+            name: "Record 2".to_string(),
+            status: "draft".to_string(),
+            count: 2,
+            active: true,
+            updated_at: now_ms(),
+        };
+
+        store.create(record1).unwrap();
+        store.create(record2).unwrap();
+
+        // Filter by status = "active"
+        let filters = vec![Filter {
+            field: "status".to_string(),
+            op: crate::filter::FilterOp::Eq,
+            value: IndexValue::String("active".to_string()),
+        }];
+
+        let records: Vec<TestRecord> = store.list(&filters).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].status, "active");
+    }
+
+    #[test]
+    fn test_is_stale_detects_content_change_with_older_mtime() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+
+        let record = TestRecord {
+            id: "rec1".to_string(),
+            name: "Original".to_string(),
+            status: "active".to_string(),
+            count: 1,
+            active: true,
+            updated_at: 1000,
+        };
+        store.create(record).unwrap();
+        store.sync().unwrap();
+        assert!(!store.is_stale().unwrap());
+
+        // Simulate a checkout that rewrites the file but leaves an older mtime
+        let jsonl_path = temp.path().join(".taskstore/test_records.jsonl");
+        let original_mtime = fs::metadata(&jsonl_path).unwrap().modified().unwrap();
+        let content = fs::read_to_string(&jsonl_path).unwrap().replace("Original", "Merged!!");
+        fs::write(&jsonl_path, content).unwrap();
+        let file = fs::OpenOptions::new().write(true).open(&jsonl_path).unwrap();
+        file.set_modified(original_mtime - std::time::Duration::from_secs(60))
+            .unwrap();
+        drop(file);
+
+        assert!(store.is_stale().unwrap());
+
+        // Reopening picks up the merged-in change
+        drop(store);
+        let store = Store::open(temp.path()).unwrap();
+        let retrieved: TestRecord = store.get("rec1").unwrap().unwrap();
+        assert_eq!(retrieved.name, "Merged!!");
+        assert!(!store.is_stale().unwrap());
+    }
+
+    #[test]
+    fn test_is_stale_records_new_mtime_of_unchanged_file() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        store
+            .create(TestRecord {
+                id: "rec1".to_string(),
+                name: "Touched".to_string(),
+                status: "active".to_string(),
+                count: 1,
+                active: true,
+                updated_at: 1000,
+            })
+            .unwrap();
+        store.sync().unwrap();
+
+        // Same content, new mtime (e.g. a checkout that rewrote the file as it was)
+        let jsonl_path = temp.path().join(".taskstore/test_records.jsonl");
+        let mtime = fs::metadata(&jsonl_path).unwrap().modified().unwrap() + std::time::Duration::from_secs(60);
+        let file = fs::OpenOptions::new().write(true).open(&jsonl_path).unwrap();
+        file.set_modified(mtime).unwrap();
+        drop(file);
+
+        let stored_mtime = |store: &Store| -> i64 {
+            store
+                .db
+                .query_row(
+                    "SELECT file_mtime FROM sync_metadata WHERE collection = 'test_records'",
+                    [],
+                    |row| row.get(0),
+                )
+                .unwrap()
+        };
+        assert!(!store.is_stale().unwrap());
+        assert_eq!(stored_mtime(&store), Store::file_fingerprint(&jsonl_path).unwrap().0);
+    }
+
+    #[test]
+    fn test_sync_dry_run_reports_changes_without_mutating() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+
+        for i in 1..=3 {
+            let record = TestRecord {
+                id: format!("rec{}", i),
+                name: format!("Record {}", i),
+                status: "active".to_string(),
+                count: i,
+                active: true,
+                updated_at: 1000,
+            };
+            store.create(record).unwrap();
+        }
+        assert!(store.sync_dry_run().unwrap().is_empty());
+
+        // Simulate a merge: one new record, one updated, one deleted
+        let jsonl_path = temp.path().join(".taskstore/test_records.jsonl");
+        let mut content = fs::read_to_string(&jsonl_path).unwrap();
+        content.push_str(
+            r#"{"id":"rec4","name":"Record 4","status":"active","count":4,"active":true,"updated_at":2000}
+{"id":"rec1","name":"Renamed","status":"active","count":1,"active":true,"updated_at":2000}
+{"id":"rec2","deleted":true,"updated_at":2000}
+"#,
+        );
+        fs::write(&jsonl_path, content).unwrap();
+
+        let plan = store.sync_dry_run().unwrap();
+        assert_eq!(
+            plan.collections,
+            vec![CollectionSyncPlan {
+                collection: "test_records".to_string(),
+                added: 1,
+                updated: 1,
+                removed: 1,
+            }]
+        );
+
+        // Nothing was modified
+        let records: Vec<TestRecord> = store.list(&[]).unwrap();
+        assert_eq!(records.len(), 3);
+        assert!(store.is_stale().unwrap());
+    }
+
+    #[test]
+    fn test_sync_reindexes_unregistered_collection_from_raw_json() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        store
+            .create(TestRecord {
+                id: "rec1".to_string(),
+                name: "Record 1".to_string(),
+                status: "active".to_string(),
+                count: 1,
+                active: true,
+                updated_at: 1000,
+            })
+            .unwrap();
+
+        let jsonl_path = temp.path().join(".taskstore/test_records.jsonl");
+        let mut content = fs::read_to_string(&jsonl_path).unwrap();
+        content.push_str(
+            r#"{"id":"rec2","name":"Record 2","status":"active","count":2,"active":false,"updated_at":2000}
+"#,
+        );
+        fs::write(&jsonl_path, content).unwrap();
+        store.sync().unwrap();
+
+        let active: Vec<TestRecord> = store.list(Query::new().eq("status", "active").filters()).unwrap();
+        assert_eq!(active.len(), 2);
+        let inactive: Vec<TestRecord> = store.list(Query::new().eq("active", false).filters()).unwrap();
+        assert_eq!(inactive.len(), 1);
+        assert_eq!(inactive[0].id, "rec2");
+    }
+
+    #[test]
+    fn test_sync_loads_unknown_collections_latest_versions() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+
+        fs::write(
+            temp.path().join(".taskstore/notes.jsonl"),
+            concat!(
+                r#"{"id":"n1","text":"draft","updated_at":1000}"#,
+                "\n",
+                r#"{"id":"n2","text":"gone","updated_at":1000}"#,
+                "\n",
+                r#"{"id":"n1","text":"final","updated_at":2000}"#,
+                "\n",
+                r#"{"id":"n2","deleted":true,"updated_at":2000}"#,
+                "\n",
+            ),
+        )
+        .unwrap();
+        assert!(store.is_stale().unwrap());
+        store.sync().unwrap();
+        assert!(!store.is_stale().unwrap());
+
+        let notes: Vec<(String, String)> = store
+            .with_connection(|conn| {
+                let mut stmt = conn.prepare("SELECT id, data_json FROM records WHERE collection = 'notes'")?;
+                let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+                rows.collect()
+            })
+            .unwrap();
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0].0, "n1");
+        assert!(notes[0].1.contains("final"));
+    }
+
+    #[test]
+    fn test_sync_collection_only_touches_stale_collection() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        store
+            .create(TestRecord {
+                id: "rec1".to_string(),
+                name: "Record 1".to_string(),
+                status: "active".to_string(),
+                count: 1,
+                active: true,
+                updated_at: 1000,
+            })
+            .unwrap();
+        store
+            .create(LabelRecord {
+                id: "l1".to_string(),
+                label: "urgent".to_string(),
+                updated_at: 1000,
+            })
+            .unwrap();
+        store.sync().unwrap();
+        assert!(store.stale_collections().unwrap().is_empty());
+
+        // Change labels.jsonl behind the store's back, and sneak a row into
+        // test_records that a sync of that collection would drop
+        let labels_path = temp.path().join(".taskstore/labels.jsonl");
+        let mut content = fs::read_to_string(&labels_path).unwrap();
+        content.push_str("{\"id\":\"l2\",\"label\":\"later\",\"updated_at\":2000}\n");
+        fs::write(&labels_path, content).unwrap();
+        store
+            .db
+            .execute(
+                "INSERT INTO records (collection, id, data_json, updated_at) VALUES ('test_records', 'ghost', '{}', 0)",
+                [],
+            )
+            .unwrap();
+        assert_eq!(store.stale_collections().unwrap(), vec!["labels"]);
+
+        store.sync_collection("labels").unwrap();
+        assert!(!store.is_stale().unwrap());
+        assert_eq!(store.list_ids::<LabelRecord>(&[]).unwrap().len(), 2);
+        assert!(
+            store
+                .list_ids::<TestRecord>(&[])
+                .unwrap()
+                .contains(&"ghost".to_string())
+        );
+
+        // A removed file is stale too, and syncing it drops the collection
+        fs::remove_file(&labels_path).unwrap();
+        assert_eq!(store.stale_collections().unwrap(), vec!["labels"]);
+        store.sync_collection("labels").unwrap();
+        assert!(store.list_ids::<LabelRecord>(&[]).unwrap().is_empty());
+        assert!(!store.is_stale().unwrap());
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    struct SeqRecord {
+        id: String,
+        updated_at: i64,
+    }
+
+    impl Record for SeqRecord {
+        fn id(&self) -> &str {
+            &self.id
+        }
+
+        fn id_index_value(&self) -> IndexValue {
+            IndexValue::Int(self.id.parse().unwrap())
+        }
+
+        fn updated_at(&self) -> i64 {
+            self.updated_at
+        }
+
+        fn collection_name() -> &'static str {
+            "sequence"
+        }
+    }
+
+    #[test]
+    fn test_sync_collection_reads_only_appended_lines() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        store.register::<TestRecord>();
+        let record = |id: &str, status: &str, updated_at: i64| TestRecord {
+            id: id.to_string(),
+            name: id.to_string(),
+            status: status.to_string(),
+            count: 0,
+            active: true,
+            updated_at,
+        };
+        for id in ["a", "c", "z"] {
+            store.create(record(id, "active", 1000)).unwrap();
+        }
+        store.sync().unwrap();
+
+        // Drift that only a full reload would repair
+        store
+            .db()
+            .execute("DELETE FROM records WHERE collection = 'test_records' AND id = 'z'", [])
+            .unwrap();
+
+        let jsonl_path = temp.path().join(".taskstore/test_records.jsonl");
+        jsonl::append_jsonl(&jsonl_path, &record("a", "done", 2000)).unwrap();
+        jsonl::append_jsonl(&jsonl_path, &record("b", "done", 1000)).unwrap();
+        jsonl::append_jsonl(
+            &jsonl_path,
+            &serde_json::json!({"id": "c", "deleted": true, "updated_at": 2000}),
+        )
+        .unwrap();
+        assert!(store.is_stale().unwrap());
+
+        store.sync_collection("test_records").unwrap();
+        assert!(!store.is_stale().unwrap());
+        let done: Vec<TestRecord> = store.list(Query::new().eq("status", "done").filters()).unwrap();
+        let mut ids: Vec<_> = done.iter().map(|r| r.id.as_str()).collect();
+        ids.sort();
+        assert_eq!(ids, vec!["a", "b"]);
+        assert!(store.get::<TestRecord>("c").unwrap().is_none());
+        assert!(store.get::<TestRecord>("z").unwrap().is_none()); // not reloaded
+
+        // A rewritten file can't be synced incrementally
+        jsonl::compact_jsonl(&jsonl_path).unwrap();
+        store.sync_collection("test_records").unwrap();
+        assert!(store.get::<TestRecord>("z").unwrap().is_some());
+        assert!(!store.is_stale().unwrap());
+    }
+
+    #[test]
+    fn test_sync_collection_appended_version_older_than_tombstone_stays_deleted() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        store.register::<TestRecord>();
+        let record = |id: &str, updated_at: i64| TestRecord {
+            id: id.to_string(),
+            name: id.to_string(),
+            status: "active".to_string(),
+            count: 0,
+            active: true,
+            updated_at,
+        };
+        store.create(record("gone", 1000)).unwrap();
+        store.delete::<TestRecord>("gone").unwrap();
+        store.sync().unwrap();
+
+        // A stale version from another branch, and a genuinely new record
+        let jsonl_path = temp.path().join(".taskstore/test_records.jsonl");
+        jsonl::append_jsonl(&jsonl_path, &record("gone", 2000)).unwrap();
+        jsonl::append_jsonl(&jsonl_path, &record("new", 2000)).unwrap();
+
+        store.sync_collection("test_records").unwrap();
+        let incremental = store.list_ids::<TestRecord>(&[]).unwrap();
+        assert_eq!(incremental, vec!["new"]);
+        store.sync().unwrap();
+        assert_eq!(store.list_ids::<TestRecord>(&[]).unwrap(), incremental);
+    }
+
+    #[test]
+    fn test_compact_keeps_latest_versions() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        for version in 0..5 {
+            store
+                .create(TestRecord {
+                    id: "rec1".to_string(),
+                    name: "Versioned".to_string(),
+                    status: "active".to_string(),
+                    count: version,
+                    active: true,
+                    updated_at: 1000 + version,
+                })
+                .unwrap();
+        }
+        store
+            .create(User {
+                id: "u1".to_string(),
+                email: "u1@example.com".to_string(),
+                updated_at: 1000,
+            })
+            .unwrap();
+        store.delete::<User>("u1").unwrap();
+
+        let stats = store.compact::<TestRecord>().unwrap();
+        assert_eq!((stats.lines_before, stats.lines_after), (5, 1));
+        assert!(stats.bytes_saved() > 0);
+
+        let all = store.compact_all().unwrap();
+        assert_eq!(all.keys().collect::<Vec<_>>(), vec!["test_records", "users"]);
+        assert_eq!(all["test_records"].lines_after, 1);
+        assert_eq!((all["users"].lines_before, all["users"].lines_after), (2, 1));
+
+        store.sync().unwrap();
+        assert_eq!(store.get::<TestRecord>("rec1").unwrap().unwrap().count, 4);
+        assert!(store.get::<User>("u1").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_compact_waiting_on_a_rotation_rewrites_the_new_file() {
+        use fs2::FileExt;
+        use std::io::Write;
+
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        let record = |id: &str, count: i64| TestRecord {
+            id: id.to_string(),
+            name: "Rotated".to_string(),
+            status: "active".to_string(),
+            count,
+            active: true,
+            updated_at: 1000 + count,
+        };
+        store.create(record("a", 0)).unwrap();
+        store.update(record("a", 1)).unwrap();
+        let path = store.jsonl_path("test_records").unwrap();
+
+        // Hold the file's lock as a rotation does, so the compaction waits on the old inode
+        let mut other = Store::open(temp.path()).unwrap();
+        let held = fs::File::open(&path).unwrap();
+        held.lock_exclusive().unwrap();
+        let compaction = std::thread::spawn(move || other.compact_collection("test_records").unwrap());
+        std::thread::sleep(std::time::Duration::from_millis(200));
+
+        // Finish the rotation: archive the lines, then a fresh file with a new append
+        let mut archive = flate2::write::GzEncoder::new(
+            fs::File::create(format!("{}.1.gz", path.display())).unwrap(),
+            flate2::Compression::default(),
+        );
+        archive.write_all(&fs::read(&path).unwrap()).unwrap();
+        archive.finish().unwrap();
+        let fresh = temp.path().join("fresh.jsonl");
+        jsonl::append_jsonl(&fresh, &record("b", 0)).unwrap();
+        fs::rename(&fresh, &path).unwrap();
+        drop(held);
+        compaction.join().unwrap();
+
+        let live = fs::read_to_string(&path).unwrap();
+        assert_eq!(live.lines().count(), 1);
+        assert!(live.contains("\"b\""));
+    }
+
+    #[test]
+    fn test_prune_expired_deletes_and_compacts() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        assert!(store.set_retention::<TestRecord>(0).is_err());
+        store.set_retention::<TestRecord>(60_000).unwrap();
+        let path = temp.path().join(".taskstore/test_records.jsonl");
+
+        for (id, name, updated_at) in [("old", "Expiring", 1000), ("new", "Fresh", now_ms())] {
+            let mut record = TestRecord {
+                id: id.to_string(),
+                name: name.to_string(),
+                status: "done".to_string(),
+                count: 0,
+                active: true,
+                updated_at,
+            };
+            store.create(record.clone()).unwrap();
+            record.count = 1;
+            record.updated_at += 1;
+            store.update(record).unwrap();
+            // Leave the expiring versions in an archive too
+            if id == "old" {
+                jsonl::rotate_jsonl(&path).unwrap().unwrap();
+            }
+        }
+        // No retention set for users
+        store
+            .create(User {
+                id: "u1".to_string(),
+                email: "u1@example.com".to_string(),
+                updated_at: 1000,
+            })
+            .unwrap();
+
+        assert_eq!(store.prune_expired().unwrap(), 1);
+        assert!(store.get::<TestRecord>("old").unwrap().is_none());
+        assert!(store.get::<TestRecord>("new").unwrap().is_some());
+        assert!(store.get::<User>("u1").unwrap().is_some());
+        assert_eq!(store.prune_expired().unwrap(), 0);
+
+        // Only the latest line per record is left, and the tombstone for "old" is bare
+        assert!(jsonl::archive_paths(&path).unwrap().is_empty());
+        let jsonl = fs::read_to_string(&path).unwrap();
+        assert_eq!(jsonl.lines().count(), 2);
+        let tombstone: serde_json::Value = jsonl
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .find(|line: &serde_json::Value| line["id"] == "old")
+            .unwrap();
+        let mut fields: Vec<&str> = tombstone.as_object().unwrap().keys().map(String::as_str).collect();
+        fields.sort();
+        assert_eq!(fields, ["deleted", "deleted_at", "id", "updated_at"]);
+
+        // None of the pruned record's values are left in any JSONL file or archive
+        for entry in fs::read_dir(temp.path().join(".taskstore")).unwrap() {
+            let file = entry.unwrap().path();
+            if !file.file_name().unwrap().to_string_lossy().contains(".jsonl") {
+                continue;
+            }
+            let mut content = String::new();
+            if file.extension().is_some_and(|ext| ext == "gz") {
+                flate2::read::GzDecoder::new(fs::File::open(&file).unwrap())
+                    .read_to_string(&mut content)
+                    .unwrap();
+            } else {
+                content = fs::read_to_string(&file).unwrap();
+            }
+            assert!(!content.contains("Expiring"), "{}", file.display());
+        }
+        // Nothing to rebuild the pruned record from
+        assert_eq!(store.list_including_deleted::<TestRecord>().unwrap().len(), 1);
+        drop(store);
+
+        fs::remove_file(temp.path().join(".taskstore").join(DEFAULT_DB_FILENAME)).unwrap();
+        let store = Store::open(temp.path()).unwrap();
+        assert!(store.get::<TestRecord>("old").unwrap().is_none());
+        assert_eq!(store.list::<TestRecord>(&[]).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_delete_where_matches_count() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        for i in 0..6 {
+            store
+                .create(TestRecord {
+                    id: format!("rec{}", i),
+                    name: "Purge".to_string(),
+                    status: if i % 2 == 0 { "complete" } else { "running" }.to_string(),
+                    count: i,
+                    active: true,
+                    updated_at: 1000 + i,
+                })
+                .unwrap();
+        }
+
+        let purge = Query::new().eq("status", "complete").lt("count", 4);
+        let expected = store.count::<TestRecord>(purge.filters()).unwrap();
+        assert_eq!(store.delete_where::<TestRecord>(purge.filters()).unwrap(), expected);
+        assert_eq!(expected, 2);
+        assert_eq!(store.delete_where::<TestRecord>(purge.filters()).unwrap(), 0);
+
+        let mut remaining = store.list_ids::<TestRecord>(&[]).unwrap();
+        remaining.sort();
+        assert_eq!(remaining, vec!["rec1", "rec3", "rec4", "rec5"]);
+        assert!(matches!(
+            store.delete_where::<TestRecord>(&[]),
+            Err(StoreError::Schema(_))
+        ));
+
+        // The tombstones reach JSONL, so the deletes survive a rebuild from it
+        drop(store);
+        fs::remove_file(temp.path().join(".taskstore/taskstore.db")).unwrap();
+        let store = Store::open(temp.path()).unwrap();
+        assert_eq!(store.count::<TestRecord>(&[]).unwrap(), 4);
+        let deleted: Vec<MaybeDeleted<TestRecord>> = store.list_including_deleted().unwrap();
+        assert_eq!(deleted.iter().filter(|r| r.is_deleted()).count(), 2);
+    }
+
+    #[test]
+    fn test_between_filter_is_inclusive() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        for count in 1..=5 {
+            store
+                .create(TestRecord {
+                    id: format!("rec{}", count),
+                    name: "Between".to_string(),
+                    status: format!("s{}", count),
+                    count,
+                    active: true,
+                    updated_at: 1000 * count,
+                })
+                .unwrap();
+        }
+
+        let ids = |filter: Filter| -> Vec<String> {
+            let mut ids = store.list_ids::<TestRecord>(&[filter]).unwrap();
+            ids.sort();
+            ids
+        };
+        assert_eq!(ids(Filter::between("count", 2, 4)), vec!["rec2", "rec3", "rec4"]);
+        assert_eq!(ids(Filter::between("count", 4, 2)), Vec::<String>::new());
+        assert_eq!(ids(Filter::between("status", "s4", "s9")), vec!["rec4", "rec5"]);
+        assert_eq!(ids(Filter::between("updated_at", 1000, 2000)), vec!["rec1", "rec2"]);
+        let query = Query::new().between("count", 1, 3).ne("status", "s2");
+        assert_eq!(store.count::<TestRecord>(query.filters()).unwrap(), 2);
+
+        for invalid in [
+            Filter::between("count", 1, 2.5),
+            Filter::between("active", false, true),
+            Filter {
+                field: "count".to_string(),
+                op: FilterOp::Between,
+                value: IndexValue::Int(3),
+            },
+            Filter {
+                field: "count".to_string(),
+                op: FilterOp::Eq,
+                value: IndexValue::Range(Box::new(1.into()), Box::new(3.into())),
+            },
+        ] {
+            let err = store.list::<TestRecord>(&[invalid]).unwrap_err();
+            assert!(matches!(err, StoreError::Schema(_)));
+        }
+    }
+
+    #[test]
+    fn test_list_json_filters_any_collection() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        for (id, status, count) in [("rec1", "active", 1), ("rec2", "active", 7), ("rec3", "done", 9)] {
+            store
+                .create(TestRecord {
+                    id: id.to_string(),
+                    name: format!("Record {}", id),
+                    status: status.to_string(),
+                    count,
+                    active: true,
+                    updated_at: 1000 + count,
+                })
+                .unwrap();
+        }
+
+        let ids = |filters: &[&str]| -> Vec<String> {
+            let filters: Vec<Filter> = filters.iter().map(|f| f.parse().unwrap()).collect();
+            store
+                .list_json("test_records", &filters)
+                .unwrap()
+                .iter()
+                .map(|r| r["id"].as_str().unwrap().to_string())
+                .collect()
+        };
+        assert_eq!(ids(&[]), vec!["rec3", "rec2", "rec1"]);
+        assert_eq!(ids(&["status=active", "count>=5"]), vec!["rec2"]);
+        assert_eq!(ids(&["status~ct"]), vec!["rec2", "rec1"]);
+        assert_eq!(ids(&["updated_at<1005"]), vec!["rec1"]);
+
+        let err = store
+            .list_json("test_records", &["name=Record rec1".parse().unwrap()])
+            .unwrap_err();
+        assert!(matches!(err, StoreError::Schema(_)));
+        assert!(
+            store
+                .list_json("other", &["name=x".parse().unwrap()])
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_create_json_writes_and_indexes_raw_record() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        store
+            .create(TestRecord {
+                id: "rec1".to_string(),
+                name: "Typed".to_string(),
+                status: "active".to_string(),
+                count: 1,
+                active: true,
+                updated_at: 1000,
+            })
+            .unwrap();
+
+        let id = store
+            .create_json(
+                "test_records",
+                serde_json::json!({"id": "rec2", "name": "Raw", "status": "active", "count": 2, "active": false}),
+            )
+            .unwrap();
+        assert_eq!(id, "rec2");
+        let raw: TestRecord = store.get("rec2").unwrap().unwrap();
+        assert!(raw.updated_at > 1000);
+        assert_eq!(
+            store
+                .count::<TestRecord>(Query::new().eq("status", "active").filters())
+                .unwrap(),
+            2
+        );
+
+        for (record, conflict) in [
+            (serde_json::json!({"id": "rec1"}), true),
+            (serde_json::json!({"name": "No ID"}), false),
+            (serde_json::json!({"id": "rec3", "updated_at": "yesterday"}), false),
+            (serde_json::json!(["rec3"]), false),
+        ] {
+            let err = store.create_json("test_records", record).unwrap_err();
+            if conflict {
+                assert!(matches!(err, StoreError::Conflict(_)));
+            } else {
+                assert!(matches!(err, StoreError::Validation(_)));
+            }
+        }
+        assert_eq!(store.count::<TestRecord>(&[]).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_create_json_with_refs_checks_parents() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        store.create(member("m1", "p1")).unwrap();
+        let refs = [("member_id", "members")];
+        let json = |member_id: &str| serde_json::to_value(note("n1", member_id, "p1")).unwrap();
+
+        let err = store
+            .create_json_with_refs("notes", json("m-missing"), &refs)
+            .unwrap_err();
+        assert!(matches!(err, StoreError::Validation(_)));
+        // Nothing reached JSONL either
+        store.sync().unwrap();
+        assert!(store.get::<Note>("n1").unwrap().is_none());
+
+        assert_eq!(store.create_json_with_refs("notes", json("m1"), &refs).unwrap(), "n1");
+        assert_eq!(store.get::<Note>("n1").unwrap().unwrap().member_id, "m1");
+    }
 
-        if !output.status.success() {
-            return Err(eyre!("Failed to configure merge driver command"));
+    #[test]
+    fn test_integer_id_index_value() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        for id in [2, 9, 10, 100] {
+            store
+                .create(SeqRecord {
+                    id: id.to_string(),
+                    updated_at: 1000,
+                })
+                .unwrap();
         }
+        let ids = |store: &Store, query: Query| -> Vec<String> {
+            let (filters, options) = query.into_parts();
+            store
+                .list_with::<SeqRecord>(&filters, &options)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.id)
+                .collect()
+        };
 
-        Ok(())
-    }
-}
+        // Numeric, not lexicographic, ordering and ranges
+        assert_eq!(ids(&store, Query::new().order_by("id")), vec!["2", "9", "10", "100"]);
+        assert_eq!(
+            ids(&store, Query::new().gte("id", 9).lt("id", 100).order_by("id")),
+            vec!["9", "10"]
+        );
 
-// Helper function for timestamps
-pub fn now_ms() -> i64 {
-    use std::time::{SystemTime, UNIX_EPOCH};
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .expect("System time before Unix epoch")
-        .as_millis() as i64
-}
+        // Survives a sync, with or without the type registered
+        store.sync().unwrap();
+        assert_eq!(ids(&store, Query::new().gt("id", 9).order_by("id")), vec!["10", "100"]);
+        store.register::<SeqRecord>();
+        store.sync().unwrap();
+        assert_eq!(ids(&store, Query::new().gt("id", 9).order_by("id")), vec!["10", "100"]);
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use serde::{Deserialize, Serialize};
-    use std::collections::HashMap;
-    use tempfile::TempDir;
+        // JSONL keeps the canonical string form
+        let jsonl = fs::read_to_string(temp.path().join(".taskstore/sequence.jsonl")).unwrap();
+        assert!(jsonl.contains(r#""id":"100""#));
+    }
 
-    // Test record type
-    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-    struct TestRecord {
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    struct LabelRecord {
         id: String,
-        name: String,
-        status: String,
-        count: i64,
-        active: bool,
+        label: String,
         updated_at: i64,
     }
 
-    impl Record for TestRecord {
-        fn id(&self) -> &str {
-            &self.id
+    impl Record for LabelRecord {
+        fn id(&self) -> &str {
+            &self.id
+        }
+
+        fn updated_at(&self) -> i64 {
+            self.updated_at
+        }
+
+        fn collection_name() -> &'static str {
+            "labels"
+        }
+
+        fn indexed_fields(&self) -> HashMap<String, IndexValue> {
+            // Computed, so it can't be recovered from the raw JSON
+            HashMap::from([("label_upper".to_string(), self.label.to_uppercase().into())])
+        }
+    }
+
+    #[test]
+    fn test_rebuild_all_indexes_uses_registered_types() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        store
+            .create(LabelRecord {
+                id: "l1".to_string(),
+                label: "urgent".to_string(),
+                updated_at: 1000,
+            })
+            .unwrap();
+        store
+            .create(TestRecord {
+                id: "rec1".to_string(),
+                name: "Record 1".to_string(),
+                status: "active".to_string(),
+                count: 1,
+                active: true,
+                updated_at: 1000,
+            })
+            .unwrap();
+        let urgent = Query::new().eq("label_upper", "URGENT");
+
+        // Unregistered: the computed field has no top-level counterpart
+        store.sync().unwrap();
+        assert!(store.list::<LabelRecord>(urgent.filters()).unwrap().is_empty());
+
+        store.register::<LabelRecord>();
+        store.sync().unwrap();
+        assert_eq!(store.list::<LabelRecord>(urgent.filters()).unwrap().len(), 1);
+
+        let counts = store.rebuild_all_indexes().unwrap();
+        assert_eq!(
+            counts,
+            HashMap::from([("labels".to_string(), 1), ("test_records".to_string(), 1)])
+        );
+    }
+
+    #[test]
+    fn test_vacuum_shrinks_database() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+
+        for i in 0..200 {
+            let record = TestRecord {
+                id: format!("rec{}", i),
+                name: "x".repeat(1000),
+                status: "active".to_string(),
+                count: i,
+                active: true,
+                updated_at: now_ms(),
+            };
+            store.create(record).unwrap();
+        }
+        for i in 0..200 {
+            store.delete::<TestRecord>(&format!("rec{}", i)).unwrap();
+        }
+
+        let db_path = temp.path().join(".taskstore/taskstore.db");
+        let before = fs::metadata(&db_path).unwrap().len();
+        store.vacuum().unwrap();
+        let after = fs::metadata(&db_path).unwrap().len();
+        assert!(after < before, "expected {} < {}", after, before);
+    }
+
+    #[test]
+    fn test_concurrent_writers_do_not_fail_on_busy() {
+        let temp = TempDir::new().unwrap();
+        Store::open(temp.path()).unwrap();
+
+        let handles: Vec<_> = (0..2)
+            .map(|t| {
+                let path = temp.path().to_path_buf();
+                std::thread::spawn(move || {
+                    let mut store = Store::open_with(
+                        &path,
+                        StoreOptions {
+                            busy_timeout_ms: 10_000,
+                            ..Default::default()
+                        },
+                    )
+                    .unwrap();
+                    for i in 0..50 {
+                        let record = TestRecord {
+                            id: format!("t{}-rec{}", t, i),
+                            name: "Concurrent".to_string(),
+                            status: "active".to_string(),
+                            count: i,
+                            active: true,
+                            updated_at: now_ms(),
+                        };
+                        store.create(record).unwrap();
+                    }
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let store = Store::open(temp.path()).unwrap();
+        let records: Vec<TestRecord> = store.list(&[]).unwrap();
+        assert_eq!(records.len(), 100);
+    }
+
+    #[test]
+    fn test_list_ids() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+
+        for (i, status) in ["active", "draft", "active"].iter().enumerate() {
+            store
+                .create(TestRecord {
+                    id: format!("rec{}", i),
+                    name: "Listed".to_string(),
+                    status: status.to_string(),
+                    count: i as i64,
+                    active: true,
+                    updated_at: 1000 + i as i64,
+                })
+                .unwrap();
+        }
+
+        assert_eq!(store.list_ids::<TestRecord>(&[]).unwrap(), vec!["rec2", "rec1", "rec0"]);
+
+        let active = vec![Filter {
+            field: "status".to_string(),
+            op: FilterOp::Eq,
+            value: IndexValue::String("active".to_string()),
+        }];
+        assert_eq!(store.list_ids::<TestRecord>(&active).unwrap(), vec!["rec2", "rec0"]);
+    }
+
+    #[test]
+    fn test_list_iter_matches_list_across_pages() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+
+        // More than one page, with shared timestamps to exercise the keyset tiebreak
+        for i in 0..(LIST_ITER_PAGE_SIZE + 50) {
+            store
+                .create(TestRecord {
+                    id: format!("rec{:04}", i),
+                    name: "Streamed".to_string(),
+                    status: if i % 3 == 0 { "active" } else { "draft" }.to_string(),
+                    count: i as i64,
+                    active: true,
+                    updated_at: 1000 + (i / 7) as i64,
+                })
+                .unwrap();
+        }
+
+        let listed: Vec<TestRecord> = store.list(&[]).unwrap();
+        let streamed: Vec<TestRecord> = store.list_iter(&[]).unwrap().collect::<Result<_>>().unwrap();
+        assert_eq!(streamed.len(), listed.len());
+        let mut listed_ids: Vec<&str> = listed.iter().map(|r| r.id.as_str()).collect();
+        let mut streamed_ids: Vec<&str> = streamed.iter().map(|r| r.id.as_str()).collect();
+        assert!(streamed.windows(2).all(|w| w[0].updated_at >= w[1].updated_at));
+        listed_ids.sort();
+        streamed_ids.sort();
+        assert_eq!(listed_ids, streamed_ids);
+
+        let active = vec![Filter {
+            field: "status".to_string(),
+            op: FilterOp::Eq,
+            value: IndexValue::String("active".to_string()),
+        }];
+        let total: i64 = store
+            .list_iter::<TestRecord>(&active)
+            .unwrap()
+            .map(|r| r.unwrap().count)
+            .sum();
+        let expected: i64 = (0..(LIST_ITER_PAGE_SIZE + 50) as i64).filter(|i| i % 3 == 0).sum();
+        assert_eq!(total, expected);
+    }
+
+    #[test]
+    fn test_iter_steps_through_rows_in_list_order() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        for i in 0..30 {
+            store
+                .create(TestRecord {
+                    id: format!("rec{:02}", i),
+                    name: "Stepped".to_string(),
+                    status: if i % 3 == 0 { "active" } else { "draft" }.to_string(),
+                    count: i,
+                    active: true,
+                    updated_at: 1000 + i / 4,
+                })
+                .unwrap();
+        }
+
+        let ids = |records: Vec<TestRecord>| records.into_iter().map(|r| r.id).collect::<Vec<_>>();
+        let stepped: Vec<TestRecord> = store.iter(&[]).unwrap().collect::<Result<_>>().unwrap();
+        let paged: Vec<TestRecord> = store.list_iter(&[]).unwrap().collect::<Result<_>>().unwrap();
+        assert_eq!(ids(stepped), ids(paged));
+
+        let active = Query::new().eq("status", "active");
+        let stepped: Vec<TestRecord> = store.iter(active.filters()).unwrap().collect::<Result<_>>().unwrap();
+        assert_eq!(stepped.len(), 10);
+        assert!(stepped.iter().all(|r| r.status == "active"));
+
+        // Another connection's write waits on the iterator's read lock
+        let mut other = Store::open_with(
+            temp.path(),
+            StoreOptions {
+                busy_timeout_ms: 10,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let mut iter = store.iter::<TestRecord>(&[]).unwrap();
+        assert!(iter.next().unwrap().is_ok());
+        let record = TestRecord {
+            id: "late".to_string(),
+            name: "Late".to_string(),
+            status: "active".to_string(),
+            count: 0,
+            active: true,
+            updated_at: 2000,
+        };
+        assert!(matches!(other.create(record.clone()), Err(StoreError::Locked(_))));
+        drop(iter);
+        other.create(record).unwrap();
+
+        // A row that doesn't deserialize ends the iteration with its error
+        store
+            .db()
+            .execute("UPDATE records SET data_json = 'not json' WHERE id = 'rec28'", [])
+            .unwrap();
+        let mut iter = store.iter::<TestRecord>(&[]).unwrap();
+        assert!(iter.next().unwrap().is_ok());
+        assert!(iter.next().unwrap().is_err());
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn test_latest_and_earliest() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+
+        assert!(store.latest::<TestRecord>(&[], None).unwrap().is_none());
+
+        for (i, (status, count)) in [("active", 5), ("draft", 9), ("active", 1), ("active", 3)]
+            .iter()
+            .enumerate()
+        {
+            store
+                .create(TestRecord {
+                    id: format!("rec{}", i),
+                    name: "Ordered".to_string(),
+                    status: status.to_string(),
+                    count: *count,
+                    active: true,
+                    updated_at: 1000 + i as i64,
+                })
+                .unwrap();
+        }
+
+        let latest: TestRecord = store.latest(&[], None).unwrap().unwrap();
+        assert_eq!(latest.id, "rec3");
+        let earliest: TestRecord = store.earliest(&[], None).unwrap().unwrap();
+        assert_eq!(earliest.id, "rec0");
+
+        let active = vec![Filter {
+            field: "status".to_string(),
+            op: FilterOp::Eq,
+            value: IndexValue::String("active".to_string()),
+        }];
+        let highest: TestRecord = store.latest(&active, Some("count")).unwrap().unwrap();
+        assert_eq!(highest.id, "rec0");
+        let lowest: TestRecord = store.earliest(&active, Some("count")).unwrap().unwrap();
+        assert_eq!(lowest.id, "rec2");
+    }
+
+    #[test]
+    fn test_count_by() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+
+        // Empty collection is not an error
+        assert!(store.count_by::<TestRecord>("status").unwrap().is_empty());
+
+        for (i, status) in ["active", "draft", "active", "done", "active", "draft"]
+            .iter()
+            .enumerate()
+        {
+            store
+                .create(TestRecord {
+                    id: format!("rec{}", i),
+                    name: "Counted".to_string(),
+                    status: status.to_string(),
+                    count: i as i64,
+                    active: i % 2 == 0,
+                    updated_at: now_ms(),
+                })
+                .unwrap();
+        }
+        store.delete::<TestRecord>("rec5").unwrap();
+
+        let counts = store.count_by::<TestRecord>("status").unwrap();
+        assert_eq!(
+            counts,
+            vec![
+                (IndexValue::String("active".to_string()), 3),
+                (IndexValue::String("done".to_string()), 1),
+                (IndexValue::String("draft".to_string()), 1),
+            ]
+        );
+
+        let counts = store.count_by::<TestRecord>("active").unwrap();
+        assert_eq!(counts, vec![(IndexValue::Bool(true), 3), (IndexValue::Bool(false), 2)]);
+
+        assert!(store.count_by::<TestRecord>("not_indexed").is_err());
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+
+        assert_eq!(store.aggregate::<TestRecord>("count", Agg::Avg, &[]).unwrap(), None);
+        assert_eq!(
+            store.aggregate::<TestRecord>("count", Agg::Count, &[]).unwrap(),
+            Some(0.0)
+        );
+
+        for (i, status) in ["active", "draft", "active", "active"].iter().enumerate() {
+            store
+                .create(TestRecord {
+                    id: format!("rec{}", i),
+                    name: "Aggregated".to_string(),
+                    status: status.to_string(),
+                    count: (i as i64 + 1) * 10,
+                    active: true,
+                    updated_at: now_ms(),
+                })
+                .unwrap();
+        }
+
+        let active = vec![Filter {
+            field: "status".to_string(),
+            op: FilterOp::Eq,
+            value: IndexValue::String("active".to_string()),
+        }];
+
+        // Active counts: 10, 30, 40
+        assert_eq!(
+            store.aggregate::<TestRecord>("count", Agg::Sum, &active).unwrap(),
+            Some(80.0)
+        );
+        assert_eq!(
+            store.aggregate::<TestRecord>("count", Agg::Min, &active).unwrap(),
+            Some(10.0)
+        );
+        assert_eq!(
+            store.aggregate::<TestRecord>("count", Agg::Max, &active).unwrap(),
+            Some(40.0)
+        );
+        assert_eq!(
+            store.aggregate::<TestRecord>("count", Agg::Count, &active).unwrap(),
+            Some(3.0)
+        );
+        assert_eq!(
+            store.aggregate::<TestRecord>("count", Agg::Avg, &[]).unwrap(),
+            Some(25.0)
+        );
+
+        let none = vec![Filter {
+            field: "status".to_string(),
+            op: FilterOp::Eq,
+            value: IndexValue::String("missing".to_string()),
+        }];
+        assert_eq!(store.aggregate::<TestRecord>("count", Agg::Avg, &none).unwrap(), None);
+
+        // Non-numeric fields only support Count
+        assert!(store.aggregate::<TestRecord>("status", Agg::Sum, &[]).is_err());
+        assert_eq!(
+            store.aggregate::<TestRecord>("status", Agg::Count, &[]).unwrap(),
+            Some(4.0)
+        );
+    }
+
+    #[test]
+    fn test_find_orphans() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+
+        store
+            .create(Project {
+                id: "p1".to_string(),
+                updated_at: 1000,
+            })
+            .unwrap();
+        for (id, project) in [("m1", "p1"), ("m2", "p-missing"), ("m3", "p1")] {
+            store.create(member(id, project)).unwrap();
+        }
+
+        let orphans = store.find_orphans("members", "project_id", "projects").unwrap();
+        assert_eq!(orphans, vec!["m2".to_string()]);
+
+        // Missing fields are not orphans
+        assert!(store.find_orphans("members", "nope", "projects").unwrap().is_empty());
+        assert!(store.find_orphans("members", "bad-field", "projects").is_err());
+    }
+
+    #[test]
+    fn test_validation_collection_name() {
+        // Valid
+        assert!(Store::validate_collection_name("valid_name").is_ok());
+        assert!(Store::validate_collection_name("valid-name").is_ok());
+
+        // Invalid
+        assert!(Store::validate_collection_name("invalid/name").is_err());
+        assert!(Store::validate_collection_name("").is_err());
+        assert!(Store::validate_collection_name(&"a".repeat(65)).is_err());
+    }
+
+    #[test]
+    fn test_validation_field_name() {
+        // Valid
+        assert!(Store::validate_field_name("valid_field").is_ok());
+
+        // Invalid
+        assert!(Store::validate_field_name("invalid-field").is_err());
+        assert!(Store::validate_field_name("").is_err());
+        assert!(Store::validate_field_name(&"a".repeat(65)).is_err());
+    }
+
+    #[test]
+    fn test_check_reports_problems_without_mutating() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        for i in 0..2 {
+            store
+                .create(TestRecord {
+                    id: format!("rec{}", i),
+                    name: "Checked".to_string(),
+                    status: "active".to_string(),
+                    count: i,
+                    active: true,
+                    updated_at: 1000,
+                })
+                .unwrap();
+        }
+        store.create(member("m1", "p-missing")).unwrap();
+        assert!(store.check().unwrap().is_healthy());
+
+        // A conflicting version of rec0 with the same timestamp, a new record, and junk
+        let jsonl_path = temp.path().join(".taskstore/test_records.jsonl");
+        let mut content = fs::read_to_string(&jsonl_path).unwrap();
+        content.push_str(
+            r#"{"id":"rec0","name":"Other","status":"active","count":0,"active":true,"updated_at":1000}
+{"id":"rec2","name":"New","status":"active","count":2,"active":true,"updated_at":1000}
+{"id":"rec3","#,
+        );
+        fs::write(&jsonl_path, content).unwrap();
+
+        let report = store.check_with_refs(&[("members", "project_id", "projects")]).unwrap();
+        assert!(!report.is_healthy());
+        assert!(report.stale);
+        assert_eq!(report.drift.len(), 1);
+        assert_eq!(report.drift[0].added, 1);
+        assert_eq!(report.jsonl[0].collection, "test_records");
+        assert_eq!(report.jsonl[0].unparseable_lines, vec![5]);
+        assert_eq!(report.jsonl[0].ambiguous_ids, vec!["rec0"]);
+        assert_eq!(report.orphans[0].ids, vec!["m1"]);
+
+        // Nothing was synced
+        assert!(store.is_stale().unwrap());
+        assert!(store.get::<TestRecord>("rec2").unwrap().is_none());
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["drift"][0]["collection"], "test_records");
+    }
+
+    #[test]
+    fn test_synchronous_pragma_is_applied() {
+        let temp = TempDir::new().unwrap();
+        let synchronous = |store: &Store| -> i64 {
+            store
+                .db()
+                .query_row("PRAGMA synchronous", [], |row| row.get(0))
+                .unwrap()
+        };
+
+        assert_eq!(synchronous(&Store::open(temp.path()).unwrap()), 1); // NORMAL
+        for (setting, expected) in [(Synchronous::Off, 0), (Synchronous::Full, 2)] {
+            let store = Store::open_with(
+                temp.path(),
+                StoreOptions {
+                    synchronous: setting,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            assert_eq!(synchronous(&store), expected);
+        }
+    }
+
+    #[test]
+    fn test_fsync_policy_on_flush_defers_fsync() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open_with(
+            temp.path(),
+            StoreOptions {
+                fsync_policy: FsyncPolicy::OnFlush,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        for i in 0..5 {
+            store
+                .create(TestRecord {
+                    id: format!("rec{}", i),
+                    name: "Bulk".to_string(),
+                    status: "active".to_string(),
+                    count: i,
+                    active: true,
+                    updated_at: now_ms(),
+                })
+                .unwrap();
+        }
+        assert_eq!(store.fsync.pending_files(), 1);
+        store.flush().unwrap();
+        assert_eq!(store.fsync.pending_files(), 0);
+        drop(store);
+
+        let store = Store::open(temp.path()).unwrap();
+        assert_eq!(store.list::<TestRecord>(&[]).unwrap().len(), 5);
+    }
+
+    #[test]
+    fn test_compress_rotated_archives_large_jsonl() {
+        let temp = TempDir::new().unwrap();
+        let options = || StoreOptions {
+            compress_rotated: true,
+            rotate_bytes: 512,
+            ..Default::default()
+        };
+        let mut store = Store::open_with(temp.path(), options()).unwrap();
+        for i in 0..20 {
+            store
+                .create(TestRecord {
+                    id: format!("rec{}", i),
+                    name: "Rotated".to_string(),
+                    status: "active".to_string(),
+                    count: i,
+                    active: true,
+                    updated_at: now_ms(),
+                })
+                .unwrap();
+        }
+        let path = temp.path().join(".taskstore/test_records.jsonl");
+        let archives = jsonl::archive_paths(&path).unwrap();
+        assert!(!archives.is_empty());
+        assert!(fs::metadata(&path).unwrap().len() <= 512);
+
+        // Versions in the live file win over archived ones, tombstones included
+        let mut record: TestRecord = store.get("rec0").unwrap().unwrap();
+        record.name = "Updated".to_string();
+        record.updated_at += 1;
+        store.update(record).unwrap();
+        store.delete::<TestRecord>("rec1").unwrap();
+        drop(store);
+
+        fs::remove_file(temp.path().join(".taskstore").join(DEFAULT_DB_FILENAME)).unwrap();
+        let store = Store::open_with(temp.path(), options()).unwrap();
+        assert_eq!(store.list::<TestRecord>(&[]).unwrap().len(), 19);
+        assert_eq!(store.get::<TestRecord>("rec0").unwrap().unwrap().name, "Updated");
+        assert!(store.get::<TestRecord>("rec1").unwrap().is_none());
+        assert!(store.get::<TestRecord>("rec19").unwrap().is_some());
+    }
+
+    #[test]
+    fn test_open_truncates_partial_jsonl_line() {
+        let temp = TempDir::new().unwrap();
+        let path = {
+            let mut store = Store::open(temp.path()).unwrap();
+            store
+                .create(TestRecord {
+                    id: "rec1".to_string(),
+                    name: "Kept".to_string(),
+                    status: "active".to_string(),
+                    count: 1,
+                    active: true,
+                    updated_at: now_ms(),
+                })
+                .unwrap();
+            store.jsonl_path("test_records").unwrap()
+        };
+        let complete = fs::read_to_string(&path).unwrap();
+        let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        std::io::Write::write_all(&mut file, br#"{"id":"rec2","name":"Interru"#).unwrap();
+        drop(file);
+
+        let mut store = Store::open(temp.path()).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), complete);
+        store
+            .create(TestRecord {
+                id: "rec2".to_string(),
+                name: "Retried".to_string(),
+                status: "active".to_string(),
+                count: 2,
+                active: true,
+                updated_at: now_ms(),
+            })
+            .unwrap();
+        drop(store);
+
+        let store = Store::open(temp.path()).unwrap();
+        assert_eq!(store.list::<TestRecord>(&[]).unwrap().len(), 2);
+        assert!(jsonl::scan_jsonl(&path).unwrap().is_clean());
+    }
+
+    #[test]
+    fn test_rebuild_recreates_database_from_jsonl() {
+        let temp = TempDir::new().unwrap();
+        {
+            let mut store = Store::open(temp.path()).unwrap();
+            store
+                .create(TestRecord {
+                    id: "rec1".to_string(),
+                    name: "Kept".to_string(),
+                    status: "active".to_string(),
+                    count: 1,
+                    active: true,
+                    updated_at: 1000,
+                })
+                .unwrap();
+            // Simulate a damaged cache
+            store.db().execute("DELETE FROM records", []).unwrap();
+        }
+
+        let store = Store::rebuild_with(temp.path(), StoreOptions::default()).unwrap();
+        assert_eq!(store.get::<TestRecord>("rec1").unwrap().unwrap().name, "Kept");
+        assert!(!store.is_stale().unwrap());
+    }
+
+    #[test]
+    fn test_import_jsonl_applies_strategy() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        store.register::<TestRecord>();
+        for (id, updated_at) in [("old", 1000), ("new", 3000)] {
+            store
+                .create(TestRecord {
+                    id: id.to_string(),
+                    name: "Existing".to_string(),
+                    status: "active".to_string(),
+                    count: 0,
+                    active: true,
+                    updated_at,
+                })
+                .unwrap();
         }
 
-        fn updated_at(&self) -> i64 {
-            self.updated_at
-        }
+        let input = [
+            r#"{"id":"old","name":"Imported","status":"done","count":1,"active":true,"updated_at":2000}"#,
+            r#"{"id":"new","name":"Imported","status":"done","count":1,"active":true,"updated_at":2000}"#,
+            r#"{"id":"fresh","name":"Imported","status":"done","count":1,"active":true,"updated_at":2000}"#,
+            "not json",
+            r#"{"name":"no id"}"#,
+        ]
+        .join("\n");
+
+        let report = store
+            .import_jsonl("test_records", input.as_bytes(), ImportStrategy::KeepNewer)
+            .unwrap();
+        assert_eq!((report.imported, report.updated, report.skipped), (1, 1, 1));
+        assert_eq!(
+            report.rejected.iter().map(|(line, _)| *line).collect::<Vec<_>>(),
+            vec![4, 5]
+        );
+        assert_eq!(store.get::<TestRecord>("old").unwrap().unwrap().name, "Imported");
+        assert_eq!(store.get::<TestRecord>("new").unwrap().unwrap().name, "Existing");
+        // Reindexed, and unchanged by a resync
+        store.sync().unwrap();
+        let done: Vec<TestRecord> = store.list(Query::new().eq("status", "done").filters()).unwrap();
+        assert_eq!(done.len(), 2);
+
+        let report = store
+            .import_jsonl("test_records", input.as_bytes(), ImportStrategy::SkipExisting)
+            .unwrap();
+        assert_eq!((report.imported, report.skipped), (0, 3));
+
+        let report = store
+            .import_jsonl("test_records", input.as_bytes(), ImportStrategy::Overwrite)
+            .unwrap();
+        assert_eq!(report.updated, 3);
+        store.sync().unwrap();
+        assert_eq!(store.get::<TestRecord>("new").unwrap().unwrap().name, "Imported");
+    }
 
-        fn collection_name() -> &'static str {
-            "test_records"
-        }
+    #[test]
+    fn test_diff_compares_latest_live_records() {
+        let record = |id: &str, name: &str, updated_at: i64| TestRecord {
+            id: id.to_string(),
+            name: name.to_string(),
+            status: "active".to_string(),
+            count: 0,
+            active: true,
+            updated_at,
+        };
 
-        fn indexed_fields(&self) -> HashMap<String, IndexValue> {
-            let mut fields = HashMap::new();
-            fields.insert("status".to_string(), IndexValue::String(self.status.clone()));
-            fields.insert("count".to_string(), IndexValue::Int(self.count));
-            fields.insert("active".to_string(), IndexValue::Bool(self.active));
-            fields
+        let (temp_a, temp_b) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+        let mut a = Store::open(temp_a.path()).unwrap();
+        let mut b = Store::open(temp_b.path()).unwrap();
+        for store in [&mut a, &mut b] {
+            store.create(record("same", "Same", 1000)).unwrap();
+            store.create(record("edited", "Before", 1000)).unwrap();
+            store.create(record("deleted", "Gone", 1000)).unwrap();
         }
+        a.update(record("edited", "After", 2000)).unwrap();
+        a.create(record("new", "Only A", 1000)).unwrap();
+        b.delete::<TestRecord>("deleted").unwrap();
+
+        let diff = a.diff(&b, None).unwrap();
+        assert_eq!(diff.collections.len(), 1);
+        let c = &diff.collections[0];
+        assert_eq!(c.collection, "test_records");
+        assert_eq!(c.only_in_self, vec!["deleted", "new"]);
+        assert!(c.only_in_other.is_empty());
+        assert_eq!(c.changed, vec!["edited"]);
+
+        assert_eq!(
+            b.diff(&a, Some("test_records")).unwrap().collections[0].only_in_other,
+            c.only_in_self
+        );
+        assert!(a.diff(&a, None).unwrap().is_empty());
+        assert!(a.diff(&b, Some("other")).unwrap().is_empty());
     }
 
     #[test]
-    fn test_store_open_creates_directory() {
+    fn test_sizes_reports_lines_live_records_and_index_rows() {
+        let record = |id: &str, updated_at: i64| TestRecord {
+            id: id.to_string(),
+            name: "Name".to_string(),
+            status: "active".to_string(),
+            count: 0,
+            active: true,
+            updated_at,
+        };
+
         let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        assert!(store.sizes().unwrap().is_empty());
+
+        store.create(record("keep", 1000)).unwrap();
+        store.create(record("drop", 1000)).unwrap();
+        store.update(record("keep", 2000)).unwrap();
+        store.delete::<TestRecord>("drop").unwrap();
+
+        let sizes = store.sizes().unwrap();
+        assert_eq!(sizes.len(), 1);
+        let s = &sizes[0];
+        assert_eq!(s.collection, "test_records");
+        assert_eq!(s.total_lines, 4);
+        assert_eq!(s.live_records, 1);
+        assert_eq!(s.index_rows, 3);
+        assert_eq!(s.stale_ratio, 4.0);
+        assert_eq!(
+            s.jsonl_bytes,
+            fs::metadata(store.jsonl_path("test_records").unwrap()).unwrap().len()
+        );
 
-        let _store = Store::open(temp.path()).unwrap();
-        let store_path = temp.path().join(".taskstore");
-        assert!(store_path.exists());
-        assert!(store_path.join("taskstore.db").exists());
-        assert!(store_path.join(".gitignore").exists());
-        assert!(store_path.join(".version").exists());
+        // Compaction keeps the tombstone as the latest line for "drop"
+        store.compact_collection("test_records").unwrap();
+        assert_eq!(store.sizes().unwrap()[0].stale_ratio, 2.0);
     }
 
     #[test]
-    fn test_generic_create() {
+    fn test_sizes_history_and_check_read_rotated_archives() {
+        let record = |id: &str, name: &str, updated_at: i64| TestRecord {
+            id: id.to_string(),
+            name: name.to_string(),
+            status: "active".to_string(),
+            count: 0,
+            active: true,
+            updated_at,
+        };
+
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        store.create(record("rec1", "First", 1000)).unwrap();
+        store.create(record("rec2", "Other", 1000)).unwrap();
+        let path = store.jsonl_path("test_records").unwrap();
+        let archive = jsonl::rotate_jsonl(&path).unwrap().unwrap();
+        store.update(record("rec1", "Second", 2000)).unwrap();
+
+        let name_at = |at: i64| store.get_at_time::<TestRecord>("rec1", at).unwrap().map(|r| r.name);
+        assert_eq!(name_at(1500).as_deref(), Some("First"));
+        assert_eq!(name_at(2500).as_deref(), Some("Second"));
+
+        let s = &store.sizes().unwrap()[0];
+        assert_eq!(s.total_lines, 3);
+        assert_eq!(s.live_records, 2);
+        assert_eq!(
+            s.jsonl_bytes,
+            fs::metadata(&path).unwrap().len() + fs::metadata(&archive).unwrap().len()
+        );
+        assert!(store.check().unwrap().is_healthy());
+
+        // A tie with an archived version and junk in a later archive
+        fs::write(
+            &path,
+            "{\"id\":\"rec2\",\"name\":\"Tied\",\"status\":\"active\",\"count\":0,\"active\":true,\"updated_at\":1000}\n{\n",
+        )
+        .unwrap();
+        jsonl::rotate_jsonl(&path).unwrap().unwrap();
+        let report = store.check().unwrap();
+        assert_eq!(report.jsonl[0].unparseable_lines, Vec::<usize>::new());
+        assert_eq!(report.jsonl[0].archived_unparseable_lines, 1);
+        assert_eq!(report.jsonl[0].ambiguous_ids, vec!["rec2"]);
+
+        // Repair folds the archives back into the file
+        jsonl::repair_jsonl(&path).unwrap();
+        assert!(jsonl::archive_paths(&path).unwrap().is_empty());
+        assert!(jsonl::scan_jsonl(&path).unwrap().is_clean());
+    }
+
+    #[test]
+    fn test_update_existing_requires_record() {
         let temp = TempDir::new().unwrap();
         let mut store = Store::open(temp.path()).unwrap();
 
         let record = TestRecord {
             id: "rec1".to_string(),
-            name: "Test Record 1".to_string(),
+            name: "Original".to_string(),
             status: "active".to_string(),
-            count: 42,
+            count: 1,
             active: true,
-            updated_at: now_ms(),
+            updated_at: 1000,
         };
 
-        let id = store.create(record.clone()).unwrap();
-        assert_eq!(id, "rec1");
+        let err = store.update_existing(record.clone()).unwrap_err();
+        assert!(err.is_not_found());
+        assert_eq!(err.to_string(), "Record not found in test_records: rec1");
+        assert!(!temp.path().join(".taskstore/test_records.jsonl").exists());
 
-        // Verify JSONL file was created
-        let jsonl_path = temp.path().join(".taskstore/test_records.jsonl");
-        assert!(jsonl_path.exists());
+        store.create(record.clone()).unwrap();
+        store
+            .update_existing(TestRecord {
+                name: "Updated".to_string(),
+                updated_at: 2000,
+                ..record
+            })
+            .unwrap();
+        let retrieved: TestRecord = store.get("rec1").unwrap().unwrap();
+        assert_eq!(retrieved.name, "Updated");
+    }
 
-        // Verify record in SQLite
-        let retrieved: Option<TestRecord> = store.get("rec1").unwrap();
-        assert!(retrieved.is_some());
-        let retrieved = retrieved.unwrap();
-        assert_eq!(retrieved.name, "Test Record 1");
-        assert_eq!(retrieved.status, "active");
-        assert_eq!(retrieved.count, 42);
-        assert!(retrieved.active);
+    #[test]
+    fn test_list_with_query_order_and_pagination() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+
+        for (i, count) in [5, 9, 1, 9, 7].iter().enumerate() {
+            store
+                .create(TestRecord {
+                    id: format!("rec{}", i),
+                    name: "Paged".to_string(),
+                    status: if i == 2 { "done" } else { "pending" }.to_string(),
+                    count: *count,
+                    active: true,
+                    updated_at: 1000 + i as i64,
+                })
+                .unwrap();
+        }
+
+        let ids = |query: &Query| -> Vec<String> {
+            store
+                .list_with::<TestRecord>(query.filters(), query.options())
+                .unwrap()
+                .into_iter()
+                .map(|r| r.id)
+                .collect()
+        };
+
+        // Ties on count are broken by id
+        let query = Query::new().eq("status", "pending").order_by_desc("count");
+        assert_eq!(ids(&query), vec!["rec1", "rec3", "rec4", "rec0"]);
+        assert_eq!(ids(&query.clone().limit(2).offset(1)), vec!["rec3", "rec4"]);
+        assert_eq!(ids(&query.offset(3)), vec!["rec0"]);
+
+        // Default order matches `list`
+        assert_eq!(ids(&Query::new().gte("count", 7)), vec!["rec4", "rec3", "rec1"]);
+        assert_eq!(ids(&Query::new().order_by("updated_at").limit(1)), vec!["rec0"]);
+
+        let err = store
+            .list_with::<TestRecord>(&[], &Query::new().order_by("missing").into_parts().1)
+            .unwrap_err();
+        assert!(matches!(err, StoreError::Schema(_)));
+        assert!(err.to_string().contains("missing"));
     }
 
     #[test]
-    fn test_generic_get_nonexistent() {
+    fn test_first_and_last_by_order() {
         let temp = TempDir::new().unwrap();
-        let store = Store::open(temp.path()).unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        assert!(store.first::<TestRecord>(&[], "count").unwrap().is_none());
+
+        for (i, count) in [5, 9, 1, 9, 7].iter().enumerate() {
+            store
+                .create(TestRecord {
+                    id: format!("rec{}", i),
+                    name: "Ordered".to_string(),
+                    status: if i == 2 { "done" } else { "pending" }.to_string(),
+                    count: *count,
+                    active: true,
+                    updated_at: 1000 + i as i64,
+                })
+                .unwrap();
+        }
 
-        let result: Option<TestRecord> = store.get("nonexistent").unwrap();
-        assert!(result.is_none());
+        let id = |record: Option<TestRecord>| record.map(|r| r.id);
+        let pending = ["status=pending".parse().unwrap()];
+        assert_eq!(id(store.first(&[], "count").unwrap()).as_deref(), Some("rec2"));
+        assert_eq!(id(store.first(&pending, "count").unwrap()).as_deref(), Some("rec0"));
+        // Ties are broken by id
+        assert_eq!(id(store.last(&pending, "count").unwrap()).as_deref(), Some("rec1"));
+        assert_eq!(id(store.last(&[], "updated_at").unwrap()).as_deref(), Some("rec4"));
+
+        let none = ["status=archived".parse().unwrap()];
+        assert!(store.last::<TestRecord>(&none, "count").unwrap().is_none());
     }
 
     #[test]
-    fn test_generic_update() {
+    fn test_case_insensitive_filters() {
         let temp = TempDir::new().unwrap();
         let mut store = Store::open(temp.path()).unwrap();
 
-        // Create initial record
-        let mut record = TestRecord {
-            id: "rec1".to_string(),
-            name: "Original".to_string(),
-            status: "draft".to_string(),
-            count: 1,
-            active: false,
-            updated_at: 1000,
+        for (i, status) in ["Admin", "ADMIN", "admin", "user", "Super_Admin"].iter().enumerate() {
+            store
+                .create(TestRecord {
+                    id: format!("rec{}", i),
+                    name: "Case".to_string(),
+                    status: status.to_string(),
+                    count: i as i64,
+                    active: true,
+                    updated_at: 1000 + i as i64,
+                })
+                .unwrap();
+        }
+
+        let filter = |op: FilterOp, value: &str| Filter {
+            field: "status".to_string(),
+            op,
+            value: IndexValue::String(value.to_string()),
+        };
+        let ids = |filters: &[Filter]| {
+            let mut ids = store.list_ids::<TestRecord>(filters).unwrap();
+            ids.sort();
+            ids
         };
-        store.create(record.clone()).unwrap();
 
-        // Update record
-        record.name = "Updated".to_string();
-        record.status = "active".to_string();
-        record.count = 2;
-        record.active = true;
-        record.updated_at = 2000;
-        store.update(record.clone()).unwrap();
+        assert_eq!(ids(&[filter(FilterOp::Eq, "admin")]), vec!["rec2"]);
+        assert_eq!(
+            ids(&[filter(FilterOp::EqIgnoreCase, "admin")]),
+            vec!["rec0", "rec1", "rec2"]
+        );
+        assert_eq!(ids(&[filter(FilterOp::NeIgnoreCase, "admin")]), vec!["rec3", "rec4"]);
+        assert_eq!(
+            ids(&[filter(FilterOp::ContainsIgnoreCase, "admin")]),
+            vec!["rec0", "rec1", "rec2", "rec4"]
+        );
+        // `_` in the needle is literal, not a LIKE wildcard
+        assert_eq!(ids(&[filter(FilterOp::ContainsIgnoreCase, "r_a")]), vec!["rec4"]);
 
-        // Verify update
-        let retrieved: Option<TestRecord> = store.get("rec1").unwrap();
-        assert!(retrieved.is_some());
-        let retrieved = retrieved.unwrap();
-        assert_eq!(retrieved.name, "Updated");
-        assert_eq!(retrieved.status, "active");
-        assert_eq!(retrieved.count, 2);
-        assert!(retrieved.active);
-        assert_eq!(retrieved.updated_at, 2000);
+        let non_string = Filter {
+            field: "count".to_string(),
+            op: FilterOp::EqIgnoreCase,
+            value: IndexValue::Int(1),
+        };
+        assert!(store.list::<TestRecord>(&[non_string]).is_err());
     }
 
     #[test]
-    fn test_generic_delete() {
+    fn test_starts_with_filter_is_anchored_and_literal() {
         let temp = TempDir::new().unwrap();
         let mut store = Store::open(temp.path()).unwrap();
 
-        // Create record
-        let record = TestRecord {
-            id: "rec1".to_string(),
-            name: "To Delete".to_string(),
-            status: "active".to_string(),
-            count: 1,
-            active: true,
-            updated_at: now_ms(),
+        let branches = [
+            "feature/login",
+            "hotfix/feature-x",
+            "feature_x/y",
+            "featureAx/z",
+            "100%/done",
+            "1000/done",
+        ];
+        for (i, branch) in branches.iter().enumerate() {
+            store
+                .create(TestRecord {
+                    id: format!("rec{}", i),
+                    name: "Branch".to_string(),
+                    status: branch.to_string(),
+                    count: i as i64,
+                    active: true,
+                    updated_at: 1000 + i as i64,
+                })
+                .unwrap();
+        }
+
+        let ids = |prefix: &str| {
+            let mut ids = store
+                .list_ids::<TestRecord>(&[format!("status^={}", prefix).parse().unwrap()])
+                .unwrap();
+            ids.sort();
+            ids
         };
-        store.create(record).unwrap();
+        assert_eq!(ids("feature/"), vec!["rec0"]);
+        // `_` and `%` in the prefix are literal, not LIKE wildcards
+        assert_eq!(ids("feature_"), vec!["rec2"]);
+        assert_eq!(ids("100%"), vec!["rec4"]);
+        assert_eq!(ids("feature"), vec!["rec0", "rec2", "rec3"]);
+        // ASCII case is ignored, like `LIKE` does
+        assert_eq!(ids("FEATURE/"), vec!["rec0"]);
+
+        let non_string = Filter {
+            field: "count".to_string(),
+            op: FilterOp::StartsWith,
+            value: IndexValue::Int(1),
+        };
+        assert!(store.list::<TestRecord>(&[non_string]).is_err());
+    }
 
-        // Delete record
-        store.delete::<TestRecord>("rec1").unwrap();
+    /// `TestRecord` with a composite index on (status, count)
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    #[serde(transparent)]
+    struct CompositeRecord(TestRecord);
 
-        // Verify deleted from SQLite
-        let retrieved: Option<TestRecord> = store.get("rec1").unwrap();
-        assert!(retrieved.is_none());
+    impl Record for CompositeRecord {
+        fn id(&self) -> &str {
+            self.0.id()
+        }
 
-        // Verify tombstone in JSONL
-        let jsonl_path = temp.path().join(".taskstore/test_records.jsonl");
-        let content = fs::read_to_string(jsonl_path).unwrap();
-        assert!(content.contains("\"deleted\":true"));
+        fn updated_at(&self) -> i64 {
+            self.0.updated_at()
+        }
+
+        fn collection_name() -> &'static str {
+            TestRecord::collection_name()
+        }
+
+        fn indexed_fields(&self) -> HashMap<String, IndexValue> {
+            self.0.indexed_fields()
+        }
+
+        fn composite_indexes() -> &'static [&'static [&'static str]] {
+            &[&["status", "count"]]
+        }
     }
 
     #[test]
-    fn test_generic_list_no_filters() {
+    fn test_composite_index_matches_single_field_results() {
         let temp = TempDir::new().unwrap();
         let mut store = Store::open(temp.path()).unwrap();
 
-        // Create multiple records
-        for i in 1..=3 {
-            let record = TestRecord {
-                id: format!("rec{}", i),
-                name: format!("Record {}", i),
-                status: "active".to_string(),
-                count: i,
-                active: true,
-                updated_at: now_ms(),
-            };
-            store.create(record).unwrap();
+        // Written before the composite exists; backfilled on the first CompositeRecord write
+        for i in 0..20 {
+            store
+                .create(TestRecord {
+                    id: format!("rec{:02}", i),
+                    name: "Composite".to_string(),
+                    status: if i % 2 == 0 { "pending" } else { "done" }.to_string(),
+                    count: i,
+                    active: true,
+                    updated_at: 1000 + i,
+                })
+                .unwrap();
         }
 
-        // List all records
-        let records: Vec<TestRecord> = store.list(&[]).unwrap();
-        assert_eq!(records.len(), 3);
+        let filters = vec![
+            Filter {
+                field: "status".to_string(),
+                op: FilterOp::Eq,
+                value: IndexValue::String("pending".to_string()),
+            },
+            Filter {
+                field: "count".to_string(),
+                op: FilterOp::Gt,
+                value: IndexValue::Int(5),
+            },
+        ];
+        assert!(store.ready_composites::<CompositeRecord>(&filters).unwrap().is_empty());
+
+        store
+            .update(CompositeRecord(TestRecord {
+                id: "rec18".to_string(),
+                name: "Composite".to_string(),
+                status: "done".to_string(),
+                count: 18,
+                active: true,
+                updated_at: 2000,
+            }))
+            .unwrap();
+
+        let composites = store.ready_composites::<CompositeRecord>(&filters).unwrap();
+        assert_eq!(composites, vec![&["status", "count"][..]]);
+
+        let expected = store.list_ids::<TestRecord>(&filters).unwrap();
+        assert_eq!(expected, vec!["rec16", "rec14", "rec12", "rec10", "rec08", "rec06"]);
+        assert_eq!(store.list_ids::<CompositeRecord>(&filters).unwrap(), expected);
+        let iterated: Vec<String> = store
+            .list_iter::<CompositeRecord>(&filters)
+            .unwrap()
+            .map(|r| r.unwrap().0.id)
+            .collect();
+        assert_eq!(iterated, expected);
+
+        // The compound index drives the query, which explain shows without running it
+        let explained = store.explain::<CompositeRecord>(&filters).unwrap();
+        assert!(explained.starts_with("SELECT r.data_json"));
+        assert!(explained.contains("QUERY PLAN\n"));
+        assert!(explained.contains("idx_record_composite_indexes"));
+        assert!(
+            !store
+                .explain::<TestRecord>(&filters)
+                .unwrap()
+                .contains("idx_record_composite_indexes")
+        );
+
+        // Deleting removes the record's composite row
+        store.delete::<CompositeRecord>("rec16").unwrap();
+        let remaining: i64 = store
+            .db()
+            .query_row(
+                "SELECT COUNT(*) FROM record_composite_indexes WHERE id = 'rec16'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(remaining, 0);
+        assert_eq!(
+            store.list_ids::<CompositeRecord>(&filters).unwrap(),
+            store.list_ids::<TestRecord>(&filters).unwrap()
+        );
     }
 
     #[test]
-    fn test_generic_list_with_filter() {
+    fn test_untyped_reindex_drops_incomplete_composites() {
         let temp = TempDir::new().unwrap();
         let mut store = Store::open(temp.path()).unwrap();
-
-        // Create records with different statuses
-        let record1 = TestRecord {
-            id: "rec1".to_string(),
-            name: "Record 1".to_string(),
-            status: "active".to_string(),
-            count: 1,
-            active: true,
-            updated_at: now_ms(),
-        };
-        let record2 = TestRecord {
-            id: "rec2".to_string(),
-            name: "Record 2".to_string(),
-            status: "draft".to_string(),
-            count: 2,
-            active: true,
-            updated_at: now_ms(),
+        let record = |id: &str| {
+            CompositeRecord(TestRecord {
+                id: id.to_string(),
+                name: id.to_string(),
+                status: "pending".to_string(),
+                count: 1,
+                active: true,
+                updated_at: 1000,
+            })
         };
 
-        store.create(record1).unwrap();
-        store.create(record2).unwrap();
+        store.create(record("t0")).unwrap();
+        store.sync_collection("test_records").unwrap();
+        store.create(record("t1")).unwrap();
+        let jsonl_path = temp.path().join(".taskstore/test_records.jsonl");
+        jsonl::append_jsonl(&jsonl_path, &record("t2").0).unwrap();
+
+        // Reindexed from raw JSON, which has no composites to give t2 a row
+        store.sync_collection("test_records").unwrap();
+        let status = Query::new().eq("status", "pending");
+        assert_eq!(store.list_ids::<CompositeRecord>(status.filters()).unwrap().len(), 3);
+        let both = status.eq("count", 1);
+        assert!(
+            store
+                .ready_composites::<CompositeRecord>(both.filters())
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(store.list_ids::<CompositeRecord>(both.filters()).unwrap().len(), 3);
 
-        // Filter by status = "active"
-        let filters = vec![Filter {
-            field: "status".to_string(),
-            op: crate::filter::FilterOp::Eq,
-            value: IndexValue::String("active".to_string()),
-        }];
+        // The next typed write backfills it
+        store.update(record("t0")).unwrap();
+        assert_eq!(
+            store.ready_composites::<CompositeRecord>(both.filters()).unwrap().len(),
+            1
+        );
+        assert_eq!(store.list_ids::<CompositeRecord>(both.filters()).unwrap().len(), 3);
+    }
 
-        let records: Vec<TestRecord> = store.list(&filters).unwrap();
-        assert_eq!(records.len(), 1);
-        assert_eq!(records[0].status, "active");
+    /// `TestRecord` that also indexes a range, which only filters may use
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    #[serde(transparent)]
+    struct RangeRecord(TestRecord);
+
+    impl Record for RangeRecord {
+        fn id(&self) -> &str {
+            self.0.id()
+        }
+
+        fn updated_at(&self) -> i64 {
+            self.0.updated_at()
+        }
+
+        fn collection_name() -> &'static str {
+            TestRecord::collection_name()
+        }
+
+        fn indexed_fields(&self) -> HashMap<String, IndexValue> {
+            let mut fields = self.0.indexed_fields();
+            fields.insert(
+                "window".to_string(),
+                IndexValue::Range(Box::new(0.into()), Box::new(self.0.count.into())),
+            );
+            fields
+        }
     }
 
     #[test]
-    fn test_validation_collection_name() {
-        // Valid
-        assert!(Store::validate_collection_name("valid_name").is_ok());
-        assert!(Store::validate_collection_name("valid-name").is_ok());
+    fn test_indexing_a_range_is_rejected() {
+        let temp = TempDir::new().unwrap();
+        let mut store = Store::open(temp.path()).unwrap();
+        let record = RangeRecord(TestRecord {
+            id: "r1".to_string(),
+            name: "Ranged".to_string(),
+            status: "active".to_string(),
+            count: 5,
+            active: true,
+            updated_at: 1000,
+        });
 
-        // Invalid
-        assert!(Store::validate_collection_name("invalid/name").is_err());
-        assert!(Store::validate_collection_name("").is_err());
-        assert!(Store::validate_collection_name(&"a".repeat(65)).is_err());
+        let err = store.create(record).unwrap_err();
+        assert!(matches!(err, StoreError::Schema(_)), "{:?}", err);
+        assert!(store.get::<TestRecord>("r1").unwrap().is_none());
+        assert_eq!(
+            fs::read_to_string(temp.path().join(".taskstore/test_records.jsonl")).unwrap_or_default(),
+            ""
+        );
     }
 
     #[test]
-    fn test_validation_field_name() {
-        // Valid
-        assert!(Store::validate_field_name("valid_field").is_ok());
+    fn test_match_composite_prefix() {
+        let eq = |field: &str| Filter {
+            field: field.to_string(),
+            op: FilterOp::Eq,
+            value: IndexValue::Int(1),
+        };
+        let gt = |field: &str| Filter {
+            field: field.to_string(),
+            op: FilterOp::Gt,
+            value: IndexValue::Int(1),
+        };
+        let composites: &[&'static [&'static str]] = &[&["a", "b"], &["a", "b", "c"]];
 
-        // Invalid
-        assert!(Store::validate_field_name("invalid-field").is_err());
-        assert!(Store::validate_field_name("").is_err());
-        assert!(Store::validate_field_name(&"a".repeat(65)).is_err());
+        let filters = [eq("a"), eq("b"), gt("c")];
+        let (composite, prefix) = Store::match_composite(&filters, composites).unwrap();
+        assert_eq!(composite, &["a", "b", "c"]);
+        assert_eq!(prefix.len(), 3);
+
+        // A range ends the usable prefix
+        let filters = [gt("a"), eq("b")];
+        assert!(Store::match_composite(&filters, composites).is_none());
+
+        // A gap in the prefix stops matching
+        let filters = [eq("a"), eq("c")];
+        assert!(Store::match_composite(&filters, composites).is_none());
     }
 }
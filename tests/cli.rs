@@ -0,0 +1,69 @@
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+use tempfile::TempDir;
+
+/// Run the `taskstore` CLI against the store in `dir`, with logging off so stdout is only its output
+fn taskstore(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_taskstore"))
+        .env("RUST_LOG", "off")
+        .arg("--store-path")
+        .arg(dir)
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+/// A store whose `notes.jsonl` has a live record, an older version of it, and a deleted one
+fn store_with_notes() -> TempDir {
+    let temp = TempDir::new().unwrap();
+    fs::create_dir_all(temp.path().join(".taskstore")).unwrap();
+    fs::write(
+        temp.path().join(".taskstore/notes.jsonl"),
+        concat!(
+            r#"{"id":"n1","text":"first","updated_at":1000}"#,
+            "\n",
+            r#"{"id":"n1","text":"second","updated_at":2000}"#,
+            "\n",
+            r#"{"id":"n2","text":"gone","updated_at":1000}"#,
+            "\n",
+            r#"{"deleted":true,"deleted_at":3000,"id":"n2","text":"gone","updated_at":3000}"#,
+            "\n",
+        ),
+    )
+    .unwrap();
+    temp
+}
+
+#[test]
+fn test_show_record_prints_latest_version() {
+    let temp = store_with_notes();
+    let output = taskstore(temp.path(), &["show-record", "notes", "n1", "--format", "json"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let record: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(record["text"], "second");
+}
+
+#[test]
+fn test_show_record_exits_2_for_missing_or_deleted_records() {
+    let temp = store_with_notes();
+    for id in ["n2", "n3"] {
+        let output = taskstore(temp.path(), &["show-record", "notes", id]);
+        assert_eq!(output.status.code(), Some(2), "{}", id);
+        assert!(output.stdout.is_empty());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.contains(&format!("Record not found in notes.jsonl: {}", id)),
+            "{}",
+            stderr
+        );
+    }
+}
+
+#[test]
+fn test_show_record_rejects_invalid_collection_names() {
+    let temp = store_with_notes();
+    let output = taskstore(temp.path(), &["show-record", "../notes", "n1"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Invalid collection name: ../notes"));
+}